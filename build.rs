@@ -25,6 +25,10 @@ fn main() {
         });
     println!("cargo:rustc-env=BUILD_DATE={}", unix_to_date(secs));
 
+    // Target triple, which names the release archive `oken self-update` fetches
+    let target = std::env::var("TARGET").unwrap_or_default();
+    println!("cargo:rustc-env=BUILD_TARGET={target}");
+
     // Rebuild only when the git HEAD moves, not on every cargo build
     println!("cargo:rerun-if-changed=.git/HEAD");
     println!("cargo:rerun-if-changed=.git/refs/heads/");
@@ -0,0 +1,107 @@
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::hosts::Host;
+use crate::hosts_toml::AddressFamily;
+
+/// How long each address gets to accept a connection on the ssh port.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Whether `ip` is only reachable from a LAN or VPN: RFC 1918, the
+/// 100.64.0.0/10 range Tailscale uses, loopback, link-local and IPv6 ULA.
+pub fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            let cgnat = a == 100 && b & 0xc0 == 64;
+            v4.is_private() || v4.is_loopback() || v4.is_link_local() || cgnat
+        }
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            v6.is_loopback() || first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80
+        }
+    }
+}
+
+/// `hostname` and then `addresses`, with private IPs moved to the front: when
+/// one of those answers you're on the LAN or VPN, and it's the shorter path.
+/// Otherwise the written order stands.
+fn ordered(hostname: &str, addresses: &[String]) -> Vec<String> {
+    let mut all: Vec<String> = Vec::new();
+    for address in std::iter::once(hostname).chain(addresses.iter().map(String::as_str)) {
+        let address = address.trim_matches(['[', ']']);
+        if !all.iter().any(|a| a == address) {
+            all.push(address.to_string());
+        }
+    }
+    let private = |a: &String| a.parse().is_ok_and(is_private);
+    let (mut first, rest): (Vec<String>, Vec<String>) = all.into_iter().partition(private);
+    first.extend(rest);
+    first
+}
+
+/// The address to connect to the host at. With `addresses` set, every
+/// candidate's ssh port is tried at once and the first in `ordered` order
+/// that accepts wins; when none does, `hostname`, so ssh reports the failure.
+/// Hosts reached through a proxy or cloud service keep `hostname`.
+pub fn choose(host: &Host) -> Option<String> {
+    let hostname = host.hostname.as_ref()?;
+    if host.addresses.is_empty() || host.proxy.is_some() || !host.connect.is_direct() {
+        return Some(hostname.clone());
+    }
+    let candidates = ordered(hostname, &host.addresses);
+    let (port, family) = (host.port.unwrap_or(22), host.address_family);
+    let (tx, rx) = mpsc::channel();
+    for (i, candidate) in candidates.iter().enumerate() {
+        let (tx, candidate) = (tx.clone(), candidate.clone());
+        std::thread::spawn(move || tx.send((i, answers(&candidate, port, family))));
+    }
+    drop(tx);
+    // Settle as soon as every better candidate has failed, not when the slowest probe ends
+    let mut answered: Vec<Option<bool>> = vec![None; candidates.len()];
+    for (i, ok) in rx {
+        answered[i] = Some(ok);
+        let settled = answered.iter().take_while(|a| **a == Some(false)).count();
+        if answered.get(settled) == Some(&Some(true)) {
+            log::debug!("reaching '{}' at {}", host.alias, candidates[settled]);
+            return Some(candidates[settled].clone());
+        }
+    }
+    log::debug!("no address of '{}' answered; trying {hostname}", host.alias);
+    Some(hostname.clone())
+}
+
+/// Whether something accepts a TCP connection on `address:port`, over the
+/// IP version `family` allows.
+fn answers(address: &str, port: u16, family: AddressFamily) -> bool {
+    let Ok(addrs) = (address, port).to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .filter(|a| match family {
+            AddressFamily::Any => true,
+            AddressFamily::Inet => a.is_ipv4(),
+            AddressFamily::Inet6 => a.is_ipv6(),
+        })
+        .any(|a| TcpStream::connect_timeout(&a, PROBE_TIMEOUT).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn puts_private_addresses_first() {
+        assert!(is_private("10.1.2.3".parse().unwrap()));
+        assert!(is_private("100.101.102.103".parse().unwrap()));
+        assert!(is_private("fd7a:115c:a1e0::1".parse().unwrap()));
+        assert!(!is_private("100.128.0.1".parse().unwrap()));
+        assert!(!is_private("2001:db8::1".parse().unwrap()));
+        let addresses = ["10.0.0.5".to_string(), "[fd00::5]".to_string(), "web.example.com".into()];
+        assert_eq!(
+            ordered("203.0.113.5", &addresses),
+            ["10.0.0.5", "fd00::5", "203.0.113.5", "web.example.com"]
+        );
+    }
+}
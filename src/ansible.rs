@@ -0,0 +1,546 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use crate::hosts_toml::HostEntry;
+
+type Vars = BTreeMap<String, String>;
+
+/// Groups of an Ansible inventory, keyed by name. `all` and `ungrouped`
+/// are only created when the inventory mentions them.
+#[derive(Debug, Default)]
+struct Inventory {
+    groups: BTreeMap<String, Group>,
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    /// Host name -> the vars set on its line (INI) or entry (YAML).
+    hosts: BTreeMap<String, Vars>,
+    vars: Vars,
+    children: BTreeSet<String>,
+}
+
+impl Inventory {
+    fn group(&mut self, name: &str) -> &mut Group {
+        self.groups.entry(name.to_string()).or_default()
+    }
+}
+
+/// Hosts of an INI or YAML inventory file. Groups become tags (besides
+/// `all` and `ungrouped`), and `ansible_host`, `ansible_user`, `ansible_port`
+/// and `ansible_ssh_private_key_file` become the hostname, user, port and
+/// identity file, with host vars winning over group vars and child groups
+/// over their parents.
+pub fn inventory_hosts(path: &Path) -> Result<BTreeMap<String, HostEntry>> {
+    if path.is_dir() {
+        bail!("{} is a directory; pass the inventory file", path.display());
+    }
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let yaml = match path.extension().and_then(|e| e.to_str()) {
+        Some("yml" | "yaml") => true,
+        Some("ini" | "cfg") => false,
+        _ => looks_like_yaml(&text),
+    };
+    let inventory = if yaml {
+        parse_yaml(&text).with_context(|| format!("failed to parse {}", path.display()))?
+    } else {
+        parse_ini(&text).with_context(|| format!("failed to parse {}", path.display()))?
+    };
+    Ok(to_entries(&inventory))
+}
+
+/// Extension-less inventories: YAML starts with `---` or a `group:` key,
+/// INI with a `[group]` header or a host line.
+fn looks_like_yaml(text: &str) -> bool {
+    let first = text
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with(';'));
+    first.is_some_and(|l| l == "---" || (l.ends_with(':') && !l.starts_with('[')))
+}
+
+fn parse_ini(text: &str) -> Result<Inventory> {
+    enum Section {
+        Hosts,
+        Vars,
+        Children,
+    }
+    let mut inventory = Inventory::default();
+    let mut group = "ungrouped".to_string();
+    let mut section = Section::Hosts;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let (name, kind) = header.split_once(':').unwrap_or((header, ""));
+            section = match kind {
+                "" => Section::Hosts,
+                "vars" => Section::Vars,
+                "children" => Section::Children,
+                other => bail!("line {}: unknown section type ':{other}'", n + 1),
+            };
+            group = name.to_string();
+            inventory.group(&group);
+            continue;
+        }
+        match section {
+            Section::Hosts => {
+                let mut words = split_words(line).into_iter();
+                let Some(pattern) = words.next() else {
+                    continue;
+                };
+                let mut vars = Vars::new();
+                for word in words {
+                    let Some((key, value)) = word.split_once('=') else {
+                        bail!("line {}: expected key=value, got '{word}'", n + 1);
+                    };
+                    vars.insert(key.to_string(), value.to_string());
+                }
+                // `host:2222` is shorthand for ansible_port (but not an IPv6 address)
+                let (pattern, port) = match pattern.rsplit_once(':') {
+                    Some((h, p))
+                        if (!h.contains(':') || h.ends_with(']')) && p.parse::<u16>().is_ok() =>
+                    {
+                        (h.to_string(), Some(p.to_string()))
+                    }
+                    _ => (pattern, None),
+                };
+                if let Some(port) = port {
+                    vars.entry("ansible_port".to_string()).or_insert(port);
+                }
+                for host in expand_range(&pattern)? {
+                    inventory.group(&group).hosts.insert(host, vars.clone());
+                }
+            }
+            Section::Vars => {
+                let Some((key, value)) = line.split_once('=') else {
+                    bail!("line {}: expected key=value, got '{line}'", n + 1);
+                };
+                let value = split_words(value.trim()).join(" ");
+                inventory.group(&group).vars.insert(key.trim().to_string(), value);
+            }
+            Section::Children => {
+                inventory.group(line);
+                inventory.group(&group).children.insert(line.to_string());
+            }
+        }
+    }
+    Ok(inventory)
+}
+
+/// Whitespace-separated words, with `'…'` and `"…"` quoting.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote = None;
+    let mut in_word = false;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '#') if !in_word => break,
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// Expand `web[01:03].example.com` and `db-[a:c]` host patterns.
+fn expand_range(pattern: &str) -> Result<Vec<String>> {
+    let Some((prefix, rest)) = pattern.split_once('[') else {
+        return Ok(vec![pattern.to_string()]);
+    };
+    let Some((range, suffix)) = rest.split_once(']') else {
+        bail!("unclosed '[' in host pattern '{pattern}'");
+    };
+    let (from, to) = range
+        .split_once(':')
+        .with_context(|| format!("expected [start:end] in host pattern '{pattern}'"))?;
+    let middles: Vec<String> = match (from.parse::<u32>(), to.parse::<u32>()) {
+        (Ok(a), Ok(b)) if a <= b => {
+            let width = if from.starts_with('0') { from.len() } else { 0 };
+            (a..=b).map(|i| format!("{i:0width$}")).collect()
+        }
+        _ => match (from.as_bytes(), to.as_bytes()) {
+            ([a], [b]) if a.is_ascii_alphabetic() && a <= b => {
+                (*a..=*b).map(|c| (c as char).to_string()).collect()
+            }
+            _ => bail!("invalid range [{range}] in host pattern '{pattern}'"),
+        },
+    };
+    let mut hosts = Vec::new();
+    for middle in middles {
+        for tail in expand_range(suffix)? {
+            hosts.push(format!("{prefix}{middle}{tail}"));
+        }
+    }
+    Ok(hosts)
+}
+
+fn parse_yaml(text: &str) -> Result<Inventory> {
+    let mut inventory = Inventory::default();
+    let root = yaml::parse(text)?;
+    let serde_json::Value::Object(groups) = root else {
+        if root.is_null() {
+            return Ok(inventory);
+        }
+        bail!("expected groups at the top level");
+    };
+    for (name, group) in &groups {
+        walk_yaml_group(&mut inventory, name, group)?;
+    }
+    Ok(inventory)
+}
+
+fn walk_yaml_group(inventory: &mut Inventory, name: &str, group: &serde_json::Value) -> Result<()> {
+    inventory.group(name);
+    if group.is_null() {
+        return Ok(());
+    }
+    let section = |key: &str| group.get(key).and_then(|v| v.as_object());
+    for (host, vars) in section("hosts").into_iter().flatten() {
+        let vars = yaml_vars(vars);
+        for host in expand_range(host)? {
+            inventory.group(name).hosts.insert(host, vars.clone());
+        }
+    }
+    let vars = group.get("vars").map(yaml_vars).unwrap_or_default();
+    inventory.group(name).vars.extend(vars);
+    for (child, value) in section("children").into_iter().flatten() {
+        inventory.group(name).children.insert(child.clone());
+        walk_yaml_group(inventory, child, value)?;
+    }
+    Ok(())
+}
+
+/// Scalar vars only; oken has no use for nested ones.
+fn yaml_vars(value: &serde_json::Value) -> Vars {
+    value
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+        .collect()
+}
+
+fn to_entries(inventory: &Inventory) -> BTreeMap<String, HostEntry> {
+    // Depth below `all`, so parents' vars apply before their children's
+    let mut parents: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (name, group) in &inventory.groups {
+        for child in &group.children {
+            parents.entry(child).or_default().push(name);
+        }
+    }
+    fn depth(group: &str, parents: &BTreeMap<&str, Vec<&str>>, seen: &mut Vec<String>) -> usize {
+        if group == "all" || seen.iter().any(|s| s == group) {
+            return 0;
+        }
+        seen.push(group.to_string());
+        let parent_depth = parents
+            .get(group)
+            .into_iter()
+            .flatten()
+            .map(|p| depth(p, parents, seen))
+            .max()
+            .unwrap_or(0);
+        seen.pop();
+        parent_depth + 1
+    }
+    fn ancestors<'a>(
+        group: &'a str,
+        parents: &BTreeMap<&'a str, Vec<&'a str>>,
+        out: &mut BTreeSet<&'a str>,
+    ) {
+        if out.insert(group) {
+            for parent in parents.get(group).into_iter().flatten() {
+                ancestors(parent, parents, out);
+            }
+        }
+    }
+
+    let mut memberships: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for (name, group) in &inventory.groups {
+        for host in group.hosts.keys() {
+            let groups = memberships.entry(host).or_default();
+            ancestors(name, &parents, groups);
+        }
+    }
+
+    let mut hosts = BTreeMap::new();
+    for (host, groups) in memberships {
+        let mut ordered: Vec<&str> = groups.iter().copied().collect();
+        ordered.sort_by_key(|g| depth(g, &parents, &mut Vec::new()));
+        let mut vars = inventory.groups.get("all").map(|g| g.vars.clone()).unwrap_or_default();
+        for group in &ordered {
+            vars.extend(inventory.groups[*group].vars.clone());
+        }
+        for group in &ordered {
+            if let Some(host_vars) = inventory.groups[*group].hosts.get(host) {
+                vars.extend(host_vars.clone());
+            }
+        }
+        let var = |names: &[&str]| names.iter().find_map(|n| vars.get(*n)).cloned();
+        let mut tags = vec!["ansible".to_string()];
+        tags.extend(
+            groups
+                .iter()
+                .filter(|g| !matches!(**g, "all" | "ungrouped"))
+                .map(|g| g.to_string()),
+        );
+        let hostname = var(&["ansible_host", "ansible_ssh_host"]);
+        let entry = HostEntry {
+            hostname: hostname.unwrap_or_else(|| host.to_string()),
+            user: var(&["ansible_user", "ansible_ssh_user"]),
+            port: var(&["ansible_port", "ansible_ssh_port"]).and_then(|p| p.parse().ok()),
+            identity_file: var(&["ansible_ssh_private_key_file", "ansible_private_key_file"]),
+            tags,
+            ..Default::default()
+        };
+        hosts.insert(crate::mapping::slug(host), entry);
+    }
+    hosts
+}
+
+/// Just enough YAML for inventories: block mappings and sequences, plain,
+/// quoted and empty scalars, and `{}`/`[a, b]` flow collections. Every
+/// scalar becomes a string.
+mod yaml {
+    use anyhow::{Result, bail};
+    use serde_json::{Map, Value};
+
+    struct Line<'a> {
+        number: usize,
+        indent: usize,
+        text: &'a str,
+    }
+
+    pub fn parse(text: &str) -> Result<Value> {
+        let mut lines = Vec::new();
+        for (n, raw) in text.lines().enumerate() {
+            let trimmed = raw.trim_start();
+            if raw.starts_with('\t') {
+                bail!("line {}: tabs are not allowed for indentation", n + 1);
+            }
+            let text = strip_comment(trimmed).trim_end();
+            if text.is_empty() || text == "---" || text == "..." {
+                continue;
+            }
+            lines.push(Line {
+                number: n + 1,
+                indent: raw.len() - trimmed.len(),
+                text,
+            });
+        }
+        if lines.is_empty() {
+            return Ok(Value::Null);
+        }
+        let mut i = 0;
+        let indent = lines[0].indent;
+        let value = block(&lines, &mut i, indent)?;
+        if let Some(line) = lines.get(i) {
+            bail!("line {}: unexpected indentation", line.number);
+        }
+        Ok(value)
+    }
+
+    fn block(lines: &[Line], i: &mut usize, indent: usize) -> Result<Value> {
+        if lines[*i].text.starts_with("- ") || lines[*i].text == "-" {
+            return sequence(lines, i, indent);
+        }
+        let mut map = Map::new();
+        while let Some(line) = lines.get(*i) {
+            if line.indent < indent {
+                break;
+            }
+            if line.indent > indent {
+                bail!("line {}: unexpected indentation", line.number);
+            }
+            let Some((key, rest)) = split_key(line.text) else {
+                bail!("line {}: expected 'key: value'", line.number);
+            };
+            *i += 1;
+            let value = if !rest.is_empty() {
+                scalar(rest)
+            } else {
+                match lines.get(*i) {
+                    Some(next) if next.indent > indent => block(lines, i, next.indent)?,
+                    // A sequence may sit at its key's indentation
+                    Some(next) if next.indent == indent && next.text.starts_with("- ") => {
+                        sequence(lines, i, indent)?
+                    }
+                    _ => Value::Null,
+                }
+            };
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+
+    fn sequence(lines: &[Line], i: &mut usize, indent: usize) -> Result<Value> {
+        let mut items = Vec::new();
+        while let Some(line) = lines.get(*i) {
+            if line.indent != indent || !(line.text.starts_with("- ") || line.text == "-") {
+                break;
+            }
+            *i += 1;
+            let item = line.text[1..].trim_start();
+            if split_key(item).is_some() {
+                bail!("line {}: mappings inside lists are not supported", line.number);
+            }
+            items.push(scalar(item));
+        }
+        Ok(Value::Array(items))
+    }
+
+    /// `key: value` or `key:`, with an optionally quoted key.
+    fn split_key(text: &str) -> Option<(String, &str)> {
+        if let Some(quoted) = text.strip_prefix(['"', '\'']) {
+            let end = quoted.find(text.as_bytes()[0] as char)?;
+            let rest = quoted[end + 1..].strip_prefix(':')?;
+            return Some((quoted[..end].to_string(), rest.trim()));
+        }
+        if let Some((key, rest)) = text.split_once(": ") {
+            return Some((key.trim().to_string(), rest.trim()));
+        }
+        let key = text.strip_suffix(':')?;
+        Some((key.trim().to_string(), ""))
+    }
+
+    fn scalar(text: &str) -> Value {
+        match text {
+            "" | "~" | "null" | "Null" | "NULL" => Value::Null,
+            "{}" => Value::Object(Map::new()),
+            _ if text.starts_with('[') && text.ends_with(']') => Value::Array(
+                text[1..text.len() - 1]
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(scalar)
+                    .collect(),
+            ),
+            _ => Value::String(unquote(text)),
+        }
+    }
+
+    fn unquote(text: &str) -> String {
+        for q in ['"', '\''] {
+            if text.len() >= 2 && text.starts_with(q) && text.ends_with(q) {
+                return text[1..text.len() - 1].to_string();
+            }
+        }
+        text.to_string()
+    }
+
+    /// Drop a ` # comment`, unless the `#` is inside quotes.
+    fn strip_comment(line: &str) -> &str {
+        let mut quote = None;
+        let mut prev = ' ';
+        for (i, c) in line.char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '#') if prev.is_whitespace() => return &line[..i],
+                _ => {}
+            }
+            prev = c;
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_ini_inventories() {
+        let inventory = parse_ini(
+            "bastion.example.com:2222\n\
+             \n\
+             [web]\n\
+             web[01:02] ansible_host=10.0.0.1 # first\n\
+             web03 ansible_host=\"10.0.0.3\" ansible_user=admin\n\
+             \n\
+             [db]\n\
+             db1 ansible_host=10.0.1.1 ansible_port=5022\n\
+             \n\
+             [prod:children]\n\
+             web\n\
+             db\n\
+             \n\
+             [prod:vars]\n\
+             ansible_user=deploy\n\
+             [web:vars]\n\
+             ansible_user = www\n",
+        )
+        .unwrap();
+        let hosts = to_entries(&inventory);
+        assert_eq!(hosts.len(), 5);
+        assert_eq!(hosts["bastion.example.com"].port, Some(2222));
+        assert_eq!(hosts["bastion.example.com"].tags, ["ansible"]);
+        assert_eq!(hosts["web01"].hostname, "10.0.0.1");
+        assert_eq!(hosts["web02"].user.as_deref(), Some("www"));
+        assert_eq!(hosts["web03"].user.as_deref(), Some("admin"));
+        assert_eq!(hosts["web03"].tags, ["ansible", "prod", "web"]);
+        assert_eq!(hosts["db1"].user.as_deref(), Some("deploy"));
+        assert_eq!(hosts["db1"].port, Some(5022));
+    }
+
+    #[test]
+    fn reads_yaml_inventories() {
+        let inventory = parse_yaml(
+            "---\n\
+             all:\n\
+             \x20 vars:\n\
+             \x20   ansible_user: ubuntu   # everyone\n\
+             \x20 hosts:\n\
+             \x20   jump:\n\
+             \x20     ansible_host: 203.0.113.5\n\
+             \x20 children:\n\
+             \x20   web:\n\
+             \x20     hosts:\n\
+             \x20       web[1:2].example.com:\n\
+             \x20     vars:\n\
+             \x20       ansible_port: \"2200\"\n\
+             \x20   staging:\n\
+             \x20     children:\n\
+             \x20       web: {}\n\
+             \x20     hosts:\n\
+             \x20       'stage db':\n\
+             \x20         ansible_host: 10.1.0.9\n\
+             \x20         ansible_user: postgres\n",
+        )
+        .unwrap();
+        let hosts = to_entries(&inventory);
+        assert_eq!(hosts["jump"].hostname, "203.0.113.5");
+        assert_eq!(hosts["jump"].user.as_deref(), Some("ubuntu"));
+        assert_eq!(hosts["jump"].tags, ["ansible"]);
+        assert_eq!(hosts["web1.example.com"].hostname, "web1.example.com");
+        assert_eq!(hosts["web2.example.com"].port, Some(2200));
+        assert_eq!(hosts["web2.example.com"].tags, ["ansible", "staging", "web"]);
+        assert_eq!(hosts["stage-db"].user.as_deref(), Some("postgres"));
+        assert!(looks_like_yaml("# fleet\nall:\n  hosts:\n"));
+        assert!(!looks_like_yaml("[web]\nweb1\n"));
+    }
+}
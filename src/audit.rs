@@ -5,16 +5,45 @@ use anyhow::Result;
 
 use crate::time_utils;
 
+/// A completed session, as written to the audit log.
+pub struct SessionEntry<'a> {
+    pub alias: &'a str,
+    pub target: &'a str,
+    pub duration_secs: u64,
+    pub exit_code: i32,
+    /// Transcript file name under the recordings dir, if the session was recorded.
+    pub recording: Option<&'a str>,
+    /// Justification given for a compliance-mode connection.
+    pub reason: Option<&'a str>,
+    /// The host matched `danger_tags` or a `[[danger_rules]]` entry.
+    pub danger: bool,
+}
+
 /// Append a completed session entry to the audit log. Silently ignores errors.
-pub fn log_session(alias: &str, target: &str, duration_secs: u64, exit_code: i32) {
-    let _ = log_impl(alias, target, duration_secs, exit_code);
+pub fn log_session(entry: &SessionEntry) {
+    let _ = log_impl(entry);
 }
 
-fn log_impl(alias: &str, target: &str, duration_secs: u64, exit_code: i32) -> Result<()> {
+fn log_impl(entry: &SessionEntry) -> Result<()> {
     let path = crate::config::data_dir()?.join("audit.log");
     let ts = current_timestamp();
-    // Format: timestamp \t alias \t target \t duration_secs \t exit_code
-    let line = format!("{ts}\t{alias}\t{target}\t{duration_secs}\t{exit_code}\n");
+    let SessionEntry {
+        alias,
+        target,
+        duration_secs,
+        exit_code,
+        ..
+    } = entry;
+    let target = crate::redact::text(target);
+    let recording = entry.recording.unwrap_or("");
+    // Free text must not break the tab-separated format
+    let reason = crate::redact::text(entry.reason.unwrap_or("")).replace(['\t', '\n', '\r'], " ");
+    let danger = if entry.danger { "danger" } else { "" };
+    // Format: timestamp \t alias \t target \t duration_secs \t exit_code \t recording \t reason \t danger
+    let line = format!(
+        "{ts}\t{alias}\t{target}\t{duration_secs}\t{exit_code}\t{recording}\t{reason}\t{danger}\n"
+    );
+    crate::config::create_parent(&path)?;
     let mut file = std::fs::OpenOptions::new()
         .create(true)
         .append(true)
@@ -23,6 +52,67 @@ fn log_impl(alias: &str, target: &str, duration_secs: u64, exit_code: i32) -> Re
     Ok(())
 }
 
+/// One line of the audit log, as read back.
+pub struct Record {
+    /// 1-based line number, which stays stable as the log grows.
+    pub id: usize,
+    /// `2026-01-31 14:02:11`, UTC.
+    pub time: String,
+    pub alias: String,
+    pub target: String,
+    pub duration_secs: Option<u64>,
+    pub exit_code: Option<i32>,
+    /// Transcript file name under the recordings dir, if the session was recorded.
+    pub recording: Option<String>,
+    pub reason: String,
+    /// Whether the host was a danger host; unknown for entries written
+    /// before the log recorded it.
+    pub danger: Option<bool>,
+}
+
+/// The last `n` audit log entries, newest first. Empty when there's no log yet.
+pub fn recent(n: usize) -> Result<Vec<Record>> {
+    let path = crate::config::data_dir()?.join("audit.log");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(&path)?;
+    let all_lines: Vec<&str> = content.lines().collect();
+    let start = all_lines.len().saturating_sub(n);
+    let records = all_lines[start..]
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, line)| parse_record(start + i + 1, line))
+        .collect();
+    Ok(records)
+}
+
+/// The newest session with `alias`. Entries for `oken exec`, reboots and
+/// shutdowns, whose target is a command, are skipped.
+pub fn last_session(alias: &str) -> Option<Record> {
+    recent(usize::MAX)
+        .ok()?
+        .into_iter()
+        .find(|r| r.alias == alias && !r.target.contains(' '))
+}
+
+fn parse_record(id: usize, line: &str) -> Record {
+    let parts: Vec<&str> = line.split('\t').collect();
+    let part = |i: usize| parts.get(i).copied().unwrap_or("");
+    Record {
+        id,
+        time: part(0).replace('T', " ").trim_end_matches('Z').to_string(),
+        alias: part(1).to_string(),
+        target: part(2).to_string(),
+        duration_secs: part(3).parse().ok(),
+        exit_code: part(4).parse().ok(),
+        recording: Some(part(5)).filter(|r| !r.is_empty()).map(str::to_string),
+        reason: part(6).to_string(),
+        danger: parts.get(7).map(|d| *d == "danger"),
+    }
+}
+
 /// Display the last `n` audit log entries.
 pub fn show_recent(n: usize) -> Result<()> {
     let path = crate::config::data_dir()?.join("audit.log");
@@ -31,61 +121,56 @@ pub fn show_recent(n: usize) -> Result<()> {
         return Ok(());
     }
 
-    let content = std::fs::read_to_string(&path)?;
-    let all_lines: Vec<&str> = content.lines().collect();
-    if all_lines.is_empty() {
+    let recent = recent(n)?;
+    if recent.is_empty() {
         println!("No connections recorded.");
         return Ok(());
     }
-
-    let start = all_lines.len().saturating_sub(n);
-    let recent: Vec<&str> = all_lines[start..].iter().rev().cloned().collect();
+    let id_w = recent.first().map(|r| r.id.to_string().len()).unwrap_or(2).max(2);
 
     // Column widths
-    let alias_w = recent
-        .iter()
-        .filter_map(|l| l.splitn(5, '\t').nth(1))
-        .map(|s| s.len())
-        .max()
-        .unwrap_or(5)
-        .max(5);
-    let target_w = recent
-        .iter()
-        .filter_map(|l| l.splitn(5, '\t').nth(2))
-        .map(|s| s.len())
-        .max()
-        .unwrap_or(6)
-        .max(6);
+    let alias_w = recent.iter().map(|r| r.alias.len()).max().unwrap_or(5).max(5);
+    let target_w = recent.iter().map(|r| r.target.len()).max().unwrap_or(6).max(6);
 
     println!(
-        "{:<19}  {:<alias_w$}  {:<target_w$}  {:>8}  {}",
-        "TIME", "ALIAS", "TARGET", "DURATION", "EXIT"
+        "{:>id_w$}  {:<19}  {:<alias_w$}  {:<target_w$}  {:>8}  {:<4}  {:<3}  REASON",
+        "ID", "TIME", "ALIAS", "TARGET", "DURATION", "EXIT", "REC"
     );
 
-    for line in &recent {
-        let parts: Vec<&str> = line.splitn(5, '\t').collect();
-        let ts = parts.first().copied().unwrap_or("");
-        let alias = parts.get(1).copied().unwrap_or("");
-        let target = parts.get(2).copied().unwrap_or("");
-        let duration = parts.get(3).copied().unwrap_or("").parse::<u64>().ok();
-        let exit_code = parts.get(4).copied().unwrap_or("").parse::<i32>().ok();
-
-        let display_ts = ts.replace('T', " ").trim_end_matches('Z').to_string();
-        let display_dur = duration.map(format_duration).unwrap_or_else(|| "-".into());
-        let display_exit = exit_code
+    for r in &recent {
+        let display_dur = r.duration_secs.map(format_duration).unwrap_or_else(|| "-".into());
+        let display_exit = r
+            .exit_code
             .map(|c| c.to_string())
             .unwrap_or_else(|| "-".into());
+        let recorded = if r.recording.is_some() { "●" } else { "" };
 
         println!(
-            "{:<19}  {:<alias_w$}  {:<target_w$}  {:>8}  {}",
-            display_ts, alias, target, display_dur, display_exit
+            "{:>id_w$}  {:<19}  {:<alias_w$}  {:<target_w$}  {:>8}  {:<4}  {:<3}  {}",
+            r.id, r.time, r.alias, r.target, display_dur, display_exit, recorded, r.reason
         );
     }
 
     Ok(())
 }
 
-fn format_duration(secs: u64) -> String {
+/// Replay the session recording attached to audit entry `id` (as shown by `oken audit`).
+pub fn play(id: usize) -> Result<()> {
+    let path = crate::config::data_dir()?.join("audit.log");
+    let content = std::fs::read_to_string(&path).unwrap_or_default();
+    let line = id
+        .checked_sub(1)
+        .and_then(|i| content.lines().nth(i))
+        .ok_or_else(|| anyhow::anyhow!("no audit entry with id {id}"))?;
+    let file = line
+        .split('\t')
+        .nth(5)
+        .filter(|f| !f.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("audit entry {id} has no recording"))?;
+    crate::recording::play(&crate::recording::recordings_dir()?.join(file))
+}
+
+pub fn format_duration(secs: u64) -> String {
     if secs < 60 {
         format!("{secs}s")
     } else if secs < 3600 {
@@ -0,0 +1,159 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+/// Install and start a login-time service that runs `oken tunnel start <name>`:
+/// a systemd user unit on Linux, a launchd agent on macOS.
+pub fn enable(name: &str) -> Result<PathBuf> {
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+    {
+        bail!("tunnel name '{name}' can't be used in a service name (use letters, digits, - _ .)");
+    }
+    let exe = std::env::current_exe().context("failed to locate the oken binary")?;
+    let path = service_path(name)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if cfg!(target_os = "macos") {
+        std::fs::write(&path, launchd_plist(&exe, name))?;
+        // Unload first so re-enabling picks up a changed plist
+        let _ = Command::new("launchctl").arg("unload").arg(&path).output();
+        run("launchctl", &["load", "-w", &path.to_string_lossy()])?;
+    } else {
+        std::fs::write(&path, systemd_unit(&exe, name))?;
+        run("systemctl", &["--user", "daemon-reload"])?;
+        run("systemctl", &["--user", "enable", "--now", &unit_name(name)])?;
+    }
+    Ok(path)
+}
+
+/// Stop and remove the service installed by `enable`.
+pub fn disable(name: &str) -> Result<()> {
+    let path = service_path(name)?;
+    if !path.exists() {
+        bail!("tunnel '{name}' is not enabled");
+    }
+    if cfg!(target_os = "macos") {
+        run("launchctl", &["unload", "-w", &path.to_string_lossy()])?;
+        std::fs::remove_file(&path)?;
+    } else {
+        run("systemctl", &["--user", "disable", "--now", &unit_name(name)])?;
+        std::fs::remove_file(&path)?;
+        run("systemctl", &["--user", "daemon-reload"])?;
+    }
+    Ok(())
+}
+
+fn unit_name(name: &str) -> String {
+    format!("oken-tunnel-{name}.service")
+}
+
+fn launchd_label(name: &str) -> String {
+    format!("com.oken.tunnel.{name}")
+}
+
+/// `~/.config/systemd/user/oken-tunnel-<name>.service` or
+/// `~/Library/LaunchAgents/com.oken.tunnel.<name>.plist`
+fn service_path(name: &str) -> Result<PathBuf> {
+    let home = dirs::home_dir().context("could not determine home directory")?;
+    Ok(if cfg!(target_os = "macos") {
+        home.join("Library/LaunchAgents")
+            .join(format!("{}.plist", launchd_label(name)))
+    } else {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .unwrap_or_else(|| home.join(".config"));
+        config.join("systemd/user").join(unit_name(name))
+    })
+}
+
+fn systemd_unit(exe: &Path, name: &str) -> String {
+    let exe = systemd_quote(&exe.to_string_lossy());
+    format!(
+        "[Unit]
+Description=oken tunnel {name}
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=oneshot
+RemainAfterExit=yes
+ExecStart={exe} tunnel start {name}
+ExecStop={exe} tunnel stop {name}
+
+[Install]
+WantedBy=default.target
+"
+    )
+}
+
+fn systemd_quote(s: &str) -> String {
+    if s.contains([' ', '"', '\\']) {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn launchd_plist(exe: &Path, name: &str) -> String {
+    let label = xml_escape(&launchd_label(name));
+    let exe = xml_escape(&exe.to_string_lossy());
+    let name = xml_escape(name);
+    // AbandonProcessGroup keeps the backgrounded ssh master alive after
+    // `oken tunnel start` itself exits
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+        <string>tunnel</string>
+        <string>start</string>
+        <string>{name}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>AbandonProcessGroup</key>
+    <true/>
+</dict>
+</plist>
+"#
+    )
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("failed to run {program}"))?;
+    if !status.success() {
+        bail!("`{program} {}` failed ({status})", args.join(" "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn systemd_unit_runs_tunnel_start() {
+        let unit = systemd_unit(Path::new("/opt/my tools/oken"), "db");
+        assert!(unit.contains("ExecStart=\"/opt/my tools/oken\" tunnel start db\n"));
+        assert!(unit.contains("ExecStop=\"/opt/my tools/oken\" tunnel stop db\n"));
+    }
+}
@@ -1,7 +1,8 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, ArgGroup, Args, Parser, Subcommand, ValueEnum, ValueHint};
 pub use clap_complete;
+use oken_core::oken_config::OpenIn;
 
 #[derive(Parser)]
 #[command(
@@ -26,6 +27,18 @@ pub struct Cli {
     #[arg(long)]
     pub tag: Option<String>,
 
+    /// With --tag, connect to one of the matching hosts instead of opening the picker
+    #[arg(
+        long,
+        value_enum,
+        value_name = "HOW",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "lru",
+        requires = "tag"
+    )]
+    pub any: Option<AnyStrategy>,
+
     /// Skip the production-host warning prompt
     #[arg(long)]
     pub yes: bool,
@@ -34,11 +47,90 @@ pub struct Cli {
     #[arg(long = "no-reconnect")]
     pub no_reconnect: bool,
 
+    /// Record the session (replay later with `oken audit play <id>`)
+    #[arg(long)]
+    pub record: bool,
+
+    /// Connect with mosh instead of ssh
+    #[arg(long)]
+    pub mosh: bool,
+
+    /// Start a look-don't-touch shell that refuses destructive commands
+    #[arg(long)]
+    pub safe: bool,
+
+    /// Reason for connecting, required for danger hosts in compliance mode
+    #[arg(long)]
+    pub reason: Option<String>,
+
+    /// Connect to the host with exactly this alias, even if other aliases contain it
+    #[arg(long)]
+    pub exact: bool,
+
+    /// Open the connection in a new tmux window (zellij: tab) named after the host
+    #[arg(long, conflicts_with_all = ["tmux_split", "here"])]
+    pub tmux_window: bool,
+
+    /// Open the connection in a new tmux or zellij pane split off this one
+    #[arg(long, conflicts_with = "here")]
+    pub tmux_split: bool,
+
+    /// Connect in this terminal, even if `picker_open` says otherwise
+    #[arg(long)]
+    pub here: bool,
+
+    /// Print the command that would run (connect, tunnel start, push, pull) instead of running it
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Explain what oken is doing on stderr (-vv for more detail)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Only print errors, without update and connection notices
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// When to use colors (auto honors NO_COLOR and skips output that isn't a terminal)
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t, global = true)]
+    pub color: ColorChoice,
+
     /// Arguments to pass through to ssh
-    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_hint = ValueHint::Hostname)]
     pub ssh_args: Vec<String>,
 }
 
+impl Cli {
+    /// Where `--here`, `--tmux-window` or `--tmux-split` asked to connect.
+    pub fn open_in(&self) -> Option<OpenIn> {
+        if self.tmux_window {
+            Some(OpenIn::Window)
+        } else if self.tmux_split {
+            Some(OpenIn::Split)
+        } else {
+            self.here.then_some(OpenIn::Here)
+        }
+    }
+}
+
+/// How `--any` picks a host from a group.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum AnyStrategy {
+    /// The one connected to longest ago (or never), according to history
+    Lru,
+    /// The next one by alias after the last host `--any` picked for this tag
+    RoundRobin,
+}
+
+/// When oken uses ANSI colors (`--color`).
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
 #[derive(Subcommand)]
 pub enum Command {
     /// Manage SSH hosts
@@ -46,70 +138,392 @@ pub enum Command {
         #[command(subcommand)]
         command: HostCommand,
     },
+    /// List the tags on saved hosts
+    Tag {
+        #[command(subcommand)]
+        command: TagCommand,
+    },
     /// Manage SSH tunnels
     Tunnel {
         #[command(subcommand)]
         command: TunnelCommand,
     },
-    /// Execute commands on remote hosts
+    /// Mount a host's files locally with sshfs (at ~/mnt/<alias> by default)
+    #[command(args_conflicts_with_subcommands = true)]
+    Mount {
+        #[command(subcommand)]
+        command: Option<MountCommand>,
+        /// Host alias (or user@host)
+        #[arg(value_hint = ValueHint::Hostname)]
+        host: Option<String>,
+        /// Remote directory (default: your home directory)
+        path: Option<String>,
+        /// Local directory to mount on
+        #[arg(long)]
+        at: Option<PathBuf>,
+    },
+    /// Unmount a directory mounted with `oken mount`
+    Umount {
+        /// Host alias or mountpoint
+        target: String,
+    },
+    /// Copy local files to a host (into the last directory used with it, or your home)
+    Push {
+        /// Host alias (or user@host)
+        #[arg(value_hint = ValueHint::Hostname)]
+        host: String,
+        /// Local files or directories
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Remote directory, remembered for next time (`~` for your home)
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Copy files from a host (relative paths start in the last directory used with it)
+    Pull {
+        /// Host alias (or user@host)
+        #[arg(value_hint = ValueHint::Hostname)]
+        host: String,
+        /// Remote files or directories
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Local directory to copy into
+        #[arg(long, default_value = ".")]
+        to: PathBuf,
+    },
+    /// Connect to a saved host through another saved host as the jump host
+    Via {
+        /// Jump host alias
+        #[arg(value_hint = ValueHint::Hostname)]
+        bastion: String,
+        /// Destination host alias
+        #[arg(value_hint = ValueHint::Hostname)]
+        host: String,
+    },
+    /// Open a host in VS Code over Remote-SSH
+    Code {
+        /// Host alias (or user@host)
+        #[arg(value_hint = ValueHint::Hostname)]
+        host: String,
+        /// Remote folder to open
+        path: Option<String>,
+    },
+    /// Start a one-off background port forward without saving a tunnel profile
+    Forward {
+        /// Host alias (or user@host)
+        #[arg(required_unless_present = "stop", value_hint = ValueHint::Hostname)]
+        host: Option<String>,
+        /// Forwards: 5432 (same port on the host's localhost) or 15432:db.internal:5432
+        #[arg(required_unless_present = "stop")]
+        specs: Vec<String>,
+        /// Forward ports on the remote host back to this machine (-R) instead
+        #[arg(short = 'R', long)]
+        remote: bool,
+        /// Stop a forward started earlier
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["host", "specs", "remote"])]
+        stop: Option<String>,
+    },
+    /// Open an interactive sftp session with a saved host's user, port and key
+    Sftp {
+        /// Host alias (or user@host)
+        #[arg(value_hint = ValueHint::Hostname)]
+        host: String,
+    },
+    /// Import hosts from cloud providers into hosts.toml
+    Cloud {
+        #[command(subcommand)]
+        command: CloudCommand,
+    },
+    /// Scan a subnet for SSH servers and pick which ones to save
+    Discover {
+        /// Range to scan, e.g. 10.0.1.0/24
+        cidr: String,
+        /// Port to probe
+        #[arg(long, default_value_t = 22)]
+        port: u16,
+        /// Connections to try at once
+        #[arg(long, default_value_t = 64)]
+        concurrency: usize,
+        /// Connect timeout per address, in milliseconds
+        #[arg(long, default_value_t = 500)]
+        timeout: u64,
+        /// Login user for the saved hosts
+        #[arg(long)]
+        user: Option<String>,
+        /// Tags for the saved hosts
+        #[arg(long, num_args = 1..)]
+        tag: Vec<String>,
+    },
+    /// Check which saved hosts answer on their ssh port
+    Ping {
+        /// Only hosts with one of these tags
+        #[arg(long, num_args = 1..)]
+        tag: Vec<String>,
+        /// Print results as JSON
+        #[arg(long)]
+        json: bool,
+        /// Give up on a host after this many milliseconds
+        #[arg(long, default_value_t = 3000)]
+        timeout: u64,
+    },
+    /// Open several hosts in tmux panes and type into all of them at once
+    Cluster {
+        /// Saved hosts to open
+        hosts: Vec<String>,
+        /// Also open every saved host with one of these tags
+        #[arg(long, num_args = 1..)]
+        tag: Vec<String>,
+    },
+    /// Run a command on several hosts at once, streaming their output
     Exec {
-        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
-        args: Vec<String>,
+        /// Saved hosts to run on
+        hosts: Vec<String>,
+        /// Also run on every saved host with one of these tags
+        #[arg(long, num_args = 1..)]
+        tag: Vec<String>,
+        /// Also run on the hosts read from stdin, one alias or user@host per line
+        #[arg(long)]
+        stdin: bool,
+        /// How many hosts to run on at the same time
+        #[arg(long, default_value_t = 32)]
+        parallel: usize,
+        /// Run the command with sudo, asking for the password once
+        #[arg(long)]
+        sudo: bool,
+        /// Run the command with sudo as this user (default: the host's become_user, or root)
+        #[arg(long, value_name = "USER")]
+        become_user: Option<String>,
+        /// The command to run, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Serve a read-only dashboard of hosts, tunnels and recent sessions on 127.0.0.1
+    Web {
+        /// Port to listen on
+        #[arg(long, default_value_t = 7370)]
+        port: u16,
+    },
+    /// Wake a host with a Wake-on-LAN packet, wait for ssh, then connect
+    Wake {
+        /// Saved host with a mac_address
+        #[arg(value_hint = ValueHint::Hostname)]
+        host: String,
+        /// Send the packet from this host on the sleeping host's network
+        /// (default: the host's last ProxyJump hop, or this machine)
+        #[arg(long, value_name = "HOST", conflicts_with = "local")]
+        via: Option<String>,
+        /// Broadcast the packet from this machine even when the host has a jump host
+        #[arg(long)]
+        local: bool,
+        /// Give up waiting for ssh after this many seconds
+        #[arg(long, default_value_t = 180)]
+        timeout: u64,
+        /// Only wake the host and wait for ssh; don't connect
+        #[arg(long)]
+        no_connect: bool,
     },
-    /// Manage command snippets
+    /// Reboot a host, after confirming (production hosts as for connecting)
+    Reboot {
+        /// Saved host (or user@host)
+        #[arg(value_hint = ValueHint::Hostname)]
+        host: String,
+        /// When to reboot: now, +MINUTES or HH:MM (passed on to shutdown)
+        #[arg(long, default_value = "now", allow_hyphen_values = true)]
+        when: String,
+    },
+    /// Power off a host, after confirming (production hosts as for connecting)
+    Shutdown {
+        /// Saved host (or user@host)
+        #[arg(value_hint = ValueHint::Hostname)]
+        host: String,
+        /// When to shut down: now, +MINUTES or HH:MM (passed on to shutdown)
+        #[arg(long, default_value = "now", allow_hyphen_values = true)]
+        when: String,
+    },
+    /// Run the commands saved under [snippets] in config.toml
     Snippet {
-        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
-        args: Vec<String>,
+        #[command(subcommand)]
+        command: SnippetCommand,
     },
     /// Print the resolved SSH command for a host
     Print {
         /// Alias or host to resolve
+        #[arg(value_hint = ValueHint::Hostname)]
         host: String,
+        /// Print the binary, argv and where each part came from as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show sessions and time spent in them per week, from the audit log
+    Stats {
+        /// Only count sessions on danger hosts (danger_tags and [[danger_rules]])
+        #[arg(long)]
+        danger: bool,
+        /// Number of weeks to show, this one included
+        #[arg(long, default_value_t = 8)]
+        weeks: u32,
     },
     /// View connection history
     Audit {
+        #[command(subcommand)]
+        command: Option<AuditCommand>,
         /// Number of recent entries to show
         #[arg(short = 'n', long, default_value_t = 50)]
         lines: usize,
     },
     /// Manage SSH keys
     Keys {
-        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
-        args: Vec<String>,
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+    /// Print the current TOTP code for a host, or manage its seed
+    Otp {
+        host: String,
+        /// Save the host's TOTP seed (base32 or otpauth:// URI, read from stdin) in the keyring
+        #[arg(long, conflicts_with_all = ["remove", "copy"])]
+        set: bool,
+        /// Delete the host's TOTP seed from the keyring
+        #[arg(long, conflicts_with = "copy")]
+        remove: bool,
+        /// Copy the code to the clipboard instead of printing it
+        #[arg(short, long)]
+        copy: bool,
     },
     /// Export oken configuration
     Export {
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
     },
-    /// Import oken configuration
+    /// Import hosts from other tools into hosts.toml
+    #[command(group(ArgGroup::new("source").required(true)))]
     Import {
-        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
-        args: Vec<String>,
+        /// Nodes of a Kubernetes cluster, via kubectl
+        #[arg(long, group = "source")]
+        from_kubectl: bool,
+        /// kubectl context to read (default: the current one)
+        #[arg(long, requires = "from_kubectl")]
+        context: Option<String>,
+        /// Machines of a Vagrant project, via `vagrant ssh-config`
+        #[arg(
+            long,
+            group = "source",
+            value_name = "DIR",
+            num_args = 0..=1,
+            default_missing_value = "."
+        )]
+        from_vagrant: Option<PathBuf>,
+        /// Docker contexts with ssh:// endpoints
+        #[arg(long, group = "source")]
+        from_docker: bool,
+        /// Instances in a terraform state (a directory, or a state .json file)
+        #[arg(long, group = "source", value_name = "PATH")]
+        from_terraform: Option<PathBuf>,
+        /// Read hosts with this [mappings.<name>] table from config.toml
+        #[arg(long, value_name = "NAME", requires = "from_terraform")]
+        mapping: Option<String>,
+        /// Format of the INVENTORY file
+        #[arg(long, value_enum, group = "source", requires = "inventory")]
+        format: Option<ImportFormat>,
+        /// Inventory file to read with --format (INI or YAML)
+        #[arg(requires = "format")]
+        inventory: Option<PathBuf>,
+        /// Also remove previously imported hosts that are gone from the source
+        #[arg(long)]
+        prune: bool,
+        /// Which address to connect to (Kubernetes: ExternalIP is public, InternalIP private)
+        #[arg(long, value_enum, default_value_t = AddressChoice::Auto)]
+        ip: AddressChoice,
+        /// Login user for the imported hosts
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Run or query okend, the background tunnel and host-status daemon
+    Daemon {
+        #[command(subcommand)]
+        command: DaemonCommand,
     },
     /// Show current configuration (defaults + ~/.config/oken/config.toml overrides)
-    Config,
-    /// Check for a newer version and print upgrade instructions
+    Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommand>,
+    },
+    /// Check your setup (ssh, config files, keys, agent) and suggest fixes
+    Doctor,
+    /// Check for a newer version and show its release notes
     Update,
-    /// Install shell completions (auto-detects shell and directory)
+    /// Download the latest release for this platform and replace this binary
+    SelfUpdate {
+        /// Install this version instead of the latest (e.g. 0.3.7)
+        #[arg(long)]
+        version: Option<String>,
+    },
+    /// Print the shell completion script, or install it with --install
     Completions {
-        /// Shell to install for (default: auto-detected from $SHELL)
+        /// Shell to generate for (default: auto-detected from $SHELL)
         #[arg(long)]
         shell: Option<clap_complete::Shell>,
-        /// Directory to write the completion file into (default: auto-detected)
+        /// Write the script where the shell loads it and set the shell up to find it
         #[arg(long)]
+        install: bool,
+        /// Directory to install the completion file into (default: auto-detected)
+        #[arg(long, requires = "install")]
+        dir: Option<PathBuf>,
+    },
+    /// Print the oken(1) man page, or write pages for every command with --dir
+    Man {
+        /// Write oken.1 and an oken-<command>.1 per subcommand into this directory
+        #[arg(long, value_hint = ValueHint::DirPath)]
         dir: Option<PathBuf>,
     },
 }
 
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the active value of a setting, e.g. `reconnect` or `hooks.on_connect`
+    Get { key: String },
+    /// Write a setting to config.toml, keeping its comments
+    Set {
+        key: String,
+        /// The value; lists take several, or a TOML array like '["a", "b"]'
+        #[arg(required = true, num_args = 1.., allow_hyphen_values = true)]
+        values: Vec<String>,
+    },
+    /// Remove a setting from config.toml so its default applies again
+    Unset { key: String },
+    /// Open config.toml in $EDITOR and check it for typos and bad values
+    Edit,
+}
+
+#[derive(Subcommand)]
+pub enum TagCommand {
+    /// List tags with the hosts that carry them
+    List {
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: ListFormat,
+    },
+}
+
+/// How `host list` and `tag list` print.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    /// Aligned columns for reading
+    #[default]
+    Table,
+    /// A JSON array
+    Json,
+    /// Tab-separated values with a header row
+    Tsv,
+}
+
 #[derive(Subcommand)]
 pub enum HostCommand {
-    /// Add a new host
+    /// Add a new host (run without a target to be asked step by step)
     Add {
         /// Alias name for the host
-        name: String,
+        name: Option<String>,
         /// Target in the form user@host or just host
-        target: String,
+        target: Option<String>,
         /// SSH port
         #[arg(long)]
         port: Option<u16>,
@@ -119,40 +533,167 @@ pub enum HostCommand {
         /// Tags for organizing hosts
         #[arg(long, num_args = 1..)]
         tag: Vec<String>,
+        /// Record every session to this host
+        #[arg(long)]
+        record: bool,
+        /// Connect through a ProxyCommand preset (e.g. aws-ssm) or command
+        #[arg(long)]
+        proxy: Option<String>,
+        /// Hide the host after this long (e.g. 12h, 7d, 2w)
+        #[arg(long, conflicts_with = "expires")]
+        ttl: Option<String>,
+        /// Hide the host after this date (YYYY-MM-DD or YYYY-MM-DDTHH:MM:SSZ, UTC)
+        #[arg(long)]
+        expires: Option<String>,
+        /// Who to ask before changing or rebooting the host
+        #[arg(long)]
+        owner: Option<String>,
+        /// Team responsible for the host
+        #[arg(long)]
+        team: Option<String>,
+        /// Pick hosts to save from the ones connected to before without saving
+        #[arg(long, conflicts_with_all = ["name", "target", "port", "key"])]
+        from_history: bool,
     },
     /// List all configured hosts
-    List,
+    List {
+        /// Show only expired hosts, and offer to remove them
+        #[arg(long)]
+        expired: bool,
+        /// Only hosts owned by this person
+        #[arg(long)]
+        owner: Option<String>,
+        /// Only hosts of this team
+        #[arg(long)]
+        team: Option<String>,
+        /// Output format; json and tsv include every field, for scripts
+        #[arg(long, value_enum, default_value_t)]
+        format: ListFormat,
+    },
+    /// Show everything saved for one host, and its certificate's validity
+    Show {
+        /// Alias name of the host
+        #[arg(value_hint = ValueHint::Hostname)]
+        name: String,
+    },
+    /// Find hosts you no longer use and archive or delete them
+    Prune {
+        /// Flag hosts not connected to in this many months
+        #[arg(long, default_value_t = 6)]
+        months: u64,
+        /// Skip checking that hostnames still resolve
+        #[arg(long)]
+        no_dns: bool,
+    },
+    /// Find hosts that reach the same machine and merge them
+    Dedupe,
     /// Remove a host by name
     Remove {
         /// Alias name of the host to remove
+        #[arg(value_hint = ValueHint::Hostname)]
         name: String,
     },
     /// Open hosts.toml in $EDITOR
     Edit {
         /// Alias name (currently opens the whole file)
+        #[arg(value_hint = ValueHint::Hostname)]
         name: Option<String>,
     },
 }
 
+#[derive(Subcommand)]
+pub enum SnippetCommand {
+    /// List the saved snippets
+    List,
+    /// Run a snippet on several hosts at once, like `oken exec`
+    Run {
+        /// Snippet name
+        name: String,
+        /// Saved hosts to run on
+        hosts: Vec<String>,
+        /// Also run on every saved host with one of these tags
+        #[arg(long, num_args = 1..)]
+        tag: Vec<String>,
+        /// How many hosts to run on at the same time
+        #[arg(long, default_value_t = 32)]
+        parallel: usize,
+        /// Run with sudo even if the snippet doesn't ask for it
+        #[arg(long)]
+        sudo: bool,
+        /// Run with sudo as this user (default: the snippet's become_user)
+        #[arg(long, value_name = "USER")]
+        become_user: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum TunnelCommand {
     /// Add a new tunnel profile (e.g., oken tunnel add db -L 5432:localhost:5432 prod-db)
     Add {
         /// Tunnel profile name
         name: String,
+        /// Tags for bulk operations (e.g. --tag work --tag db)
+        #[arg(long)]
+        tag: Vec<String>,
         /// SSH flags and target host
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
     },
-    /// Start a saved tunnel in the background
+    /// Start saved tunnels in the background
     Start {
-        /// Tunnel profile name
-        name: String,
+        #[command(flatten)]
+        select: TunnelSelect,
+        /// If a local forward port is taken, use the next free one instead
+        #[arg(long)]
+        auto_port: bool,
+        /// Check that each local forward reaches a listening remote service
+        #[arg(long)]
+        verify: bool,
+        /// Values for a template tunnel's placeholders (e.g. --host staging-db --local 15432)
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "PARAMS")]
+        params: Vec<String>,
     },
-    /// Stop a running tunnel
+    /// Stop running tunnels
     Stop {
+        #[command(flatten)]
+        select: TunnelSelect,
+    },
+    /// Stop and start tunnels again (e.g. after switching networks)
+    Restart {
+        #[command(flatten)]
+        select: TunnelSelect,
+        /// If a local forward port is taken, use the next free one instead
+        #[arg(long)]
+        auto_port: bool,
+        /// Check that each local forward reaches a listening remote service
+        #[arg(long)]
+        verify: bool,
+        /// Values for a template tunnel's placeholders (e.g. --host staging-db --local 15432)
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "PARAMS")]
+        params: Vec<String>,
+    },
+    /// Change a saved tunnel (opens just that entry in $EDITOR when no flags are given)
+    Edit {
         /// Tunnel profile name
         name: String,
+        /// New target host
+        #[arg(long, value_hint = ValueHint::Hostname)]
+        host: Option<String>,
+        /// Add a local forward (-L), e.g. 6379:localhost:6379
+        #[arg(long, value_name = "SPEC")]
+        add_forward: Vec<String>,
+        /// Add a remote forward (-R)
+        #[arg(long, value_name = "SPEC")]
+        add_remote: Vec<String>,
+        /// Remove the -L/-R/-D forward with this spec
+        #[arg(long, value_name = "SPEC")]
+        remove_forward: Vec<String>,
+        /// Add a tag
+        #[arg(long)]
+        tag: Vec<String>,
+        /// Remove a tag
+        #[arg(long)]
+        untag: Vec<String>,
     },
     /// Remove a saved tunnel profile
     Remove {
@@ -160,5 +701,230 @@ pub enum TunnelCommand {
         name: String,
     },
     /// List all tunnel profiles and their status
+    ///
+    /// With -v, also show open connections, traffic, and when each tunnel was last used.
+    List {
+        /// Print state, PID, socket and parsed forwards as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Start a tunnel automatically at login (systemd user unit / launchd agent)
+    Enable {
+        /// Tunnel profile name
+        name: String,
+    },
+    /// Stop starting a tunnel at login
+    Disable {
+        /// Tunnel profile name
+        name: String,
+    },
+    /// Show a tunnel's ssh output (auth errors, forwarding failures, …)
+    Logs {
+        /// Tunnel profile name
+        name: String,
+        /// Keep printing new output as it is written
+        #[arg(short, long)]
+        follow: bool,
+        /// Number of lines to show
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+    },
+    /// Keep tunnels running, restarting them with backoff when they die
+    Watch {
+        /// Tunnel profile names (default: all)
+        names: Vec<String>,
+        /// Seconds between health checks
+        #[arg(long, default_value_t = 10)]
+        interval: u64,
+    },
+}
+
+/// Which tunnels a start/stop/restart applies to.
+#[derive(Args)]
+pub struct TunnelSelect {
+    /// Tunnel profile name
+    #[arg(required_unless_present_any = ["all", "tag"])]
+    pub name: Option<String>,
+    /// Every saved tunnel
+    #[arg(long, conflicts_with_all = ["name", "tag"])]
+    pub all: bool,
+    /// Every tunnel with this tag
+    #[arg(long, conflicts_with = "name")]
+    pub tag: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum CloudCommand {
+    /// Write a provider's machines into the [managed.<provider>] section of hosts.toml
+    Sync {
+        #[command(subcommand)]
+        provider: SyncProvider,
+        /// Also remove previously synced hosts the provider no longer returns
+        #[arg(long, global = true)]
+        prune: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SyncProvider {
+    /// EC2 instances, via the aws CLI (aliases from the Name tag)
+    Aws {
+        /// Named profile from ~/.aws/config
+        #[arg(long)]
+        profile: Option<String>,
+        /// Region (default: the profile's region)
+        #[arg(long)]
+        region: Option<String>,
+        /// Only instances matching this filter, e.g. tag:Team=infra (repeatable)
+        #[arg(long, value_name = "KEY=VALUE")]
+        filter: Vec<String>,
+        /// Which address to connect to
+        #[arg(long, value_enum, default_value_t = AddressChoice::Auto)]
+        ip: AddressChoice,
+        /// Login user for the imported hosts (e.g. ec2-user, ubuntu)
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Compute Engine instances, via the gcloud CLI (IAP tunnels for private ones)
+    Gcp {
+        /// Project ID (default: gcloud's configured project)
+        #[arg(long)]
+        project: Option<String>,
+        /// Only instances matching this gcloud filter, e.g. labels.team=infra (repeatable)
+        #[arg(long, value_name = "EXPR")]
+        filter: Vec<String>,
+        /// Which address to connect to
+        #[arg(long, value_enum, default_value_t = AddressChoice::Auto)]
+        ip: AddressChoice,
+        /// Login user for the imported hosts
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Peers on your tailnet, from `tailscale status` (by MagicDNS name)
+    Tailscale {
+        /// Use each peer's Tailscale IP instead of its MagicDNS name
+        #[arg(long)]
+        by_ip: bool,
+        /// Login user for the imported hosts
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Any JSON inventory endpoint, read with a [mappings.<name>] table (or NetBox)
+    Http {
+        /// Mapping from config.toml; its name is also the hosts.toml section
+        #[arg(required_unless_present = "netbox")]
+        mapping: Option<String>,
+        /// Endpoint to fetch (default: the mapping's url)
+        #[arg(long, conflicts_with = "netbox")]
+        url: Option<String>,
+        /// NetBox base URL; imports devices and virtual machines with a primary IP
+        #[arg(long, value_name = "URL", conflicts_with = "mapping")]
+        netbox: Option<String>,
+        /// API token (default: $OKEN_HTTP_TOKEN, or $NETBOX_TOKEN with --netbox)
+        #[arg(long)]
+        token: Option<String>,
+        /// Login user for the imported hosts
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// DigitalOcean droplets (API token from --token or $DIGITALOCEAN_TOKEN)
+    Digitalocean {
+        #[command(flatten)]
+        api: ApiSync,
+    },
+    /// Hetzner Cloud servers (API token from --token or $HCLOUD_TOKEN)
+    Hetzner {
+        #[command(flatten)]
+        api: ApiSync,
+    },
+    /// Linode instances (API token from --token or $LINODE_TOKEN)
+    Linode {
+        #[command(flatten)]
+        api: ApiSync,
+    },
+}
+
+/// Options shared by the API-token sync providers.
+#[derive(Args)]
+pub struct ApiSync {
+    /// API token (read-only is enough)
+    #[arg(long)]
+    pub token: Option<String>,
+    /// Which address to connect to
+    #[arg(long, value_enum, default_value_t = AddressChoice::Auto)]
+    pub ip: AddressChoice,
+    /// Login user for the imported hosts (e.g. root)
+    #[arg(long)]
+    pub user: Option<String>,
+}
+
+/// Inventory formats `oken import --format` reads.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// Ansible inventory, INI or YAML; groups become tags
+    Ansible,
+}
+
+/// Which of a cloud machine's addresses to save as its hostname.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AddressChoice {
+    /// The public address, else a tunnel by ID (AWS SSM, GCP IAP), else the private address
+    Auto,
+    /// Only machines with a public address
+    Public,
+    /// The private address (over a VPN or from inside the network)
+    Private,
+}
+
+#[derive(Subcommand)]
+pub enum MountCommand {
+    /// Show current sshfs mounts
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum DaemonCommand {
+    /// Run the daemon in the foreground (for systemd, launchd, or a spare terminal)
+    Run,
+    /// Show what a running daemon knows about hosts and tunnels
+    Status,
+    /// Measure round-trip times to every host once and cache them for the RTT column
+    Probe,
+}
+
+#[derive(Subcommand)]
+pub enum KeysCommand {
+    /// List keys in ~/.ssh and the agent, with certificate validity
     List,
+    /// Create an ed25519 key pair in ~/.ssh, optionally on a FIDO2 security key
+    Generate {
+        /// File name in ~/.ssh (default: id_ed25519, or id_ed25519_sk with --sk)
+        name: Option<String>,
+        /// Keep the private key on a FIDO2 security key (ed25519-sk)
+        #[arg(long)]
+        sk: bool,
+        /// Store the key on the security key itself, to download on other machines
+        #[arg(long, requires = "sk")]
+        resident: bool,
+        /// Comment stored with the key (default: user@hostname)
+        #[arg(short = 'C', long)]
+        comment: Option<String>,
+    },
+    /// Download resident keys from a FIDO2 security key into ~/.ssh
+    Download,
+    /// Delete a host's entries from known_hosts, e.g. after it was rebuilt
+    Forget {
+        /// Saved host alias, or a hostname
+        #[arg(value_hint = ValueHint::Hostname)]
+        host: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AuditCommand {
+    /// Replay a recorded session by its audit ID
+    Play {
+        /// Entry ID from `oken audit`
+        id: usize,
+    },
 }
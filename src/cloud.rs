@@ -0,0 +1,813 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::Ipv4Addr;
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::cli::AddressChoice;
+use crate::hosts_toml::{Connect, HostEntry};
+use crate::mapping::{self, Mapping, slug};
+use crate::util;
+
+/// A machine as reported by a provider, before it becomes a hosts.toml entry.
+#[derive(Debug, Default)]
+pub struct Machine {
+    /// Provider ID, used as the alias when the machine has no name.
+    pub id: String,
+    pub name: Option<String>,
+    pub public_ip: Option<String>,
+    pub private_ip: Option<String>,
+    pub tags: Vec<String>,
+    pub identity_file: Option<String>,
+    /// Per-machine args for the fallback connection (e.g. its zone).
+    pub connect_args: Vec<String>,
+}
+
+/// How `--ip auto` reaches machines with no public address, by ID through
+/// a cloud service instead of by private IP.
+pub struct Fallback {
+    pub connect: Connect,
+    pub connect_args: Vec<String>,
+}
+
+/// Options for `oken cloud sync aws`.
+pub struct AwsOptions {
+    pub profile: Option<String>,
+    pub region: Option<String>,
+    pub filters: Vec<String>,
+}
+
+impl AwsOptions {
+    /// Session Manager, with the same profile and region as the sync.
+    pub fn ssm_fallback(&self) -> Fallback {
+        let mut connect_args = Vec::new();
+        if let Some(profile) = &self.profile {
+            connect_args.extend(["--profile".to_string(), profile.clone()]);
+        }
+        if let Some(region) = &self.region {
+            connect_args.extend(["--region".to_string(), region.clone()]);
+        }
+        Fallback {
+            connect: Connect::Ssm,
+            connect_args,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeInstances {
+    #[serde(default)]
+    reservations: Vec<Reservation>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Reservation {
+    #[serde(default)]
+    instances: Vec<Instance>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Instance {
+    instance_id: String,
+    public_ip_address: Option<String>,
+    private_ip_address: Option<String>,
+    key_name: Option<String>,
+    #[serde(default)]
+    tags: Vec<Tag>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Tag {
+    key: String,
+    value: String,
+}
+
+/// Running EC2 instances, via `aws ec2 describe-instances`.
+pub fn aws_machines(opts: &AwsOptions) -> Result<Vec<Machine>> {
+    if !util::on_path("aws") {
+        bail!("aws CLI not found on PATH (https://aws.amazon.com/cli/)");
+    }
+    let mut cmd = Command::new("aws");
+    cmd.args(["ec2", "describe-instances", "--output", "json"]);
+    if let Some(profile) = &opts.profile {
+        cmd.args(["--profile", profile]);
+    }
+    if let Some(region) = &opts.region {
+        cmd.args(["--region", region]);
+    }
+    cmd.args(["--filters", "Name=instance-state-name,Values=running"]);
+    for filter in &opts.filters {
+        cmd.arg(aws_filter(filter)?);
+    }
+    let output = cmd.output().context("failed to run aws")?;
+    if !output.status.success() {
+        bail!(
+            "aws ec2 describe-instances failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    parse_aws(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// `tag:Team=infra` -> `Name=tag:Team,Values=infra`
+fn aws_filter(filter: &str) -> Result<String> {
+    let Some((name, value)) = filter.split_once('=') else {
+        bail!("invalid filter '{filter}' (expected KEY=VALUE, e.g. tag:Team=infra)");
+    };
+    Ok(format!("Name={name},Values={value}"))
+}
+
+fn parse_aws(json: &str) -> Result<Vec<Machine>> {
+    let parsed: DescribeInstances =
+        serde_json::from_str(json).context("unexpected aws ec2 describe-instances output")?;
+    let home = dirs::home_dir();
+    Ok(parsed
+        .reservations
+        .into_iter()
+        .flat_map(|r| r.instances)
+        .map(|i| {
+            let name = i.tags.iter().find(|t| t.key == "Name").map(|t| t.value.clone());
+            // Tag values become oken tags, so Environment=prod matches danger_tags
+            let values = i
+                .tags
+                .iter()
+                .filter(|t| t.key != "Name" && !t.key.starts_with("aws:"))
+                .map(|t| t.value.clone());
+            let tags = provider_tags("aws", values);
+            // Use the launch key pair when it sits in ~/.ssh under the usual name
+            let identity_file = i.key_name.and_then(|k| {
+                let pem = home.as_ref()?.join(".ssh").join(format!("{k}.pem"));
+                pem.is_file().then(|| format!("~/.ssh/{k}.pem"))
+            });
+            Machine {
+                id: i.instance_id,
+                name,
+                public_ip: i.public_ip_address,
+                private_ip: i.private_ip_address,
+                tags,
+                identity_file,
+                connect_args: Vec::new(),
+            }
+        })
+        .collect())
+}
+
+/// Options for `oken cloud sync gcp`.
+pub struct GcpOptions {
+    pub project: Option<String>,
+    pub filters: Vec<String>,
+}
+
+impl GcpOptions {
+    /// IAP tunnels, in the same project as the sync.
+    pub fn iap_fallback(&self) -> Fallback {
+        Fallback {
+            connect: Connect::Iap,
+            connect_args: self
+                .project
+                .iter()
+                .flat_map(|p| ["--project".to_string(), p.clone()])
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GcpInstance {
+    name: String,
+    #[serde(default)]
+    zone: String,
+    #[serde(default)]
+    network_interfaces: Vec<GcpInterface>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GcpInterface {
+    #[serde(rename = "networkIP")]
+    network_ip: Option<String>,
+    #[serde(default)]
+    access_configs: Vec<GcpAccessConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GcpAccessConfig {
+    #[serde(rename = "natIP")]
+    nat_ip: Option<String>,
+}
+
+/// Running Compute Engine instances, via `gcloud compute instances list`.
+pub fn gcp_machines(opts: &GcpOptions) -> Result<Vec<Machine>> {
+    if !util::on_path("gcloud") {
+        bail!("gcloud CLI not found on PATH (https://cloud.google.com/sdk)");
+    }
+    let mut cmd = Command::new("gcloud");
+    cmd.args(["compute", "instances", "list", "--format=json"]);
+    if let Some(project) = &opts.project {
+        cmd.args(["--project", project]);
+    }
+    let filter = std::iter::once("status=RUNNING")
+        .chain(opts.filters.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    cmd.args(["--filter", &filter]);
+    let output = cmd.output().context("failed to run gcloud")?;
+    if !output.status.success() {
+        bail!(
+            "gcloud compute instances list failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    parse_gcp(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_gcp(json: &str) -> Result<Vec<Machine>> {
+    let instances: Vec<GcpInstance> =
+        serde_json::from_str(json).context("unexpected gcloud compute instances output")?;
+    Ok(instances
+        .into_iter()
+        .map(|i| {
+            let nic = i.network_interfaces.first();
+            let public_ip = nic
+                .and_then(|n| n.access_configs.iter().find_map(|a| a.nat_ip.clone()));
+            let tags = provider_tags("gcp", i.labels.into_values());
+            // `zone` is a URL ending in the zone name
+            let zone = i.zone.rsplit('/').next().unwrap_or_default().to_string();
+            Machine {
+                id: i.name.clone(),
+                name: Some(i.name),
+                public_ip,
+                private_ip: nic.and_then(|n| n.network_ip.clone()),
+                tags,
+                connect_args: if zone.is_empty() {
+                    Vec::new()
+                } else {
+                    vec!["--zone".to_string(), zone]
+                },
+                ..Default::default()
+            }
+        })
+        .collect())
+}
+
+/// An API-token provider: DigitalOcean, Hetzner Cloud or Linode.
+#[derive(Debug, Clone, Copy)]
+pub enum ApiProvider {
+    DigitalOcean,
+    Hetzner,
+    Linode,
+}
+
+impl ApiProvider {
+    /// Section name in hosts.toml, and the tag every synced host gets.
+    pub fn name(self) -> &'static str {
+        match self {
+            ApiProvider::DigitalOcean => "digitalocean",
+            ApiProvider::Hetzner => "hetzner",
+            ApiProvider::Linode => "linode",
+        }
+    }
+
+    fn token_var(self) -> &'static str {
+        match self {
+            ApiProvider::DigitalOcean => "DIGITALOCEAN_TOKEN",
+            ApiProvider::Hetzner => "HCLOUD_TOKEN",
+            ApiProvider::Linode => "LINODE_TOKEN",
+        }
+    }
+
+    /// Every machine on the account, following the API's pagination.
+    pub fn machines(self, token: Option<String>) -> Result<Vec<Machine>> {
+        let var = self.token_var();
+        let Some(token) = token.or_else(|| std::env::var(var).ok()).filter(|t| !t.is_empty())
+        else {
+            bail!("no API token for {} (pass --token or set ${var})", self.name());
+        };
+        let bearer = format!("Bearer {token}");
+        let mut machines = Vec::new();
+        for page in 1.. {
+            let (batch, more) = match self {
+                ApiProvider::DigitalOcean => {
+                    let url = format!(
+                        "https://api.digitalocean.com/v2/droplets?per_page=200&page={page}"
+                    );
+                    parse_digitalocean(get_json(&url, Some(&bearer))?)
+                }
+                ApiProvider::Hetzner => {
+                    let url =
+                        format!("https://api.hetzner.cloud/v1/servers?per_page=50&page={page}");
+                    parse_hetzner(get_json(&url, Some(&bearer))?)
+                }
+                ApiProvider::Linode => {
+                    let url = format!(
+                        "https://api.linode.com/v4/linode/instances?page_size=500&page={page}"
+                    );
+                    parse_linode(get_json(&url, Some(&bearer))?)
+                }
+            };
+            machines.extend(batch);
+            if !more {
+                break;
+            }
+        }
+        Ok(machines)
+    }
+}
+
+fn get_json<T: DeserializeOwned>(url: &str, authorization: Option<&str>) -> Result<T> {
+    let mut request = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(5))
+        .timeout(Duration::from_secs(30))
+        .build()
+        .get(url)
+        .set("User-Agent", &format!("oken/{}", env!("CARGO_PKG_VERSION")))
+        .set("Accept", "application/json");
+    if let Some(authorization) = authorization {
+        request = request.set("Authorization", authorization);
+    }
+    let body = request
+        .call()
+        .with_context(|| format!("request to {url} failed"))?
+        .into_string()?;
+    serde_json::from_str(&body).with_context(|| format!("unexpected response from {url}"))
+}
+
+#[derive(Deserialize)]
+struct DoDroplets {
+    droplets: Vec<DoDroplet>,
+    #[serde(default)]
+    links: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct DoDroplet {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    networks: DoNetworks,
+}
+
+#[derive(Deserialize)]
+struct DoNetworks {
+    #[serde(default)]
+    v4: Vec<DoAddress>,
+}
+
+#[derive(Deserialize)]
+struct DoAddress {
+    ip_address: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// A page of droplets, and whether there's a next one.
+fn parse_digitalocean(page: DoDroplets) -> (Vec<Machine>, bool) {
+    let more = !page.links["pages"]["next"].is_null();
+    let machines = page
+        .droplets
+        .into_iter()
+        .map(|d| {
+            let address = |kind: &str| {
+                d.networks.v4.iter().find(|a| a.kind == kind).map(|a| a.ip_address.clone())
+            };
+            Machine {
+                id: d.id.to_string(),
+                public_ip: address("public"),
+                private_ip: address("private"),
+                name: Some(d.name),
+                tags: provider_tags("digitalocean", d.tags),
+                ..Default::default()
+            }
+        })
+        .collect();
+    (machines, more)
+}
+
+#[derive(Deserialize)]
+struct HetznerServers {
+    servers: Vec<HetznerServer>,
+    #[serde(default)]
+    meta: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct HetznerServer {
+    id: u64,
+    name: String,
+    public_net: HetznerPublicNet,
+    #[serde(default)]
+    private_net: Vec<HetznerPrivateNet>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct HetznerPublicNet {
+    ipv4: Option<HetznerIp>,
+}
+
+#[derive(Deserialize)]
+struct HetznerIp {
+    ip: String,
+}
+
+#[derive(Deserialize)]
+struct HetznerPrivateNet {
+    ip: String,
+}
+
+fn parse_hetzner(page: HetznerServers) -> (Vec<Machine>, bool) {
+    let more = !page.meta["pagination"]["next_page"].is_null();
+    let machines = page
+        .servers
+        .into_iter()
+        .map(|s| Machine {
+            id: s.id.to_string(),
+            name: Some(s.name),
+            public_ip: s.public_net.ipv4.map(|v| v.ip),
+            private_ip: s.private_net.into_iter().next().map(|n| n.ip),
+            tags: provider_tags("hetzner", s.labels.into_values()),
+            ..Default::default()
+        })
+        .collect();
+    (machines, more)
+}
+
+#[derive(Deserialize)]
+struct LinodeInstances {
+    data: Vec<LinodeInstance>,
+    page: u32,
+    pages: u32,
+}
+
+#[derive(Deserialize)]
+struct LinodeInstance {
+    id: u64,
+    label: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    ipv4: Vec<String>,
+}
+
+fn parse_linode(page: LinodeInstances) -> (Vec<Machine>, bool) {
+    let more = page.page < page.pages;
+    let machines = page
+        .data
+        .into_iter()
+        .map(|l| {
+            // Linode lists public and private (192.168/16) addresses together
+            let (private, public): (Vec<_>, Vec<_>) = l
+                .ipv4
+                .into_iter()
+                .partition(|ip| ip.parse::<Ipv4Addr>().is_ok_and(|a| a.is_private()));
+            Machine {
+                id: l.id.to_string(),
+                name: Some(l.label),
+                public_ip: public.into_iter().next(),
+                private_ip: private.into_iter().next(),
+                tags: provider_tags("linode", l.tags),
+                ..Default::default()
+            }
+        })
+        .collect();
+    (machines, more)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TailscaleStatus {
+    #[serde(default)]
+    peer: BTreeMap<String, TailscalePeer>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TailscalePeer {
+    host_name: String,
+    #[serde(rename = "DNSName", default)]
+    dns_name: String,
+    #[serde(rename = "TailscaleIPs", default)]
+    tailscale_ips: Vec<String>,
+    #[serde(default)]
+    online: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl TailscalePeer {
+    /// MagicDNS name without the trailing dot.
+    fn magic_dns(&self) -> Option<String> {
+        Some(self.dns_name.trim_end_matches('.').to_string()).filter(|n| !n.is_empty())
+    }
+}
+
+fn tailscale_status() -> Result<TailscaleStatus> {
+    let output = Command::new("tailscale")
+        .args(["status", "--json"])
+        .output()
+        .context("failed to run tailscale")?;
+    if !output.status.success() {
+        bail!(
+            "tailscale status failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    serde_json::from_slice(&output.stdout).context("unexpected tailscale status output")
+}
+
+/// Peers on the tailnet, addressed by MagicDNS name (their Tailscale IP
+/// with `--ip private`). ACL tags like `tag:prod` become oken tags.
+pub fn tailscale_machines() -> Result<Vec<Machine>> {
+    if !util::on_path("tailscale") {
+        bail!("tailscale not found on PATH");
+    }
+    Ok(parse_tailscale(tailscale_status()?))
+}
+
+fn parse_tailscale(status: TailscaleStatus) -> Vec<Machine> {
+    status
+        .peer
+        .into_values()
+        .map(|p| {
+            let tags = p.tags.iter().map(|t| t.trim_start_matches("tag:").to_string());
+            Machine {
+                id: p.host_name.clone(),
+                public_ip: p.magic_dns(),
+                private_ip: p.tailscale_ips.first().cloned(),
+                tags: provider_tags("tailscale", tags),
+                name: Some(p.host_name),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Online state of every tailnet peer, keyed by MagicDNS name and by each
+/// Tailscale IP. Empty when tailscale isn't installed or running.
+pub fn tailscale_online() -> HashMap<String, bool> {
+    if !util::on_path("tailscale") {
+        return HashMap::new();
+    }
+    let Ok(status) = tailscale_status() else {
+        return HashMap::new();
+    };
+    let mut online = HashMap::new();
+    for peer in status.peer.into_values() {
+        for key in peer.magic_dns().into_iter().chain(peer.tailscale_ips.iter().cloned()) {
+            online.insert(key, peer.online);
+        }
+    }
+    online
+}
+
+/// The provider's name followed by the machine's own (deduplicated) tags.
+fn provider_tags(provider: &str, tags: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut out = vec![provider.to_string()];
+    for tag in tags {
+        if !tag.is_empty() && !out.contains(&tag) {
+            out.push(tag);
+        }
+    }
+    out
+}
+
+/// NetBox devices and virtual machines with a primary IP, tagged with
+/// their NetBox tags, role, site and tenant.
+pub fn netbox_mapping() -> Mapping {
+    let paths = |paths: &[&str]| paths.join(" || ");
+    Mapping {
+        items: "results[*]".to_string(),
+        alias: "name".to_string(),
+        hostname: paths(&["primary_ip4.address", "primary_ip.address"]),
+        user: None,
+        port: None,
+        tags: ["tags[*].slug", "role.slug || device_role.slug", "site.slug", "tenant.slug"]
+            .map(String::from)
+            .to_vec(),
+        url: None,
+        next: Some("next".to_string()),
+        auth: Some("Token".to_string()),
+    }
+}
+
+/// Every item `mapping` finds at `urls`, following its `next` links, as
+/// hosts. `token` is sent as `Authorization: <mapping.auth or Bearer> <token>`.
+pub fn http_hosts(
+    urls: &[String],
+    mapping: &Mapping,
+    token: Option<&str>,
+    source: &str,
+) -> Result<(BTreeMap<String, HostEntry>, usize)> {
+    let authorization = token.map(|t| {
+        format!("{} {t}", mapping.auth.as_deref().unwrap_or("Bearer"))
+    });
+    let mut items = Vec::new();
+    for url in urls {
+        let mut next = Some(url.clone());
+        while let Some(url) = next.take() {
+            let page: serde_json::Value = get_json(&url, authorization.as_deref())?;
+            items.extend(mapping::select(&page, &mapping.items).into_iter().cloned());
+            next = mapping.next.as_deref().and_then(|p| mapping::first_string(&page, p));
+        }
+    }
+    let flat = Mapping {
+        items: "[*]".to_string(),
+        ..mapping.clone()
+    };
+    Ok(flat.apply(&serde_json::Value::Array(items), source))
+}
+
+/// Turn machines into hosts.toml entries keyed by alias. Returns the entries
+/// and how many machines were skipped for lack of a usable address.
+pub fn to_entries(
+    mut machines: Vec<Machine>,
+    ip: AddressChoice,
+    user: Option<&str>,
+    fallback: Option<&Fallback>,
+) -> (BTreeMap<String, HostEntry>, usize) {
+    machines.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+    let mut entries = BTreeMap::new();
+    let mut skipped = 0;
+    for m in machines {
+        let (address, via) = match (ip, m.public_ip, fallback) {
+            (AddressChoice::Auto, Some(public), _) => (Some(public), None),
+            (AddressChoice::Auto, None, Some(f)) => (Some(m.id.clone()), Some(f)),
+            (AddressChoice::Auto, None, None) => (m.private_ip, None),
+            (AddressChoice::Public, public, _) => (public, None),
+            (AddressChoice::Private, _, _) => (m.private_ip, None),
+        };
+        let Some(hostname) = address else {
+            skipped += 1;
+            continue;
+        };
+        let mut alias = m.name.as_deref().map(slug).filter(|s| !s.is_empty());
+        if alias.as_ref().is_some_and(|a| entries.contains_key(a)) {
+            // Several machines share a name; keep them apart by ID
+            alias = alias.map(|a| format!("{a}-{}", slug(&m.id)));
+        }
+        entries.insert(
+            alias.unwrap_or_else(|| slug(&m.id)),
+            HostEntry {
+                hostname,
+                user: user.map(String::from),
+                identity_file: m.identity_file,
+                tags: m.tags,
+                connect: via.map(|f| f.connect).unwrap_or_default(),
+                connect_args: via
+                    .map(|f| [f.connect_args.as_slice(), &m.connect_args].concat())
+                    .unwrap_or_default(),
+                ..Default::default()
+            },
+        );
+    }
+    (entries, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_ec2_instances() {
+        let json = r#"{"Reservations": [{"Instances": [
+            {"InstanceId": "i-0aa", "PublicIpAddress": "3.3.3.3",
+             "PrivateIpAddress": "10.0.0.1",
+             "Tags": [{"Key": "Name", "Value": "Web Server"},
+                      {"Key": "Environment", "Value": "prod"},
+                      {"Key": "aws:autoscaling:groupName", "Value": "asg"}]},
+            {"InstanceId": "i-0bb", "PrivateIpAddress": "10.0.0.2",
+             "Tags": [{"Key": "Name", "Value": "web-server"}]},
+            {"InstanceId": "i-0cc"}
+        ]}]}"#;
+        assert_eq!(aws_filter("tag:Team=infra").unwrap(), "Name=tag:Team,Values=infra");
+
+        let (entries, skipped) =
+            to_entries(parse_aws(json).unwrap(), AddressChoice::Auto, None, None);
+        assert_eq!(skipped, 1);
+        assert_eq!(entries["web-server"].hostname, "3.3.3.3");
+        assert_eq!(entries["web-server"].tags, ["aws", "prod"]);
+        assert_eq!(entries["web-server-i-0bb"].hostname, "10.0.0.2");
+
+        let opts = AwsOptions {
+            profile: Some("work".to_string()),
+            region: None,
+            filters: Vec::new(),
+        };
+        let fallback = opts.ssm_fallback();
+        let (entries, skipped) =
+            to_entries(parse_aws(json).unwrap(), AddressChoice::Auto, None, Some(&fallback));
+        assert_eq!(skipped, 0);
+        assert_eq!(entries["web-server"].connect, Connect::Direct);
+        assert_eq!(entries["web-server-i-0bb"].hostname, "i-0bb");
+        assert_eq!(entries["web-server-i-0bb"].connect, Connect::Ssm);
+        assert_eq!(entries["web-server-i-0bb"].connect_args, ["--profile", "work"]);
+
+        let (entries, skipped) =
+            to_entries(parse_aws(json).unwrap(), AddressChoice::Public, Some("ec2-user"), None);
+        assert_eq!(skipped, 2);
+        assert_eq!(entries["web-server"].user.as_deref(), Some("ec2-user"));
+    }
+
+    #[test]
+    fn maps_token_provider_pages() {
+        let page: LinodeInstances = serde_json::from_str(
+            r#"{"page": 1, "pages": 2, "data": [{"id": 7, "label": "db-1", "tags": ["prod"],
+                "ipv4": ["192.168.130.4", "45.79.1.2"]}]}"#,
+        )
+        .unwrap();
+        let (machines, more) = parse_linode(page);
+        assert!(more);
+        assert_eq!(machines[0].public_ip.as_deref(), Some("45.79.1.2"));
+        assert_eq!(machines[0].private_ip.as_deref(), Some("192.168.130.4"));
+        assert_eq!(machines[0].tags, ["linode", "prod"]);
+
+        let page: DoDroplets = serde_json::from_str(
+            r#"{"droplets": [{"id": 1, "name": "web", "tags": [], "networks": {"v4": [
+                {"ip_address": "10.1.0.2", "type": "private"},
+                {"ip_address": "167.1.1.1", "type": "public"}]}}],
+               "links": {}}"#,
+        )
+        .unwrap();
+        let (machines, more) = parse_digitalocean(page);
+        assert!(!more);
+        assert_eq!(machines[0].public_ip.as_deref(), Some("167.1.1.1"));
+    }
+
+    #[test]
+    fn maps_tailscale_peers() {
+        let status: TailscaleStatus = serde_json::from_str(
+            r#"{"Self": {"HostName": "me"}, "Peer": {"nodekey:1": {
+                "HostName": "Build Box", "DNSName": "build-box.tail1234.ts.net.",
+                "TailscaleIPs": ["100.64.0.7", "fd7a:115c::7"], "Online": false,
+                "Tags": ["tag:ci"]}}}"#,
+        )
+        .unwrap();
+        let (entries, _) =
+            to_entries(parse_tailscale(status), AddressChoice::Auto, None, None);
+        assert_eq!(entries["build-box"].hostname, "build-box.tail1234.ts.net");
+        assert_eq!(entries["build-box"].tags, ["tailscale", "ci"]);
+    }
+
+    #[test]
+    fn maps_gcp_instances() {
+        let json = r#"[
+            {"name": "api-1", "zone": "https://compute/v1/projects/p/zones/us-central1-a",
+             "labels": {"env": "prod"},
+             "networkInterfaces": [{"networkIP": "10.128.0.2",
+                                    "accessConfigs": [{"natIP": "34.1.2.3"}]}]},
+            {"name": "worker-1", "zone": "https://compute/v1/projects/p/zones/europe-west1-b",
+             "networkInterfaces": [{"networkIP": "10.132.0.5", "accessConfigs": []}]}
+        ]"#;
+        let opts = GcpOptions {
+            project: Some("p".to_string()),
+            filters: Vec::new(),
+        };
+        let fallback = opts.iap_fallback();
+        let (entries, _) =
+            to_entries(parse_gcp(json).unwrap(), AddressChoice::Auto, None, Some(&fallback));
+        assert_eq!(entries["api-1"].hostname, "34.1.2.3");
+        assert_eq!(entries["api-1"].tags, ["gcp", "prod"]);
+        assert_eq!(entries["worker-1"].hostname, "worker-1");
+        assert_eq!(entries["worker-1"].connect, Connect::Iap);
+        assert_eq!(
+            entries["worker-1"].connect_args,
+            ["--project", "p", "--zone", "europe-west1-b"]
+        );
+    }
+
+    #[test]
+    fn maps_netbox_devices() {
+        let page: serde_json::Value = serde_json::from_str(
+            r#"{"next": null, "results": [
+                {"name": "Core SW 1", "primary_ip": {"address": "10.1.0.2/24"},
+                 "primary_ip4": {"address": "10.1.0.2/24"}, "role": {"slug": "switch"},
+                 "site": {"slug": "fra1"}, "tenant": null,
+                 "tags": [{"slug": "network"}]},
+                {"name": "vm-db", "primary_ip": {"address": "2001:db8::5/64"},
+                 "primary_ip4": null, "device_role": {"slug": "db"}, "site": {"slug": "ams"}}
+            ]}"#,
+        )
+        .unwrap();
+        let mapping = netbox_mapping();
+        let (entries, skipped) = mapping.apply(&page, "netbox");
+        assert_eq!(skipped, 0);
+        assert_eq!(entries["core-sw-1"].hostname, "10.1.0.2");
+        assert_eq!(entries["core-sw-1"].tags, ["netbox", "network", "switch", "fra1"]);
+        assert_eq!(entries["vm-db"].hostname, "2001:db8::5");
+        assert_eq!(entries["vm-db"].tags, ["netbox", "db", "ams"]);
+        assert_eq!(mapping::first_string(&page, "next"), None);
+    }
+}
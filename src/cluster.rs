@@ -0,0 +1,92 @@
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::multiplexer::{self, strings, tmux, tmux_output};
+use crate::util;
+
+/// Open one tmux pane per host, each running `oken <alias>` so history, the
+/// audit log and danger prompts work as usual, then synchronize their input.
+/// `flags` are oken options to pass on to every pane (`--record`, …).
+/// Inside tmux this adds a window; otherwise it starts a session and attaches.
+pub fn open(aliases: &[String], flags: &[String], dry_run: bool) -> Result<()> {
+    if !dry_run && !util::on_path("tmux") {
+        bail!("tmux not found on PATH (oken cluster opens its panes in tmux)");
+    }
+    let exe = std::env::current_exe().context("failed to locate the oken binary")?;
+    let exe = exe.to_string_lossy();
+    let panes: Vec<Vec<String>> = aliases
+        .iter()
+        .map(|alias| {
+            let mut argv = strings(&[&exe, "--exact"]);
+            argv.extend(flags.iter().cloned());
+            argv.push(alias.clone());
+            multiplexer::hold_on_failure(&argv)
+        })
+        .collect();
+    let (first, rest) = panes.split_first().context("no hosts to open")?;
+    let inside = multiplexer::current() == Some(multiplexer::Multiplexer::Tmux);
+
+    let session = if inside { None } else { Some(session_name(dry_run)) };
+    let create: Vec<String> = match &session {
+        Some(name) => {
+            // A detached session is 80x24 unless told otherwise, too small for many panes
+            let (cols, rows) = crossterm::terminal::size()
+                .ok()
+                .filter(|&(cols, rows)| cols > 0 && rows > 0)
+                .unwrap_or((200, 50));
+            let mut args = strings(&["new-session", "-d", "-s", name, "-n", "cluster"]);
+            args.extend(strings(&["-x", &cols.to_string(), "-y", &rows.to_string()]));
+            args.extend(first.iter().cloned());
+            args
+        }
+        None => {
+            let mut args = strings(&["new-window", "-n", "cluster", "-P", "-F", "#{window_id}"]);
+            args.extend(first.iter().cloned());
+            args
+        }
+    };
+    let target = match (&session, dry_run) {
+        (Some(name), _) => {
+            tmux(&create, dry_run)?;
+            name.clone()
+        }
+        (None, true) => {
+            tmux(&create, dry_run)?;
+            "cluster".to_string()
+        }
+        (None, false) => tmux_output(&create)?,
+    };
+    for pane in rest {
+        let mut split = strings(&["split-window", "-t", &target]);
+        split.extend(pane.iter().cloned());
+        tmux(&split, dry_run)?;
+        // Re-tile after every split so later ones still have room
+        tmux(&strings(&["select-layout", "-t", &target, "tiled"]), dry_run)?;
+    }
+    let sync = strings(&["set-window-option", "-t", &target, "synchronize-panes", "on"]);
+    tmux(&sync, dry_run)?;
+    if let Some(name) = &session {
+        tmux(&strings(&["attach-session", "-t", name]), dry_run)?;
+    }
+    Ok(())
+}
+
+/// `oken-cluster`, or `oken-cluster-2` and so on if that session exists.
+fn session_name(dry_run: bool) -> String {
+    let exists = |name: &str| {
+        !dry_run
+            && Command::new("tmux")
+                .args(["has-session", "-t", &format!("={name}")])
+                .stderr(std::process::Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+    };
+    let mut name = "oken-cluster".to_string();
+    let mut n = 1;
+    while exists(&name) {
+        n += 1;
+        name = format!("oken-cluster-{n}");
+    }
+    name
+}
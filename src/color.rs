@@ -0,0 +1,49 @@
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cli::ColorChoice;
+
+static STDOUT: AtomicBool = AtomicBool::new(false);
+static STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Decide once whether stdout and stderr get ANSI colors. `auto` colors a
+/// stream only when it is a terminal and `NO_COLOR` is unset or empty.
+pub fn init(choice: ColorChoice) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let (out, err) = match choice {
+        ColorChoice::Always => (true, true),
+        ColorChoice::Never => (false, false),
+        ColorChoice::Auto if no_color => (false, false),
+        ColorChoice::Auto => (
+            std::io::stdout().is_terminal(),
+            std::io::stderr().is_terminal(),
+        ),
+    };
+    STDOUT.store(out, Ordering::Relaxed);
+    STDERR.store(err, Ordering::Relaxed);
+}
+
+/// Whether text printed on stderr (prompts, notices, the picker) gets color.
+pub fn stderr() -> bool {
+    STDERR.load(Ordering::Relaxed)
+}
+
+/// `text` in the SGR style `code` (`"2"` dim, `"1;33"` bold yellow, …) when
+/// stdout gets color, plain otherwise.
+pub fn paint(code: &str, text: impl Display) -> String {
+    wrap(STDOUT.load(Ordering::Relaxed), code, text)
+}
+
+/// Like `paint`, for text printed on stderr.
+pub fn epaint(code: &str, text: impl Display) -> String {
+    wrap(stderr(), code, text)
+}
+
+fn wrap(enabled: bool, code: &str, text: impl Display) -> String {
+    if enabled {
+        format!("\x1b[{code}m{text}\x1b[0m")
+    } else {
+        text.to_string()
+    }
+}
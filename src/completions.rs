@@ -1,13 +1,24 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result, bail};
-use clap::CommandFactory;
+use clap::{CommandFactory, ValueHint};
 use clap_complete::{Shell, generate};
 
 use crate::cli::Cli;
 
+/// Write the completion script for `shell` (or the one in `$SHELL`) to
+/// stdout. Called by `oken completions`.
+pub fn print(shell: Option<Shell>) -> Result<()> {
+    let shell = match shell {
+        Some(s) => s,
+        None => detect_shell()?,
+    };
+    print!("{}", script(shell));
+    Ok(())
+}
+
 /// Resolve the target directory, create it if needed, write the completion
-/// file, and print what was done. Called by `oken completions`.
+/// file, and print what was done. Called by `oken completions --install`.
 pub fn install(shell: Option<Shell>, dir: Option<PathBuf>) -> Result<()> {
     let shell = match shell {
         Some(s) => s,
@@ -233,9 +244,109 @@ fn resolve_fish_dir() -> Result<PathBuf> {
 // ── shared ────────────────────────────────────────────────────────────────────
 
 fn write_completions(shell: Shell, path: &std::path::Path) -> Result<()> {
-    let mut buf = Vec::new();
-    generate(shell, &mut Cli::command(), "oken", &mut buf);
-    std::fs::write(path, &buf)
+    std::fs::write(path, script(shell))
         .with_context(|| format!("could not write {}", path.display()))?;
     Ok(())
 }
+
+/// clap's completion script for `shell`, with host arguments and `--tag`
+/// values completed from the saved hosts through `oken __complete`.
+pub fn script(shell: Shell) -> String {
+    let mut buf = Vec::new();
+    generate(shell, &mut Cli::command(), "oken", &mut buf);
+    let script = String::from_utf8_lossy(&buf).into_owned();
+    match shell {
+        Shell::Zsh => zsh_inventory(&script),
+        Shell::Bash => format!("{script}{}", bash_inventory()),
+        Shell::Fish => fish_inventory(&script),
+        _ => script,
+    }
+}
+
+/// Subcommands whose first argument is a host alias.
+fn host_commands() -> Vec<String> {
+    Cli::command()
+        .get_subcommands()
+        .filter(|cmd| {
+            cmd.get_positionals()
+                .next()
+                .is_some_and(|arg| arg.get_value_hint() == ValueHint::Hostname)
+        })
+        .map(|cmd| cmd.get_name().to_string())
+        .collect()
+}
+
+/// Host arguments are generated as `_hosts`; offer saved aliases first.
+fn zsh_inventory(script: &str) -> String {
+    let helpers = r#"
+(( $+functions[_oken_hosts] )) ||
+_oken_hosts() {
+    local -a aliases
+    aliases=(${(f)"$(oken __complete hosts 2>/dev/null)"})
+    compadd -a aliases
+    _hosts
+}
+
+(( $+functions[_oken_tags] )) ||
+_oken_tags() {
+    local -a tags
+    tags=(${(f)"$(oken __complete tags 2>/dev/null)"})
+    compadd -a tags
+}
+"#;
+    let body = script
+        .replace(":_hosts' \\", ":_oken_hosts' \\")
+        .replace(":TAG:_default' \\", ":TAG:_oken_tags' \\");
+    // The helpers go after the `#compdef oken` line that zsh reads
+    match body.split_once('\n') {
+        Some((compdef, rest)) => format!("{compdef}\n{helpers}{rest}"),
+        None => body,
+    }
+}
+
+/// clap's bash function only completes subcommands and flags; wrap it to add
+/// aliases after `oken` or a command that takes a host, and tags after `--tag`.
+fn bash_inventory() -> String {
+    format!(
+        r#"
+_oken_inventory() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    if [[ "$prev" == "--tag" ]]; then
+        COMPREPLY=($(compgen -W "$(oken __complete tags 2>/dev/null)" -- "$cur"))
+        return 0
+    fi
+    _oken "$@"
+    if [[ "$cur" != -* ]] && [[ $COMP_CWORD -eq 1 || " {commands} " == *" $prev "* ]]; then
+        COMPREPLY+=($(compgen -W "$(oken __complete hosts 2>/dev/null)" -- "$cur"))
+    fi
+}}
+complete -F _oken_inventory -o bashdefault -o default oken
+"#,
+        commands = host_commands().join(" ")
+    )
+}
+
+/// Add alias completion for the first argument and host commands, and tag
+/// completion to every `--tag`.
+fn fish_inventory(script: &str) -> String {
+    let mut out: String = script
+        .lines()
+        .map(|line| {
+            if line.contains(" -l tag ") && line.ends_with(" -r") {
+                format!("{line} -f -a \"(oken __complete tags)\"\n")
+            } else {
+                format!("{line}\n")
+            }
+        })
+        .collect();
+    out.push_str(
+        "complete -c oken -n \"__fish_oken_needs_command\" -f -a \"(oken __complete hosts)\"\n",
+    );
+    for command in host_commands() {
+        out.push_str(&format!(
+            "complete -c oken -n \"__fish_oken_using_subcommand {command}\" -f \
+             -a \"(oken __complete hosts)\"\n"
+        ));
+    }
+    out
+}
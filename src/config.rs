@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 
@@ -6,24 +6,59 @@ fn home() -> Result<PathBuf> {
     dirs::home_dir().context("could not determine home directory")
 }
 
-/// Returns `$XDG_CONFIG_HOME/oken` or `~/.config/oken`.
+/// Returns `$OKEN_CONFIG_DIR`, `$XDG_CONFIG_HOME/oken` or `~/.config/oken`.
+/// The directory may not exist yet; writers call `create_parent` first.
 pub fn config_dir() -> Result<PathBuf> {
-    let base = std::env::var_os("XDG_CONFIG_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|| home().unwrap().join(".config"));
-    let dir = base.join("oken");
-    std::fs::create_dir_all(&dir)
-        .with_context(|| format!("failed to create config dir: {}", dir.display()))?;
-    Ok(dir)
+    dir("OKEN_CONFIG_DIR", "XDG_CONFIG_HOME", ".config")
 }
 
-/// Returns `$XDG_DATA_HOME/oken` or `~/.local/share/oken`.
+/// Returns `$OKEN_DATA_DIR`, `$XDG_DATA_HOME/oken` or `~/.local/share/oken`.
+/// The directory may not exist yet; writers call `create_parent` first.
 pub fn data_dir() -> Result<PathBuf> {
-    let base = std::env::var_os("XDG_DATA_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|| home().unwrap().join(".local/share"));
-    let dir = base.join("oken");
-    std::fs::create_dir_all(&dir)
-        .with_context(|| format!("failed to create data dir: {}", dir.display()))?;
-    Ok(dir)
+    dir("OKEN_DATA_DIR", "XDG_DATA_HOME", ".local/share")
+}
+
+/// Returns `$OKEN_STATE_DIR`, `$XDG_STATE_HOME/oken` or `~/.local/state/oken`,
+/// for caches and other state that can be lost without harm.
+pub fn state_dir() -> Result<PathBuf> {
+    dir("OKEN_STATE_DIR", "XDG_STATE_HOME", ".local/state")
+}
+
+/// `name` in the state directory, moved there from the data directory where
+/// older versions kept it.
+pub fn state_file(name: &str) -> Result<PathBuf> {
+    let path = state_dir()?.join(name);
+    if !path.exists()
+        && let Ok(old) = data_dir().map(|d| d.join(name))
+        && old.exists()
+    {
+        match create_parent(&path).and_then(|()| Ok(std::fs::rename(&old, &path)?)) {
+            Ok(()) => log::debug!("moved {} to {}", old.display(), path.display()),
+            Err(e) => log::debug!("could not move {}: {e:#}", old.display()),
+        }
+    }
+    Ok(path)
+}
+
+/// oken's directory: the override as given, else `oken` under the XDG base
+/// directory or its default under the home directory.
+fn dir(override_var: &str, xdg_var: &str, default_base: &str) -> Result<PathBuf> {
+    let set = |var| std::env::var_os(var).filter(|v| !v.is_empty()).map(PathBuf::from);
+    if let Some(dir) = set(override_var) {
+        return Ok(dir);
+    }
+    let base = match set(xdg_var) {
+        Some(base) => base,
+        None => home()?.join(default_base),
+    };
+    Ok(base.join("oken"))
+}
+
+/// Create the directory `path` goes in, before writing it.
+pub fn create_parent(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    Ok(())
 }
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Everything the daemon knows, as served over its socket.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub hosts: BTreeMap<String, HostStatus>,
+    pub tunnels: BTreeMap<String, TunnelStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostStatus {
+    pub reachable: bool,
+    /// TCP connect time to the ssh port.
+    pub latency_ms: Option<u64>,
+    /// Unix time of the probe.
+    pub checked_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelStatus {
+    pub running: bool,
+    /// Whether the user started it (and so the daemon keeps it up).
+    pub wanted: bool,
+    pub restarts: u32,
+    /// Unix time a client connection was last seen going through the tunnel.
+    #[serde(default)]
+    pub last_active: Option<u64>,
+}
+
+const HOST_PROBE_INTERVAL: Duration = Duration::from_secs(60);
+const TUNNEL_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Returns `~/.local/state/oken/okend.sock`
+fn socket_path() -> Result<PathBuf> {
+    crate::config::state_file("okend.sock")
+}
+
+/// Ask a running daemon for its snapshot. Returns `None` quickly when no
+/// daemon is running, so callers can fall back to doing the work themselves.
+#[cfg(unix)]
+pub fn query() -> Option<Snapshot> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(socket_path().ok()?).ok()?;
+    stream.set_read_timeout(Some(Duration::from_millis(300))).ok()?;
+    stream.write_all(b"status\n").ok()?;
+    let mut buf = String::new();
+    stream.read_to_string(&mut buf).ok()?;
+    serde_json::from_str(&buf).ok()
+}
+
+#[cfg(not(unix))]
+pub fn query() -> Option<Snapshot> {
+    None
+}
+
+/// Run the daemon in the foreground: supervise wanted tunnels, probe hosts,
+/// and answer `status` requests on the socket. Runs until SIGINT, SIGTERM or
+/// SIGHUP.
+#[cfg(unix)]
+pub fn run() -> Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::{Context, bail};
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    let sock = socket_path()?;
+    if sock.exists() {
+        if UnixStream::connect(&sock).is_ok() {
+            bail!("okend is already running ({})", sock.display());
+        }
+        // Left behind by a daemon that didn't shut down cleanly
+        std::fs::remove_file(&sock)?;
+    }
+    crate::config::create_parent(&sock)?;
+    let listener =
+        UnixListener::bind(&sock).with_context(|| format!("failed to bind {}", sock.display()))?;
+    eprintln!("okend listening on {}", sock.display());
+
+    // Remove the socket on the way out, so clients don't find a dead one
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    let bound = sock.clone();
+    std::thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            let _ = std::fs::remove_file(&bound);
+            std::process::exit(0);
+        }
+    });
+
+    let state = Arc::new(Mutex::new(Snapshot::default()));
+
+    let hosts_state = Arc::clone(&state);
+    std::thread::spawn(move || {
+        loop {
+            let hosts = probe_hosts();
+            if crate::oken_config::load_config().latency_probe {
+                let rtt_ms = hosts
+                    .iter()
+                    .filter_map(|(alias, s)| Some((alias.clone(), s.latency_ms?)))
+                    .collect();
+                let _ = crate::latency::store(&crate::latency::Cache {
+                    checked_at: unix_now(),
+                    rtt_ms,
+                });
+            }
+            lock(&hosts_state).hosts = hosts;
+            std::thread::sleep(HOST_PROBE_INTERVAL);
+        }
+    });
+
+    let tunnel_state = Arc::clone(&state);
+    std::thread::spawn(move || {
+        let mut supervisor = crate::tunnels::Supervisor::default();
+        let mut last_active: BTreeMap<String, u64> = BTreeMap::new();
+        loop {
+            if let Ok(path) = crate::tunnels_toml_path() {
+                // Reloaded each pass, like the tunnels, so edits to config.toml apply
+                let proxies = crate::oken_config::load_config().proxies;
+                if let Err(e) = supervisor.tick(&path, &proxies, crate::tunnels::is_wanted) {
+                    eprintln!("okend: {e:#}");
+                }
+                let tunnels = crate::tunnels::load_effective(&path, &proxies)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(name, entry)| {
+                        let running = crate::tunnels::is_running(&name, &entry.host);
+                        let in_use = running
+                            && crate::tunnels::traffic(&entry).is_some_and(|t| t.connections > 0);
+                        if in_use {
+                            last_active.insert(name.clone(), unix_now());
+                        }
+                        let status = TunnelStatus {
+                            running,
+                            wanted: crate::tunnels::is_wanted(&name),
+                            restarts: supervisor.restarts(&name),
+                            last_active: last_active.get(&name).copied(),
+                        };
+                        (name, status)
+                    })
+                    .collect();
+                lock(&tunnel_state).tunnels = tunnels;
+            }
+            std::thread::sleep(TUNNEL_CHECK_INTERVAL);
+        }
+    });
+
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+        let state = Arc::clone(&state);
+        // A client that never sends its request only holds up its own thread
+        std::thread::spawn(move || {
+            let mut line = String::new();
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+            if BufReader::new(&stream).read_line(&mut line).is_err() {
+                return;
+            }
+            let reply = match line.trim() {
+                "status" => serde_json::to_string(&*lock(&state)).unwrap_or_default(),
+                other => {
+                    serde_json::json!({ "error": format!("unknown request '{other}'") })
+                        .to_string()
+                }
+            };
+            let _ = stream.write_all(reply.as_bytes());
+        });
+    }
+    Ok(())
+}
+
+/// The snapshot, even if a thread panicked while holding it: each field is
+/// replaced whole, so it is never left half-written.
+#[cfg(unix)]
+fn lock(state: &std::sync::Mutex<Snapshot>) -> std::sync::MutexGuard<'_, Snapshot> {
+    state.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+#[cfg(not(unix))]
+pub fn run() -> Result<()> {
+    anyhow::bail!("okend needs unix sockets and is not available on this platform")
+}
+
+/// Probe every saved host's ssh port, 32 at a time.
+pub fn probe_hosts() -> BTreeMap<String, HostStatus> {
+    let hosts = crate::hosts::list_all_hosts().unwrap_or_default();
+    let now = unix_now();
+    hosts
+        .chunks(32)
+        .flat_map(|chunk| probe_chunk(chunk, now))
+        .collect()
+}
+
+fn probe_chunk(hosts: &[crate::hosts::Host], now: u64) -> Vec<(String, HostStatus)> {
+    std::thread::scope(|s| {
+        let handles: Vec<_> = hosts
+            .iter()
+            .map(|h| {
+                s.spawn(move || {
+                    let latency = crate::probe::endpoint(h).and_then(|(host, port)| {
+                        crate::probe::tcp_connect(&host, port, Duration::from_secs(3))
+                    });
+                    let status = HostStatus {
+                        reachable: latency.is_some(),
+                        latency_ms: latency.map(|d| d.as_millis() as u64),
+                        checked_at: now,
+                    };
+                    (h.alias.clone(), status)
+                })
+            })
+            .collect();
+        handles.into_iter().filter_map(|h| h.join().ok()).collect()
+    })
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,120 @@
+use glob::{MatchOptions, Pattern};
+
+use crate::hosts::Host;
+use crate::oken_config::OkenConfig;
+
+/// Why a host is considered dangerous.
+#[derive(Debug, Default)]
+pub struct DangerMatch {
+    /// Host tags matched by `danger_tags` or a rule.
+    pub tags: Vec<String>,
+    /// Rule patterns that matched the host alias itself.
+    pub alias_rules: Vec<String>,
+    /// Custom messages from matching `[[danger_rules]]`.
+    pub messages: Vec<String>,
+}
+
+impl DangerMatch {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.alias_rules.is_empty()
+    }
+
+    /// One-line reason for the warning banner, e.g. `is tagged [prod]`.
+    pub fn describe(&self) -> String {
+        match (self.tags.is_empty(), self.alias_rules.is_empty()) {
+            (false, _) => format!("is tagged [{}]", self.tags.join(", ")),
+            (true, false) => format!("matches [{}]", self.alias_rules.join(", ")),
+            (true, true) => String::new(),
+        }
+    }
+}
+
+/// Check a host against `danger_tags` (glob patterns over tags) and
+/// `danger_rules` (glob patterns over tags and the alias). Like `--tag`, a
+/// pattern without a namespace also matches namespaced tags by their value.
+pub fn evaluate(host: &Host, cfg: &OkenConfig) -> DangerMatch {
+    let mut m = DangerMatch::default();
+    for tag in &host.tags {
+        if cfg.danger_tags.iter().any(|p| tag_glob_match(p, tag)) {
+            m.tags.push(tag.clone());
+        }
+    }
+    for rule in &cfg.danger_rules {
+        let mut hit = false;
+        for tag in &host.tags {
+            if tag_glob_match(&rule.pattern, tag) {
+                hit = true;
+                if !m.tags.contains(tag) {
+                    m.tags.push(tag.clone());
+                }
+            }
+        }
+        if glob_match(&rule.pattern, &host.alias) {
+            hit = true;
+            m.alias_rules.push(rule.pattern.clone());
+        }
+        if hit && let Some(ref msg) = rule.message {
+            m.messages.push(msg.clone());
+        }
+    }
+    m
+}
+
+/// `glob_match` against the whole tag, or just its value (`prod` of
+/// `env:prod`) when the pattern has no namespace of its own.
+fn tag_glob_match(pattern: &str, tag: &str) -> bool {
+    glob_match(pattern, tag)
+        || (!pattern.contains(':')
+            && tag.split_once(':').is_some_and(|(_, value)| glob_match(pattern, value)))
+}
+
+/// Case-insensitive glob match; an invalid pattern falls back to plain equality.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let opts = MatchOptions {
+        case_sensitive: false,
+        ..MatchOptions::new()
+    };
+    match Pattern::new(pattern) {
+        Ok(p) => p.matches_with(text, opts),
+        Err(_) => pattern.eq_ignore_ascii_case(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oken_config::DangerRule;
+
+    fn host(alias: &str, tags: &[&str]) -> Host {
+        Host {
+            alias: alias.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_tags_and_alias_rules() {
+        let cfg = OkenConfig {
+            danger_tags: vec!["prod*".to_string()],
+            danger_rules: vec![DangerRule {
+                pattern: "db-*".to_string(),
+                message: Some("Change ticket required".to_string()),
+            }],
+            ..OkenConfig::default()
+        };
+
+        let m = evaluate(&host("web1", &["Production"]), &cfg);
+        assert_eq!(m.tags, vec!["Production"]);
+        assert!(m.messages.is_empty());
+
+        let m = evaluate(&host("db-main", &["staging"]), &cfg);
+        assert_eq!(m.alias_rules, vec!["db-*"]);
+        assert_eq!(m.messages, vec!["Change ticket required"]);
+
+        assert!(evaluate(&host("web2", &["staging"]), &cfg).is_empty());
+
+        let m = evaluate(&host("web3", &["env:prod", "role:web"]), &cfg);
+        assert_eq!(m.tags, vec!["env:prod"]);
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::process::{Command, Stdio};
+
+use oken_core::hosts::Host;
+use oken_core::hosts_toml::HostEntry;
+use oken_core::ssh::{self, build_ssh_args};
+
+/// Where ssh really ends up for a host, after ~/.ssh/config, and the
+/// addresses its name resolves to.
+pub struct Destination {
+    pub user: String,
+    pub hostname: String,
+    pub port: u16,
+    names: Vec<String>,
+}
+
+impl Destination {
+    /// `user@hostname:port`, for the heading of a group.
+    pub fn describe(&self) -> String {
+        format!("{}@{}:{}", self.user, self.hostname, self.port)
+    }
+}
+
+/// Resolve every host with `ssh -G` and DNS, side by side so a long list
+/// doesn't wait on one lookup at a time. None for hosts ssh can't resolve.
+pub fn resolve(hosts: &[Host], proxies: &BTreeMap<String, String>) -> Vec<Option<Destination>> {
+    std::thread::scope(|scope| {
+        let lookups: Vec<_> =
+            hosts.iter().map(|h| scope.spawn(move || destination(h, proxies))).collect();
+        lookups.into_iter().map(|l| l.join().ok().flatten()).collect()
+    })
+}
+
+fn destination(host: &Host, proxies: &BTreeMap<String, String>) -> Option<Destination> {
+    let args = if host.from_ssh_config {
+        vec![host.alias.clone()]
+    } else {
+        build_ssh_args(host, None, proxies)
+    };
+    let output = Command::new(ssh::find_ssh().ok()?)
+        .arg("-G")
+        .args(&args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let (mut user, mut hostname, mut port) = (String::new(), None, 22);
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.split_once(' ') {
+            Some(("user", v)) => user = v.to_string(),
+            Some(("hostname", v)) => hostname = Some(v.to_lowercase()),
+            Some(("port", v)) => port = v.parse().unwrap_or(22),
+            _ => {}
+        }
+    }
+    let hostname = hostname?;
+    let mut names = vec![hostname.clone()];
+    // Names behind a proxy or cloud service resolve on the far side, if at all
+    let direct = host.proxy.is_none() && host.connect.is_direct();
+    if direct && hostname.parse::<IpAddr>().is_err() {
+        let addrs = (hostname.as_str(), port).to_socket_addrs().into_iter().flatten();
+        names.extend(addrs.map(|a| a.ip().to_string()));
+    }
+    Some(Destination {
+        user,
+        hostname,
+        port,
+        names,
+    })
+}
+
+/// Groups (as indexes into `destinations`) of two or more hosts with the same
+/// user and port whose names or addresses overlap.
+pub fn find_duplicates(destinations: &[Option<Destination>]) -> Vec<Vec<usize>> {
+    let same = |a: &Destination, b: &Destination| {
+        a.user == b.user && a.port == b.port && a.names.iter().any(|n| b.names.contains(n))
+    };
+    let mut group_of: Vec<Option<usize>> = vec![None; destinations.len()];
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (i, a) in destinations.iter().enumerate() {
+        let Some(a) = a else {
+            continue;
+        };
+        let found = (0..i).find(|&j| destinations[j].as_ref().is_some_and(|b| same(a, b)));
+        match found.and_then(|j| group_of[j]) {
+            Some(g) => {
+                groups[g].push(i);
+                group_of[i] = Some(g);
+            }
+            None => {
+                group_of[i] = Some(groups.len());
+                groups.push(vec![i]);
+            }
+        }
+    }
+    groups.retain(|g| g.len() > 1);
+    groups
+}
+
+/// One entry from several: `base` wins where both have a value, the others
+/// fill in what it leaves out, and the tags of all of them are kept.
+pub fn merge_entries(mut base: HostEntry, others: &[HostEntry]) -> HostEntry {
+    for other in others {
+        let other = other.clone();
+        base.user = base.user.or(other.user);
+        base.port = base.port.or(other.port);
+        base.identity_file = base.identity_file.or(other.identity_file);
+        base.certificate_file = base.certificate_file.or(other.certificate_file);
+        base.proxy = base.proxy.or(other.proxy);
+        base.host_key = base.host_key.or(other.host_key);
+        base.rebuild = base.rebuild.or(other.rebuild);
+        base.become_user = base.become_user.or(other.become_user);
+        base.mac_address = base.mac_address.or(other.mac_address);
+        base.owner = base.owner.or(other.owner);
+        base.team = base.team.or(other.team);
+        base.record |= other.record;
+        base.mfa |= other.mfa;
+        if base.on_connect.is_empty() {
+            base.on_connect = other.on_connect;
+        }
+        if base.on_disconnect.is_empty() {
+            base.on_disconnect = other.on_disconnect;
+        }
+        for tag in other.tags {
+            if !base.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                base.tags.push(tag);
+            }
+        }
+    }
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_and_merges_duplicates() {
+        let dest = |user: &str, port: u16, names: &[&str]| {
+            Some(Destination {
+                user: user.to_string(),
+                hostname: names[0].to_string(),
+                port,
+                names: names.iter().map(|n| n.to_string()).collect(),
+            })
+        };
+        let destinations = [
+            dest("deploy", 22, &["web.internal", "10.0.0.5"]),
+            dest("deploy", 22, &["10.0.0.9"]),
+            dest("deploy", 22, &["10.0.0.5"]),
+            dest("root", 22, &["10.0.0.5"]),
+            None,
+            dest("deploy", 2222, &["10.0.0.9"]),
+        ];
+        assert_eq!(find_duplicates(&destinations), [vec![0, 2]]);
+
+        let entry = |tags: &[&str], owner: Option<&str>| HostEntry {
+            hostname: "10.0.0.5".to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            owner: owner.map(str::to_string),
+            ..Default::default()
+        };
+        let merged = merge_entries(
+            entry(&["prod"], None),
+            &[entry(&["Prod", "web"], Some("alice")), entry(&[], Some("bob"))],
+        );
+        assert_eq!(merged.tags, ["prod", "web"]);
+        assert_eq!(merged.owner.as_deref(), Some("alice"));
+    }
+}
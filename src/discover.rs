@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+
+use crate::util;
+
+/// Largest range `oken discover` will scan (a /16).
+const MAX_ADDRESSES: usize = 65_536;
+
+/// A machine that answered on the SSH port.
+pub struct Found {
+    pub ip: Ipv4Addr,
+    pub latency: Duration,
+    /// Identification line the server sent, e.g. `SSH-2.0-OpenSSH_9.6p1 Ubuntu-3`.
+    pub banner: Option<String>,
+    /// `SHA256:… (ED25519)` from ssh-keyscan, when available.
+    pub fingerprint: Option<String>,
+}
+
+/// Host addresses in an IPv4 CIDR, skipping the network and broadcast
+/// addresses of anything larger than a /31. A bare address is a /32.
+pub fn parse_cidr(cidr: &str) -> Result<Vec<Ipv4Addr>> {
+    let (addr, prefix) = cidr.split_once('/').unwrap_or((cidr, "32"));
+    let addr: Ipv4Addr = addr
+        .parse()
+        .with_context(|| format!("invalid IPv4 address in '{cidr}'"))?;
+    let prefix: u32 = match prefix.parse() {
+        Ok(p) if p <= 32 => p,
+        _ => bail!("invalid prefix length in '{cidr}'"),
+    };
+    let size = 1usize << (32 - prefix);
+    if size > MAX_ADDRESSES {
+        bail!("{cidr} has {size} addresses; scan a /16 or smaller");
+    }
+    let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+    let network = u32::from(addr) & mask;
+    let (first, last) = if prefix >= 31 {
+        (network, network + size as u32 - 1)
+    } else {
+        (network + 1, network + size as u32 - 2)
+    };
+    Ok((first..=last).map(Ipv4Addr::from).collect())
+}
+
+/// Try `port` on every address, `concurrency` at a time.
+pub fn scan(addrs: &[Ipv4Addr], port: u16, concurrency: usize, timeout: Duration) -> Vec<Found> {
+    let next = AtomicUsize::new(0);
+    let found = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, addrs.len().max(1)) {
+            scope.spawn(|| {
+                while let Some(&ip) = addrs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if let Some(f) = probe(ip, port, timeout) {
+                        found.lock().unwrap().push(f);
+                    }
+                }
+            });
+        }
+    });
+    let mut found = found.into_inner().unwrap();
+    found.sort_by_key(|f| f.ip);
+    found
+}
+
+fn probe(ip: Ipv4Addr, port: u16, timeout: Duration) -> Option<Found> {
+    let start = Instant::now();
+    let stream = TcpStream::connect_timeout(&SocketAddr::from((ip, port)), timeout).ok()?;
+    let latency = start.elapsed();
+    // Servers send their identification line first; don't wait long for it
+    stream.set_read_timeout(Some(timeout.max(Duration::from_secs(1)))).ok()?;
+    let mut line = String::new();
+    let banner = BufReader::new(stream)
+        .read_line(&mut line)
+        .ok()
+        .map(|_| line.trim().to_string())
+        .filter(|l| l.starts_with("SSH-"));
+    Some(Found {
+        ip,
+        latency,
+        banner,
+        fingerprint: None,
+    })
+}
+
+/// Fill in host key fingerprints with `ssh-keyscan | ssh-keygen -lf -`,
+/// preferring ED25519 keys. Leaves them empty if either tool is missing.
+pub fn add_fingerprints(found: &mut [Found], port: u16) {
+    if found.is_empty() || !util::on_path("ssh-keyscan") {
+        return;
+    }
+    let mut keyscan = Command::new("ssh-keyscan");
+    keyscan.args(["-T", "3", "-p", &port.to_string()]);
+    keyscan.args(found.iter().map(|f| f.ip.to_string()));
+    let Ok(keys) = keyscan.stderr(Stdio::null()).output() else {
+        return;
+    };
+    let Ok(mut keygen) = Command::new("ssh-keygen")
+        .args(["-l", "-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return;
+    };
+    if let Some(mut stdin) = keygen.stdin.take() {
+        let _ = stdin.write_all(&keys.stdout);
+    }
+    let Ok(output) = keygen.wait_with_output() else {
+        return;
+    };
+    let fingerprints = parse_fingerprints(&String::from_utf8_lossy(&output.stdout));
+    for f in found.iter_mut() {
+        f.fingerprint = fingerprints.get(&f.ip.to_string()).cloned();
+    }
+}
+
+/// `256 SHA256:abc 10.0.0.5 (ED25519)` lines -> address -> `SHA256:abc (ED25519)`.
+/// Non-default ports show the host as `[10.0.0.5]:2222`.
+fn parse_fingerprints(output: &str) -> HashMap<String, String> {
+    let mut out: HashMap<String, String> = HashMap::new();
+    for line in output.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let [_, fingerprint, host, .., kind] = parts[..] else {
+            continue;
+        };
+        let host = host.trim_start_matches('[');
+        let host = host.split_once("]:").map_or(host, |(h, _)| h);
+        let value = format!("{fingerprint} {kind}");
+        if kind == "(ED25519)" || !out.contains_key(host) {
+            out.insert(host.to_string(), value);
+        }
+    }
+    out
+}
+
+/// Parse a checklist answer like `1 3-5`, `2,4` or `all` into 0-based indices.
+pub fn parse_selection(input: &str, count: usize) -> Result<Vec<usize>> {
+    if input.trim().eq_ignore_ascii_case("all") {
+        return Ok((0..count).collect());
+    }
+    let mut picked = Vec::new();
+    for part in input.split([',', ' ']).filter(|p| !p.is_empty()) {
+        let (from, to) = part.split_once('-').unwrap_or((part, part));
+        let (Ok(from), Ok(to)) = (from.parse::<usize>(), to.parse::<usize>()) else {
+            bail!("'{part}' is not a number or range");
+        };
+        if from == 0 || to > count || from > to {
+            bail!("'{part}' is outside 1-{count}");
+        }
+        for i in from - 1..to {
+            if !picked.contains(&i) {
+                picked.push(i);
+            }
+        }
+    }
+    Ok(picked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ranges_and_selections() {
+        let addrs = parse_cidr("10.0.1.77/30").unwrap();
+        assert_eq!(addrs, [Ipv4Addr::new(10, 0, 1, 77), Ipv4Addr::new(10, 0, 1, 78)]);
+        assert_eq!(parse_cidr("10.0.1.0/24").unwrap().len(), 254);
+        assert_eq!(parse_cidr("192.168.1.9").unwrap(), [Ipv4Addr::new(192, 168, 1, 9)]);
+        assert!(parse_cidr("10.0.0.0/8").is_err());
+
+        assert_eq!(parse_selection("1 3-4,3", 5).unwrap(), [0, 2, 3]);
+        assert_eq!(parse_selection("all", 2).unwrap(), [0, 1]);
+        assert!(parse_selection("6", 5).is_err());
+
+        let fps = parse_fingerprints(
+            "3072 SHA256:rsa 10.0.0.5 (RSA)\n256 SHA256:ed 10.0.0.5 (ED25519)\n\
+             256 SHA256:other [10.0.0.6]:2222 (ECDSA)\n",
+        );
+        assert_eq!(fps["10.0.0.5"], "SHA256:ed (ED25519)");
+        assert_eq!(fps["10.0.0.6"], "SHA256:other (ECDSA)");
+    }
+}
@@ -0,0 +1,277 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::{color, config, hosts, hosts_toml, oken_config, ssh, ssh_config, tunnels};
+use crate::ssh::IdentityProblem;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    level: Level,
+    what: String,
+    /// How to fix it, for warnings and failures.
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(what: impl Into<String>) -> Self {
+        Check {
+            level: Level::Ok,
+            what: what.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(what: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            level: Level::Warn,
+            what: what.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(what: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            level: Level::Fail,
+            what: what.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Run every check and print the results. Returns false if any failed
+/// (warnings alone still pass).
+pub fn run() -> bool {
+    let mut checks = vec![check_ssh()];
+    checks.extend(check_files());
+    checks.push(check_data_dir());
+    checks.push(check_agent());
+    checks.extend(check_identities());
+    checks.extend(check_includes());
+
+    for check in &checks {
+        let mark = match check.level {
+            Level::Ok => color::paint("32", "✓"),
+            Level::Warn => color::paint("33", "!"),
+            Level::Fail => color::paint("31", "✗"),
+        };
+        println!("{mark} {}", check.what);
+        if let Some(fix) = &check.fix {
+            println!("  {}", color::paint("2", format!("→ {fix}")));
+        }
+    }
+    let count = |level| checks.iter().filter(|c| c.level == level).count();
+    let (failed, warned) = (count(Level::Fail), count(Level::Warn));
+    println!();
+    if failed + warned == 0 {
+        println!("Everything looks good.");
+    } else {
+        println!("{failed} problem(s), {warned} warning(s)");
+    }
+    failed == 0
+}
+
+fn check_ssh() -> Check {
+    let Ok(path) = ssh::find_ssh() else {
+        return Check::fail(
+            "ssh not found",
+            "install OpenSSH (e.g. `apt install openssh-client`) or add it to PATH",
+        );
+    };
+    // `ssh -V` prints its version on stderr
+    let version = Command::new(&path)
+        .arg("-V")
+        .output()
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o.stderr).trim().to_string())
+        .filter(|v| !v.is_empty());
+    match version {
+        Some(v) => Check::ok(format!("ssh: {} ({v})", path.display())),
+        None => Check::ok(format!("ssh: {}", path.display())),
+    }
+}
+
+/// config.toml, hosts.toml and tunnels.toml parse, and no alias is
+/// defined twice in hosts.toml.
+fn check_files() -> Vec<Check> {
+    let Ok(dir) = config::config_dir() else {
+        return vec![Check::fail(
+            "could not determine the config directory",
+            "set $HOME, or $OKEN_CONFIG_DIR to choose one",
+        )];
+    };
+    let parsed = |name: &str, result: anyhow::Result<()>| match result {
+        Ok(()) => Check::ok(format!("{name} parses")),
+        Err(e) => Check::fail(
+            format!("{name}: {}", first_line(&format!("{e:#}"))),
+            format!("fix the file with `$EDITOR {}`", dir.join(name).display()),
+        ),
+    };
+    let hosts_path = dir.join("hosts.toml");
+    let mut checks = vec![
+        parsed("config.toml", oken_config::check_config(&dir.join("config.toml"))),
+        parsed("hosts.toml", hosts_toml::load_hosts_toml(&hosts_path).map(drop)),
+        parsed("tunnels.toml", tunnels::load_tunnels(&dir.join("tunnels.toml")).map(drop)),
+    ];
+    let config_toml = std::fs::read_to_string(dir.join("config.toml")).unwrap_or_default();
+    for problem in oken_config::unknown_keys(&config_toml) {
+        checks.push(Check::warn(
+            format!("config.toml: {problem}"),
+            "oken ignores it; fix it with `oken config edit`",
+        ));
+    }
+    for (alias, sections) in hosts_toml::duplicate_aliases(&hosts_path).unwrap_or_default() {
+        checks.push(Check::warn(
+            format!("'{alias}' is defined in [{}]", sections.join("] and [")),
+            format!("the [{}] entry wins; rename or remove the others", sections[0]),
+        ));
+    }
+    let all = hosts::list_all_hosts().unwrap_or_default();
+    let mut clashes = std::collections::BTreeSet::new();
+    for name in all.iter().flat_map(|h| &h.aliases) {
+        let named: Vec<&str> =
+            all.iter().filter(|h| h.is_named(name)).map(|h| h.alias.as_str()).collect();
+        if named.len() > 1 && clashes.insert(name) {
+            let winner = hosts::find(&all, name).map_or(named[0], |h| h.alias.as_str());
+            checks.push(Check::warn(
+                format!("'{name}' names more than one host: {}", named.join(", ")),
+                format!("`oken {name}` connects to '{winner}'; change the aliases in hosts.toml"),
+            ));
+        }
+    }
+    checks
+}
+
+fn first_line(s: &str) -> &str {
+    s.lines().find(|l| !l.trim().is_empty()).unwrap_or(s).trim()
+}
+
+fn check_data_dir() -> Check {
+    let fix = "check that $OKEN_DATA_DIR, $XDG_DATA_HOME or ~/.local/share is writable";
+    let Ok(dir) = config::data_dir() else {
+        return Check::fail("could not determine the data directory", fix);
+    };
+    let probe = dir.join(".doctor");
+    match config::create_parent(&probe).and_then(|()| Ok(std::fs::write(&probe, b"")?)) {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            Check::ok(format!("data dir {} is writable", dir.display()))
+        }
+        Err(e) => Check::fail(format!("data dir {} is not writable: {e}", dir.display()), fix),
+    }
+}
+
+fn check_agent() -> Check {
+    if std::env::var_os("SSH_AUTH_SOCK").is_none() {
+        return Check::warn(
+            "ssh-agent: SSH_AUTH_SOCK is not set",
+            "start one with `eval \"$(ssh-agent)\"` and add your key with `ssh-add`",
+        );
+    }
+    // ssh-add -l: 0 = keys listed, 1 = agent has no keys, 2 = no agent
+    let status = Command::new("ssh-add")
+        .arg("-l")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output();
+    match status {
+        Ok(o) if o.status.success() => {
+            let keys = String::from_utf8_lossy(&o.stdout).lines().count();
+            Check::ok(format!("ssh-agent is running with {keys} key(s)"))
+        }
+        Ok(o) if o.status.code() == Some(1) => Check::warn(
+            "ssh-agent is running but holds no keys",
+            "add one with `ssh-add` (or `ssh-add ~/.ssh/<key>`)",
+        ),
+        Ok(_) => Check::warn(
+            "ssh-agent: cannot connect to the agent at $SSH_AUTH_SOCK",
+            "the agent may have exited; start a new one with `eval \"$(ssh-agent)\"`",
+        ),
+        Err(_) => Check::warn("ssh-add not found", "install the OpenSSH client tools"),
+    }
+}
+
+/// Identity files of saved hosts exist and aren't readable by others
+/// (ssh refuses those).
+fn check_identities() -> Vec<Check> {
+    let mut seen: Vec<(PathBuf, Vec<String>)> = Vec::new();
+    for host in hosts::list_all_hosts().unwrap_or_default() {
+        let Some(file) = &host.identity_file else {
+            continue;
+        };
+        let path = ssh::expand_tilde(file);
+        match seen.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, aliases)) => aliases.push(host.alias),
+            None => seen.push((path, vec![host.alias])),
+        }
+    }
+    let mut checks = Vec::new();
+    let mut ok = 0;
+    for (path, aliases) in seen {
+        match check_identity(&path) {
+            Some(mut check) => {
+                check.what = format!("{} (used by {})", check.what, aliases.join(", "));
+                checks.push(check);
+            }
+            None => ok += 1,
+        }
+    }
+    if ok > 0 {
+        checks.insert(0, Check::ok(format!("{ok} identity file(s) look fine")));
+    }
+    checks
+}
+
+/// A problem with one identity file, if it has one.
+fn check_identity(path: &Path) -> Option<Check> {
+    Some(match ssh::identity_problem(path)? {
+        IdentityProblem::Missing => Check::fail(
+            format!("identity file {} does not exist", path.display()),
+            "fix the host's identity_file with `oken host edit`, or restore the key",
+        ),
+        IdentityProblem::TooOpen(mode) => Check::fail(
+            format!("identity file {} is accessible by others ({mode:o})", path.display()),
+            format!("ssh will ignore it; run `chmod 600 {}`", path.display()),
+        ),
+    })
+}
+
+fn check_includes() -> Vec<Check> {
+    ssh_config::missing_includes()
+        .into_iter()
+        .map(|(file, pattern)| {
+            Check::warn(
+                format!("{}: Include {pattern} matches no files", file.display()),
+                "create the file or remove the Include line",
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_missing_and_open_identity_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("id_test");
+        assert!(check_identity(&key).is_some_and(|c| c.level == Level::Fail));
+
+        std::fs::write(&key, "key").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&key, std::fs::Permissions::from_mode(0o644)).unwrap();
+            let check = check_identity(&key).unwrap();
+            assert!(check.what.contains("(644)"));
+            std::fs::set_permissions(&key, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        assert!(check_identity(&key).is_none());
+    }
+}
@@ -0,0 +1,260 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::audit;
+use crate::color::{epaint, paint};
+use crate::util::shell_quote;
+
+/// One host to run the command on.
+pub struct Job {
+    pub alias: String,
+    /// The full ssh command line, program first.
+    pub argv: Vec<String>,
+    /// Written to the command's stdin before closing it (the sudo password).
+    pub input: Option<String>,
+}
+
+/// How the command went on one host.
+pub struct Outcome {
+    /// ssh's exit code: the remote command's, or 255 when ssh itself failed.
+    pub exit_code: i32,
+    pub duration: Duration,
+}
+
+enum Event {
+    Line { job: usize, text: String, stderr: bool },
+    Done { job: usize, outcome: Outcome },
+}
+
+/// Colors for host prefixes; red is left out so it only means failure.
+const PALETTE: &[&str] = &["32", "33", "34", "35", "36", "92", "93", "94", "95", "96"];
+
+/// A color for a host that stays the same from run to run.
+fn host_color(alias: &str) -> &'static str {
+    // FNV-1a, which unlike the std hasher doesn't change between runs
+    let hash = alias
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01b3));
+    PALETTE[(hash % PALETTE.len() as u64) as usize]
+}
+
+/// Run every job, at most `parallel` at a time, printing each line of output
+/// as it arrives behind a colored host prefix (stderr lines go to stderr),
+/// then a summary. Outcomes are in the same order as `jobs`.
+pub fn run(jobs: &[Job], parallel: usize) -> Vec<Outcome> {
+    let width = jobs.iter().map(|j| j.alias.len()).max().unwrap_or(0);
+    let prefixes: Vec<(String, String)> = jobs
+        .iter()
+        .map(|j| {
+            let label = format!("{:<width$} |", j.alias);
+            (paint(host_color(&j.alias), &label), epaint(host_color(&j.alias), &label))
+        })
+        .collect();
+    let mut outcomes: Vec<Option<Outcome>> = jobs.iter().map(|_| None).collect();
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    std::thread::scope(|s| {
+        for _ in 0..parallel.clamp(1, jobs.len().max(1)) {
+            let tx = tx.clone();
+            let next = &next;
+            s.spawn(move || {
+                loop {
+                    let job = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(j) = jobs.get(job) else { break };
+                    let outcome = run_one(job, j, &tx);
+                    let _ = tx.send(Event::Done { job, outcome });
+                }
+            });
+        }
+        drop(tx);
+        for event in rx {
+            match event {
+                Event::Line { job, text, stderr: false } => println!("{} {text}", prefixes[job].0),
+                Event::Line { job, text, stderr: true } => eprintln!("{} {text}", prefixes[job].1),
+                Event::Done { job, outcome } => outcomes[job] = Some(outcome),
+            }
+        }
+    });
+    let outcomes: Vec<Outcome> = outcomes
+        .into_iter()
+        .map(|o| o.expect("every job reports when it's done"))
+        .collect();
+    print_summary(jobs, &outcomes, width);
+    outcomes
+}
+
+/// Run one job, sending its output line by line.
+fn run_one(job: usize, j: &Job, tx: &mpsc::Sender<Event>) -> Outcome {
+    let start = Instant::now();
+    let stdin = if j.input.is_some() { Stdio::piped() } else { Stdio::null() };
+    let child = Command::new(&j.argv[0])
+        .args(&j.argv[1..])
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            let text = format!("failed to run {}: {e}", j.argv[0]);
+            let _ = tx.send(Event::Line { job, text, stderr: true });
+            return Outcome {
+                exit_code: 255,
+                duration: start.elapsed(),
+            };
+        }
+    };
+    if let (Some(input), Some(mut stdin)) = (&j.input, child.stdin.take()) {
+        // A host that never reads it would only close the pipe early
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    std::thread::scope(|s| {
+        if let Some(out) = stdout {
+            s.spawn(move || forward_lines(out, job, false, tx));
+        }
+        if let Some(err) = stderr {
+            s.spawn(move || forward_lines(err, job, true, tx));
+        }
+    });
+    let exit_code = child.wait().ok().and_then(|s| s.code()).unwrap_or(255);
+    Outcome {
+        exit_code,
+        duration: start.elapsed(),
+    }
+}
+
+fn forward_lines(stream: impl Read, job: usize, stderr: bool, tx: &mpsc::Sender<Event>) {
+    let mut reader = BufReader::new(stream);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let text = String::from_utf8_lossy(&buf);
+                let text = text.trim_end_matches(['\n', '\r']).to_string();
+                let _ = tx.send(Event::Line { job, text, stderr });
+            }
+        }
+    }
+}
+
+/// The remote command line that runs `command` through sudo (as `user`, or
+/// root), with the password arriving as the first line of stdin. `sudo -v`
+/// takes the password and the command then runs with `sudo -n`, so the
+/// password never reaches the command's stdin, even on hosts where sudo
+/// doesn't ask for it. Wrapped in `sh -c` whatever the login shell is.
+pub fn sudo_command(command: &[String], user: Option<&str>) -> String {
+    let user = user.map(|u| format!("-u {} ", shell_quote(u))).unwrap_or_default();
+    // Without a tty, sudo keys the credentials `-v` caches by parent PID, so
+    // both sudos have to be children of the same shell: no `exec` for the
+    // second one, or its parent would be the login shell and it would find
+    // no cached password.
+    let script = format!(
+        "IFS= read -r p; printf \"%s\\n\" \"$p\" | sudo -S -p \"\" -v 2>/dev/null \
+         || {{ echo \"oken: sudo did not accept the password\" >&2; exit 1; }}; unset p; \
+         sudo -n {user}-- sh -c {}",
+        shell_quote(&command.join(" "))
+    );
+    format!("sh -c {}", shell_quote(&script))
+}
+
+/// `1.4s` under a minute, `3m 05s` and up beyond.
+fn format_elapsed(d: Duration) -> String {
+    if d.as_secs() < 60 {
+        format!("{:.1}s", d.as_secs_f64())
+    } else {
+        audit::format_duration(d.as_secs())
+    }
+}
+
+/// The per-host result table, on stderr so stdout stays the hosts' output.
+fn print_summary(jobs: &[Job], outcomes: &[Outcome], width: usize) {
+    let width = width.max("HOST".len());
+    eprintln!();
+    eprintln!("{}", epaint("1", format!("{:<width$}  {:<17}  DURATION", "HOST", "RESULT")));
+    for (j, o) in jobs.iter().zip(outcomes) {
+        let result = if o.exit_code == 0 {
+            epaint("32", format!("{:<17}", "ok"))
+        } else {
+            epaint("31", format!("{:<17}", format!("failed (exit {})", o.exit_code)))
+        };
+        eprintln!("{:<width$}  {result}  {}", j.alias, format_elapsed(o.duration));
+    }
+    let failed = outcomes.iter().filter(|o| o.exit_code != 0).count();
+    let summary = format!("{} ok, {failed} failed", outcomes.len() - failed);
+    eprintln!("{}", if failed > 0 { epaint("31", summary) } else { summary });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn streams_each_hosts_output() {
+        let job = |alias: &str, script: &str| Job {
+            alias: alias.to_string(),
+            argv: vec!["sh".into(), "-c".into(), script.into()],
+            input: None,
+        };
+        let jobs = [job("a", "echo one; echo two >&2"), job("b", "printf partial; exit 3")];
+        let outcomes = run(&jobs, 2);
+        assert_eq!(outcomes[0].exit_code, 0);
+        assert_eq!(outcomes[1].exit_code, 3);
+        assert_eq!(host_color("web-1"), host_color("web-1"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn runs_commands_through_sudo() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A stand-in sudo that logs its arguments and the password it reads,
+        // and like the real one only trusts `-v` from the same parent process
+        let dir = tempfile::tempdir().unwrap();
+        let sudo = dir.path().join("sudo");
+        std::fs::write(
+            &sudo,
+            "#!/bin/sh\necho \"sudo $*\" >> \"$LOG\"\n\
+             if [ \"$1\" = -S ]; then\n\
+               IFS= read -r pw; echo \"password $pw\" >> \"$LOG\"; echo $PPID > \"$LOG.ts\"; exit\n\
+             fi\n\
+             [ \"$(cat \"$LOG.ts\")\" = $PPID ] || { echo 'a password is required' >&2; exit 1; }\n\
+             shift; [ \"$1\" = -u ] && shift 2; shift; exec \"$@\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&sudo, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let log = dir.path().join("log");
+        let path = std::env::var("PATH").unwrap_or_default();
+        let path = format!("{}:{path}", dir.path().display());
+
+        let command = ["echo 'a  b'".to_string(), "$HOME;".to_string(), "cat".to_string()];
+        let mut child = Command::new("sh")
+            .args(["-c", &sudo_command(&command, Some("post'gres"))])
+            .env("PATH", path)
+            .env("LOG", &log)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"hunter2\nrest\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        let log = std::fs::read_to_string(&log).unwrap();
+
+        // The command line is parsed once, by the shell sudo starts, and only
+        // what follows the password reaches the command's stdin
+        let home = std::env::var("HOME").unwrap_or_default();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), format!("a  b {home}\nrest\n"));
+        assert_eq!(
+            log,
+            "sudo -S -p  -v\npassword hunter2\n\
+             sudo -n -u post'gres -- sh -c echo 'a  b' $HOME; cat\n"
+        );
+    }
+}
@@ -11,6 +11,7 @@ fn db_path() -> Result<PathBuf> {
 
 fn open_db() -> Result<Connection> {
     let path = db_path()?;
+    config::create_parent(&path)?;
     let conn = Connection::open(&path)
         .with_context(|| format!("failed to open history db: {}", path.display()))?;
     conn.execute_batch(
@@ -22,7 +23,11 @@ fn open_db() -> Result<Connection> {
             port         INTEGER,
             connected_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
         );
-        CREATE INDEX IF NOT EXISTS idx_connections_host_alias ON connections (host_alias);",
+        CREATE INDEX IF NOT EXISTS idx_connections_host_alias ON connections (host_alias);
+        CREATE TABLE IF NOT EXISTS remote_dirs (
+            host_alias TEXT PRIMARY KEY,
+            dir        TEXT NOT NULL
+        );",
     )?;
     Ok(conn)
 }
@@ -33,6 +38,45 @@ pub fn record_connection(alias: &str, hostname: Option<&str>, user: Option<&str>
         "INSERT INTO connections (host_alias, hostname, user, port) VALUES (?1, ?2, ?3, ?4)",
         rusqlite::params![alias, hostname, user, port],
     )?;
+    log::debug!("recorded a connection to {alias}");
+    Ok(())
+}
+
+/// The remote directory last used with `oken push`/`pull` for this host.
+pub fn last_remote_dir(alias: &str) -> Result<Option<String>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare("SELECT dir FROM remote_dirs WHERE host_alias = ?1")?;
+    let mut rows = stmt.query([alias])?;
+    Ok(match rows.next()? {
+        Some(row) => Some(row.get(0)?),
+        None => None,
+    })
+}
+
+/// Remember `dir` for the next transfer with this host (`None` forgets it).
+pub fn set_remote_dir(alias: &str, dir: Option<&str>) -> Result<()> {
+    let conn = open_db()?;
+    match dir {
+        Some(dir) => conn.execute(
+            "INSERT INTO remote_dirs (host_alias, dir) VALUES (?1, ?2)
+             ON CONFLICT(host_alias) DO UPDATE SET dir = excluded.dir",
+            rusqlite::params![alias, dir],
+        )?,
+        None => conn.execute("DELETE FROM remote_dirs WHERE host_alias = ?1", [alias])?,
+    };
+    Ok(())
+}
+
+/// Move the connections (and remembered transfer directory) of `from` to
+/// `to`, after two saved hosts are merged into one. `to` keeps its own
+/// directory if it has one.
+pub fn rename_alias(from: &str, to: &str) -> Result<()> {
+    let conn = open_db()?;
+    conn.execute("UPDATE connections SET host_alias = ?2 WHERE host_alias = ?1", [from, to])?;
+    conn.execute("UPDATE OR IGNORE remote_dirs SET host_alias = ?2 WHERE host_alias = ?1", [
+        from, to,
+    ])?;
+    conn.execute("DELETE FROM remote_dirs WHERE host_alias = ?1", [from])?;
     Ok(())
 }
 
@@ -63,6 +107,42 @@ pub fn last_connected_hosts() -> Result<Vec<RecentHost>> {
     Ok(hosts)
 }
 
+/// A target as it was connected to, with how often and when last.
+#[derive(Debug)]
+pub struct RecentTarget {
+    /// The saved alias, or the host name for a plain `user@host` connection.
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub connections: u32,
+    pub last_connected: String,
+}
+
+/// Every distinct host, user and port in the history, most recent first.
+pub fn recent_targets() -> Result<Vec<RecentTarget>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare(
+        "SELECT host_alias, user, port, COUNT(*), MAX(connected_at) as last_connected
+         FROM connections
+         GROUP BY host_alias, user, port
+         ORDER BY last_connected DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(RecentTarget {
+            host: row.get(0)?,
+            user: row.get(1)?,
+            port: row.get(2)?,
+            connections: row.get(3)?,
+            last_connected: row.get(4)?,
+        })
+    })?;
+    let mut targets = Vec::new();
+    for row in rows {
+        targets.push(row?);
+    }
+    Ok(targets)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,86 @@
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+/// Session metadata exported to hook commands as `OKEN_*` environment variables.
+#[derive(Clone)]
+pub struct HookEnv<'a> {
+    /// `connect`, `danger_connect`, `reconnect` or `disconnect`.
+    pub event: &'static str,
+    pub host: &'a str,
+    /// The ssh target as typed or resolved (`user@host`).
+    pub target: &'a str,
+    pub hostname: Option<&'a str>,
+    pub user: Option<&'a str>,
+    pub port: Option<u16>,
+    pub tags: &'a [String],
+    pub danger: bool,
+    pub reason: Option<&'a str>,
+    /// ssh's exit code; only set for post-disconnect hooks.
+    pub exit_code: Option<i32>,
+    /// Session length; only set for post-disconnect hooks.
+    pub duration_secs: Option<u64>,
+    /// Reconnect attempt, counting from 1; only set for reconnect hooks.
+    pub attempt: Option<u32>,
+}
+
+impl HookEnv<'_> {
+    fn vars(&self) -> [(&'static str, String); 12] {
+        let opt = |v: Option<String>| v.unwrap_or_default();
+        [
+            ("OKEN_EVENT", self.event.to_string()),
+            ("OKEN_HOST", self.host.to_string()),
+            ("OKEN_TARGET", self.target.to_string()),
+            ("OKEN_HOSTNAME", opt(self.hostname.map(String::from))),
+            ("OKEN_USER", opt(self.user.map(String::from))),
+            ("OKEN_PORT", opt(self.port.map(|p| p.to_string()))),
+            ("OKEN_TAGS", self.tags.join(",")),
+            ("OKEN_DANGER", if self.danger { "1" } else { "0" }.to_string()),
+            ("OKEN_REASON", opt(self.reason.map(String::from))),
+            ("OKEN_EXIT", opt(self.exit_code.map(|c| c.to_string()))),
+            ("OKEN_DURATION", opt(self.duration_secs.map(|s| s.to_string()))),
+            ("OKEN_ATTEMPT", opt(self.attempt.map(|a| a.to_string()))),
+        ]
+    }
+}
+
+/// Run each hook command through the shell, in order. Stops at the first
+/// command that fails and returns an error naming it.
+pub fn run(commands: &[String], env: &HookEnv) -> Result<()> {
+    for command in commands {
+        log::debug!("running {} hook `{command}`", env.event);
+        let status = shell(command)
+            .envs(env.vars())
+            .status()
+            .with_context(|| format!("failed to run hook `{command}`"))?;
+        if !status.success() {
+            bail!(
+                "hook `{command}` exited with status {}",
+                status.code().unwrap_or(1)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Run every hook command, warning about failures instead of stopping.
+/// Used after a session ends, when there is nothing left to abort.
+pub fn run_all(commands: &[String], env: &HookEnv) {
+    for command in commands {
+        if let Err(e) = run(std::slice::from_ref(command), env) {
+            eprintln!("oken: {e:#}");
+        }
+    }
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
@@ -0,0 +1,687 @@
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+
+use crate::color::epaint;
+use crate::hosts::Host;
+use crate::recording::{self, Recording};
+use crate::{hosts_toml, ssh, time_utils, util};
+
+/// Where ssh looks up a host's key, according to `ssh -G`.
+pub struct Lookup {
+    pub hostname: String,
+    pub port: u16,
+    /// `UserKnownHostsFile`, with `~` expanded; new keys go in the first.
+    pub files: Vec<PathBuf>,
+    /// `HashKnownHosts yes`: write hashed names like ssh would.
+    pub hash: bool,
+    /// `HostKeyAlias`: the name ssh checks the key under instead of the host.
+    pub alias: Option<String>,
+    /// Reached through a jump host or ProxyCommand, which ssh-keyscan can't follow.
+    pub proxied: bool,
+}
+
+impl Lookup {
+    /// The name known_hosts uses: `host`, or `[host]:port` off port 22.
+    pub fn known_hosts_name(&self) -> String {
+        let host = self.alias.as_deref().unwrap_or(&self.hostname);
+        if self.port == 22 {
+            host.to_string()
+        } else {
+            format!("[{host}]:{}", self.port)
+        }
+    }
+}
+
+/// A host key offered by the server, as a known_hosts line.
+pub struct ScannedKey {
+    pub line: String,
+    /// `SHA256:…`
+    pub fingerprint: String,
+    /// `ED25519`, `ECDSA`, `RSA`
+    pub kind: String,
+}
+
+/// Resolve where ssh keeps the key for these ssh args.
+pub fn lookup(args: &[String]) -> Option<Lookup> {
+    let ssh = ssh::find_ssh().ok()?;
+    let output = Command::new(ssh)
+        .arg("-G")
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_lookup(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_lookup(ssh_g: &str) -> Option<Lookup> {
+    let mut hostname = None;
+    let mut port = 22;
+    let mut files = Vec::new();
+    let mut hash = false;
+    let mut alias = None;
+    let mut proxied = false;
+    for line in ssh_g.lines() {
+        match line.split_once(' ') {
+            Some(("hostname", v)) => hostname = Some(v.to_string()),
+            Some(("port", v)) => port = v.parse().unwrap_or(22),
+            Some(("userknownhostsfile", v)) => {
+                files = v.split_whitespace().map(ssh::expand_tilde).collect();
+            }
+            Some(("hashknownhosts", v)) => hash = v == "yes",
+            Some(("hostkeyalias", v)) if v != "none" => alias = Some(v.to_string()),
+            Some(("proxyjump" | "proxycommand", v)) if v != "none" => proxied = true,
+            _ => {}
+        }
+    }
+    if files.is_empty() {
+        files.push(ssh::expand_tilde("~/.ssh/known_hosts"));
+    }
+    Some(Lookup {
+        hostname: hostname?,
+        port,
+        files,
+        hash,
+        alias,
+        proxied,
+    })
+}
+
+/// Whether any known_hosts file has a key for the host. `ssh-keygen -F`
+/// also matches hashed entries.
+pub fn is_known(lookup: &Lookup) -> bool {
+    let name = lookup.known_hosts_name();
+    lookup.files.iter().filter(|f| f.exists()).any(|file| {
+        Command::new("ssh-keygen")
+            .args(["-F", &name, "-f"])
+            .arg(file)
+            .stderr(Stdio::null())
+            .output()
+            .is_ok_and(|o| o.status.success() && !o.stdout.is_empty())
+    })
+}
+
+/// Fetch the host's keys with ssh-keyscan, best first: ED25519, then ECDSA,
+/// then RSA.
+pub fn scan(lookup: &Lookup) -> Result<Vec<ScannedKey>> {
+    let mut keyscan = Command::new("ssh-keyscan");
+    keyscan.args(["-T", "5", "-p", &lookup.port.to_string()]);
+    if lookup.hash {
+        keyscan.arg("-H");
+    }
+    let output = keyscan
+        .arg(&lookup.hostname)
+        .stderr(Stdio::null())
+        .output()
+        .context("failed to run ssh-keyscan")?;
+    let lines: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.starts_with('#') && !l.trim().is_empty())
+        .map(str::to_string)
+        .collect();
+    let mut keys = Vec::new();
+    for line in lines {
+        let fingerprint = keygen(&["-l"], &line)?;
+        let parts: Vec<&str> = fingerprint.split_whitespace().collect();
+        let [_, fingerprint, .., kind] = parts[..] else {
+            continue;
+        };
+        keys.push(ScannedKey {
+            fingerprint: fingerprint.to_string(),
+            kind: kind.trim_matches(['(', ')']).to_string(),
+            line,
+        });
+    }
+    let rank = |kind: &str| match kind {
+        "ED25519" => 0,
+        "ECDSA" => 1,
+        "RSA" => 2,
+        _ => 3,
+    };
+    keys.sort_by_key(|k| rank(&k.kind));
+    Ok(keys)
+}
+
+/// Run `ssh-keygen <args> -f -` on one known_hosts line.
+fn keygen(args: &[&str], line: &str) -> Result<String> {
+    let mut child = Command::new("ssh-keygen")
+        .args(args)
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to run ssh-keygen")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{line}")?;
+    }
+    let output = child.wait_with_output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Delete the host's keys from every known_hosts file with `ssh-keygen -R`,
+/// which also finds hashed entries. Returns how many keys were removed.
+pub fn forget(lookup: &Lookup) -> Result<usize> {
+    let name = lookup.known_hosts_name();
+    let mut removed = 0;
+    for file in lookup.files.iter().filter(|f| f.exists()) {
+        let output = Command::new("ssh-keygen")
+            .args(["-R", &name, "-f"])
+            .arg(file)
+            .stderr(Stdio::null())
+            .output()
+            .context("failed to run ssh-keygen")?;
+        if !output.status.success() {
+            bail!("ssh-keygen could not update {}", file.display());
+        }
+        // One "# Host … found: line N" comment per removed key
+        let found = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|l| l.starts_with("# Host "))
+            .count();
+        if found > 0 {
+            log::debug!("removed {found} key(s) for {name} from {}", file.display());
+        }
+        removed += found;
+    }
+    Ok(removed)
+}
+
+/// Add the key to the first known_hosts file, so ssh checks against it.
+pub fn trust(lookup: &Lookup, key: &ScannedKey) -> Result<()> {
+    let path = &lookup.files[0];
+    crate::config::create_parent(path)?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("could not open {}", path.display()))?;
+    let existing = std::fs::read(path).unwrap_or_default();
+    if existing.last().is_some_and(|&b| b != b'\n') {
+        writeln!(file)?;
+    }
+    writeln!(file, "{}", key.line)?;
+    Ok(())
+}
+
+/// Before the first connection to a saved host, show the key it offers and
+/// ask whether to trust it, instead of ssh's one-line prompt. The accepted
+/// fingerprint is kept in hosts.toml as `host_key`; a later first connection
+/// (new laptop, cleared known_hosts) that sees the same key is trusted
+/// without asking. Does nothing when the host is already in known_hosts.
+pub fn review(host: &Host, args: &[String]) -> Result<()> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Ok(());
+    }
+    if !util::on_path("ssh-keyscan") || !util::on_path("ssh-keygen") {
+        log::debug!("ssh-keyscan or ssh-keygen not found, leaving host key checks to ssh");
+        return Ok(());
+    }
+    let Some(lookup) = lookup(args) else {
+        return Ok(());
+    };
+    if lookup.proxied || lookup.alias.is_some() {
+        log::debug!("'{}' is reached through a proxy, leaving its host key to ssh", host.alias);
+        return Ok(());
+    }
+    if is_known(&lookup) {
+        return Ok(());
+    }
+    let keys = scan(&lookup).unwrap_or_default();
+    let Some(key) = keys.first() else {
+        log::debug!("ssh-keyscan got no keys from {}", lookup.known_hosts_name());
+        return Ok(());
+    };
+
+    if host.host_key.as_deref() == Some(key.fingerprint.as_str()) {
+        log::debug!("{} matches the saved host_key, trusting it", key.fingerprint);
+        return trust(&lookup, key);
+    }
+
+    eprintln!(
+        "{} ({})",
+        epaint("1", format!("First connection to {}", host.alias)),
+        lookup.known_hosts_name()
+    );
+    if let Some(saved) = &host.host_key {
+        eprintln!(
+            "{} this is not the key saved for '{}' ({saved})",
+            epaint("1;31", "WARNING:"),
+            host.alias
+        );
+    }
+    let art = keygen(&["-l", "-v"], &key.line).unwrap_or_default();
+    eprintln!("{} key fingerprint is {}", key.kind, epaint("1", &key.fingerprint));
+    for line in art.lines().skip(1) {
+        eprintln!("  {line}");
+    }
+    if !crate::prompt::confirm("Trust this key and connect?", false)? {
+        bail!("host key for '{}' not trusted, not connecting", host.alias);
+    }
+
+    trust(&lookup, key)?;
+    if !host.from_ssh_config && host.managed_by.is_none() {
+        let path = crate::hosts_toml_path()?;
+        hosts_toml::set_host_key(&path, &host.alias, Some(&key.fingerprint))?;
+        log::debug!("saved host_key for '{}' in {}", host.alias, path.display());
+    }
+    Ok(())
+}
+
+/// How long ssh may wait on a security key touch before oken points it out.
+const TOUCH_REMINDER: Duration = Duration::from_secs(5);
+
+/// How an ssh session ended, as far as host keys are concerned.
+pub enum Exit {
+    Code(i32),
+    /// ssh refused a changed host key and the user didn't replace it.
+    KeyRefused,
+    /// ssh couldn't resolve or connect to the server, so it never got as far
+    /// as authentication.
+    Unreachable,
+}
+
+impl Exit {
+    pub fn code(self) -> i32 {
+        match self {
+            Exit::Code(code) => code,
+            Exit::KeyRefused | Exit::Unreachable => 255,
+        }
+    }
+}
+
+/// What ssh reported when it refused a host because its key changed.
+#[derive(Debug, PartialEq)]
+pub struct KeyChange {
+    /// `ED25519`, `ECDSA`, `RSA`
+    pub kind: String,
+    /// Fingerprint of the key the server sent this time.
+    pub fingerprint: String,
+    /// known_hosts file and line number of the key ssh expected.
+    pub offending: Option<(PathBuf, usize)>,
+}
+
+/// Run ssh like `ssh::run`, but catch its "REMOTE HOST IDENTIFICATION HAS
+/// CHANGED" banner and explain it instead. If the user replaces the key,
+/// ssh is run again.
+pub fn run_ssh(
+    args: &[String],
+    recording: Option<&Recording>,
+    host: Option<&Host>,
+) -> Result<Exit> {
+    loop {
+        let (code, change, unreachable) = run_watched(args, recording)?;
+        let Some(change) = change else {
+            return Ok(if code == 255 && unreachable { Exit::Unreachable } else { Exit::Code(code) });
+        };
+        if !explain_change(&change, host, args)? {
+            return Ok(Exit::KeyRefused);
+        }
+    }
+}
+
+fn run_watched(
+    args: &[String],
+    recording: Option<&Recording>,
+) -> Result<(i32, Option<KeyChange>, bool)> {
+    let ssh = ssh::find_ssh().context("failed to locate ssh")?;
+    let mut cmd = match recording {
+        Some(rec) => rec.command(&ssh, args),
+        None => {
+            let mut c = Command::new(&ssh);
+            c.args(args);
+            c
+        }
+    };
+    let mut child = cmd
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run ssh at {}", ssh.display()))?;
+    let mut stderr = child.stderr.take().context("ssh stderr was not captured")?;
+    let watch = Arc::new(Mutex::new(Watch::default()));
+    let (done_tx, done_rx) = mpsc::channel();
+    let forwarder = Arc::clone(&watch);
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        while let Ok(n) = stderr.read(&mut buf)
+            && n > 0
+        {
+            let show = forwarder.lock().map(|mut w| w.feed(&buf[..n])).unwrap_or_default();
+            let mut out = io::stderr().lock();
+            let _ = out.write_all(&show);
+            let _ = out.flush();
+        }
+        let _ = done_tx.send(());
+    });
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    let reminder = Arc::clone(&watch);
+    std::thread::spawn(move || {
+        // Checks once a second until ssh exits and drops the sender
+        while stop_rx.recv_timeout(Duration::from_secs(1)) == Err(mpsc::RecvTimeoutError::Timeout) {
+            if reminder.lock().is_ok_and(|mut w| w.touch_overdue()) {
+                let note = "Touch your security key to continue (it may be blinking)";
+                eprint!("\r\n{}\x07\r\n", epaint("1;33", note));
+            }
+        }
+    });
+    let status = child.wait()?;
+    drop(stop_tx);
+    // A ProxyCommand or ControlMaster left running can keep the pipe open,
+    // so don't wait for EOF for long
+    let _ = done_rx.recv_timeout(Duration::from_millis(200));
+    let (held, unreachable) =
+        watch.lock().map(|mut w| (w.finish(), w.unreachable)).unwrap_or_default();
+    let change = parse_change(&String::from_utf8_lossy(&held));
+    if change.is_none() {
+        let _ = io::stderr().write_all(&held);
+    }
+    Ok((recording::exit_code(recording, status), change, unreachable))
+}
+
+/// Passes ssh's stderr through as it arrives, except for the banner ssh
+/// prints when a host key changed, which is held back to explain it. Only a
+/// line of `@`s is ever delayed, and only until the line after it shows
+/// which banner it is. Also notes when ssh is waiting for a security key
+/// to be touched, and when it couldn't reach the server at all.
+#[derive(Default)]
+struct Watch {
+    /// When ssh asked to confirm user presence on a FIDO key, until it moves on.
+    touch_prompt: Option<Instant>,
+    reminded: bool,
+    /// The current line, while it might be part of the banner.
+    line: Vec<u8>,
+    /// A complete line of `@`s, waiting for the banner's title line.
+    rule: Vec<u8>,
+    mid_line: bool,
+    /// The banner and everything ssh printed after it.
+    held: Vec<u8>,
+    holding: bool,
+    /// ssh said it couldn't resolve the name or connect to the port.
+    unreachable: bool,
+}
+
+impl Watch {
+    /// Take in a chunk of stderr and return the part to show now.
+    fn feed(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let text = String::from_utf8_lossy(bytes);
+        if text.contains("Confirm user presence") {
+            self.touch_prompt = Some(Instant::now());
+            self.reminded = false;
+        } else if !text.trim().is_empty() {
+            self.touch_prompt = None;
+        }
+        if text.contains("ssh: connect to host ") || text.contains("ssh: Could not resolve hostname") {
+            self.unreachable = true;
+        }
+        let mut show = Vec::new();
+        for &b in bytes {
+            if self.holding {
+                self.held.push(b);
+                continue;
+            }
+            let line_start = !self.mid_line;
+            self.mid_line = b != b'\n';
+            let buffering =
+                !self.line.is_empty() || !self.rule.is_empty() || (line_start && b == b'@');
+            if !buffering {
+                show.push(b);
+                continue;
+            }
+            self.line.push(b);
+            // Before the rule is complete, anything but `@` means it isn't one
+            if self.rule.is_empty() && !matches!(b, b'@' | b'\r' | b'\n') {
+                show.append(&mut self.line);
+                continue;
+            }
+            if b != b'\n' {
+                continue;
+            }
+            let line = std::mem::take(&mut self.line);
+            if self.rule.is_empty() {
+                self.rule = line;
+            } else if String::from_utf8_lossy(&line).contains("IDENTIFICATION HAS CHANGED") {
+                self.holding = true;
+                self.held.append(&mut self.rule);
+                self.held.extend(line);
+            } else {
+                show.append(&mut self.rule);
+                show.extend(line);
+            }
+        }
+        show
+    }
+
+    /// Whether ssh has waited on a security key touch long enough that the
+    /// user probably missed its prompt. True once per prompt.
+    fn touch_overdue(&mut self) -> bool {
+        let overdue = self.touch_prompt.is_some_and(|at| at.elapsed() >= TOUCH_REMINDER);
+        if overdue && !self.reminded {
+            self.reminded = true;
+            return true;
+        }
+        false
+    }
+
+    /// Everything not yet shown when ssh exits: the held banner, or a
+    /// half-finished line that turned out not to be one.
+    fn finish(&mut self) -> Vec<u8> {
+        let mut rest = std::mem::take(&mut self.held);
+        rest.append(&mut self.rule);
+        rest.append(&mut self.line);
+        rest
+    }
+}
+
+/// Pick the new fingerprint and the offending known_hosts line out of ssh's
+/// host key banner.
+fn parse_change(banner: &str) -> Option<KeyChange> {
+    if !banner.contains("REMOTE HOST IDENTIFICATION HAS CHANGED") {
+        return None;
+    }
+    let mut lines = banner.lines().map(str::trim);
+    let intro = lines.find(|l| l.contains("key sent by the remote host is"))?;
+    let kind = intro
+        .strip_prefix("The fingerprint for the ")?
+        .split_whitespace()
+        .next()?
+        .to_string();
+    let fingerprint = lines.next()?.trim_end_matches('.').to_string();
+    let offending = banner.lines().find_map(|l| {
+        let rest = l.trim().strip_prefix("Offending ")?;
+        let (_, location) = rest.split_once(" key in ")?;
+        let (file, line) = location.rsplit_once(':')?;
+        Some((PathBuf::from(file), line.trim().parse().ok()?))
+    });
+    Some(KeyChange {
+        kind,
+        fingerprint,
+        offending,
+    })
+}
+
+/// `ED25519 SHA256:…` for the key on `line` of a known_hosts file.
+fn known_fingerprint(file: &std::path::Path, line: usize) -> Option<String> {
+    let text = std::fs::read_to_string(file).ok()?;
+    let entry = text.lines().nth(line.checked_sub(1)?)?;
+    let out = keygen(&["-l"], entry).ok()?;
+    let parts: Vec<&str> = out.split_whitespace().collect();
+    let [_, fingerprint, .., kind] = parts[..] else {
+        return None;
+    };
+    Some(format!("{} {fingerprint}", kind.trim_matches(['(', ')'])))
+}
+
+/// Show what changed in place of ssh's warning, say whether hosts.toml
+/// expects it, and on an explicit confirmation replace the old key.
+/// Returns whether the key was replaced.
+fn explain_change(change: &KeyChange, host: Option<&Host>, args: &[String]) -> Result<bool> {
+    let lookup = lookup(args);
+    let target = lookup.as_ref().map(Lookup::known_hosts_name).unwrap_or_default();
+    let name = host.map_or(target.as_str(), |h| h.alias.as_str());
+    // \x1b[K clears the "Connecting to …" line this replaces
+    eprintln!(
+        "\r{} ({target})\x1b[K",
+        epaint("1;31", format!("The host key for {name} has changed"))
+    );
+    if let Some((file, line)) = &change.offending {
+        let old = known_fingerprint(file, *line).unwrap_or_else(|| "?".to_string());
+        eprintln!("  was  {old}  ({}:{line})", file.display());
+    }
+    eprintln!("  now  {} {}", change.kind, epaint("1", &change.fingerprint));
+
+    if let Some(h) = host {
+        if let Some(date) = &h.rebuild {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            match time_utils::iso8601_to_unix(date) {
+                Some(at) if at <= now => eprintln!(
+                    "{name} has a rebuild noted for {date} in hosts.toml, so a new key is expected."
+                ),
+                _ => eprintln!(
+                    "{name} has a rebuild planned for {date}, which has not come yet."
+                ),
+            }
+        } else if !h.from_ssh_config {
+            eprintln!("hosts.toml notes no planned rebuild for {name} (`rebuild = \"<date>\"`).");
+        }
+        if h.host_key.as_deref() == Some(change.fingerprint.as_str()) {
+            eprintln!("The new key matches the host_key saved for {name}.");
+        }
+    }
+    eprintln!(
+        "A new key is normal after a server is rebuilt or reinstalled, but it can also mean \
+         someone is intercepting the connection. Check the fingerprint with whoever runs the \
+         server before replacing it."
+    );
+
+    let Some(lookup) = lookup.filter(|_| io::stdin().is_terminal()) else {
+        eprintln!("To replace the old key: oken keys forget {name}");
+        return Ok(false);
+    };
+    eprint!("Type '{name}' to replace the old key and connect: ");
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    if line.trim() != name {
+        eprintln!("Keeping the old key, not connecting.");
+        return Ok(false);
+    }
+
+    forget(&lookup)?;
+    // Trust the new key now if it is the one ssh just saw; otherwise ssh asks
+    let scanned = if lookup.proxied || lookup.alias.is_some() {
+        Vec::new()
+    } else {
+        scan(&lookup).unwrap_or_default()
+    };
+    if let Some(key) = scanned.iter().find(|k| k.fingerprint == change.fingerprint) {
+        trust(&lookup, key)?;
+        if let Some(h) = host.filter(|h| !h.from_ssh_config && h.managed_by.is_none()) {
+            let path = crate::hosts_toml_path()?;
+            hosts_toml::set_host_key(&path, &h.alias, Some(&key.fingerprint))?;
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_hosts_lookup() {
+        let lookup = parse_lookup(concat!(
+            "hostname 10.0.0.1\n",
+            "port 2222\n",
+            "userknownhostsfile /etc/kh /tmp/kh2\n",
+            "hashknownhosts yes\n",
+            "proxyjump none\n",
+        ))
+        .unwrap();
+        assert_eq!(lookup.known_hosts_name(), "[10.0.0.1]:2222");
+        assert_eq!(lookup.files, vec![PathBuf::from("/etc/kh"), PathBuf::from("/tmp/kh2")]);
+        assert!(lookup.hash);
+        assert!(!lookup.proxied);
+
+        let lookup = parse_lookup("hostname 10.0.0.1\nproxyjump bastion\nhostkeyalias db\n");
+        let lookup = lookup.unwrap();
+        assert!(lookup.proxied);
+        assert_eq!(lookup.known_hosts_name(), "db");
+    }
+
+    const BANNER: &str = "\
+@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+@    WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!     @
+@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+IT IS POSSIBLE THAT SOMEONE IS DOING SOMETHING NASTY!
+The fingerprint for the ED25519 key sent by the remote host is
+SHA256:QBdGWRnIRZB2gzQIspBdKdabcN7/04DO2hKOHLPSjYw.
+Please contact your system administrator.
+Offending ED25519 key in /home/me/.ssh/known_hosts:3
+  remove with:
+  ssh-keygen -f '/home/me/.ssh/known_hosts' -R '10.0.0.1'
+Host key verification failed.
+";
+
+    #[test]
+    fn notices_security_key_prompts() {
+        let mut watch = Watch::default();
+        let prompt = b"Confirm user presence for key ED25519-SK SHA256:x\r\n";
+        assert_eq!(watch.feed(prompt), prompt);
+        assert!(!watch.touch_overdue());
+        watch.touch_prompt = Instant::now().checked_sub(TOUCH_REMINDER);
+        assert!(watch.touch_overdue());
+        assert!(!watch.touch_overdue());
+        watch.feed(b"User presence confirmed\r\n");
+        assert!(watch.touch_prompt.is_none());
+    }
+
+    #[test]
+    fn notices_unreachable_servers() {
+        let mut watch = Watch::default();
+        watch.feed(b"Permission denied (publickey).\r\n");
+        assert!(!watch.unreachable);
+        watch.feed(b"ssh: connect to host 10.0.0.5 port 22: Connection timed out\r\n");
+        assert!(watch.unreachable);
+    }
+
+    #[test]
+    fn parses_host_key_change() {
+        let change = parse_change(BANNER).unwrap();
+        assert_eq!(change.kind, "ED25519");
+        assert_eq!(change.fingerprint, "SHA256:QBdGWRnIRZB2gzQIspBdKdabcN7/04DO2hKOHLPSjYw");
+        assert_eq!(change.offending, Some((PathBuf::from("/home/me/.ssh/known_hosts"), 3)));
+        assert!(parse_change("Permission denied (publickey).\n").is_none());
+    }
+
+    #[test]
+    fn holds_back_only_the_key_change_banner() {
+        let mut watch = Watch::default();
+        let mut shown = watch.feed(b"Warning: Permanently added '10.0.0.2'\n@@@@");
+        shown.extend(watch.feed(b"@@\n@ WARNING: UNPROTECTED PRIVATE KEY FILE! @\n"));
+        shown.extend(watch.feed(b"@user 50% done\r"));
+        assert_eq!(
+            String::from_utf8(shown).unwrap(),
+            "Warning: Permanently added '10.0.0.2'\n@@@@@@\n\
+             @ WARNING: UNPROTECTED PRIVATE KEY FILE! @\n@user 50% done\r"
+        );
+
+        let mut watch = Watch::default();
+        let (head, tail) = BANNER.split_at(70);
+        assert!(watch.feed(head.as_bytes()).is_empty());
+        assert!(watch.feed(tail.as_bytes()).is_empty());
+        assert_eq!(String::from_utf8(watch.finish()).unwrap(), BANNER);
+    }
+}
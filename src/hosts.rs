@@ -3,19 +3,111 @@ use std::collections::HashMap;
 use anyhow::Result;
 
 use crate::config;
-use crate::hosts_toml;
+use crate::hosts_toml::{self, AddressFamily, Connect, Transport};
+use crate::oken_config::HostDefaults;
 use crate::ssh_config;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct Host {
     pub alias: String,
+    /// Other names the host answers to; history is kept under `alias`.
+    pub aliases: Vec<String>,
     pub hostname: Option<String>,
+    /// Other addresses to try along with `hostname` (see [`crate::addresses`]).
+    pub addresses: Vec<String>,
+    pub address_family: AddressFamily,
+    /// Tried in order when ssh can't reach the host (see `main::run_ssh`).
+    pub fallback_hostnames: Vec<String>,
     pub user: Option<String>,
     pub port: Option<u16>,
     pub identity_file: Option<String>,
+    /// Certificate passed to ssh as `CertificateFile`.
+    pub certificate_file: Option<String>,
     pub tags: Vec<String>,
+    /// Record sessions to this host.
+    pub record: bool,
+    pub transport: Transport,
+    /// ProxyCommand preset or command, expanded by `proxy::expand`.
+    pub proxy: Option<String>,
+    pub connect: Connect,
+    /// Extra args for the `connect` CLI (profile, region, …).
+    pub connect_args: Vec<String>,
+    /// Expiry date from hosts.toml, as written there.
+    pub expires: Option<String>,
+    pub on_connect: Vec<String>,
+    pub on_disconnect: Vec<String>,
+    /// Host key fingerprint accepted on first connection (`SHA256:…`).
+    pub host_key: Option<String>,
+    /// Planned rebuild date from hosts.toml, as written there.
+    pub rebuild: Option<String>,
+    /// The host asks for a TOTP code; show it before connecting.
+    pub mfa: bool,
+    /// User `oken exec --sudo` runs commands as (root when unset).
+    pub become_user: Option<String>,
+    /// MAC address for Wake-on-LAN (`oken wake`).
+    pub mac_address: Option<String>,
+    /// Who to ask about the host, and their team.
+    pub owner: Option<String>,
+    pub team: Option<String>,
     /// True when this host comes from ~/.ssh/config (read-only; not managed by oken).
+    #[serde(skip)]
     pub from_ssh_config: bool,
+    /// Cloud provider that syncs this host (`oken cloud sync <provider>`).
+    pub managed_by: Option<String>,
+}
+
+impl Host {
+    /// Where the host is defined: `hosts.toml` or `ssh_config`.
+    pub fn source(&self) -> &'static str {
+        if self.from_ssh_config { "ssh_config" } else { "hosts.toml" }
+    }
+
+    /// Whether the host's `expires` date has passed. Unparseable dates never expire.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires
+            .as_deref()
+            .and_then(crate::time_utils::iso8601_to_unix)
+            .is_some_and(|at| at <= now)
+    }
+
+    /// Whether `name` is the host's alias or one of its other names.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.alias == name || self.aliases.iter().any(|a| a == name)
+    }
+
+    /// Whether one of the host's tags answers to `wanted` (see [`tag_matches`]).
+    pub fn has_tag(&self, wanted: &str) -> bool {
+        self.tags.iter().any(|t| tag_matches(t, wanted))
+    }
+}
+
+/// The host called `name`. A host's own alias wins over another host's
+/// `aliases` entry with the same name.
+pub fn find<'a>(hosts: &'a [Host], name: &str) -> Option<&'a Host> {
+    hosts
+        .iter()
+        .find(|h| h.alias == name)
+        .or_else(|| hosts.iter().find(|h| h.is_named(name)))
+}
+
+/// Whether `tag` answers to `wanted`, ignoring case. Tags can be flat
+/// (`prod`) or namespaced (`env:prod`). `env:prod` only matches that tag,
+/// `env:*` any tag in the namespace, and a bare `prod` matches the flat tag
+/// as well as any namespaced tag with that value, so `--tag prod` keeps
+/// working after the tags move to `env:prod`.
+pub fn tag_matches(tag: &str, wanted: &str) -> bool {
+    if let Some(namespace) = wanted.strip_suffix(":*") {
+        return tag
+            .split_once(':')
+            .is_some_and(|(ns, _)| ns.eq_ignore_ascii_case(namespace));
+    }
+    if tag.eq_ignore_ascii_case(wanted) {
+        return true;
+    }
+    !wanted.contains(':')
+        && tag
+            .split_once(':')
+            .is_some_and(|(_, value)| value.eq_ignore_ascii_case(wanted))
 }
 
 /// Load all hosts from ssh_config and hosts.toml, with hosts.toml winning on conflicts.
@@ -23,18 +115,43 @@ pub fn list_all_hosts() -> Result<Vec<Host>> {
     let mut hosts_map: HashMap<String, Host> = HashMap::new();
 
     // 1. Load from ~/.ssh/config
-    let ssh_hosts = ssh_config::parse_ssh_config().unwrap_or_default();
+    let ssh_hosts = ssh_config::parse_ssh_config().unwrap_or_else(|e| {
+        log::warn!("could not read ~/.ssh/config: {e:#}");
+        Vec::new()
+    });
+    log::debug!("{} host(s) from ~/.ssh/config", ssh_hosts.len());
     for alias in ssh_hosts {
         hosts_map.insert(
             alias.clone(),
             Host {
                 alias,
+                aliases: Vec::new(),
                 hostname: None, // resolved lazily via ssh -G
+                addresses: Vec::new(),
+                address_family: AddressFamily::Any,
+                fallback_hostnames: Vec::new(),
                 user: None,
                 port: None,
                 identity_file: None,
+                certificate_file: None,
                 tags: Vec::new(),
+                record: false,
+                transport: Transport::Ssh,
+                proxy: None,
+                connect: Connect::Direct,
+                connect_args: Vec::new(),
+                expires: None,
+                on_connect: Vec::new(),
+                on_disconnect: Vec::new(),
+                host_key: None,
+                rebuild: None,
+                mfa: false,
+                become_user: None,
+                mac_address: None,
+                owner: None,
+                team: None,
                 from_ssh_config: true,
+                managed_by: None,
             },
         );
     }
@@ -42,18 +159,48 @@ pub fn list_all_hosts() -> Result<Vec<Host>> {
     // 2. Overlay from hosts.toml (wins on conflict)
     let config_dir = config::config_dir()?;
     let toml_path = config_dir.join("hosts.toml");
-    let toml_hosts = hosts_toml::load_hosts_toml(&toml_path).unwrap_or_default();
-    for (alias, entry) in toml_hosts {
+    let toml_hosts = hosts_toml::load_hosts_toml(&toml_path).unwrap_or_else(|e| {
+        log::warn!("ignoring {}: {e:#}", toml_path.display());
+        Default::default()
+    });
+    log::debug!("{} host(s) from {}", toml_hosts.len(), toml_path.display());
+    let defaults = crate::oken_config::host_defaults();
+    for (alias, mut entry) in toml_hosts {
+        apply_defaults(&mut entry, &defaults);
+        if hosts_map.contains_key(&alias) {
+            log::debug!("'{alias}' from hosts.toml overrides the ~/.ssh/config entry");
+        }
         hosts_map.insert(
             alias.clone(),
             Host {
                 alias,
+                aliases: entry.aliases,
                 hostname: Some(entry.hostname),
+                addresses: entry.addresses,
+                address_family: entry.address_family,
+                fallback_hostnames: entry.fallback_hostnames,
                 user: entry.user,
                 port: entry.port,
                 identity_file: entry.identity_file,
+                certificate_file: entry.certificate_file,
                 tags: entry.tags,
+                record: entry.record,
+                transport: entry.transport,
+                proxy: entry.proxy,
+                connect: entry.connect,
+                connect_args: entry.connect_args,
+                expires: entry.expires,
+                on_connect: entry.on_connect,
+                on_disconnect: entry.on_disconnect,
+                host_key: entry.host_key,
+                rebuild: entry.rebuild,
+                mfa: entry.mfa,
+                become_user: entry.become_user,
+                mac_address: entry.mac_address,
+                owner: entry.owner,
+                team: entry.team,
                 from_ssh_config: false,
+                managed_by: entry.managed_by,
             },
         );
     }
@@ -62,3 +209,54 @@ pub fn list_all_hosts() -> Result<Vec<Host>> {
     hosts.sort_by(|a, b| a.alias.cmp(&b.alias));
     Ok(hosts)
 }
+
+/// Fill in what a hosts.toml entry leaves out from config.toml's `[defaults]`.
+/// SSM hosts without a user get a Session Manager shell rather than ssh, so
+/// the default user doesn't turn them into ssh hosts.
+fn apply_defaults(entry: &mut hosts_toml::HostEntry, defaults: &HostDefaults) {
+    if entry.user.is_none() && entry.connect != Connect::Ssm {
+        entry.user = defaults.user.clone();
+    }
+    entry.port = entry.port.or(defaults.port);
+    if entry.identity_file.is_none() {
+        entry.identity_file = defaults.identity_file.clone();
+    }
+    if entry.tags.is_empty() {
+        entry.tags = defaults.tags.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_namespaced_tags() {
+        assert!(tag_matches("env:prod", "env:prod"));
+        assert!(tag_matches("Env:Prod", "env:prod"));
+        assert!(tag_matches("env:prod", "prod"));
+        assert!(tag_matches("env:prod", "env:*"));
+        assert!(tag_matches("prod", "prod"));
+        assert!(!tag_matches("prod", "env:prod"));
+        assert!(!tag_matches("region:eu-west", "env:*"));
+        assert!(!tag_matches("env:prod", "region:prod"));
+        let host = Host {
+            tags: vec!["role:db".to_string(), "env:staging".to_string()],
+            ..Default::default()
+        };
+        assert!(host.has_tag("db") && host.has_tag("ENV:*") && !host.has_tag("env:prod"));
+    }
+
+    #[test]
+    fn finds_hosts_by_other_names() {
+        let host = |alias: &str, aliases: &[&str]| Host {
+            alias: alias.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+        let hosts = [host("web1", &["w1", "db"]), host("db", &[])];
+        assert_eq!(find(&hosts, "w1").unwrap().alias, "web1");
+        assert_eq!(find(&hosts, "db").unwrap().alias, "db");
+        assert!(find(&hosts, "web").is_none());
+    }
+}
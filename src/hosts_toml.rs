@@ -1,42 +1,229 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Program used to open interactive sessions to a host.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    #[default]
+    Ssh,
+    Mosh,
+    /// Eternal Terminal, falling back to ssh when unavailable.
+    Et,
+    /// `aws ssm start-session` shell, chosen for `connect = "ssm"` hosts without a user.
+    #[serde(skip)]
+    Ssm,
+}
+
+impl Transport {
+    fn is_ssh(&self) -> bool {
+        *self == Transport::Ssh
+    }
+}
+
+/// How a host is reached, for machines without a routable sshd.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Connect {
+    /// Straight to `hostname`.
+    #[default]
+    Direct,
+    /// Through AWS Session Manager; `hostname` is the instance ID.
+    Ssm,
+    /// Through a GCP Identity-Aware Proxy tunnel; `hostname` is the instance name.
+    Iap,
+}
+
+impl Connect {
+    pub fn is_direct(&self) -> bool {
+        *self == Connect::Direct
+    }
+
+    /// Built-in proxy preset that carries ssh over this connection.
+    pub fn proxy_preset(&self) -> Option<&'static str> {
+        match self {
+            Connect::Direct => None,
+            Connect::Ssm => Some("aws-ssm"),
+            Connect::Iap => Some("gcp-iap"),
+        }
+    }
+}
+
+/// IP version ssh uses to reach a host (`AddressFamily` in ssh_config).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    #[default]
+    Any,
+    /// IPv4 only.
+    Inet,
+    /// IPv6 only.
+    Inet6,
+}
+
+impl AddressFamily {
+    pub fn is_any(&self) -> bool {
+        *self == AddressFamily::Any
+    }
+
+    /// The value ssh takes, as written in hosts.toml.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AddressFamily::Any => "any",
+            AddressFamily::Inet => "inet",
+            AddressFamily::Inet6 => "inet6",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct HostEntry {
     pub hostname: String,
+    /// Other names for the host on the command line, in the picker and in
+    /// completions (`aliases = ["w1", "legacy-name"]`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// More addresses of a dual-homed host (private and public, IPv4 and
+    /// IPv6), tried along with `hostname` when connecting.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<String>,
+    /// Connect over IPv4 (`inet`) or IPv6 (`inet6`) only.
+    #[serde(default, skip_serializing_if = "AddressFamily::is_any")]
+    pub address_family: AddressFamily,
+    /// Hostnames to try in order when ssh can't reach `hostname` at all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_hostnames: Vec<String>,
     pub user: Option<String>,
     pub port: Option<u16>,
     pub identity_file: Option<String>,
+    /// OpenSSH certificate to present along with the key (`CertificateFile`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate_file: Option<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub tags: Vec<String>,
+    /// Record every session to this host (see `oken audit play`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub record: bool,
+    /// Connect with `ssh` (default), `mosh`, or `et`.
+    #[serde(default, skip_serializing_if = "Transport::is_ssh")]
+    pub transport: Transport,
+    /// ProxyCommand preset (e.g. "aws-ssm", "corkscrew proxy:3128") or a literal command.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Reach the host through a cloud service instead of its address ("ssm", "iap").
+    #[serde(default, skip_serializing_if = "Connect::is_direct")]
+    pub connect: Connect,
+    /// Extra args for the `connect` CLI, e.g. ["--profile", "work", "--region", "eu-west-1"].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub connect_args: Vec<String>,
+    /// After this date (`2026-03-01` or `2026-03-01T14:30:00Z`, UTC) the host
+    /// is hidden, and `oken host list --expired` offers to remove it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    /// Shell commands run before connecting to this host (`pre_connect` is
+    /// the old name).
+    #[serde(default, alias = "pre_connect", skip_serializing_if = "Vec::is_empty")]
+    pub on_connect: Vec<String>,
+    /// Shell commands run after a session with this host ends
+    /// (`post_disconnect` is the old name).
+    #[serde(default, alias = "post_disconnect", skip_serializing_if = "Vec::is_empty")]
+    pub on_disconnect: Vec<String>,
+    /// Fingerprint (`SHA256:…`) of the host key accepted on first connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_key: Option<String>,
+    /// Date of a planned rebuild or reinstall (`2026-03-01`); a changed host
+    /// key from then on is expected rather than suspicious.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rebuild: Option<String>,
+    /// The host prompts for a TOTP code; `oken otp` shows it before connecting.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mfa: bool,
+    /// User `oken exec --sudo` runs commands as on this host, instead of root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub become_user: Option<String>,
+    /// MAC address `oken wake` sends the Wake-on-LAN packet to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac_address: Option<String>,
+    /// Who to ask before changing or rebooting the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Team responsible for the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    /// Provider whose `[managed.<provider>]` section this entry lives in.
+    #[serde(skip)]
+    pub managed_by: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 struct HostsFile {
     #[serde(default)]
     hosts: HashMap<String, HostEntry>,
+    /// Hosts written by `oken cloud sync <provider>`, one table per provider.
+    /// Rewritten on every sync, so hand edits belong in `[hosts]`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    managed: BTreeMap<String, BTreeMap<String, HostEntry>>,
+    /// Hosts put away by `oken host prune`; not loaded. Move one back to
+    /// `[hosts]` to use it again.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    archived: BTreeMap<String, HostEntry>,
 }
 
-/// Parse `~/.config/oken/hosts.toml` and return the hosts map.
-/// Returns an empty map if the file doesn't exist.
-pub fn load_hosts_toml(path: &Path) -> Result<HashMap<String, HostEntry>> {
+/// Hosts added, refreshed, or pruned by a provider sync.
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: Vec<String>,
+}
+
+fn load_file(path: &Path) -> Result<HostsFile> {
     if !path.exists() {
-        return Ok(HashMap::new());
+        return Ok(HostsFile::default());
     }
     let contents = std::fs::read_to_string(path)?;
-    let file: HostsFile = toml::from_str(&contents)?;
-    Ok(file.hosts)
+    Ok(toml::from_str(&contents)?)
 }
 
-/// Serialize and write hosts map back to the TOML file.
-fn save_hosts_toml(path: &Path, hosts: &HashMap<String, HostEntry>) -> Result<()> {
-    let file = HostsFile {
-        hosts: hosts.clone(),
-    };
-    let contents = toml::to_string_pretty(&file)?;
+/// Parse `~/.config/oken/hosts.toml` and return the hosts map, including
+/// provider-managed hosts (`[hosts]` wins when an alias is in both).
+/// Returns an empty map if the file doesn't exist.
+pub fn load_hosts_toml(path: &Path) -> Result<HashMap<String, HostEntry>> {
+    let file = load_file(path)?;
+    let mut hosts = HashMap::new();
+    for (provider, entries) in file.managed {
+        for (alias, mut entry) in entries {
+            entry.managed_by = Some(provider.clone());
+            hosts.insert(alias, entry);
+        }
+    }
+    hosts.extend(file.hosts);
+    Ok(hosts)
+}
+
+/// Aliases defined in more than one section of hosts.toml, with the
+/// sections (`hosts`, `managed.aws`, …) in the order they're shadowed.
+pub fn duplicate_aliases(path: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+    let file = load_file(path)?;
+    let mut sections: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for alias in file.hosts.keys() {
+        sections.entry(alias.clone()).or_default().push("hosts".to_string());
+    }
+    for (provider, entries) in &file.managed {
+        for alias in entries.keys() {
+            sections.entry(alias.clone()).or_default().push(format!("managed.{provider}"));
+        }
+    }
+    sections.retain(|_, found| found.len() > 1);
+    Ok(sections)
+}
+
+/// Serialize and write the hosts file back to disk.
+fn save_hosts_toml(path: &Path, file: &HostsFile) -> Result<()> {
+    let contents = toml::to_string_pretty(file)?;
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
     }
@@ -46,21 +233,99 @@ fn save_hosts_toml(path: &Path, hosts: &HashMap<String, HostEntry>) -> Result<()
 
 /// Add a host entry. Errors if the name already exists.
 pub fn add_host(path: &Path, name: &str, entry: HostEntry) -> Result<()> {
-    let mut hosts = load_hosts_toml(path)?;
-    if hosts.contains_key(name) {
+    let mut file = load_file(path)?;
+    if file.hosts.contains_key(name) || file.managed.values().any(|m| m.contains_key(name)) {
         bail!("host '{}' already exists", name);
     }
-    hosts.insert(name.to_string(), entry);
-    save_hosts_toml(path, &hosts)
+    file.hosts.insert(name.to_string(), entry);
+    save_hosts_toml(path, &file)
 }
 
-/// Remove a host entry. Errors if the name doesn't exist.
+/// Remove a host entry. Errors if the name doesn't exist or belongs to a
+/// provider sync (it would come back on the next one).
 pub fn remove_host(path: &Path, name: &str) -> Result<()> {
-    let mut hosts = load_hosts_toml(path)?;
-    if hosts.remove(name).is_none() {
+    let mut file = load_file(path)?;
+    if file.hosts.remove(name).is_none() {
+        if let Some((provider, _)) = file.managed.iter().find(|(_, m)| m.contains_key(name)) {
+            bail!("host '{name}' is managed by `oken cloud sync {provider}`");
+        }
         bail!("host '{}' not found", name);
     }
-    save_hosts_toml(path, &hosts)
+    save_hosts_toml(path, &file)
+}
+
+/// Set or clear the saved host key fingerprint of a `[hosts]` entry.
+pub fn set_host_key(path: &Path, name: &str, fingerprint: Option<&str>) -> Result<()> {
+    let mut file = load_file(path)?;
+    let Some(entry) = file.hosts.get_mut(name) else {
+        bail!("host '{}' not found", name);
+    };
+    entry.host_key = fingerprint.map(str::to_string);
+    save_hosts_toml(path, &file)
+}
+
+/// Remove the named hosts wherever they live, including provider sections
+/// (expired machines are gone, so a sync wouldn't bring them back).
+pub fn remove_hosts(path: &Path, names: &[String]) -> Result<()> {
+    let mut file = load_file(path)?;
+    file.hosts.retain(|alias, _| !names.contains(alias));
+    for section in file.managed.values_mut() {
+        section.retain(|alias, _| !names.contains(alias));
+    }
+    file.managed.retain(|_, section| !section.is_empty());
+    save_hosts_toml(path, &file)
+}
+
+/// Replace the `[hosts]` entries named in `merged` with `entry` under `keep`.
+pub fn merge_hosts(path: &Path, keep: &str, entry: HostEntry, merged: &[String]) -> Result<()> {
+    let mut file = load_file(path)?;
+    file.hosts.retain(|alias, _| !merged.contains(alias));
+    file.hosts.insert(keep.to_string(), entry);
+    save_hosts_toml(path, &file)
+}
+
+/// Move the named `[hosts]` entries to `[archived]`, replacing older
+/// archived entries with the same alias.
+pub fn archive_hosts(path: &Path, names: &[String]) -> Result<()> {
+    let mut file = load_file(path)?;
+    for name in names {
+        if let Some(entry) = file.hosts.remove(name) {
+            file.archived.insert(name.clone(), entry);
+        }
+    }
+    save_hosts_toml(path, &file)
+}
+
+/// Merge hosts from a provider into its `[managed.<provider>]` section,
+/// replacing entries with the same alias. The rest are kept, or dropped
+/// when `prune` is set.
+pub fn sync_managed(
+    path: &Path,
+    provider: &str,
+    entries: BTreeMap<String, HostEntry>,
+    prune: bool,
+) -> Result<SyncSummary> {
+    let mut file = load_file(path)?;
+    let section = file.managed.entry(provider.to_string()).or_default();
+    let mut summary = SyncSummary::default();
+    if prune {
+        section.retain(|alias, _| {
+            let keep = entries.contains_key(alias);
+            if !keep {
+                summary.removed.push(alias.clone());
+            }
+            keep
+        });
+    }
+    for (alias, entry) in entries {
+        match section.insert(alias, entry) {
+            Some(_) => summary.updated += 1,
+            None => summary.added += 1,
+        }
+    }
+    file.managed.retain(|_, section| !section.is_empty());
+    save_hosts_toml(path, &file)?;
+    Ok(summary)
 }
 
 #[cfg(test)]
@@ -98,6 +363,33 @@ hostname = "10.0.2.10"
         assert!(hosts["staging"].tags.is_empty());
     }
 
+    #[test]
+    fn managed_sections_merge_under_manual_hosts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts.toml");
+        add_host(&path, "web", HostEntry { hostname: "10.0.0.1".into(), ..Default::default() })
+            .unwrap();
+
+        let synced = BTreeMap::from([
+            ("web".to_string(), HostEntry { hostname: "3.3.3.3".into(), ..Default::default() }),
+            ("db".to_string(), HostEntry { hostname: "10.0.0.2".into(), ..Default::default() }),
+        ]);
+        let summary = sync_managed(&path, "aws", synced, false).unwrap();
+        assert_eq!((summary.added, summary.updated), (2, 0));
+
+        let hosts = load_hosts_toml(&path).unwrap();
+        assert_eq!(hosts["web"].hostname, "10.0.0.1");
+        assert!(hosts["web"].managed_by.is_none());
+        assert_eq!(hosts["db"].managed_by.as_deref(), Some("aws"));
+        assert!(remove_host(&path, "db").is_err());
+        assert!(add_host(&path, "db", HostEntry::default()).is_err());
+
+        let synced = BTreeMap::from([("db".to_string(), HostEntry::default())]);
+        let summary = sync_managed(&path, "aws", synced, true).unwrap();
+        assert_eq!((summary.updated, summary.removed), (1, vec!["web".to_string()]));
+        assert_eq!(load_hosts_toml(&path).unwrap()["web"].hostname, "10.0.0.1");
+    }
+
     #[test]
     fn missing_file_returns_empty() {
         let hosts = load_hosts_toml(Path::new("/nonexistent/hosts.toml")).unwrap();
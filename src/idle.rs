@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::audit::format_duration;
+
+/// How often the watchdog looks at the terminal.
+const CHECK_EVERY: Duration = Duration::from_secs(30);
+
+/// Sends a desktop notification when the session's terminal has had no input
+/// for a while. Stops watching when dropped.
+pub struct Watchdog {
+    _stop: mpsc::Sender<()>,
+}
+
+/// Watch the terminal `alias`'s session runs in, or `None` when stdin isn't
+/// a terminal oken can find. The kernel bumps a terminal's access time when
+/// it's read from, so that's when ssh last got a key press.
+pub fn watch(alias: &str, after: Duration) -> Option<Watchdog> {
+    let tty = terminal_path()?;
+    log::debug!("reminding about '{alias}' after {}s without input on {}", after.as_secs(), tty.display());
+    let (stop, stopped) = mpsc::channel::<()>();
+    let alias = alias.to_string();
+    thread::spawn(move || {
+        // Remind once per idle stretch, not every check
+        let mut reminded: Option<SystemTime> = None;
+        while stopped.recv_timeout(CHECK_EVERY) == Err(mpsc::RecvTimeoutError::Timeout) {
+            let Ok(last_input) = std::fs::metadata(&tty).and_then(|m| m.accessed()) else {
+                continue;
+            };
+            let idle = last_input.elapsed().unwrap_or_default();
+            if idle >= after && reminded != Some(last_input) {
+                reminded = Some(last_input);
+                // A forgotten shell is often still the window in front
+                crate::notify::show(
+                    "oken: idle session",
+                    &format!(
+                        "{alias} has had no input for {} — close it if you're done",
+                        format_duration(idle.as_secs())
+                    ),
+                );
+            }
+        }
+    });
+    Some(Watchdog { _stop: stop })
+}
+
+/// The terminal device on stdin, as `tty` prints it.
+fn terminal_path() -> Option<PathBuf> {
+    let out = Command::new("tty")
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let path = String::from_utf8(out.stdout).ok()?;
+    out.status.success().then(|| PathBuf::from(path.trim()))
+}
@@ -0,0 +1,429 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::cloud::Machine;
+use crate::hosts_toml::HostEntry;
+use crate::mapping::Mapping;
+use crate::ssh_config;
+use crate::util;
+
+#[derive(Deserialize)]
+struct NodeList {
+    #[serde(default)]
+    items: Vec<Node>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    metadata: NodeMetadata,
+    #[serde(default)]
+    status: NodeStatus,
+}
+
+#[derive(Deserialize)]
+struct NodeMetadata {
+    name: String,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+#[derive(Default, Deserialize)]
+struct NodeStatus {
+    #[serde(default)]
+    addresses: Vec<NodeAddress>,
+}
+
+#[derive(Deserialize)]
+struct NodeAddress {
+    #[serde(rename = "type")]
+    kind: String,
+    address: String,
+}
+
+/// Nodes of the cluster behind a kubectl context (the current one by
+/// default). Returns the context name, for tagging, and the nodes.
+pub fn kubectl_nodes(context: Option<&str>) -> Result<(String, Vec<Machine>)> {
+    if !util::on_path("kubectl") {
+        bail!("kubectl not found on PATH");
+    }
+    let context = match context {
+        Some(c) => c.to_string(),
+        None => kubectl(&["config", "current-context"])?.trim().to_string(),
+    };
+    let json = kubectl(&["get", "nodes", "-o", "json", "--context", &context])?;
+    Ok((context.clone(), parse_nodes(&json, &context)?))
+}
+
+fn kubectl(args: &[&str]) -> Result<String> {
+    let output = Command::new("kubectl")
+        .args(args)
+        .output()
+        .context("failed to run kubectl")?;
+    if !output.status.success() {
+        bail!(
+            "kubectl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// ExternalIP/InternalIP become the public/private addresses; the context
+/// and each `node-role.kubernetes.io/<role>` label become tags.
+fn parse_nodes(json: &str, context: &str) -> Result<Vec<Machine>> {
+    let list: NodeList = serde_json::from_str(json).context("unexpected kubectl output")?;
+    Ok(list
+        .items
+        .into_iter()
+        .map(|node| {
+            let address = |kind: &str| {
+                node.status
+                    .addresses
+                    .iter()
+                    .find(|a| a.kind == kind)
+                    .map(|a| a.address.clone())
+            };
+            let mut tags = vec!["k8s".to_string(), context.to_string()];
+            tags.extend(
+                node.metadata
+                    .labels
+                    .keys()
+                    .filter_map(|k| k.strip_prefix("node-role.kubernetes.io/"))
+                    .filter(|role| !role.is_empty())
+                    .map(String::from),
+            );
+            Machine {
+                id: node.metadata.name.clone(),
+                public_ip: address("ExternalIP"),
+                private_ip: address("InternalIP"),
+                name: Some(node.metadata.name),
+                tags,
+                ..Default::default()
+            }
+        })
+        .collect())
+}
+
+/// Machines of the Vagrant project in `dir` (an absolute path), from
+/// `vagrant ssh-config`.
+/// Aliases are `<project dir>-<machine>`, since most Vagrantfiles name their
+/// only machine `default`.
+pub fn vagrant_hosts(dir: &Path) -> Result<BTreeMap<String, HostEntry>> {
+    if !util::on_path("vagrant") {
+        bail!("vagrant not found on PATH");
+    }
+    let output = Command::new("vagrant")
+        .arg("ssh-config")
+        .current_dir(dir)
+        .output()
+        .context("failed to run vagrant")?;
+    // vagrant exits non-zero when some machines aren't running but still
+    // prints the ones that are
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() && stdout.trim().is_empty() {
+        bail!(
+            "vagrant ssh-config failed (are the machines up?): {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let project = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "vagrant".to_string());
+    Ok(parse_vagrant(&stdout, &project))
+}
+
+fn parse_vagrant(output: &str, project: &str) -> BTreeMap<String, HostEntry> {
+    let mut hosts = BTreeMap::new();
+    let mut current: Option<(String, HostEntry)> = None;
+    for line in output.lines() {
+        let Some((key, value)) = ssh_config::split_keyword(line.trim()) else {
+            continue;
+        };
+        let value = value.trim_matches('"');
+        if key.eq_ignore_ascii_case("host") {
+            hosts.extend(current.take());
+            let alias = format!("{project}-{value}").to_lowercase().replace(' ', "-");
+            let entry = HostEntry {
+                tags: vec!["vagrant".to_string()],
+                ..Default::default()
+            };
+            current = Some((alias, entry));
+            continue;
+        }
+        let Some((_, entry)) = current.as_mut() else {
+            continue;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "hostname" => entry.hostname = value.to_string(),
+            "user" => entry.user = Some(value.to_string()),
+            "port" => entry.port = value.parse().ok(),
+            "identityfile" => entry.identity_file = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    hosts.extend(current);
+    hosts
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DockerContext {
+    name: String,
+    #[serde(default)]
+    endpoints: BTreeMap<String, DockerEndpoint>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DockerEndpoint {
+    #[serde(default)]
+    host: String,
+}
+
+/// Docker contexts whose endpoint is `ssh://[user@]host[:port]`.
+pub fn docker_hosts() -> Result<BTreeMap<String, HostEntry>> {
+    if !util::on_path("docker") {
+        bail!("docker not found on PATH");
+    }
+    let names = docker(&["context", "ls", "-q"])?;
+    let mut args = vec!["context", "inspect"];
+    args.extend(names.split_whitespace());
+    parse_docker_contexts(&docker(&args)?)
+}
+
+fn docker(args: &[&str]) -> Result<String> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .context("failed to run docker")?;
+    if !output.status.success() {
+        bail!(
+            "docker {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn parse_docker_contexts(json: &str) -> Result<BTreeMap<String, HostEntry>> {
+    let contexts: Vec<DockerContext> =
+        serde_json::from_str(json).context("unexpected docker context inspect output")?;
+    let mut hosts = BTreeMap::new();
+    for context in contexts {
+        let Some(url) = context
+            .endpoints
+            .get("docker")
+            .and_then(|e| e.host.strip_prefix("ssh://"))
+        else {
+            continue;
+        };
+        let url = url.trim_end_matches('/');
+        let (user, address) = match url.split_once('@') {
+            Some((u, a)) => (Some(u.to_string()), a),
+            None => (None, url),
+        };
+        let (hostname, port) = match address.rsplit_once(':') {
+            Some((h, p)) if p.parse::<u16>().is_ok() => (h, p.parse().ok()),
+            _ => (address, None),
+        };
+        hosts.insert(
+            context.name.to_lowercase(),
+            HostEntry {
+                hostname: hostname.to_string(),
+                user,
+                port,
+                tags: vec!["docker".to_string()],
+                ..Default::default()
+            },
+        );
+    }
+    Ok(hosts)
+}
+
+/// Compute instances of the common providers in a terraform state file.
+fn terraform_mapping() -> Mapping {
+    let types = [
+        "aws_instance",
+        "google_compute_instance",
+        "azurerm_linux_virtual_machine",
+        "digitalocean_droplet",
+        "hcloud_server",
+        "linode_instance",
+        "vultr_instance",
+        "openstack_compute_instance_v2",
+    ];
+    let filter = types
+        .map(|t| format!("mode=='managed' && type=='{t}'"))
+        .join(" || ");
+    let paths = |paths: &[&str]| {
+        paths.iter().map(|p| format!("attributes.{p}")).collect::<Vec<_>>().join(" || ")
+    };
+    Mapping {
+        items: format!("resources[?{filter}].instances[*]"),
+        alias: paths(&["tags.Name", "name", "label", "id"]),
+        hostname: paths(&[
+            "public_ip",
+            "ipv4_address",
+            "ip_address",
+            "public_ip_address",
+            "main_ip",
+            "access_ip_v4",
+            "network_interface[0].access_config[0].nat_ip",
+            "private_ip",
+            "network_interface[0].network_ip",
+            "private_ip_address",
+        ]),
+        user: None,
+        port: None,
+        tags: vec![
+            "attributes.tags".to_string(),
+            "attributes.tags.Environment".to_string(),
+            "attributes.labels[*]".to_string(),
+        ],
+        url: None,
+        next: None,
+        auth: None,
+    }
+}
+
+/// Hosts from a terraform state: the `--mapping` given, or else compute
+/// instances plus any outputs holding an IP address (or a map of them).
+/// `path` is a state JSON file or a terraform directory (read with
+/// `terraform state pull`). Also returns how many items were skipped.
+pub fn terraform_hosts(
+    path: &Path,
+    mapping: Option<&Mapping>,
+) -> Result<(BTreeMap<String, HostEntry>, usize)> {
+    let json = if path.is_dir() {
+        if !util::on_path("terraform") {
+            bail!("terraform not found on PATH (or pass a state .json file)");
+        }
+        let output = Command::new("terraform")
+            .args(["state", "pull"])
+            .current_dir(path)
+            .output()
+            .context("failed to run terraform")?;
+        if !output.status.success() {
+            bail!(
+                "terraform state pull failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?
+    };
+    let state: serde_json::Value =
+        serde_json::from_str(&json).context("terraform state is not valid JSON")?;
+    if let Some(mapping) = mapping {
+        return Ok(mapping.apply(&state, "terraform"));
+    }
+    let (mut hosts, skipped) = terraform_mapping().apply(&state, "terraform");
+    hosts.extend(terraform_outputs(&state));
+    Ok((hosts, skipped))
+}
+
+/// `output "bastion_ip"` -> `bastion_ip`; `output "hosts" { value = { web = ip } }` -> `web`.
+fn terraform_outputs(state: &serde_json::Value) -> BTreeMap<String, HostEntry> {
+    let is_ip = |v: &serde_json::Value| {
+        v.as_str()
+            .filter(|s| s.parse::<std::net::IpAddr>().is_ok())
+            .map(String::from)
+    };
+    let mut hosts = BTreeMap::new();
+    let Some(outputs) = state["outputs"].as_object() else {
+        return hosts;
+    };
+    for (name, output) in outputs {
+        let value = &output["value"];
+        let found: Vec<(String, String)> = match value.as_object() {
+            Some(map) => map.iter().filter_map(|(k, v)| Some((k.clone(), is_ip(v)?))).collect(),
+            None => is_ip(value).map(|ip| (name.clone(), ip)).into_iter().collect(),
+        };
+        for (alias, hostname) in found {
+            let entry = HostEntry {
+                hostname,
+                tags: vec!["terraform".to_string()],
+                ..Default::default()
+            };
+            hosts.insert(crate::mapping::slug(&alias), entry);
+        }
+    }
+    hosts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_kubernetes_nodes() {
+        let json = r#"{"items": [{"metadata": {"name": "node-a", "labels": {
+                "node-role.kubernetes.io/control-plane": "", "kubernetes.io/os": "linux"}},
+            "status": {"addresses": [{"type": "InternalIP", "address": "10.0.0.4"},
+                                     {"type": "Hostname", "address": "node-a"}]}}]}"#;
+        let nodes = parse_nodes(json, "prod-eu").unwrap();
+        assert_eq!(nodes[0].private_ip.as_deref(), Some("10.0.0.4"));
+        assert!(nodes[0].public_ip.is_none());
+        assert_eq!(nodes[0].tags, ["k8s", "prod-eu", "control-plane"]);
+    }
+
+    #[test]
+    fn reads_terraform_state() {
+        let state: serde_json::Value = serde_json::from_str(
+            r#"{"outputs": {"bastion_ip": {"value": "3.3.3.3"}, "bucket": {"value": "logs"},
+                            "workers": {"value": {"w1": "10.0.0.7"}}},
+                "resources": [
+                {"mode": "managed", "type": "aws_instance", "name": "api", "instances": [
+                    {"attributes": {"id": "i-1", "public_ip": "", "private_ip": "10.0.0.5",
+                                    "tags": {"Name": "api", "Environment": "staging"}}}]},
+                {"mode": "data", "type": "aws_instance", "instances": [
+                    {"attributes": {"id": "i-2", "private_ip": "10.0.0.6"}}]},
+                {"mode": "managed", "type": "digitalocean_droplet", "instances": [
+                    {"attributes": {"name": "blog", "ipv4_address": "167.1.1.1",
+                                    "tags": ["web"]}}]}]}"#,
+        )
+        .unwrap();
+        let (mut hosts, skipped) = terraform_mapping().apply(&state, "terraform");
+        hosts.extend(terraform_outputs(&state));
+        assert_eq!(skipped, 0);
+        assert_eq!(hosts.len(), 4);
+        assert_eq!(hosts["api"].hostname, "10.0.0.5");
+        assert_eq!(hosts["api"].tags, ["terraform", "staging"]);
+        assert_eq!(hosts["blog"].tags, ["terraform", "web"]);
+        assert_eq!(hosts["bastion_ip"].hostname, "3.3.3.3");
+        assert_eq!(hosts["w1"].hostname, "10.0.0.7");
+    }
+
+    #[test]
+    fn parses_vagrant_and_docker() {
+        let ssh_config = "Host default\n  HostName 127.0.0.1\n  User vagrant\n  Port 2222\n  \
+                          IdentityFile \"/p/.vagrant/machines/default/virtualbox/private_key\"\n\
+                          Host db\n  HostName 127.0.0.1\n  Port 2200\n";
+        let hosts = parse_vagrant(ssh_config, "shop");
+        assert_eq!(hosts["shop-default"].port, Some(2222));
+        assert_eq!(hosts["shop-default"].user.as_deref(), Some("vagrant"));
+        assert_eq!(
+            hosts["shop-default"].identity_file.as_deref(),
+            Some("/p/.vagrant/machines/default/virtualbox/private_key")
+        );
+        assert_eq!(hosts["shop-db"].port, Some(2200));
+
+        let contexts = r#"[
+            {"Name": "default", "Endpoints": {"docker": {"Host": "unix:///var/run/docker.sock"}}},
+            {"Name": "Build", "Endpoints": {"docker": {"Host": "ssh://ci@build.internal:2222"}}}]"#;
+        let hosts = parse_docker_contexts(contexts).unwrap();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts["build"].hostname, "build.internal");
+        assert_eq!(hosts["build"].port, Some(2222));
+        assert_eq!(hosts["build"].user.as_deref(), Some("ci"));
+    }
+}
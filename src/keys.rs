@@ -0,0 +1,414 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use base64::Engine as _;
+
+use crate::audit::format_duration;
+use crate::color::{epaint, paint};
+use crate::ssh;
+
+/// Certificates expiring sooner than this get a warning before connecting.
+const EXPIRING_SOON_SECS: u64 = 3600;
+
+/// An OpenSSH certificate: who it was issued to and when it is valid.
+pub struct Certificate {
+    /// The file it was read from, or `agent`.
+    pub source: String,
+    pub key_id: String,
+    pub principals: Vec<String>,
+    /// Unix seconds; 0 when it has no start.
+    pub valid_after: u64,
+    /// Unix seconds; `u64::MAX` when it never expires.
+    pub valid_before: u64,
+}
+
+impl Certificate {
+    /// `valid for 7h 59m`, `expired 2h 03m ago`, `valid forever`, …
+    pub fn validity(&self, now: u64) -> String {
+        if now < self.valid_after {
+            format!("not valid for another {}", span(self.valid_after - now))
+        } else if self.valid_before == u64::MAX {
+            "valid forever".to_string()
+        } else if now >= self.valid_before {
+            format!("expired {} ago", span(now - self.valid_before))
+        } else {
+            format!("valid for {}", span(self.valid_before - now))
+        }
+    }
+
+    /// `validity`, red when ssh can't use the certificate and yellow when it
+    /// is about to expire.
+    fn painted_validity(&self, now: u64) -> String {
+        let text = self.validity(now);
+        if now < self.valid_after || now >= self.valid_before {
+            paint("31", text)
+        } else if self.expiring(now) {
+            paint("33", text)
+        } else {
+            text
+        }
+    }
+
+    fn expiring(&self, now: u64) -> bool {
+        self.valid_before.saturating_sub(now) < EXPIRING_SOON_SECS
+    }
+
+    /// `key id "deploy", principals ubuntu, deploy`
+    fn identity(&self) -> String {
+        let principals = if self.principals.is_empty() {
+            "any principal".to_string()
+        } else {
+            format!("principals {}", self.principals.join(", "))
+        };
+        format!("key id \"{}\", {principals}", self.key_id)
+    }
+}
+
+/// Durations in days once they get long, since certificates are often
+/// issued for weeks or a year.
+fn span(secs: u64) -> String {
+    if secs >= 2 * 86400 {
+        format!("{}d", secs / 86400)
+    } else {
+        format_duration(secs)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Reads the ssh wire format: big-endian integers and length-prefixed strings.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_be_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
+
+/// Parse a public key line (`<type> <base64> [comment]`). `None` unless it
+/// is a certificate.
+pub fn parse(line: &str, source: &str) -> Option<Certificate> {
+    let mut fields = line.split_whitespace();
+    let kind = fields.next()?;
+    let (key_type, _) = kind.split_once("-cert-v01@openssh.com")?;
+    let blob = base64::engine::general_purpose::STANDARD
+        .decode(fields.next()?)
+        .ok()?;
+    let mut r = Reader(&blob);
+    if r.string()? != kind.as_bytes() {
+        return None;
+    }
+    r.string()?; // nonce
+    // The certified public key, whose fields depend on its type
+    let key_fields = match key_type {
+        "ssh-ed25519" => 1,
+        "ssh-rsa" | "sk-ssh-ed25519" => 2,
+        "ssh-dss" => 4,
+        t if t.starts_with("ecdsa-") => 2,
+        t if t.starts_with("sk-ecdsa-") => 3,
+        _ => return None,
+    };
+    for _ in 0..key_fields {
+        r.string()?;
+    }
+    r.u64()?; // serial
+    r.u32()?; // user or host certificate
+    let key_id = String::from_utf8_lossy(r.string()?).into_owned();
+    let mut packed = Reader(r.string()?);
+    let mut principals = Vec::new();
+    while let Some(p) = packed.string() {
+        principals.push(String::from_utf8_lossy(p).into_owned());
+    }
+    Some(Certificate {
+        source: source.to_string(),
+        key_id,
+        principals,
+        valid_after: r.u64()?,
+        valid_before: r.u64()?,
+    })
+}
+
+fn load(path: &Path) -> Option<Certificate> {
+    let text = std::fs::read_to_string(path).ok()?;
+    parse(text.lines().next()?, &path.display().to_string())
+}
+
+/// Public key lines of the keys loaded in ssh-agent; empty without an agent.
+fn agent_keys() -> Vec<String> {
+    let Ok(output) = Command::new("ssh-add").arg("-L").stderr(Stdio::null()).output() else {
+        return Vec::new();
+    };
+    // Exit 1 means an agent with no keys, 2 no agent at all
+    if !output.status.success() {
+        return Vec::new();
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// The certificates ssh would offer for these args: each `CertificateFile`,
+/// the `-cert.pub` next to each identity file, or, without any of those, the
+/// ones loaded in the agent.
+pub fn for_args(args: &[String]) -> Vec<Certificate> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for line in ssh_config(args).unwrap_or_default().lines() {
+        let path = match line.split_once(' ') {
+            Some(("certificatefile", v)) => ssh::expand_tilde(v),
+            Some(("identityfile", v)) => ssh::expand_tilde(&format!("{v}-cert.pub")),
+            _ => continue,
+        };
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    let certs: Vec<Certificate> = paths.iter().filter_map(|p| load(p)).collect();
+    if !certs.is_empty() {
+        return certs;
+    }
+    agent_keys().iter().filter_map(|line| parse(line, "agent")).collect()
+}
+
+/// `ssh -G` for these args: the options ssh would use, one per line.
+fn ssh_config(args: &[String]) -> Option<String> {
+    let output = Command::new(ssh::find_ssh().ok()?)
+        .arg("-G")
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Before connecting, warn about a certificate that has expired, isn't
+/// valid yet, or expires within the hour. ssh skips unusable certificates
+/// without saying so, which shows up as a baffling "Permission denied".
+pub fn warn_before_connect(args: &[String]) {
+    let now = now();
+    for cert in for_args(args) {
+        if now >= cert.valid_after && !cert.expiring(now) {
+            continue;
+        }
+        let (code, validity) = if now >= cert.valid_after && now < cert.valid_before {
+            ("33", format!("expires in {}", span(cert.valid_before - now)))
+        } else {
+            ("31", cert.validity(now))
+        };
+        eprintln!("{}", epaint(code, format!("! certificate {} {validity}", cert.source)));
+    }
+}
+
+/// `256 SHA256:… comment (ED25519)` for a public key line.
+fn fingerprint(line: &str) -> Option<String> {
+    let mut child = Command::new("ssh-keygen")
+        .args(["-l", "-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    writeln!(child.stdin.take()?, "{line}").ok()?;
+    let output = child.wait_with_output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// One key: `name  ED25519  SHA256:…  comment`, and for a certificate its
+/// validity and principals on the next line.
+fn print_key(name: &str, line: &str, source: &str, now: u64) {
+    let Some(print) = fingerprint(line) else {
+        return;
+    };
+    let parts: Vec<&str> = print.split_whitespace().collect();
+    let [_, hash, ref comment @ .., kind] = parts[..] else {
+        return;
+    };
+    let kind = kind.trim_matches(['(', ')']);
+    println!("  {name:<24} {kind:<13} {hash}  {}", paint("2", comment.join(" ")));
+    if let Some(cert) = parse(line, source) {
+        println!("  {:<24} {}, {}", "", cert.painted_validity(now), cert.identity());
+    }
+}
+
+/// `oken keys list`: the public keys and certificates in ~/.ssh and the keys
+/// loaded in ssh-agent, with how long each certificate is valid.
+pub fn list() -> Result<()> {
+    let now = now();
+    let dir = ssh_dir()?;
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "pub"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+
+    println!("{}", paint("1", format!("Keys in {}", dir.display())));
+    if files.is_empty() {
+        println!("  {}", paint("2", "none"));
+    }
+    for path in &files {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(line) = text.lines().next() {
+            print_key(&name, line, &path.display().to_string(), now);
+        }
+    }
+
+    println!("{}", paint("1", "ssh-agent"));
+    let agent = agent_keys();
+    if agent.is_empty() {
+        println!("  {}", paint("2", "no keys loaded (or no agent running)"));
+    }
+    for line in &agent {
+        print_key("", line, "agent", now);
+    }
+    Ok(())
+}
+
+/// Names of the private keys in ~/.ssh: files with a `.pub` beside them.
+pub fn private_keys() -> Vec<String> {
+    let Ok(dir) = ssh_dir() else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = std::fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "pub"))
+                .filter(|p| p.with_extension("").is_file())
+                .filter_map(|p| Some(p.file_stem()?.to_string_lossy().into_owned()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+fn ssh_dir() -> Result<PathBuf> {
+    Ok(dirs::home_dir().context("could not find the home directory")?.join(".ssh"))
+}
+
+/// `oken keys generate`: a new ed25519 key pair in ~/.ssh. With `--sk` the
+/// private key stays on a FIDO2 security key and the file only references
+/// it; `--resident` also stores it on the key, so `oken keys download` can
+/// fetch it on another machine.
+pub fn generate(name: Option<&str>, sk: bool, resident: bool, comment: Option<&str>) -> Result<()> {
+    let default = if sk { "id_ed25519_sk" } else { "id_ed25519" };
+    let dir = ssh_dir()?;
+    let path = dir.join(name.unwrap_or(default));
+    if path.exists() {
+        bail!("{} already exists; pass a different name", path.display());
+    }
+    std::fs::create_dir_all(&dir)?;
+    let mut keygen = Command::new("ssh-keygen");
+    keygen.args(["-t", if sk { "ed25519-sk" } else { "ed25519" }]);
+    keygen.arg("-f").arg(&path);
+    if let Some(comment) = comment {
+        keygen.args(["-C", comment]);
+    }
+    if resident {
+        keygen.args(["-O", "resident"]);
+    }
+    if sk {
+        println!("Touch your security key when it blinks.");
+    }
+    let status = keygen.status().context("failed to run ssh-keygen")?;
+    if !status.success() {
+        bail!("ssh-keygen failed{}", if sk { " (is the security key plugged in?)" } else { "" });
+    }
+    println!("Public key: {}.pub", path.display());
+    Ok(())
+}
+
+/// `oken keys download`: write the resident keys stored on a plugged-in
+/// security key to ~/.ssh with `ssh-keygen -K`, for a new machine.
+pub fn download() -> Result<()> {
+    let dir = ssh_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    println!("Enter the security key's PIN if asked, and touch it when it blinks.");
+    // ssh-keygen -K writes id_*_sk_rk* files into the working directory
+    let status = Command::new("ssh-keygen")
+        .arg("-K")
+        .current_dir(&dir)
+        .status()
+        .context("failed to run ssh-keygen")?;
+    if !status.success() {
+        bail!("ssh-keygen could not download resident keys (is the security key plugged in?)");
+    }
+    println!("Saved the resident keys in {} as id_*_sk_rk*", dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT: &str = concat!(
+        "ssh-ed25519-cert-v01@openssh.com ",
+        "AAAAIHNzaC1lZDI1NTE5LWNlcnQtdjAxQG9wZW5zc2guY29tAAAAIL7Rb7AcgRFChrU6mLWYh5SX3ysIFv3TlUCo",
+        "kk0lN5xSAAAAIIL+9qS11sEY1J9z41lT3eeYslMtGcp2AeQMAiNMNIoiAAAAAAAAAAAAAAABAAAACmRlcGxveS1r",
+        "ZXkAAAAUAAAABnVidW50dQAAAAZkZXBsb3kAAAAAaVW5AAAAAABpVwqAAAAAAAAAAIIAAAAVcGVybWl0LVgxMS1m",
+        "b3J3YXJkaW5nAAAAAAAAABdwZXJtaXQtYWdlbnQtZm9yd2FyZGluZwAAAAAAAAAWcGVybWl0LXBvcnQtZm9yd2Fy",
+        "ZGluZwAAAAAAAAAKcGVybWl0LXB0eQAAAAAAAAAOcGVybWl0LXVzZXItcmMAAAAAAAAAAAAAADMAAAALc3NoLWVk",
+        "MjU1MTkAAAAgyhTGAOjpHmmDNXJw+kBHd+Bjz5Tq7fTeRHf2IkxQGhQAAABTAAAAC3NzaC1lZDI1NTE5AAAAQHfS",
+        "oj2f6uJiqWHPRN1iBLXX4l1yVQM3oJz4PHOgsralpyy/cXvBH3lgl5iuPfVAOdBw/Wz8ncImn0qjsOifJgM=",
+        " me@laptop",
+    );
+
+    #[test]
+    fn reads_certificate_validity() {
+        let cert = parse(CERT, "id_ed25519-cert.pub").unwrap();
+        assert_eq!(cert.key_id, "deploy-key");
+        assert_eq!(cert.principals, ["ubuntu", "deploy"]);
+        // ssh-keygen -V 20260101000000:20260102000000, in UTC
+        assert_eq!((cert.valid_after, cert.valid_before), (1767225600, 1767312000));
+        assert_eq!(cert.validity(1767225600 + 3600), "valid for 23h 00m");
+        assert_eq!(cert.validity(1767312000 + 5 * 86400), "expired 5d ago");
+        assert_eq!(cert.validity(1767225600 - 60), "not valid for another 1m 00s");
+        assert!(cert.expiring(1767312000 - 600));
+
+        let key = concat!(
+            "ssh-ed25519 ",
+            "AAAAC3NzaC1lZDI1NTE5AAAAIL7Rb7AcgRFChrU6mLWYh5SX3ysIFv3TlUCokk0lN5xS"
+        );
+        assert!(parse(key, "id_ed25519.pub").is_none());
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::daemon::unix_now;
+use crate::hosts::Host;
+
+/// Re-measure when the cache is older than this.
+const REFRESH_SECS: u64 = 5 * 60;
+
+/// Round-trip times to each host's ssh port, in `latency.json`. Hosts that
+/// didn't answer are left out.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cache {
+    pub checked_at: u64,
+    pub rtt_ms: BTreeMap<String, u64>,
+}
+
+fn cache_path() -> Result<PathBuf> {
+    crate::config::state_file("latency.json")
+}
+
+pub fn load() -> Cache {
+    cache_path()
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn store(cache: &Cache) -> Result<()> {
+    let path = cache_path()?;
+    crate::config::create_parent(&path)?;
+    std::fs::write(path, serde_json::to_string(cache)?)?;
+    Ok(())
+}
+
+/// Measure every host now: the best of three TCP connects to its ssh port
+/// (or its first jump host), 32 hosts at a time.
+pub fn measure(hosts: &[Host]) -> Cache {
+    let rtt_ms = hosts
+        .chunks(32)
+        .flat_map(|chunk| {
+            std::thread::scope(|s| {
+                let handles: Vec<_> = chunk
+                    .iter()
+                    .map(|h| {
+                        s.spawn(move || {
+                            let (host, port) = crate::probe::endpoint(h)?;
+                            let best = (0..3)
+                                .filter_map(|_| {
+                                    crate::probe::tcp_connect(&host, port, Duration::from_secs(2))
+                                })
+                                .min()?;
+                            Some((h.alias.clone(), best.as_millis() as u64))
+                        })
+                    })
+                    .collect();
+                handles.into_iter().filter_map(|h| h.join().ok().flatten()).collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    Cache {
+        checked_at: unix_now(),
+        rtt_ms,
+    }
+}
+
+/// Start `oken daemon probe` in the background when the cache is stale, so
+/// the next listing has fresh numbers. Never blocks.
+pub fn maybe_refresh(cache: &Cache) {
+    if unix_now().saturating_sub(cache.checked_at) < REFRESH_SECS {
+        return;
+    }
+    // Claim this refresh so other oken processes don't start their own
+    let claimed = Cache {
+        checked_at: unix_now(),
+        rtt_ms: cache.rtt_ms.clone(),
+    };
+    if store(&claimed).is_err() {
+        return;
+    }
+    if let Ok(exe) = std::env::current_exe() {
+        let _ = Command::new(exe)
+            .args(["daemon", "probe"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+    }
+}
+
+/// `23ms`, or `-` when the host didn't answer.
+pub fn format(rtt: Option<u64>) -> String {
+    rtt.map(|ms| format!("{ms}ms")).unwrap_or_else(|| "-".to_string())
+}
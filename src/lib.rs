@@ -0,0 +1,47 @@
+//! Host resolution, history and tunnel profiles behind the `oken` CLI.
+//!
+//! The `oken` binary is a thin layer over this crate; status bars, TUIs and CI
+//! helpers can link it to read the same inventory without shelling out:
+//!
+//! ```no_run
+//! let hosts = oken_core::hosts::list_all_hosts()?;
+//! let proxies = oken_core::oken_config::load_config().proxies;
+//! for host in hosts.iter().filter(|h| h.tags.iter().any(|t| t == "prod")) {
+//!     let args = oken_core::ssh::build_ssh_args(host, None, &proxies);
+//!     println!("{} -> ssh {}", host.alias, args.join(" "));
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! Everything reads and writes the same files as the CLI (`hosts.toml`,
+//! `tunnels.toml`, `config.toml` and `history.db` under the directories in
+//! [`config`]), so changes made here show up in `oken` and vice versa.
+
+/// Picking which of a dual-homed host's addresses to connect to.
+pub mod addresses;
+/// Where oken keeps its config and data.
+pub mod config;
+/// Connection history in `history.db`: recording, frecency and recent hosts.
+pub mod history;
+/// The merged host list from `hosts.toml` and `~/.ssh/config`.
+pub mod hosts;
+/// Reading and writing `hosts.toml`.
+pub mod hosts_toml;
+/// JSON-to-host mappings for inventory sync.
+pub mod mapping;
+/// `config.toml` settings.
+pub mod oken_config;
+/// `ProxyCommand` presets.
+pub mod proxy;
+/// Session recording.
+pub mod recording;
+/// ssh argument parsing and building, and locating the ssh binary.
+pub mod ssh;
+/// Parsing `~/.ssh/config`.
+pub mod ssh_config;
+/// Timestamps, TTLs and backoff.
+pub mod time_utils;
+/// Named tunnel profiles in `tunnels.toml` and their ControlMaster sockets.
+pub mod tunnels;
+/// Shell quoting and finding programs on `PATH`.
+pub mod util;
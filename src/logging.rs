@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Writes log records to stderr, dimmed so they stand apart from ssh's output.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let target = record.target();
+        let module = match target.strip_prefix("oken::").or(target.strip_prefix("oken_core::")) {
+            Some(module) => format!(" {module}"),
+            None => String::new(),
+        };
+        let level = match record.level() {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        };
+        let line = format!("oken {level}{module}: {}", record.args());
+        eprintln!("{}", crate::color::epaint("2", line));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Install the logger. Warnings show by default, `-v` adds debug and `-vv`
+/// trace; `--quiet` leaves only errors and hides oken's notices.
+pub fn init(verbose: u8, quiet: bool) {
+    let _ = log::set_logger(&LOGGER);
+    QUIET.store(quiet, Ordering::Relaxed);
+    log::set_max_level(match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    });
+}
+
+/// Whether `--quiet` was given, for notices that don't go through the logger
+/// (update available, "Connecting to …").
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
@@ -1,170 +1,843 @@
+mod ansible;
 mod audit;
+mod autostart;
 mod cli;
+mod cluster;
+mod cloud;
+mod color;
 mod completions;
+mod daemon;
+mod discover;
+mod doctor;
+mod exec;
 mod update_check;
-mod config;
-mod history;
-mod hosts;
-mod hosts_toml;
-mod oken_config;
+mod webhook;
+mod danger;
+mod dedupe;
+mod hooks;
+mod host_keys;
+mod idle;
+mod keys;
+mod import;
+mod latency;
+mod logging;
+mod man;
+mod mount;
+mod multiplexer;
+mod notify;
+mod otp;
 mod picker;
+mod plugins;
+mod pool;
+mod power;
+mod probe;
+mod prompt;
 mod reconnect;
-mod ssh;
-mod ssh_config;
-mod time_utils;
-mod tunnels;
+mod redact;
+mod safe;
+mod self_update;
+mod stale;
+mod stats;
+mod tint;
+mod transfer;
+mod transport;
+mod wake;
+mod web;
+mod wizard;
 
 use std::env;
-use std::io::{self, BufRead, Write};
+use std::io::{self, BufRead, IsTerminal, Write};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
-
-use cli::{Cli, Command, HostCommand, TunnelCommand};
+use oken_core::ssh::{build_ssh_args, extract_ssh_flags};
+use oken_core::{
+    addresses, config, history, hosts, hosts_toml, mapping, oken_config, proxy, recording, ssh,
+    ssh_config, time_utils, tunnels, util,
+};
+
+use cli::{
+    AuditCommand, Cli, CloudCommand, Command, ConfigCommand, DaemonCommand, HostCommand, ListFormat,
+    KeysCommand, MountCommand, SnippetCommand, SyncProvider, TagCommand, TunnelCommand,
+};
+use hosts_toml::{Connect, Transport};
+use oken_config::OpenIn;
 
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
-    let cfg = oken_config::load_config();
-    update_check::maybe_notify();
-
-    if args.len() > 1 && !is_known_subcommand(&args[1]) && !is_oken_flag(&args[1]) {
-        // Single bare arg that doesn't look like a direct SSH target — maybe a partial filter
-        if args.len() == 2 && !args[1].contains('@') && !args[1].starts_with('-') {
-            let all_hosts = hosts::list_all_hosts().unwrap_or_default();
-            let query = &args[1];
-            let exact = all_hosts.iter().find(|h| h.alias == *query);
-            let has_other_matches = all_hosts
-                .iter()
-                .any(|h| h.alias != *query && h.alias.contains(query.as_str()));
 
-            if exact.is_some() && !has_other_matches {
-                let host = exact.unwrap();
-                return connect_to_host(host, false, false, &cfg);
-            } else {
-                match picker::run_picker(Some(query)) {
-                    Ok(host) => return connect_to_host(&host, false, false, &cfg),
-                    Err(_) => std::process::exit(0),
-                }
+    // Called by the completion scripts on every <TAB>; kept out of clap since
+    // its bash generator can't cope with a `__`-prefixed subcommand
+    if args.get(1).is_some_and(|a| a == "__complete") {
+        return print_completions(args.get(2).map_or("hosts", String::as_str));
+    }
+
+    // `-v`/`-q` go to ssh, unless a subcommand follows
+    let first = args[1..]
+        .iter()
+        .find(|a| !((a.starts_with("-v") && a[1..].bytes().all(|b| b == b'v')) || *a == "-q"));
+    if let Some(first) = first
+        && !is_known_subcommand(first)
+        && !is_oken_flag(first)
+    {
+        let (opts, ssh_args) = take_connect_flags(&args[1..]);
+        color::init(opts.color.unwrap_or_default());
+        logging::init(opts.verbose, opts.quiet);
+        // `oken foo` runs an `oken-foo` plugin, unless foo is a saved host
+        if args[1] == *first
+            && let Some(plugin) = plugins::find(first)
+        {
+            let all = hosts::list_all_hosts().unwrap_or_default();
+            if hosts::find(&all, first).is_none() {
+                std::process::exit(plugins::run(&plugin, &args[2..])?);
             }
+            log::debug!("'{first}' is a saved host; not running {}", plugin.display());
         }
-        // Multi-arg → passthrough as-is (user typed real SSH args)
-        return connect_passthrough(&args[1..], false, false, &cfg);
+        let cfg = oken_config::load_config();
+        redact::init(&cfg.redact_patterns);
+        notify::init(cfg.notify_when_focused);
+        update_check::maybe_notify(&cfg);
+        return connect_args(&ssh_args, &opts, &cfg);
     }
 
     let cli = Cli::parse();
+    // oken's flags after the host (`oken --yes web --dry-run`) end up in ssh_args
+    let (late, ssh_args) = take_connect_flags(&cli.ssh_args);
+    let opts = ConnectOpts {
+        yes: cli.yes || late.yes,
+        no_reconnect: cli.no_reconnect || late.no_reconnect,
+        record: cli.record || late.record,
+        mosh: cli.mosh || late.mosh,
+        safe: cli.safe || late.safe,
+        open_in: cli.open_in().or(late.open_in),
+        reason: cli.reason.or(late.reason),
+        exact: cli.exact || late.exact,
+        dry_run: cli.dry_run || late.dry_run,
+        verbose: cli.verbose + late.verbose,
+        quiet: cli.quiet || late.quiet,
+        color: late.color.or(Some(cli.color)),
+    };
+    color::init(opts.color.unwrap_or_default());
+    logging::init(opts.verbose, opts.quiet);
+    let cfg = oken_config::load_config();
+    redact::init(&cfg.redact_patterns);
+    notify::init(cfg.notify_when_focused);
+    update_check::maybe_notify(&cfg);
 
     match cli.command {
-        Some(cmd) => run_subcommand(cmd, &cfg),
+        Some(cmd) => run_subcommand(cmd, &opts, &cfg),
         None => {
             // Handle --tag filter
             if let Some(ref tag) = cli.tag {
                 let all_hosts = hosts::list_all_hosts().unwrap_or_default();
-                let tag_lower = tag.to_lowercase();
-                let matches: Vec<_> = all_hosts
-                    .iter()
-                    .filter(|h| h.tags.iter().any(|t| t.to_lowercase() == tag_lower))
-                    .collect();
+                let matches: Vec<_> = all_hosts.iter().filter(|h| h.has_tag(tag)).collect();
 
                 return match matches.len() {
                     0 => {
                         eprintln!("oken: no hosts found with tag '{tag}'");
                         std::process::exit(1);
                     }
-                    1 => connect_to_host(matches[0], cli.yes, cli.no_reconnect, &cfg),
-                    _ => {
-                        let initial = format!("#{tag}");
-                        match picker::run_picker(Some(&initial)) {
-                            Ok(host) => connect_to_host(&host, cli.yes, cli.no_reconnect, &cfg),
-                            Err(_) => {
-                                std::process::exit(0);
+                    1 => connect_to_host(matches[0], &opts, &cfg),
+                    _ => match cli.any {
+                        Some(strategy) => {
+                            let host = pool::pick(&matches, tag, strategy);
+                            if !logging::quiet() {
+                                let note = format!(
+                                    "→ Picked {} of {} hosts tagged '{tag}'",
+                                    host.alias,
+                                    matches.len()
+                                );
+                                eprintln!("{}", color::epaint("2", note));
                             }
+                            connect_to_host(host, &opts, &cfg)
                         }
-                    }
+                        None => {
+                            let initial = format!("#{tag}");
+                            match picker::run_picker(Some(&initial)) {
+                                Ok(host) => connect_picked(&host, &opts, &cfg),
+                                Err(_) => {
+                                    std::process::exit(0);
+                                }
+                            }
+                        }
+                    },
                 };
             }
 
-            if cli.ssh_args.is_empty() {
+            if ssh_args.is_empty() {
                 // No args → open picker
                 match picker::run_picker(None) {
-                    Ok(host) => connect_to_host(&host, cli.yes, cli.no_reconnect, &cfg),
+                    Ok(host) => connect_picked(&host, &opts, &cfg),
                     Err(_) => Ok(()), // user cancelled, exit cleanly
                 }
             } else {
-                connect_passthrough(&cli.ssh_args, cli.yes, cli.no_reconnect, &cfg)
+                connect_args(&ssh_args, &opts, &cfg)
+            }
+        }
+    }
+}
+
+/// oken's own connection flags, gathered from either the clap path or the raw-args path.
+#[derive(Default)]
+struct ConnectOpts {
+    yes: bool,
+    no_reconnect: bool,
+    record: bool,
+    mosh: bool,
+    /// `--safe`: start a shell that refuses destructive commands.
+    safe: bool,
+    reason: Option<String>,
+    /// Connect to the host with exactly this alias, without the picker.
+    exact: bool,
+    /// `--here`, `--tmux-window` or `--tmux-split`.
+    open_in: Option<OpenIn>,
+    dry_run: bool,
+    /// `-v` count and `--quiet`; `-v` and `-q` on their own go to ssh.
+    verbose: u8,
+    quiet: bool,
+    color: Option<cli::ColorChoice>,
+}
+
+/// Pull oken's connection flags (`--yes`, `--record`, `--reason <text>`, …) out of raw args.
+/// Stops at the remote command so flags meant for it are left untouched.
+fn take_connect_flags(args: &[String]) -> (ConnectOpts, Vec<String>) {
+    let mut opts = ConnectOpts::default();
+    let mut rest = Vec::new();
+    let mut positionals = 0;
+    let mut skip_next = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if skip_next {
+            rest.push(arg.clone());
+            skip_next = false;
+            continue;
+        }
+        if positionals < 2 {
+            match arg.as_str() {
+                "--yes" => {
+                    opts.yes = true;
+                    continue;
+                }
+                "--no-reconnect" => {
+                    opts.no_reconnect = true;
+                    continue;
+                }
+                "--record" => {
+                    opts.record = true;
+                    continue;
+                }
+                "--mosh" => {
+                    opts.mosh = true;
+                    continue;
+                }
+                "--safe" => {
+                    opts.safe = true;
+                    continue;
+                }
+                "--reason" => {
+                    opts.reason = iter.next().cloned();
+                    continue;
+                }
+                "--exact" => {
+                    opts.exact = true;
+                    continue;
+                }
+                "--here" => {
+                    opts.open_in = Some(OpenIn::Here);
+                    continue;
+                }
+                "--tmux-window" => {
+                    opts.open_in = Some(OpenIn::Window);
+                    continue;
+                }
+                "--tmux-split" => {
+                    opts.open_in = Some(OpenIn::Split);
+                    continue;
+                }
+                "--dry-run" => {
+                    opts.dry_run = true;
+                    continue;
+                }
+                "--verbose" => {
+                    opts.verbose += 1;
+                    continue;
+                }
+                "--quiet" => {
+                    opts.quiet = true;
+                    continue;
+                }
+                "--color" => {
+                    opts.color = parse_color(iter.next().map(String::as_str));
+                    continue;
+                }
+                a if a.starts_with("--color=") => {
+                    opts.color = parse_color(a.strip_prefix("--color="));
+                    continue;
+                }
+                a if ssh::FLAGS_WITH_VALUES.contains(&a) => skip_next = true,
+                a if !a.starts_with('-') => positionals += 1,
+                _ => {}
+            }
+        }
+        rest.push(arg.clone());
+    }
+    (opts, rest)
+}
+
+/// Connect using raw command-line args: a single bare word is resolved against
+/// saved hosts (opening a pre-filtered picker if ambiguous), anything else is passed to ssh.
+fn connect_args(args: &[String], opts: &ConnectOpts, cfg: &oken_config::OkenConfig) -> Result<()> {
+    // Single bare arg that doesn't look like a direct SSH target — maybe a partial filter
+    if args.len() == 1 && !args[0].contains('@') && !args[0].starts_with('-') {
+        let all_hosts = hosts::list_all_hosts().unwrap_or_default();
+        let query = &args[0];
+        let exact = hosts::find(&all_hosts, query);
+        let has_other_matches = all_hosts
+            .iter()
+            .any(|h| !h.is_named(query) && h.alias.contains(query.as_str()));
+
+        if let Some(host) = exact
+            && (!has_other_matches || opts.exact)
+        {
+            log::debug!("'{query}' is a saved host and no other alias contains it (or --exact)");
+            return connect_to_host(host, opts, cfg);
+        } else {
+            match exact {
+                Some(_) => log::debug!("other aliases contain '{query}', opening the picker"),
+                None => log::debug!("no saved host is called '{query}', opening the picker"),
+            }
+            match picker::run_picker(Some(query)) {
+                Ok(host) => return connect_picked(&host, opts, cfg),
+                Err(_) => std::process::exit(0),
             }
         }
     }
+    // Multi-arg → passthrough as-is (user typed real SSH args)
+    log::debug!("passing the arguments through to ssh");
+    connect_passthrough(args, opts, cfg)
+}
+
+/// Connect to a host chosen in the picker: `picker_open` decides where,
+/// unless `--here`, `--tmux-window` or `--tmux-split` did.
+fn connect_picked(
+    host: &hosts::Host,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let open_in = opts.open_in.unwrap_or(cfg.picker_open);
+    if open_in != OpenIn::Here
+        && let Some(mux) = multiplexer::current()
+    {
+        return open_in_multiplexer(host, mux, open_in, opts);
+    }
+    connect_to_host(host, opts, cfg)
 }
 
 /// Connect to a known host with keepalive, prod warning, and optional reconnect.
 fn connect_to_host(
     host: &hosts::Host,
-    yes: bool,
-    no_reconnect: bool,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    if let Some(open_in) = opts.open_in.filter(|o| *o != OpenIn::Here) {
+        let mux = multiplexer::current()
+            .context("--tmux-window and --tmux-split only work inside tmux or zellij")?;
+        return open_in_multiplexer(host, mux, open_in, opts);
+    }
+    // Probing a dual-homed host's addresses takes a moment, so only real connections do
+    let address = if opts.dry_run { None } else { addresses::choose(host) };
+    connect_with_args(host, build_ssh_args(host, address.as_deref(), &cfg.proxies), opts, cfg)
+}
+
+/// Run `oken <alias>` in a new tmux/zellij window or pane named after the host.
+/// The new oken makes the connection, so prompts and hooks happen there.
+fn open_in_multiplexer(
+    host: &hosts::Host,
+    mux: multiplexer::Multiplexer,
+    open_in: OpenIn,
+    opts: &ConnectOpts,
+) -> Result<()> {
+    let exe = env::current_exe().context("failed to locate the oken binary")?;
+    let mut argv = vec![exe.to_string_lossy().to_string(), "--exact".to_string()];
+    if opts.yes {
+        argv.push("--yes".to_string());
+    }
+    argv.extend(session_flags(opts));
+    argv.push(host.alias.clone());
+    multiplexer::open(mux, open_in, &host.alias, &argv, opts.dry_run)
+}
+
+/// `--record`, `--no-reconnect`, `--mosh`, `--safe` and `--reason` as given, for
+/// passing on to the oken that runs in another window or pane.
+fn session_flags(opts: &ConnectOpts) -> Vec<String> {
+    let mut flags = Vec::new();
+    for (on, flag) in [
+        (opts.record, "--record"),
+        (opts.no_reconnect, "--no-reconnect"),
+        (opts.mosh, "--mosh"),
+        (opts.safe, "--safe"),
+    ] {
+        if on {
+            flags.push(flag.to_string());
+        }
+    }
+    if let Some(reason) = &opts.reason {
+        flags.extend(["--reason".to_string(), reason.clone()]);
+    }
+    flags
+}
+
+/// Connect to a saved host with `ssh_args` built from it (plus any extras).
+fn connect_with_args(
+    host: &hosts::Host,
+    mut ssh_args: Vec<String>,
+    opts: &ConnectOpts,
     cfg: &oken_config::OkenConfig,
 ) -> Result<()> {
-    if !maybe_prod_warning(host, yes, &cfg.danger_tags)? {
+    apply_safe_mode(Some(host), &mut ssh_args, session_transport(host, opts), opts, cfg)?;
+    if opts.dry_run {
+        inject_keepalive(&mut ssh_args, cfg.keepalive_interval);
+        let transport = session_transport(host, opts);
+        print_dry_run(&transport::command_line(transport, &ssh_args, &host.connect_args)?);
         return Ok(());
     }
-    let mut ssh_args = build_ssh_args(host);
-    let target = ssh_args.first().cloned().unwrap_or_default();
+    check_identity_file(host)?;
+    let Gate::Proceed { reason } = danger_gate(host, &ssh_args, opts, cfg)? else {
+        return Ok(());
+    };
+    let target = ssh::extract_target_host_full(&ssh_args).unwrap_or_default();
     inject_keepalive(&mut ssh_args, cfg.keepalive_interval);
     record_host(host);
-    print_connecting(&ssh_args);
-    let start = std::time::Instant::now();
-    let exit_code = run_ssh(&ssh_args, no_reconnect, cfg)?;
-    audit::log_session(&host.alias, &target, start.elapsed().as_secs(), exit_code);
-    std::process::exit(exit_code);
+    let session = Session {
+        alias: host.alias.clone(),
+        target,
+        host: Some(host),
+        record: opts.record || should_record(host, cfg) || reason.is_some(),
+        transport: session_transport(host, opts),
+        reason,
+    };
+    run_session(&session, &ssh_args, opts, cfg)
+}
+
+/// Stop before connecting when the host's identity file is missing or open
+/// to others: ssh would only warn in passing and fall back to a password
+/// prompt, or ignore the key.
+fn check_identity_file(host: &hosts::Host) -> Result<()> {
+    // ssh_config paths can hold %-tokens that only ssh expands
+    let Some(file) = host.identity_file.as_deref().filter(|f| !f.contains('%')) else {
+        return Ok(());
+    };
+    let path = ssh::expand_tilde(file);
+    match ssh::identity_problem(&path) {
+        None => Ok(()),
+        Some(ssh::IdentityProblem::Missing) => anyhow::bail!(
+            "identity file {} for '{}' does not exist; fix identity_file with \
+             `oken host edit`, or restore the key",
+            path.display(),
+            host.alias
+        ),
+        Some(ssh::IdentityProblem::TooOpen(mode)) => anyhow::bail!(
+            "identity file {} for '{}' is accessible by others ({mode:o}) and ssh would \
+             ignore it; run `chmod 600 {}`",
+            path.display(),
+            host.alias,
+            path.display()
+        ),
+    }
+}
+
+/// The program a session with a saved host runs in. SSM hosts without a user
+/// get a Session Manager shell; with one, ssh runs over SSM (see `build_ssh_args`).
+fn session_transport(host: &hosts::Host, opts: &ConnectOpts) -> Transport {
+    if opts.mosh {
+        Transport::Mosh
+    } else if host.connect == Connect::Ssm && host.user.is_none() {
+        Transport::Ssm
+    } else {
+        host.transport
+    }
+}
+
+/// Connect to one saved host using another as the jump host.
+fn connect_via(
+    bastion_arg: &str,
+    host_arg: &str,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let all = hosts::list_all_hosts()?;
+    let find = |alias: &str| {
+        hosts::find(&all, alias)
+            .ok_or_else(|| anyhow::anyhow!("host '{alias}' not found"))
+    };
+    let bastion = find(bastion_arg)?;
+    let host = find(host_arg)?;
+    check_identity_file(bastion)?;
+    let mut ssh_args = build_ssh_args(host, None, &cfg.proxies);
+    ssh_args.extend(jump_args(bastion, cfg)?);
+    connect_with_args(host, ssh_args, opts, cfg)
+}
+
+/// Args that route a connection through `bastion`. `-J` can't carry the
+/// jump host's key, so a bastion with its own key becomes a ProxyCommand.
+fn jump_args(bastion: &hosts::Host, cfg: &oken_config::OkenConfig) -> Result<Vec<String>> {
+    let mut args = build_ssh_args(bastion, None, &cfg.proxies);
+    let target = args.remove(0);
+    if bastion.identity_file.is_none() {
+        let jump = match bastion.port {
+            Some(port) => format!("{target}:{port}"),
+            None => target,
+        };
+        return Ok(vec!["-J".to_string(), jump]);
+    }
+    let ssh = ssh::find_ssh()?;
+    let mut command = vec![util::shell_quote(&ssh.to_string_lossy())];
+    command.extend(args.iter().map(|a| util::shell_quote(a)));
+    command.extend(["-W".to_string(), "%h:%p".to_string(), util::shell_quote(&target)]);
+    Ok(vec!["-o".to_string(), format!("ProxyCommand={}", command.join(" "))])
 }
 
 /// Pass raw SSH args through with keepalive injection, prod warning, and optional reconnect.
 fn connect_passthrough(
     ssh_args: &[String],
-    yes: bool,
-    no_reconnect: bool,
+    opts: &ConnectOpts,
     cfg: &oken_config::OkenConfig,
 ) -> Result<()> {
+    if opts.dry_run {
+        let all = hosts::list_all_hosts().unwrap_or_default();
+        let known = known_host(&all, ssh_args);
+        let mut args = ssh_args.to_vec();
+        let (transport, extra) = match known {
+            Some(h) => (session_transport(h, opts), h.connect_args.as_slice()),
+            None if opts.mosh => (Transport::Mosh, &[][..]),
+            None => (Transport::Ssh, &[][..]),
+        };
+        apply_safe_mode(known, &mut args, transport, opts, cfg)?;
+        inject_keepalive(&mut args, cfg.keepalive_interval);
+        print_dry_run(&transport::command_line(transport, &args, extra)?);
+        return Ok(());
+    }
     maybe_prompt_save(ssh_args);
 
-    // Prod warning: look up target in known hosts
-    if !yes {
-        if let Some(target) = ssh::extract_target_host(ssh_args) {
-            let all = hosts::list_all_hosts().unwrap_or_default();
-            if let Some(host) = all
-                .iter()
-                .find(|h| h.alias == target || h.hostname.as_deref() == Some(target.as_str()))
-            {
-                if !maybe_prod_warning(host, yes, &cfg.danger_tags)? {
-                    return Ok(());
-                }
-            }
-        }
+    // Look up target in known hosts for the prod warning and recording settings
+    let all = hosts::list_all_hosts().unwrap_or_default();
+    let known = known_host(&all, ssh_args);
+    match known {
+        Some(h) => log::debug!("target matches saved host '{}'", h.alias),
+        None => log::debug!("target is not a saved host"),
     }
-
+    let reason = match known {
+        Some(host) => match danger_gate(host, ssh_args, opts, cfg)? {
+            Gate::Proceed { reason } => reason,
+            Gate::Declined => return Ok(()),
+        },
+        None => None,
+    };
+
+    let transport = match known {
+        Some(h) => session_transport(h, opts),
+        None if opts.mosh => Transport::Mosh,
+        None => Transport::Ssh,
+    };
     let mut args = ssh_args.to_vec();
+    apply_safe_mode(known, &mut args, transport, opts, cfg)?;
     inject_keepalive(&mut args, cfg.keepalive_interval);
     record_if_connecting(&args);
     let alias = ssh::extract_target_host_full(ssh_args).unwrap_or_default();
-    print_connecting(&args);
+    let session = Session {
+        target: alias.clone(),
+        alias,
+        host: known,
+        record: opts.record || known.is_some_and(|h| should_record(h, cfg)) || reason.is_some(),
+        transport,
+        reason,
+    };
+    run_session(&session, &args, opts, cfg)
+}
+
+/// The saved host an ssh command line connects to, by alias or hostname.
+fn known_host<'a>(all: &'a [hosts::Host], ssh_args: &[String]) -> Option<&'a hosts::Host> {
+    let target = ssh::extract_target_host(ssh_args)?;
+    all.iter()
+        .find(|h| h.is_named(&target) || h.hostname.as_deref() == Some(target.as_str()))
+}
+
+/// `--dry-run`: show the command line, quoted for a shell, instead of running it.
+fn print_dry_run(argv: &[String]) {
+    let quoted: Vec<String> = argv.iter().map(|p| util::shell_quote(p)).collect();
+    println!("{}", quoted.join(" "));
+}
+
+/// A connection that has passed the danger gate and is about to run.
+struct Session<'a> {
+    alias: String,
+    target: String,
+    /// The saved host being connected to, if the target is known.
+    host: Option<&'a hosts::Host>,
+    record: bool,
+    transport: Transport,
+    reason: Option<String>,
+}
+
+/// Run the ssh session (recording and reconnecting as configured), write the
+/// audit entry, and exit with ssh's exit code.
+fn run_session(
+    session: &Session,
+    args: &[String],
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let is_danger = session
+        .host
+        .is_some_and(|h| !danger::evaluate(h, cfg).is_empty());
+    let mut hook_env = hooks::HookEnv {
+        event: "connect",
+        host: &session.alias,
+        target: &session.target,
+        hostname: session.host.and_then(|h| h.hostname.as_deref()),
+        user: session.host.and_then(|h| h.user.as_deref()),
+        port: session.host.and_then(|h| h.port),
+        tags: session.host.map(|h| h.tags.as_slice()).unwrap_or_default(),
+        danger: is_danger,
+        reason: session.reason.as_deref(),
+        exit_code: None,
+        duration_secs: None,
+        attempt: None,
+    };
+    // Global hooks wrap the host's: global on_connect runs first, global
+    // on_disconnect last
+    let (host_connect, host_disconnect) = session
+        .host
+        .map(|h| (h.on_connect.as_slice(), h.on_disconnect.as_slice()))
+        .unwrap_or_default();
+    hooks::run(&cfg.hooks.on_connect, &hook_env)
+        .context("on_connect hook failed, not connecting")?;
+    hooks::run(host_connect, &hook_env)
+        .with_context(|| format!("{}'s on_connect hook failed, not connecting", session.alias))?;
+    if is_danger {
+        hook_env.event = "danger_connect";
+        hooks::run(&cfg.hooks.on_danger_connect, &hook_env)
+            .context("on_danger_connect hook failed, not connecting")?;
+    }
+
+    let danger_webhook = cfg.danger_webhook.as_deref().filter(|_| is_danger);
+    if let Some(url) = danger_webhook {
+        webhook::post(
+            url,
+            &webhook::DangerEvent::Connect {
+                host: &session.alias,
+                reason: session.reason.as_deref(),
+            },
+        );
+    }
+
+    if cfg.host_key_review
+        && session.transport != Transport::Ssm
+        && let Some(host) = session.host
+    {
+        host_keys::review(host, args)?;
+    }
+
+    if session.transport != Transport::Ssm {
+        keys::warn_before_connect(args);
+    }
+
+    if session.host.is_some_and(|h| h.mfa) {
+        otp::show_before_connect(&session.alias);
+    }
+
+    print_last_session(&session.alias);
+    let recording = start_recording(&session.alias, session.record)?;
+    let tint = if is_danger { tint::apply(cfg) } else { None };
+    let _idle = if is_danger && cfg.danger_idle_secs > 0 {
+        idle::watch(&session.alias, std::time::Duration::from_secs(cfg.danger_idle_secs))
+    } else {
+        None
+    };
+    print_connecting(args);
     let start = std::time::Instant::now();
-    let exit_code = run_ssh(&args, no_reconnect, cfg)?;
-    audit::log_session(&alias, &alias, start.elapsed().as_secs(), exit_code);
+    let exit_code = match session.transport {
+        Transport::Ssh => run_ssh(args, session.host, recording.as_ref(), &hook_env, opts, cfg)?,
+        Transport::Mosh => transport::run_mosh(args, recording.as_ref())?,
+        Transport::Ssm => {
+            let extra = session.host.map(|h| h.connect_args.as_slice()).unwrap_or_default();
+            transport::run_ssm(args, extra, recording.as_ref())?
+        }
+        Transport::Et => match transport::run_et(args, recording.as_ref())? {
+            Some(code) => code,
+            None => run_ssh(args, session.host, recording.as_ref(), &hook_env, opts, cfg)?,
+        },
+    };
+    let duration_secs = start.elapsed().as_secs();
+    // process::exit skips destructors, so restore the terminal explicitly
+    drop(tint);
+    // Before post hooks, which may ship the transcript off the machine
+    if let Some(rec) = &recording
+        && let Err(e) = redact::recording(&rec.path)
+    {
+        log::warn!("could not redact {}: {e:#}", rec.path.display());
+    }
+    if !logging::quiet() {
+        let summary = format!(
+            "● Session with {} ended after {} (exit {exit_code})",
+            session.alias,
+            audit::format_duration(duration_secs)
+        );
+        eprintln!("{}", color::epaint("2", summary));
+    }
+    if cfg.notify && duration_secs >= cfg.notify_after_secs {
+        notify::send(
+            "oken: session ended",
+            &format!(
+                "{} closed after {} (exit {exit_code})",
+                session.alias,
+                audit::format_duration(duration_secs)
+            ),
+        );
+    }
+    if let Some(url) = danger_webhook {
+        webhook::post(
+            url,
+            &webhook::DangerEvent::Disconnect {
+                host: &session.alias,
+                duration_secs,
+                exit_code,
+            },
+        );
+    }
+    hook_env.event = "disconnect";
+    hook_env.exit_code = Some(exit_code);
+    hook_env.duration_secs = Some(duration_secs);
+    hooks::run_all(host_disconnect, &hook_env);
+    hooks::run_all(&cfg.hooks.on_disconnect, &hook_env);
+    let rec_name = recording.as_ref().map(|r| r.file_name());
+    audit::log_session(&audit::SessionEntry {
+        alias: &session.alias,
+        target: &session.target,
+        duration_secs,
+        exit_code,
+        recording: rec_name.as_deref(),
+        reason: session.reason.as_deref(),
+        danger: is_danger,
+    });
     std::process::exit(exit_code);
 }
 
-/// Run SSH, using the reconnect wrapper unless disabled.
-fn run_ssh(args: &[String], no_reconnect: bool, cfg: &oken_config::OkenConfig) -> Result<i32> {
-    if no_reconnect || !cfg.reconnect {
-        ssh::run(args)
+/// Run SSH, moving on to the host's `fallback_hostnames` in turn while ssh
+/// can't reach the server at all.
+fn run_ssh(
+    args: &[String],
+    host: Option<&hosts::Host>,
+    recording: Option<&recording::Recording>,
+    hook_env: &hooks::HookEnv,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<i32> {
+    let fallbacks = host.map(|h| h.fallback_hostnames.as_slice()).unwrap_or_default();
+    let mut args = args.to_vec();
+    let mut exit = run_ssh_at(&args, host, recording, hook_env, opts, cfg, !fallbacks.is_empty())?;
+    for (i, hostname) in fallbacks.iter().enumerate() {
+        if !matches!(exit, host_keys::Exit::Unreachable) {
+            break;
+        }
+        let failed = ssh::extract_target_host(&args).unwrap_or_default();
+        eprintln!("{}", color::epaint("2", format!("Couldn't reach {failed}, trying {hostname}…")));
+        args = ssh::with_target_host(&args, hostname);
+        let failover = i + 1 < fallbacks.len();
+        exit = run_ssh_at(&args, host, recording, hook_env, opts, cfg, failover)?;
+    }
+    Ok(exit.code())
+}
+
+/// Run SSH once at the address in `args`, using the reconnect wrapper unless
+/// disabled. With `failover`, an unreachable server is handed back at once.
+fn run_ssh_at(
+    args: &[String],
+    host: Option<&hosts::Host>,
+    recording: Option<&recording::Recording>,
+    hook_env: &hooks::HookEnv,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+    failover: bool,
+) -> Result<host_keys::Exit> {
+    // Relaunching a remote command or a piped session could repeat its side
+    // effects, so only interactive shells get the reconnect wrapper
+    let interactive = io::stdin().is_terminal() && !ssh::is_non_interactive(args);
+    if opts.no_reconnect || !cfg.reconnect || !interactive {
+        host_keys::run_ssh(args, recording, host)
     } else {
-        reconnect::run_with_reconnect(args, cfg.reconnect_retries, cfg.reconnect_delay_secs)
+        let on_reconnect = |attempt| {
+            let env = hooks::HookEnv {
+                event: "reconnect",
+                attempt: Some(attempt),
+                ..hook_env.clone()
+            };
+            hooks::run_all(&cfg.hooks.on_reconnect, &env);
+        };
+        let policy = reconnect::Policy {
+            max_retries: cfg.reconnect_retries,
+            base_delay_secs: cfg.reconnect_delay_secs,
+            max_delay_secs: cfg.reconnect_max_delay_secs,
+            notify: cfg.notify.then_some(hook_env.host),
+            wait_for_network: cfg.reconnect_wait_for_network,
+            on_reconnect: &on_reconnect,
+            host,
+            failover,
+        };
+        reconnect::run_with_reconnect(args, recording, &policy)
     }
 }
 
+/// Turn `args` into a safe-mode session with `--safe`, or when the host has
+/// one of `safe_tags` and a shell is being opened. Safe mode needs ssh, so
+/// other transports refuse rather than quietly connecting without it.
+fn apply_safe_mode(
+    host: Option<&hosts::Host>,
+    args: &mut Vec<String>,
+    transport: Transport,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    // Remote commands run as usual on safe_tags hosts; only shells get safe mode
+    let by_tag = host.filter(|h| {
+        !ssh::is_non_interactive(args) && cfg.safe_tags.iter().any(|t| h.has_tag(t))
+    });
+    if !opts.safe && by_tag.is_none() {
+        return Ok(());
+    }
+    if let Some(h) = by_tag {
+        log::debug!("'{}' has one of safe_tags, starting in safe mode", h.alias);
+    }
+    if transport != Transport::Ssh {
+        let transport = format!("{transport:?}").to_lowercase();
+        anyhow::bail!("safe mode needs an ssh session, and this one would use {transport}");
+    }
+    safe::wrap(args)
+}
+
+/// True if the host opts into recording directly or via one of `record_tags`.
+fn should_record(host: &hosts::Host, cfg: &oken_config::OkenConfig) -> bool {
+    host.record || cfg.record_tags.iter().any(|rt| host.has_tag(rt))
+}
+
+/// Allocate a transcript for this session when recording is requested.
+fn start_recording(alias: &str, enabled: bool) -> Result<Option<recording::Recording>> {
+    if !enabled {
+        return Ok(None);
+    }
+    let rec = recording::start(alias)?;
+    let note = format!("● Recording session to {}", rec.path.display());
+    eprintln!("{}", color::epaint("2", note));
+    Ok(Some(rec))
+}
+
 /// Prepend `-o ServerAliveInterval=N -o ServerAliveCountMax=3` unless already set.
 fn inject_keepalive(args: &mut Vec<String>, interval: u32) {
     let already_set = args.iter().any(|a| a.contains("ServerAliveInterval"));
-    if !already_set {
+    if already_set {
+        log::debug!("ServerAliveInterval given explicitly, not adding keep-alive options");
+    } else {
+        log::debug!("adding keep-alive: ServerAliveInterval={interval}, ServerAliveCountMax=3");
         let mut prefix = vec![
             "-o".to_string(),
             format!("ServerAliveInterval={interval}"),
@@ -176,92 +849,182 @@ fn inject_keepalive(args: &mut Vec<String>, interval: u32) {
     }
 }
 
-/// Show a warning banner if the host has danger tags. Returns false if the user declines.
-fn maybe_prod_warning(host: &hosts::Host, yes: bool, danger_tags: &[String]) -> Result<bool> {
-    if yes {
-        return Ok(true);
-    }
-    let danger_matches: Vec<&String> = host
-        .tags
-        .iter()
-        .filter(|t| {
-            danger_tags
-                .iter()
-                .any(|dt| dt.eq_ignore_ascii_case(t.as_str()))
-        })
-        .collect();
+/// Whether to go ahead with a connection, plus the compliance reason if one was given.
+enum Gate {
+    Declined,
+    Proceed { reason: Option<String> },
+}
 
-    if danger_matches.is_empty() {
-        return Ok(true);
+/// Confirm access to a danger-tagged host. In compliance mode a reason is
+/// required instead of a y/N answer, and `--yes` does not skip it. Neither
+/// does it skip the prompt when the agent would be forwarded.
+fn danger_gate(
+    host: &hosts::Host,
+    ssh_args: &[String],
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<Gate> {
+    let danger = danger::evaluate(host, cfg);
+    if danger.is_empty() {
+        return Ok(Gate::Proceed { reason: None });
+    }
+    let forwards_agent = ssh::forwards_agent(ssh_args);
+    if forwards_agent {
+        log::debug!("ssh would forward the agent to '{}'", host.alias);
+    }
+    if !cfg.compliance {
+        let confirmation = cfg.danger_confirmation;
+        let yes = opts.yes && !forwards_agent;
+        return Ok(if maybe_prod_warning(host, yes, &danger, confirmation, forwards_agent)? {
+            Gate::Proceed { reason: None }
+        } else {
+            Gate::Declined
+        });
     }
 
-    let tags_str = danger_matches
-        .iter()
-        .map(|t| t.as_str())
-        .collect::<Vec<_>>()
-        .join(", ");
-
-    eprintln!(
-        "\x1b[1;33m⚠  WARNING:\x1b[0m '{}' is tagged [{}]",
-        host.alias, tags_str
-    );
-    eprint!("Continue? [y/N] ");
+    if let Some(ref reason) = opts.reason
+        && !reason.trim().is_empty()
+    {
+        return Ok(Gate::Proceed {
+            reason: Some(reason.trim().to_string()),
+        });
+    }
+    print_danger_banner(host, &danger, forwards_agent);
+    eprintln!("{}", color::epaint("2", "This session will be recorded."));
+    eprint!("Reason for access (Enter to cancel): ");
     io::stderr().flush()?;
 
     let mut line = String::new();
     io::stdin().lock().read_line(&mut line)?;
-
-    Ok(line.trim().eq_ignore_ascii_case("y") || line.trim().eq_ignore_ascii_case("yes"))
+    let reason = line.trim();
+    if reason.is_empty() {
+        return Ok(Gate::Declined);
+    }
+    Ok(Gate::Proceed {
+        reason: Some(reason.to_string()),
+    })
 }
 
-/// Build SSH args from a picker-selected host.
-fn build_ssh_args(host: &hosts::Host) -> Vec<String> {
-    let mut args = Vec::new();
+/// Print the danger warning line plus any custom rule messages.
+fn print_danger_banner(host: &hosts::Host, danger: &danger::DangerMatch, forwards_agent: bool) {
+    eprintln!(
+        "{} '{}' {}",
+        color::epaint("1;33", "⚠  WARNING:"),
+        host.alias,
+        danger.describe()
+    );
+    for msg in &danger.messages {
+        eprintln!("   {}", color::epaint("1", msg));
+    }
+    if forwards_agent {
+        // Root on the far end can use a forwarded agent to log in anywhere your keys do
+        eprintln!(
+            "{} agent forwarding is on: while you're connected, anyone with root on '{}' \
+             can use your keys. Connect with -a, or reach hosts behind it with ProxyJump.",
+            color::epaint("1;31", "⚠  SECURITY:"),
+            host.alias
+        );
+    }
+}
 
-    match (&host.user, &host.hostname) {
-        (Some(user), Some(hostname)) => args.push(format!("{}@{}", user, hostname)),
-        (None, Some(hostname)) => args.push(hostname.clone()),
-        // ssh_config-only host (no hostname stored) — use alias and let SSH resolve it
-        _ => args.push(host.alias.clone()),
+/// Show a warning banner for a dangerous host. Returns false if the user declines.
+fn maybe_prod_warning(
+    host: &hosts::Host,
+    yes: bool,
+    danger: &danger::DangerMatch,
+    confirmation: oken_config::DangerConfirmation,
+    forwards_agent: bool,
+) -> Result<bool> {
+    if yes {
+        return Ok(true);
     }
 
-    if let Some(port) = host.port {
-        args.push("-p".to_string());
-        args.push(port.to_string());
+    print_danger_banner(host, danger, forwards_agent);
+    Ok(match confirmation {
+        oken_config::DangerConfirmation::YesNo => prompt::confirm("Continue?", false)?,
+        oken_config::DangerConfirmation::TypeAlias => {
+            eprint!("Type '{}' to continue: ", host.alias);
+            io::stderr().flush()?;
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            line.trim() == host.alias
+        }
+    })
+}
+
+/// `last connected 3d ago · previous session 42m 07s · exit 0`, from the
+/// audit log, for context when coming back to a host.
+fn print_last_session(alias: &str) {
+    if logging::quiet() {
+        return;
     }
-    if let Some(ref identity) = host.identity_file {
-        args.push("-i".to_string());
-        args.push(identity.clone());
+    let Some(last) = audit::last_session(alias) else {
+        return;
+    };
+    // Entries are written when a session ends
+    let ended = time_utils::iso8601_to_unix(&last.time.replace(' ', "T"));
+    let started = ended.map(|t| t.saturating_sub(last.duration_secs.unwrap_or(0)));
+    let mut parts = Vec::new();
+    if let Some(at) = started {
+        let ago = picker::format_relative_time(&time_utils::unix_to_iso8601(at));
+        parts.push(format!("last connected {ago}"));
     }
-
-    args
+    if let Some(secs) = last.duration_secs {
+        parts.push(format!("previous session {}", audit::format_duration(secs)));
+    }
+    if let Some(code) = last.exit_code {
+        parts.push(format!("exit {code}"));
+    }
+    eprintln!("{}", color::epaint("2", parts.join(" · ")));
 }
 
 /// Print a "Connecting to ..." message on stderr before exec-ing into SSH.
 /// Uses \r so SSH's output overwrites it naturally.
 fn print_connecting(args: &[String]) {
+    log::debug!("running ssh {}", args.join(" "));
+    if logging::quiet() {
+        return;
+    }
     if let Some(target) = ssh::extract_target_host(args) {
-        eprint!("\x1b[2m→ Connecting to {target}…\x1b[0m\r");
+        eprint!("{}\r", color::epaint("2", format!("→ Connecting to {target}…")));
         let _ = io::stderr().flush();
     }
 }
 
-/// Record a picker-selected host to history using its alias.
+/// Record a picker-selected host to history using its alias, and move any
+/// connections recorded under its other names over to it.
 /// Silently ignores all errors — history must never block SSH.
 fn record_host(host: &hosts::Host) {
-    let _ = history::record_connection(
+    let recorded = history::record_connection(
         &host.alias,
         host.hostname.as_deref(),
         host.user.as_deref(),
         host.port,
-    );
+    )
+    .and_then(|()| {
+        host.aliases
+            .iter()
+            .try_for_each(|name| history::rename_alias(name, &host.alias))
+    });
+    if let Err(e) = recorded {
+        log::warn!("could not record the connection in history: {e:#}");
+    }
 }
 
-/// Extract the target host from SSH args and record to history DB.
+/// Extract the target host from SSH args and record to history DB, with the
+/// user and port so `oken host add --from-history` can save it later.
 /// Silently ignores all errors — history must never block SSH.
 fn record_if_connecting(args: &[String]) {
-    if let Some(host) = ssh::extract_target_host(args) {
-        let _ = history::record_connection(&host, None, None, None);
+    let Some(target) = ssh::extract_target_host_full(args) else {
+        return;
+    };
+    let (user, host) = match target.split_once('@') {
+        Some((user, host)) => (Some(user), host),
+        None => (None, target.as_str()),
+    };
+    let port = ssh::extract_port(args);
+    if let Err(e) = history::record_connection(host, Some(host), user, port) {
+        log::warn!("could not record the connection in history: {e:#}");
     }
 }
 
@@ -281,13 +1044,13 @@ fn maybe_prompt_save(args: &[String]) {
         // Check if already known (must match both user AND hostname)
         let all_hosts = hosts::list_all_hosts().unwrap_or_default();
         let host_known = all_hosts.iter().any(|h| {
-            h.alias == hostname || h.hostname.as_deref() == Some(hostname)
+            h.is_named(hostname) || h.hostname.as_deref() == Some(hostname)
         });
         let exact_known = all_hosts.iter().any(|h| {
-            let host_matches = h.alias == hostname
+            let host_matches = h.is_named(hostname)
                 || h.hostname.as_deref() == Some(hostname);
             let user_matches = h.user.as_deref() == Some(user);
-            (host_matches && user_matches) || h.alias == target
+            (host_matches && user_matches) || h.is_named(&target)
         });
         if exact_known {
             return None;
@@ -295,14 +1058,17 @@ fn maybe_prompt_save(args: &[String]) {
 
         // Show a contextual hint and prompt depending on scenario
         let stdin = io::stdin();
-        let alias;
-
-        if host_known {
+        let alias = if host_known {
             // Known host, new user — no sensible default, require a name
             eprintln!(
-                "\x1b[2mNew user \x1b[0m\x1b[1m{user}\x1b[0m\x1b[2m for known host \x1b[0m\x1b[1m{hostname}\x1b[0m\x1b[2m — save it so you can pick it next time?\x1b[0m",
+                "{}{}{}{}{}",
+                color::epaint("2", "New user "),
+                color::epaint("1", user),
+                color::epaint("2", " for known host "),
+                color::epaint("1", hostname),
+                color::epaint("2", " — save it so you can pick it next time?"),
             );
-            eprint!("\x1b[2mSave as (Enter to skip):\x1b[0m ");
+            eprint!("{} ", color::epaint("2", "Save as (Enter to skip):"));
             io::stderr().flush().ok()?;
 
             let line = stdin.lock().lines().next()?.ok()?;
@@ -310,13 +1076,20 @@ fn maybe_prompt_save(args: &[String]) {
             if input.is_empty() {
                 return None;
             }
-            alias = input;
+            input
         } else {
             // Completely new host — default alias is the hostname
             eprintln!(
-                "\x1b[2mLooks like a new host. Save \x1b[0m\x1b[1m{target}\x1b[0m\x1b[2m so it shows up in the picker?\x1b[0m",
+                "{}{}{}",
+                color::epaint("2", "Looks like a new host. Save "),
+                color::epaint("1", &target),
+                color::epaint("2", " so it shows up in the picker?"),
+            );
+            eprint!(
+                "{}{hostname}{} ",
+                color::epaint("2", "Save as (Enter = "),
+                color::epaint("2", ", \"n\" to skip):"),
             );
-            eprint!("\x1b[2mSave as (Enter = \x1b[0m{hostname}\x1b[2m, \"n\" to skip):\x1b[0m ");
             io::stderr().flush().ok()?;
 
             let line = stdin.lock().lines().next()?.ok()?;
@@ -324,12 +1097,12 @@ fn maybe_prompt_save(args: &[String]) {
             if input.eq_ignore_ascii_case("n") || input.eq_ignore_ascii_case("no") {
                 return None;
             }
-            alias = if input.is_empty() {
+            if input.is_empty() {
                 hostname.to_string()
             } else {
                 input
-            };
-        }
+            }
+        };
 
         // Prompt for tags
         eprint!("Tags (comma-separated, Enter to skip): ");
@@ -351,6 +1124,7 @@ fn maybe_prompt_save(args: &[String]) {
             port,
             identity_file,
             tags,
+            ..Default::default()
         };
 
         let path = hosts_toml_path().ok()?;
@@ -370,40 +1144,273 @@ fn is_known_subcommand(arg: &str) -> bool {
         .any(|c| c.get_name() == arg)
 }
 
+/// `--color <WHEN>` from raw args; anything unrecognised means auto.
+fn parse_color(value: Option<&str>) -> Option<cli::ColorChoice> {
+    use clap::ValueEnum;
+    Some(value.and_then(|v| cli::ColorChoice::from_str(v, true).ok()).unwrap_or_default())
+}
+
 fn is_oken_flag(arg: &str) -> bool {
+    let flag = arg.split_once('=').map_or(arg, |(flag, _)| flag);
     matches!(
-        arg,
-        "--help" | "-h" | "--version" | "-V" | "--tag" | "--yes" | "--no-reconnect"
+        flag,
+        "--help"
+            | "-h"
+            | "--version"
+            | "-V"
+            | "--tag"
+            | "--any"
+            | "--yes"
+            | "--no-reconnect"
+            | "--record"
+            | "--mosh"
+            | "--safe"
+            | "--reason"
+            | "--exact"
+            | "--here"
+            | "--tmux-window"
+            | "--tmux-split"
+            | "--dry-run"
+            | "--verbose"
+            | "--quiet"
+            | "--color"
     )
 }
 
-fn run_subcommand(cmd: Command, cfg: &oken_config::OkenConfig) -> Result<()> {
+fn run_subcommand(
+    cmd: Command,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
     match cmd {
-        Command::Host { command } => run_host_command(command),
-        Command::Tunnel { command } => run_tunnel_command(command),
-        Command::Print { host } => run_print_command(&host, cfg),
-        Command::Exec { .. } => stub("exec"),
-        Command::Snippet { .. } => stub("snippet"),
-        Command::Audit { lines } => {
-            audit::show_recent(lines)?;
-            Ok(())
+        Command::Host { command } => run_host_command(command, cfg),
+        Command::Tag {
+            command: TagCommand::List { format },
+        } => list_tags(format),
+        Command::Tunnel { command } => run_tunnel_command(command, opts, cfg),
+        Command::Forward {
+            host,
+            specs,
+            remote,
+            stop,
+        } => match stop {
+            Some(name) => stop_forward(&name),
+            None => run_forward_command(host.as_deref().unwrap_or_default(), &specs, remote, cfg),
+        },
+        Command::Mount {
+            command,
+            host,
+            path,
+            at,
+        } => match (command, host) {
+            (Some(MountCommand::List), _) => print_mounts(),
+            (None, Some(host)) => run_mount_command(&host, path.as_deref(), at, cfg),
+            (None, None) => anyhow::bail!("usage: oken mount <host> [path] | oken mount list"),
+        },
+        Command::Umount { target } => run_umount_command(&target),
+        Command::Push { host, files, to } => {
+            run_push_command(&host, &files, to.as_deref(), opts.dry_run, cfg)
+        }
+        Command::Pull { host, paths, to } => {
+            run_pull_command(&host, &paths, &to, opts.dry_run, cfg)
+        }
+        Command::Via { bastion, host } => connect_via(&bastion, &host, opts, cfg),
+        Command::Code { host, path } => run_code_command(&host, path.as_deref()),
+        Command::Sftp { host } => {
+            let code = transfer::run_sftp(&host_ssh_args(&host, cfg)?)?;
+            std::process::exit(code);
+        }
+        Command::Cloud { command } => run_cloud_command(command, cfg),
+        Command::Discover {
+            cidr,
+            port,
+            concurrency,
+            timeout,
+            user,
+            tag,
+        } => run_discover_command(&cidr, port, concurrency, timeout, user, tag),
+        Command::Print { host, json } => run_print_command(&host, json, cfg),
+        Command::Ping { tag, json, timeout } => run_ping_command(&tag, json, timeout, cfg),
+        Command::Cluster { hosts, tag } => run_cluster_command(&hosts, &tag, opts),
+        Command::Exec {
+            hosts,
+            tag,
+            stdin,
+            parallel,
+            sudo,
+            become_user,
+            command,
+        } => {
+            let sudo = (sudo || become_user.is_some()).then_some(become_user);
+            let mut selected = if stdin && hosts.is_empty() && tag.is_empty() {
+                Vec::new()
+            } else {
+                select_hosts(&hosts, &tag)?
+            };
+            if stdin {
+                read_hosts_from_stdin(&mut selected)?;
+            }
+            run_exec_command(&selected, parallel, sudo, stdin, &command, opts, cfg)
+        }
+        Command::Web { port } => web::serve(port, tunnels_toml_path()?, cfg.proxies.clone()),
+        Command::Wake {
+            host,
+            via,
+            local,
+            timeout,
+            no_connect,
+        } => run_wake_command(&host, via.as_deref(), local, timeout, no_connect, opts, cfg),
+        Command::Reboot { host, when } => {
+            run_power_command(power::Action::Reboot, &host, &when, opts, cfg)
         }
-        Command::Keys { .. } => stub("keys"),
+        Command::Shutdown { host, when } => {
+            run_power_command(power::Action::Shutdown, &host, &when, opts, cfg)
+        }
+        Command::Snippet { command } => run_snippet_command(command, opts, cfg),
+        Command::Stats { danger, weeks } => stats::show(danger, weeks, cfg),
+        Command::Audit { command, lines } => match command {
+            Some(AuditCommand::Play { id }) => audit::play(id),
+            None => audit::show_recent(lines),
+        },
+        Command::Daemon { command } => match command {
+            DaemonCommand::Run => daemon::run(),
+            DaemonCommand::Status => print_daemon_status(),
+            DaemonCommand::Probe => {
+                let all = hosts::list_all_hosts()?;
+                let cache = latency::measure(&all);
+                latency::store(&cache)?;
+                println!("Measured {} of {} host(s)", cache.rtt_ms.len(), all.len());
+                Ok(())
+            }
+        },
+        Command::Keys { command } => match command {
+            KeysCommand::List => keys::list(),
+            KeysCommand::Generate {
+                name,
+                sk,
+                resident,
+                comment,
+            } => keys::generate(name.as_deref(), sk, resident, comment.as_deref()),
+            KeysCommand::Download => keys::download(),
+            KeysCommand::Forget { host } => forget_host_keys(&host, cfg),
+        },
+        Command::Otp {
+            host,
+            set,
+            remove,
+            copy,
+        } => otp::run(&host, set, remove, copy),
         Command::Export { .. } => stub("export"),
-        Command::Import { .. } => stub("import"),
-        Command::Config => {
-            println!("reconnect:          {}", cfg.reconnect);
-            println!("reconnect_retries:  {}", cfg.reconnect_retries);
-            println!("reconnect_delay:    {}s", cfg.reconnect_delay_secs);
-            println!("keepalive_interval: {}s", cfg.keepalive_interval);
-            println!("danger_tags:        {}", cfg.danger_tags.join(", "));
+        Command::Import {
+            from_kubectl: _,
+            context,
+            from_vagrant,
+            from_docker,
+            from_terraform,
+            mapping,
+            format: _,
+            inventory,
+            prune,
+            ip,
+            user,
+        } => {
+            let source = match (inventory, from_vagrant, from_docker, from_terraform) {
+                (Some(path), ..) => ImportSource::Ansible(path),
+                (_, Some(dir), ..) => ImportSource::Vagrant(dir),
+                (_, _, true, _) => ImportSource::Docker,
+                (.., Some(path)) => ImportSource::Terraform(path, mapping),
+                _ => ImportSource::Kubectl(context),
+            };
+            run_import_command(source, ip, user.as_deref(), prune, cfg)
+        }
+        Command::Doctor => {
+            if !doctor::run() {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Config { command: Some(cmd) } => run_config_command(cmd),
+        Command::Config { command: None } => {
+            println!("reconnect:           {}", cfg.reconnect);
+            println!("reconnect_retries:   {}", cfg.reconnect_retries);
+            println!("reconnect_delay:     {}s", cfg.reconnect_delay_secs);
+            println!("reconnect_max_delay: {}s", cfg.reconnect_max_delay_secs);
+            println!("reconnect_wait_net:  {}", cfg.reconnect_wait_for_network);
+            println!("keepalive_interval:  {}s", cfg.keepalive_interval);
+            println!("danger_tags:         {}", cfg.danger_tags.join(", "));
+            println!(
+                "danger_rules:        {}",
+                cfg.danger_rules
+                    .iter()
+                    .map(|r| r.pattern.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            println!(
+                "danger_confirmation: {}",
+                cfg.danger_confirmation.as_str()
+            );
+            println!("record_tags:         {}", cfg.record_tags.join(", "));
+            println!("safe_tags:           {}", cfg.safe_tags.join(", "));
+            println!("redact_patterns:     {}", cfg.redact_patterns.join(", "));
+            println!("compliance:          {}", cfg.compliance);
+            println!("notify:              {}", cfg.notify);
+            println!("notify_after:        {}s", cfg.notify_after_secs);
+            println!("notify_focused:      {}", cfg.notify_when_focused);
+            match cfg.danger_idle_secs {
+                0 => println!("danger_idle:         off"),
+                secs => println!("danger_idle:         {secs}s"),
+            }
+            println!(
+                "danger_background:   {}",
+                cfg.danger_background.as_deref().unwrap_or("-")
+            );
+            println!(
+                "danger_webhook:      {}",
+                cfg.danger_webhook.as_deref().unwrap_or("-")
+            );
+            let mut proxies: Vec<String> = cfg.proxies.keys().cloned().collect();
+            proxies.extend(proxy::builtin_names().map(String::from));
+            println!("proxies:             {}", proxies.join(", "));
+            let mappings: Vec<&str> = cfg.mappings.keys().map(String::as_str).collect();
+            println!("mappings:            {}", mappings.join(", "));
+            let snippets: Vec<&str> = cfg.snippets.keys().map(String::as_str).collect();
+            println!("snippets:            {}", snippets.join(", "));
+            let defaults = cfg.defaults.describe();
+            println!(
+                "defaults:            {}",
+                if defaults.is_empty() { "-" } else { &defaults }
+            );
+            let events = cfg.hooks.events();
+            println!(
+                "hooks:               {}",
+                if events.is_empty() { "-".to_string() } else { events.join(", ") }
+            );
+            println!("host_key_review:     {}", cfg.host_key_review);
+            println!("latency_probe:       {}", cfg.latency_probe);
+            println!("update_check:        {}", cfg.update_check);
+            println!("update_interval:     {}h", cfg.update_interval_hours);
+            println!("update_channel:      {}", cfg.update_channel.as_str());
+            println!("update_proxy:        {}", cfg.update_proxy.as_deref().unwrap_or("-"));
+            println!("picker_open:         {}", cfg.picker_open.as_str());
+            let group_by = cfg.picker_group_by.as_deref().unwrap_or("-");
+            println!("picker_group_by:     {group_by}");
             Ok(())
         }
         Command::Update => {
-            update_check::force_check()?;
+            update_check::force_check(cfg)?;
             Ok(())
         }
-        Command::Completions { shell, dir } => completions::install(shell, dir),
+        Command::SelfUpdate { version } => {
+            self_update::run(version.as_deref(), cfg, opts.dry_run)
+        }
+        Command::Completions {
+            shell,
+            install: true,
+            dir,
+        } => completions::install(shell, dir),
+        Command::Completions { shell, .. } => completions::print(shell),
+        Command::Man { dir } => man::run(dir),
     }
 }
 
@@ -412,179 +1419,1811 @@ fn stub(name: &str) -> Result<()> {
     std::process::exit(1);
 }
 
-fn hosts_toml_path() -> Result<std::path::PathBuf> {
-    Ok(config::config_dir()?.join("hosts.toml"))
+/// `oken host show`: every field set for one host, then the certificates
+/// ssh would offer it.
+fn show_host(name: &str, cfg: &oken_config::OkenConfig) -> Result<()> {
+    let all = hosts::list_all_hosts()?;
+    let Some(h) = hosts::find(&all, name) else {
+        anyhow::bail!("no host named '{name}'");
+    };
+    let joined = |items: &[String]| (!items.is_empty()).then(|| items.join(", "));
+    let transport = match h.transport {
+        Transport::Ssh => None,
+        Transport::Mosh => Some("mosh".to_string()),
+        Transport::Et => Some("et".to_string()),
+        Transport::Ssm => Some("ssm".to_string()),
+    };
+    let connect = match h.connect {
+        Connect::Direct => None,
+        Connect::Ssm => Some("ssm".to_string()),
+        Connect::Iap => Some("iap".to_string()),
+    };
+    let fields = [
+        ("aliases", joined(&h.aliases)),
+        ("hostname", h.hostname.clone()),
+        ("addresses", joined(&h.addresses)),
+        ("address_family", (!h.address_family.is_any()).then(|| h.address_family.as_str().into())),
+        ("fallback_hostnames", joined(&h.fallback_hostnames)),
+        ("user", h.user.clone()),
+        ("port", h.port.map(|p| p.to_string())),
+        ("identity_file", h.identity_file.clone()),
+        ("certificate_file", h.certificate_file.clone()),
+        ("tags", joined(&h.tags)),
+        ("transport", transport),
+        ("proxy", h.proxy.clone()),
+        ("connect", connect),
+        ("connect_args", joined(&h.connect_args)),
+        ("record", h.record.then(|| "true".to_string())),
+        ("on_connect", joined(&h.on_connect)),
+        ("on_disconnect", joined(&h.on_disconnect)),
+        ("expires", h.expires.clone()),
+        ("host_key", h.host_key.clone()),
+        ("rebuild", h.rebuild.clone()),
+        ("mfa", h.mfa.then(|| "true".to_string())),
+        ("become_user", h.become_user.clone()),
+        ("mac_address", h.mac_address.clone()),
+        ("owner", h.owner.clone()),
+        ("team", h.team.clone()),
+        ("managed_by", h.managed_by.clone()),
+        ("source", Some(h.source().to_string())),
+    ];
+    println!("{}", color::paint("1", &h.alias));
+    for (field, value) in fields {
+        if let Some(value) = value {
+            println!("  {field:<16} {value}");
+        }
+    }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    for cert in keys::for_args(&build_ssh_args(h, None, &cfg.proxies)) {
+        println!("  {:<16} {} {}", "certificate", cert.source, cert.validity(now));
+    }
+    Ok(())
 }
 
-fn tunnels_toml_path() -> Result<std::path::PathBuf> {
-    Ok(config::config_dir()?.join("tunnels.toml"))
+/// `oken keys forget`: drop a host's known_hosts entries and its saved
+/// `host_key`, so the next connection reviews the new key from scratch.
+fn forget_host_keys(name: &str, cfg: &oken_config::OkenConfig) -> Result<()> {
+    let all = hosts::list_all_hosts().unwrap_or_default();
+    let host = hosts::find(&all, name);
+    let args = match host {
+        Some(h) => build_ssh_args(h, None, &cfg.proxies),
+        None => vec![name.to_string()],
+    };
+    let lookup = host_keys::lookup(&args)
+        .with_context(|| format!("could not resolve '{name}' with ssh -G"))?;
+    let removed = host_keys::forget(&lookup)?;
+    let saved = host.and_then(|h| h.host_key.as_ref());
+    if let Some(h) = host.filter(|_| saved.is_some())
+        && !h.from_ssh_config
+        && h.managed_by.is_none()
+    {
+        hosts_toml::set_host_key(&hosts_toml_path()?, &h.alias, None)?;
+        println!("Cleared the saved host_key for '{name}'");
+    }
+    if removed == 0 && saved.is_none() {
+        println!("No known host keys for {}", lookup.known_hosts_name());
+    } else if removed > 0 {
+        println!("Removed {removed} key(s) for {} from known_hosts", lookup.known_hosts_name());
+    }
+    Ok(())
+}
+
+/// After `host remove`, offer to delete the host's known_hosts entries too,
+/// so a different machine that later gets the address isn't refused.
+fn offer_forget_host_keys(host: &hosts::Host, cfg: &oken_config::OkenConfig) {
+    if !io::stdin().is_terminal() {
+        return;
+    }
+    let Some(lookup) = host_keys::lookup(&build_ssh_args(host, None, &cfg.proxies)) else {
+        return;
+    };
+    if !host_keys::is_known(&lookup) {
+        return;
+    }
+    let question = format!("Also remove {} from known_hosts?", lookup.known_hosts_name());
+    if !prompt::confirm(&question, false).unwrap_or(false) {
+        return;
+    }
+    match host_keys::forget(&lookup) {
+        Ok(n) => println!("Removed {n} key(s) from known_hosts"),
+        Err(e) => eprintln!("oken: {e:#}"),
+    }
+}
+
+fn hosts_toml_path() -> Result<std::path::PathBuf> {
+    Ok(config::config_dir()?.join("hosts.toml"))
 }
 
-fn run_print_command(host_arg: &str, cfg: &oken_config::OkenConfig) -> Result<()> {
+fn tunnels_toml_path() -> Result<std::path::PathBuf> {
+    Ok(config::config_dir()?.join("tunnels.toml"))
+}
+
+/// What `oken print --json` resolved a host to.
+#[derive(serde::Serialize)]
+struct ResolvedCommand {
+    binary: String,
+    /// The full command line, starting with `binary`.
+    argv: Vec<String>,
+    /// The saved host it came from; null when ssh resolves the name itself.
+    alias: Option<String>,
+    hostname: Option<String>,
+    user: Option<String>,
+    port: Option<u16>,
+    identity: Option<String>,
+    /// `-o` options oken adds (keep-alive, proxies).
+    options: Vec<String>,
+}
+
+fn run_print_command(host_arg: &str, json: bool, cfg: &oken_config::OkenConfig) -> Result<()> {
     let all = hosts::list_all_hosts()?;
-    if let Some(h) = all.iter().find(|h| h.alias == host_arg) {
-        let ssh = ssh::find_ssh()?;
-        let mut parts = build_ssh_args(h);
-        inject_keepalive(&mut parts, cfg.keepalive_interval);
-        let mut full = vec![ssh.display().to_string()];
-        full.extend(parts);
-        println!("{}", full.join(" "));
+    let host = hosts::find(&all, host_arg);
+    let (binary, parts) = match host {
+        Some(h) => {
+            let mut parts = build_ssh_args(h, None, &cfg.proxies);
+            inject_keepalive(&mut parts, cfg.keepalive_interval);
+            (ssh::find_ssh()?.display().to_string(), parts)
+        }
+        None => ("ssh".to_string(), vec![host_arg.to_string()]),
+    };
+    let mut argv = vec![binary.clone()];
+    argv.extend(parts);
+
+    if !json {
+        print_dry_run(&argv);
+        return Ok(());
+    }
+    let options = argv
+        .windows(2)
+        .filter(|w| w[0] == "-o")
+        .map(|w| w[1].clone())
+        .collect();
+    let resolved = ResolvedCommand {
+        binary,
+        argv,
+        alias: host.map(|h| h.alias.clone()),
+        hostname: host.and_then(|h| h.hostname.clone()),
+        user: host.and_then(|h| h.user.clone()),
+        port: host.and_then(|h| h.port),
+        identity: host.and_then(|h| h.identity_file.clone()),
+        options,
+    };
+    println!("{}", serde_json::to_string_pretty(&resolved)?);
+    Ok(())
+}
+
+fn run_mount_command(
+    host_arg: &str,
+    path: Option<&str>,
+    at: Option<std::path::PathBuf>,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let mountpoint = match at {
+        Some(p) => p,
+        None => mount::default_mountpoint(host_arg)?,
+    };
+    mount::mount(&host_ssh_args(host_arg, cfg)?, path.unwrap_or(""), &mountpoint)?;
+    println!("Mounted {host_arg}:{} on {}", path.unwrap_or("~"), mountpoint.display());
+    println!("Unmount with: oken umount {host_arg}");
+    Ok(())
+}
+
+fn run_umount_command(target: &str) -> Result<()> {
+    // A bare name is an alias mounted at the default place
+    let mountpoint = if target.contains(std::path::MAIN_SEPARATOR) {
+        std::path::PathBuf::from(target)
     } else {
-        println!("ssh {host_arg}");
+        mount::default_mountpoint(target)?
+    };
+    let mountpoint = mountpoint.canonicalize().unwrap_or(mountpoint);
+    if !mount::list()?.iter().any(|m| m.mountpoint == mountpoint) {
+        anyhow::bail!("{} is not an sshfs mount", mountpoint.display());
     }
+    mount::unmount(&mountpoint)?;
+    println!("Unmounted {}", mountpoint.display());
     Ok(())
 }
 
-fn run_tunnel_command(cmd: TunnelCommand) -> Result<()> {
-    let path = tunnels_toml_path()?;
-    match cmd {
-        TunnelCommand::Add { name, args } => {
-            let host = ssh::extract_target_host_full(&args)
-                .ok_or_else(|| anyhow::anyhow!("no target host found in args"))?;
+fn print_mounts() -> Result<()> {
+    let mounts = mount::list()?;
+    if mounts.is_empty() {
+        println!("No sshfs mounts. Use `oken mount <host>` to add one.");
+        return Ok(());
+    }
+    let mnt_dir = mount::default_mountpoint("")?;
+    let names: Vec<String> = mounts
+        .iter()
+        .map(|m| match m.mountpoint.strip_prefix(&mnt_dir) {
+            Ok(alias) => alias.display().to_string(),
+            Err(_) => String::new(),
+        })
+        .collect();
+    let name_w = names.iter().map(|n| n.len()).max().unwrap_or(4).max(4);
+    let mp_w = mounts
+        .iter()
+        .map(|m| m.mountpoint.display().to_string().len())
+        .max()
+        .unwrap_or(10)
+        .max(10);
+    println!("{:<name_w$}  {:<mp_w$}  SOURCE", "HOST", "MOUNTPOINT");
+    for (mount, name) in mounts.iter().zip(&names) {
+        println!(
+            "{name:<name_w$}  {:<mp_w$}  {}",
+            mount.mountpoint.display().to_string(),
+            mount.source
+        );
+    }
+    Ok(())
+}
 
-            // Collect ssh flags, excluding all positionals (the host)
-            let ssh_flags = extract_ssh_flags(&args);
+fn run_push_command(
+    host_arg: &str,
+    files: &[String],
+    to: Option<&str>,
+    dry_run: bool,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let ssh_args = host_ssh_args(host_arg, cfg)?;
+    let dir = match to {
+        Some("~") => None,
+        Some(dir) => Some(dir.to_string()),
+        None => history::last_remote_dir(host_arg).unwrap_or(None),
+    };
+    let dest = transfer::remote_spec(&ssh_args, dir.as_deref().unwrap_or(""));
+    if dry_run {
+        print_dry_run(&scp_command_line(&ssh_args, files, &dest));
+        return Ok(());
+    }
+    transfer::run_scp(&ssh_args, files, &dest)?;
+    if to.is_some() {
+        let _ = history::set_remote_dir(host_arg, dir.as_deref());
+    }
+    println!("Copied to {host_arg}:{}", dir.as_deref().unwrap_or("~"));
+    Ok(())
+}
 
-            tunnels::add_tunnel(&path, &name, tunnels::TunnelEntry { host, ssh_flags })?;
+fn run_pull_command(
+    host_arg: &str,
+    paths: &[String],
+    to: &std::path::Path,
+    dry_run: bool,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let ssh_args = host_ssh_args(host_arg, cfg)?;
+    let last = history::last_remote_dir(host_arg).unwrap_or(None);
+    let resolved: Vec<String> = paths
+        .iter()
+        .map(|p| transfer::resolve_remote(p, last.as_deref()))
+        .collect();
+    let sources: Vec<String> = resolved
+        .iter()
+        .map(|p| transfer::remote_spec(&ssh_args, p))
+        .collect();
+    if dry_run {
+        print_dry_run(&scp_command_line(&ssh_args, &sources, &to.to_string_lossy()));
+        return Ok(());
+    }
+    transfer::run_scp(&ssh_args, &sources, &to.to_string_lossy())?;
+    if let Some(dir) = resolved.first().and_then(|p| transfer::remote_parent(p)) {
+        let _ = history::set_remote_dir(host_arg, Some(dir));
+    }
+    println!("Copied into {}", to.display());
+    Ok(())
+}
+
+fn scp_command_line(ssh_args: &[String], sources: &[String], dest: &str) -> Vec<String> {
+    std::iter::once("scp".to_string())
+        .chain(transfer::scp_args(ssh_args, sources, dest))
+        .collect()
+}
+
+/// Open `host_arg` in VS Code. Remote-SSH only reads ~/.ssh/config, so hosts
+/// saved in hosts.toml are written to an included file first.
+fn run_code_command(host_arg: &str, path: Option<&str>) -> Result<()> {
+    if !util::on_path("code") {
+        anyhow::bail!(
+            "`code` not found on PATH (in VS Code, run \"Shell Command: Install 'code' command in PATH\")"
+        );
+    }
+    let all = hosts::list_all_hosts()?;
+    if let Some(host) = hosts::find(&all, host_arg) {
+        if !host.from_ssh_config && ssh_config::sync_include(&all)? {
+            eprintln!("Added an Include for oken's hosts to ~/.ssh/config");
+        }
+        record_host(host);
+    }
+    let mut cmd = std::process::Command::new("code");
+    cmd.args(["--remote", &format!("ssh-remote+{host_arg}")]);
+    cmd.args(path);
+    let status = cmd.status().context("failed to run code")?;
+    if !status.success() {
+        anyhow::bail!("code exited with {status}");
+    }
+    Ok(())
+}
+
+/// Connection args for `host_arg`: a saved host's target, port and key, or
+/// the argument itself for anything else (ssh resolves it from ~/.ssh/config).
+fn host_ssh_args(host_arg: &str, cfg: &oken_config::OkenConfig) -> Result<Vec<String>> {
+    let all = hosts::list_all_hosts()?;
+    Ok(match hosts::find(&all, host_arg) {
+        Some(h) => build_ssh_args(h, None, &cfg.proxies),
+        None => vec![host_arg.to_string()],
+    })
+}
+
+/// A tunnel through a host saved in hosts.toml keeps only the alias, so later
+/// edits to the host carry over. Anything else is stored as the ssh target.
+fn tunnel_target(target: String) -> Result<(String, Option<String>)> {
+    if hosts_toml::load_hosts_toml(&hosts_toml_path()?)?.contains_key(&target) {
+        Ok((String::new(), Some(target)))
+    } else {
+        Ok((target, None))
+    }
+}
+
+/// Start an unsaved background forward through `host_arg` (an alias from
+/// hosts.toml / ~/.ssh/config, or a raw target), reusing the tunnel machinery.
+fn run_forward_command(
+    host_arg: &str,
+    specs: &[String],
+    remote: bool,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let mut args = host_ssh_args(host_arg, cfg)?;
+    let target = args.remove(0);
+    let flag = if remote { "-R" } else { "-L" };
+    let mut ssh_flags = args;
+    for spec in specs {
+        ssh_flags.push(flag.to_string());
+        ssh_flags.push(expand_forward_spec(spec));
+    }
+    let entry = tunnels::TunnelEntry {
+        host: target,
+        ssh_flags,
+        ..Default::default()
+    };
+
+    let name = forward_name(host_arg, &expand_forward_spec(&specs[0]));
+    if tunnels::is_running(&name, &entry.host) {
+        println!("Already forwarding as '{name}'");
+        return Ok(());
+    }
+    tunnels::start(&name, &entry)?;
+    for forward in tunnels::describe_forwards(&entry) {
+        println!("Forwarding {forward}");
+    }
+    print_warnings(&tunnels::verify_remote_forwards(&name, &entry));
+    println!("Stop it with: oken forward --stop {name}");
+    Ok(())
+}
+
+fn stop_forward(name: &str) -> Result<()> {
+    // The control socket identifies the master; ssh just needs some destination
+    let entry = tunnels::TunnelEntry {
+        host: name.to_string(),
+        ..Default::default()
+    };
+    if !tunnels::is_running(name, &entry.host) {
+        anyhow::bail!("no forward named '{name}' is running");
+    }
+    tunnels::stop(name, &entry)?;
+    println!("Stopped forward '{name}'");
+    Ok(())
+}
+
+/// A bare port forwards to the same port on the remote host's localhost.
+fn expand_forward_spec(spec: &str) -> String {
+    if spec.parse::<u16>().is_ok() {
+        format!("{spec}:localhost:{spec}")
+    } else {
+        spec.to_string()
+    }
+}
+
+/// `fwd-<host>-<port>`, named after the first forward's listening port.
+fn forward_name(host: &str, spec: &str) -> String {
+    let host: String = host
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+        .collect();
+    let parts: Vec<&str> = spec.split(':').collect();
+    // [bind:]port:host:hostport
+    let port = if parts.len() == 4 { parts[1] } else { parts[0] };
+    format!("fwd-{host}-{port}")
+}
+
+fn run_tunnel_command(
+    cmd: TunnelCommand,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let dry_run = opts.dry_run;
+    let path = tunnels_toml_path()?;
+    match cmd {
+        TunnelCommand::Add { name, tag, args } => {
+            // Placeholders make it a template, filled in at `tunnel start`
+            let entry = if args.iter().any(|a| a.contains("{{")) {
+                tunnels::TunnelEntry {
+                    tags: tag,
+                    template: Some(args.join(" ")),
+                    ..Default::default()
+                }
+            } else {
+                let host = ssh::extract_target_host_full(&args)
+                    .ok_or_else(|| anyhow::anyhow!("no target host found in args"))?;
+
+                // Collect ssh flags, excluding all positionals (the host)
+                let ssh_flags = extract_ssh_flags(&args);
+
+                let (host, host_alias) = tunnel_target(host)?;
+                tunnels::TunnelEntry {
+                    host,
+                    host_alias,
+                    ssh_flags,
+                    tags: tag,
+                    ..Default::default()
+                }
+            };
+            tunnels::add_tunnel(&path, &name, entry)?;
             println!("Added tunnel '{name}'");
             Ok(())
         }
 
-        TunnelCommand::Start { name } => {
+        TunnelCommand::Start {
+            select,
+            auto_port,
+            verify,
+            mut params,
+        } => {
+            let auto_port = auto_port | take_flag(&mut params, "--auto-port");
+            let verify = verify | take_flag(&mut params, "--verify");
+            if dry_run {
+                if !params.is_empty() {
+                    anyhow::bail!(
+                        "--dry-run shows a template's saved values; \
+                         new ones are only saved by a real start"
+                    );
+                }
+                let ssh = ssh::find_ssh()?.display().to_string();
+                return for_each_tunnel(&path, &select, cfg, |name, entry| {
+                    check_resolved(name, entry)?;
+                    let entry = if auto_port {
+                        tunnels::pick_free_ports(entry)
+                    } else {
+                        entry.clone()
+                    };
+                    let mut argv = vec![ssh.clone()];
+                    argv.extend(tunnels::master_args(name, &entry)?);
+                    print_dry_run(&argv);
+                    Ok(())
+                });
+            }
+            apply_template_params(&path, &select, &params)?;
+            for_each_tunnel(&path, &select, cfg, |name, entry| {
+                if tunnels::is_running(name, &entry.host) {
+                    if params.is_empty() {
+                        println!("Tunnel '{name}' is already running");
+                    } else {
+                        println!(
+                            "Tunnel '{name}' is already running \
+                             (use `oken tunnel restart` to apply the new values)"
+                        );
+                    }
+                    return Ok(());
+                }
+                let warnings = start_tunnel(name, entry, auto_port, verify)?;
+                println!("Started tunnel '{name}'");
+                print_warnings(&warnings);
+                Ok(())
+            })
+        }
+
+        TunnelCommand::Restart {
+            select,
+            auto_port,
+            verify,
+            mut params,
+        } => {
+            let auto_port = auto_port | take_flag(&mut params, "--auto-port");
+            let verify = verify | take_flag(&mut params, "--verify");
+            apply_template_params(&path, &select, &params)?;
+            for_each_tunnel(&path, &select, cfg, |name, entry| {
+                if tunnels::is_running(name, &entry.host) {
+                    tunnels::stop(name, entry)?;
+                }
+                let warnings = start_tunnel(name, entry, auto_port, verify)?;
+                println!("Restarted tunnel '{name}'");
+                print_warnings(&warnings);
+                Ok(())
+            })
+        }
+
+        TunnelCommand::Edit {
+            name,
+            host,
+            add_forward,
+            add_remote,
+            remove_forward,
+            tag,
+            untag,
+        } => {
+            let no_flags = host.is_none()
+                && add_forward.is_empty()
+                && add_remote.is_empty()
+                && remove_forward.is_empty()
+                && tag.is_empty()
+                && untag.is_empty();
             let all = tunnels::load_tunnels(&path)?;
-            let entry = all
+            let current = all
                 .get(&name)
                 .ok_or_else(|| anyhow::anyhow!("tunnel '{name}' not found"))?;
+            let edited = if no_flags {
+                edit_tunnel_in_editor(&name, current)?
+            } else {
+                let changes_args = host.is_some()
+                    || !add_forward.is_empty()
+                    || !add_remote.is_empty()
+                    || !remove_forward.is_empty();
+                if current.template.is_some() && changes_args {
+                    anyhow::bail!(
+                        "tunnel '{name}' is a template; change it with `oken tunnel edit {name}`"
+                    );
+                }
+                let mut entry = current.clone();
+                if let Some(h) = host {
+                    (entry.host, entry.host_alias) = tunnel_target(h)?;
+                }
+                for spec in &remove_forward {
+                    if !entry.remove_forward(spec) {
+                        anyhow::bail!("tunnel '{name}' has no forward '{spec}'");
+                    }
+                }
+                for spec in add_forward {
+                    entry.ssh_flags.extend(["-L".to_string(), spec]);
+                }
+                for spec in add_remote {
+                    entry.ssh_flags.extend(["-R".to_string(), spec]);
+                }
+                entry.tags.retain(|t| !untag.iter().any(|u| u.eq_ignore_ascii_case(t)));
+                for t in tag {
+                    if !entry.tags.iter().any(|e| e.eq_ignore_ascii_case(&t)) {
+                        entry.tags.push(t);
+                    }
+                }
+                entry
+            };
+            let running = tunnels::load_effective(&path, &cfg.proxies)?
+                .get(&name)
+                .is_some_and(|e| tunnels::is_running(&name, &e.host));
+            tunnels::update_tunnel(&path, &name, |e| *e = edited)?;
+            println!("Updated tunnel '{name}'");
+            if running {
+                println!("It is running — apply the change with `oken tunnel restart {name}`");
+            }
+            Ok(())
+        }
+
+        TunnelCommand::Remove { name } => {
+            tunnels::remove_tunnel(&path, &name)?;
+            println!("Removed tunnel '{name}'");
+            Ok(())
+        }
 
-            if tunnels::is_running(&name, &entry.host) {
-                println!("Tunnel '{name}' is already running");
+        TunnelCommand::Stop { select } => for_each_tunnel(&path, &select, cfg, |name, entry| {
+            tunnels::set_wanted(name, false);
+            if (select.all || select.tag.is_some()) && !tunnels::is_running(name, &entry.host) {
                 return Ok(());
             }
+            tunnels::stop(name, entry)?;
+            println!("Stopped tunnel '{name}'");
+            Ok(())
+        }),
 
-            let sock = tunnels::socket_path(&name)?;
-            let ssh = ssh::find_ssh()?;
-
-            let mut cmd_args = vec![
-                "-N".to_string(),
-                "-M".to_string(),
-                "-S".to_string(),
-                sock.to_string_lossy().to_string(),
-            ];
-            cmd_args.extend(entry.ssh_flags.clone());
-            cmd_args.push(entry.host.clone());
-
-            let mut child = std::process::Command::new(&ssh)
-                .args(&cmd_args)
-                .stdin(std::process::Stdio::null())
-                .stdout(std::process::Stdio::null())
-                .stderr(std::process::Stdio::inherit())
-                .spawn()
-                .map_err(|e| anyhow::anyhow!("failed to start tunnel: {e}"))?;
-
-            // Brief wait to catch immediate failures (bad host, auth error, etc.)
-            std::thread::sleep(std::time::Duration::from_millis(500));
-            match child.try_wait() {
-                Ok(Some(status)) => {
-                    anyhow::bail!(
-                        "tunnel '{name}' failed to start (exit {})",
-                        status.code().unwrap_or(1)
-                    );
+        TunnelCommand::Enable { name } => {
+            if !tunnels::load_tunnels(&path)?.contains_key(&name) {
+                anyhow::bail!("tunnel '{name}' not found");
+            }
+            let service = autostart::enable(&name)?;
+            println!("Enabled tunnel '{name}' at login ({})", service.display());
+            Ok(())
+        }
+
+        TunnelCommand::Disable { name } => {
+            autostart::disable(&name)?;
+            println!("Disabled tunnel '{name}' at login");
+            Ok(())
+        }
+
+        TunnelCommand::Logs {
+            name,
+            follow,
+            lines,
+        } => {
+            if !tunnels::load_tunnels(&path)?.contains_key(&name) {
+                anyhow::bail!("tunnel '{name}' not found");
+            }
+            tunnels::show_logs(&name, lines, follow)
+        }
+
+        TunnelCommand::Watch { names, interval } => {
+            let label = if names.is_empty() {
+                "all tunnels".to_string()
+            } else {
+                names.join(", ")
+            };
+            eprintln!("Watching {label} (Ctrl-C to stop)");
+            let interval = std::time::Duration::from_secs(interval.max(1));
+            tunnels::watch(&path, &names, interval, &cfg.proxies)
+        }
+
+        TunnelCommand::List { json } => {
+            let verbose = opts.verbose > 0;
+            let all = tunnels::load_effective(&path, &cfg.proxies)?;
+            if json {
+                return print_tunnels_json(all);
+            }
+            if all.is_empty() {
+                println!("No tunnels configured. Use `oken tunnel add` to add one.");
+                return Ok(());
+            }
+
+            let mut entries: Vec<_> = all.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+            let name_w = entries.iter().map(|(n, _)| n.len()).max().unwrap_or(4).max(4);
+            // Show the saved host a tunnel goes through; templates nobody has
+            // started yet have no host at all
+            let host_of = |e: &tunnels::TunnelEntry| match (&e.host_alias, e.host.is_empty()) {
+                (Some(alias), _) => alias.clone(),
+                (None, true) => "(template)".to_string(),
+                (None, false) => e.host.clone(),
+            };
+            let host_w = entries
+                .iter()
+                .map(|(_, e)| host_of(e).len())
+                .max()
+                .unwrap_or(4)
+                .max(4);
+
+            // okend remembers when each tunnel last carried a connection
+            let snapshot = if verbose { daemon::query() } else { None };
+            let metrics_header = if verbose {
+                format!("{:>5}  {:>13}  {:>11}  ", "CONNS", "TRAFFIC", "LAST USED")
+            } else {
+                String::new()
+            };
+            println!(
+                "{:<name_w$}  {:<host_w$}  {:>7}  {:>7}  {:>7}  {metrics_header}FORWARDS",
+                "NAME", "HOST", "STATUS", "PID", "UPTIME"
+            );
+            for (name, entry) in &entries {
+                let status = tunnels::status(name, &entry.host);
+                let (state, pid, uptime) = match status {
+                    Some(s) => (
+                        "running",
+                        s.pid.map(|p| p.to_string()).unwrap_or_default(),
+                        s.uptime
+                            .map(|d| audit::format_duration(d.as_secs()))
+                            .unwrap_or_default(),
+                    ),
+                    None => ("stopped", String::new(), String::new()),
+                };
+                let mut forwards = tunnels::describe_forwards(entry);
+                if forwards.is_empty() {
+                    forwards.push(match &entry.template {
+                        Some(t) if entry.host.is_empty() => t.clone(),
+                        _ => entry.ssh_flags.join(" "),
+                    });
+                }
+                let metrics = if verbose {
+                    let running = state == "running";
+                    let last_active = snapshot
+                        .as_ref()
+                        .and_then(|s| s.tunnels.get(name.as_str()))
+                        .and_then(|t| t.last_active);
+                    tunnel_metrics(entry, running, last_active)
+                } else {
+                    String::new()
+                };
+                println!(
+                    "{:<name_w$}  {:<host_w$}  {:>7}  {:>7}  {:>7}  {metrics}{}",
+                    name,
+                    host_of(entry),
+                    state,
+                    pid,
+                    uptime,
+                    forwards.join(", ")
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `tunnel list --json`, for monitoring scripts.
+fn print_tunnels_json(all: std::collections::HashMap<String, tunnels::TunnelEntry>) -> Result<()> {
+    #[derive(serde::Serialize)]
+    struct ListedTunnel {
+        name: String,
+        /// `user@host` as passed to ssh; empty for templates not started yet.
+        host: String,
+        host_alias: Option<String>,
+        template: bool,
+        tags: Vec<String>,
+        running: bool,
+        pid: Option<u32>,
+        uptime_secs: Option<u64>,
+        socket: String,
+        /// Whether `tunnel start` asked for it and okend keeps it up.
+        wanted: bool,
+        forwards: Vec<tunnels::Forward>,
+    }
+    let mut listed = Vec::new();
+    for (name, entry) in all {
+        let status = tunnels::status(&name, &entry.host);
+        listed.push(ListedTunnel {
+            running: status.is_some(),
+            pid: status.as_ref().and_then(|s| s.pid),
+            uptime_secs: status.as_ref().and_then(|s| s.uptime).map(|d| d.as_secs()),
+            socket: tunnels::socket_path(&name)?.display().to_string(),
+            wanted: tunnels::is_wanted(&name),
+            forwards: tunnels::forwards(&entry),
+            template: entry.template.is_some(),
+            host: entry.host,
+            host_alias: entry.host_alias,
+            tags: entry.tags,
+            name,
+        });
+    }
+    listed.sort_by(|a, b| a.name.cmp(&b.name));
+    println!("{}", serde_json::to_string_pretty(&listed)?);
+    Ok(())
+}
+
+/// The CONNS, TRAFFIC and LAST USED columns of `tunnel list --verbose`.
+fn tunnel_metrics(
+    entry: &tunnels::TunnelEntry,
+    running: bool,
+    last_active: Option<u64>,
+) -> String {
+    let traffic = if running { tunnels::traffic(entry) } else { None };
+    let conns = traffic
+        .as_ref()
+        .map(|t| t.connections.to_string())
+        .unwrap_or_default();
+    let bytes = traffic
+        .as_ref()
+        .and_then(|t| t.bytes)
+        .map(|(sent, received)| format!("↓{} ↑{}", format_bytes(sent), format_bytes(received)))
+        .unwrap_or_default();
+    let last_used = if traffic.is_some_and(|t| t.connections > 0) {
+        "now".to_string()
+    } else if let Some(t) = last_active {
+        let ago = daemon::unix_now().saturating_sub(t);
+        format!("{} ago", audit::format_duration(ago))
+    } else {
+        String::new()
+    };
+    format!("{conns:>5}  {bytes:>13}  {last_used:>11}  ")
+}
+
+fn format_bytes(n: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if n < 1024 {
+        return format!("{n}B");
+    }
+    let mut value = n as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1}{}", UNITS[unit])
+}
+
+/// Print the hosts and tunnels tables from a running okend.
+fn print_daemon_status() -> Result<()> {
+    let Some(snapshot) = daemon::query() else {
+        anyhow::bail!("okend is not running (start it with `oken daemon run`)");
+    };
+    let alias_w = snapshot.hosts.keys().map(|a| a.len()).max().unwrap_or(4).max(4);
+    println!("{:<alias_w$}  {:<11}  {:>7}", "HOST", "STATUS", "LATENCY");
+    for (alias, status) in &snapshot.hosts {
+        let state = if status.reachable { "up" } else { "unreachable" };
+        let latency = status
+            .latency_ms
+            .map(|ms| format!("{ms}ms"))
+            .unwrap_or_default();
+        println!("{alias:<alias_w$}  {state:<11}  {latency:>7}");
+    }
+    if !snapshot.tunnels.is_empty() {
+        let name_w = snapshot.tunnels.keys().map(|n| n.len()).max().unwrap_or(4).max(6);
+        println!();
+        println!("{:<name_w$}  {:<7}  {:<9}  RESTARTS", "TUNNEL", "STATUS", "SUPERVISE");
+        for (name, status) in &snapshot.tunnels {
+            let state = if status.running { "running" } else { "stopped" };
+            let wanted = if status.wanted { "yes" } else { "no" };
+            println!("{name:<name_w$}  {state:<7}  {wanted:<9}  {}", status.restarts);
+        }
+    }
+    Ok(())
+}
+
+/// Start a tunnel on request of the user, optionally moving off taken ports
+/// and checking the forwards afterwards. Returns any verification warnings.
+fn start_tunnel(
+    name: &str,
+    entry: &tunnels::TunnelEntry,
+    auto_port: bool,
+    verify: bool,
+) -> Result<Vec<String>> {
+    check_resolved(name, entry)?;
+    let entry = if auto_port {
+        tunnels::pick_free_ports(entry)
+    } else {
+        entry.clone()
+    };
+    tunnels::start(name, &entry)?;
+    tunnels::set_wanted(name, true);
+    // Remote binds fail silently, so -R forwards are always checked
+    let mut warnings = tunnels::verify_remote_forwards(name, &entry);
+    if verify || entry.verify {
+        warnings.extend(tunnels::verify_forwards(&entry));
+    }
+    Ok(warnings)
+}
+
+/// Fail for a tunnel whose host alias or template values couldn't be filled in.
+fn check_resolved(name: &str, entry: &tunnels::TunnelEntry) -> Result<()> {
+    if entry.host.is_empty() {
+        match (&entry.host_alias, &entry.template) {
+            (Some(alias), _) => anyhow::bail!(
+                "tunnel '{name}' uses host '{alias}', which is not in hosts.toml or ~/.ssh/config"
+            ),
+            (None, Some(template)) => anyhow::bail!(
+                "template '{name}' needs values: oken tunnel start {name} {}",
+                tunnels::template_usage(template)
+            ),
+            (None, None) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Remove `flag` from template parameters, where clap leaves our own flags
+/// that come after the first `--key value` pair.
+fn take_flag(params: &mut Vec<String>, flag: &str) -> bool {
+    let before = params.len();
+    params.retain(|p| p != flag);
+    params.len() != before
+}
+
+/// Save `--key value` parameters given to `tunnel start/restart <template>`.
+fn apply_template_params(
+    path: &std::path::Path,
+    select: &cli::TunnelSelect,
+    params: &[String],
+) -> Result<()> {
+    if params.is_empty() {
+        return Ok(());
+    }
+    let Some(name) = &select.name else {
+        anyhow::bail!("template parameters need a single tunnel name, not --all or --tag");
+    };
+    tunnels::set_params(path, name, tunnels::parse_params(params)?)
+}
+
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("{} {warning}", color::epaint("1;33", "warning:"));
+    }
+}
+
+/// Open a single tunnel entry in $EDITOR as TOML and parse the result back.
+fn edit_tunnel_in_editor(
+    name: &str,
+    entry: &tunnels::TunnelEntry,
+) -> Result<tunnels::TunnelEntry> {
+    let tmp = env::temp_dir().join(format!("oken-tunnel-{name}-{}.toml", std::process::id()));
+    // Written as `[name]` (or `name = "..."` for a plain template) so both shapes can be edited
+    let single = std::collections::BTreeMap::from([(name, entry)]);
+    std::fs::write(&tmp, toml::to_string(&single)?)?;
+    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let status = std::process::Command::new(&editor).arg(&tmp).status();
+    let content = std::fs::read_to_string(&tmp);
+    let _ = std::fs::remove_file(&tmp);
+    let status = status?;
+    if !status.success() {
+        anyhow::bail!("editor exited with status {}", status);
+    }
+    let mut parsed: std::collections::HashMap<String, tunnels::TunnelEntry> =
+        toml::from_str(&content?).with_context(|| format!("invalid tunnel '{name}', not saved"))?;
+    parsed
+        .remove(name)
+        .ok_or_else(|| anyhow::anyhow!("tunnel '{name}' missing from the edited file, not saved"))
+}
+
+/// Apply `action` to the tunnels picked by name, `--all`, or `--tag`. Keeps
+/// going past failures so one bad tunnel doesn't block the rest.
+fn for_each_tunnel(
+    path: &std::path::Path,
+    select: &cli::TunnelSelect,
+    cfg: &oken_config::OkenConfig,
+    action: impl Fn(&str, &tunnels::TunnelEntry) -> Result<()>,
+) -> Result<()> {
+    let all = tunnels::load_effective(path, &cfg.proxies)?;
+    let mut selected: Vec<_> = match (&select.name, &select.tag) {
+        (Some(name), _) => {
+            let entry = all
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("tunnel '{name}' not found"))?;
+            vec![(name, entry)]
+        }
+        (None, Some(tag)) => all
+            .iter()
+            .filter(|(_, e)| e.tags.iter().any(|t| hosts::tag_matches(t, tag)))
+            .collect(),
+        (None, None) => all.iter().collect(),
+    };
+    if selected.is_empty() {
+        match &select.tag {
+            Some(tag) => println!("No tunnels tagged '{tag}'."),
+            None => println!("No tunnels configured. Use `oken tunnel add` to add one."),
+        }
+        return Ok(());
+    }
+    selected.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut failed = 0;
+    for (name, entry) in &selected {
+        if let Err(e) = action(name, entry) {
+            if selected.len() == 1 {
+                return Err(e);
+            }
+            eprintln!("oken: {e:#}");
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} tunnels failed", selected.len());
+    }
+    Ok(())
+}
+
+fn run_config_command(cmd: ConfigCommand) -> Result<()> {
+    match cmd {
+        ConfigCommand::Get { key } => match oken_config::get(&key)? {
+            Some(toml::Value::String(s)) => println!("{s}"),
+            Some(toml::Value::Table(table)) => print!("{}", toml::to_string(&table)?),
+            Some(value) => println!("{value}"),
+            None => std::process::exit(1),
+        },
+        ConfigCommand::Set { key, values } => {
+            oken_config::set(&key, &values)?;
+            println!("Set {key}");
+        }
+        ConfigCommand::Unset { key } => {
+            if oken_config::unset(&key)? {
+                println!("Unset {key}");
+            } else {
+                println!("{key} was not set");
+            }
+        }
+        ConfigCommand::Edit => edit_config()?,
+    }
+    Ok(())
+}
+
+/// `oken config edit`: open config.toml in $EDITOR, then check it and offer
+/// to reopen it until it loads without complaints.
+fn edit_config() -> Result<()> {
+    let path = oken_config::config_path()?;
+    config::create_parent(&path)?;
+    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    loop {
+        let status = std::process::Command::new(&editor).arg(&path).status()?;
+        if !status.success() {
+            anyhow::bail!("editor exited with status {}", status);
+        }
+        let content = std::fs::read_to_string(&path).unwrap_or_default();
+        let invalid = toml::from_str::<oken_config::OkenConfig>(&content).err();
+        if let Some(e) = &invalid {
+            eprintln!("{}", color::epaint("31", format!("config.toml is invalid: {e}")));
+        }
+        let unknown = oken_config::unknown_keys(&content);
+        for problem in &unknown {
+            eprintln!("{}", color::epaint("33", format!("config.toml: {problem}")));
+        }
+        if invalid.is_none() && unknown.is_empty() {
+            return Ok(());
+        }
+        let again = io::stdin().is_terminal() && prompt::confirm("Edit again?", true)?;
+        if !again {
+            if invalid.is_some() {
+                anyhow::bail!("config.toml is invalid; oken uses the defaults until it's fixed");
+            }
+            return Ok(());
+        }
+    }
+}
+
+fn run_cloud_command(cmd: CloudCommand, cfg: &oken_config::OkenConfig) -> Result<()> {
+    let CloudCommand::Sync { provider, prune } = cmd;
+    let (name, machines, fallback, ip, user) = match provider {
+        SyncProvider::Http {
+            mapping,
+            url,
+            netbox,
+            token,
+            user,
+        } => return sync_http(mapping, url, netbox, token, user, prune, cfg),
+        SyncProvider::Aws {
+            profile,
+            region,
+            filter,
+            ip,
+            user,
+        } => {
+            let opts = cloud::AwsOptions {
+                profile,
+                region,
+                filters: filter,
+            };
+            ("aws", cloud::aws_machines(&opts)?, Some(opts.ssm_fallback()), ip, user)
+        }
+        SyncProvider::Gcp {
+            project,
+            filter,
+            ip,
+            user,
+        } => {
+            let opts = cloud::GcpOptions {
+                project,
+                filters: filter,
+            };
+            ("gcp", cloud::gcp_machines(&opts)?, Some(opts.iap_fallback()), ip, user)
+        }
+        SyncProvider::Tailscale { by_ip, user } => {
+            let ip = if by_ip {
+                cli::AddressChoice::Private
+            } else {
+                cli::AddressChoice::Auto
+            };
+            ("tailscale", cloud::tailscale_machines()?, None, ip, user)
+        }
+        SyncProvider::Digitalocean { api } => sync_api(cloud::ApiProvider::DigitalOcean, api)?,
+        SyncProvider::Hetzner { api } => sync_api(cloud::ApiProvider::Hetzner, api)?,
+        SyncProvider::Linode { api } => sync_api(cloud::ApiProvider::Linode, api)?,
+    };
+    let found = machines.len();
+    let (entries, skipped) = cloud::to_entries(machines, ip, user.as_deref(), fallback.as_ref());
+    let summary = hosts_toml::sync_managed(&hosts_toml_path()?, name, entries, prune)?;
+    println!(
+        "Synced {found} machine(s) from {name}: {} added, {} updated, {} removed",
+        summary.added,
+        summary.updated,
+        summary.removed.len()
+    );
+    for alias in &summary.removed {
+        println!("  - {alias}");
+    }
+    if skipped > 0 {
+        println!("Skipped {skipped} without a matching address (try --ip auto or --ip private)");
+    }
+    Ok(())
+}
+
+/// `oken cloud sync http`: a `[mappings.<name>]` endpoint or NetBox, into
+/// `[managed.<name>]` or `[managed.netbox]`.
+fn sync_http(
+    mapping: Option<String>,
+    url: Option<String>,
+    netbox: Option<String>,
+    token: Option<String>,
+    user: Option<String>,
+    prune: bool,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let (name, mapping, urls, token_var) = match (netbox, mapping) {
+        (Some(base), _) => {
+            let base = base.trim_end_matches('/');
+            let urls = ["dcim/devices", "virtualization/virtual-machines"]
+                .map(|endpoint| format!("{base}/api/{endpoint}/?has_primary_ip=true&limit=1000"));
+            ("netbox".to_string(), cloud::netbox_mapping(), urls.to_vec(), "NETBOX_TOKEN")
+        }
+        (None, Some(name)) => {
+            let mapping = cfg
+                .mappings
+                .get(&name)
+                .with_context(|| format!("no [mappings.{name}] table in config.toml"))?
+                .clone();
+            let url = url.or_else(|| mapping.url.clone()).with_context(|| {
+                format!("no endpoint for {name}: pass --url or set url in [mappings.{name}]")
+            })?;
+            (name, mapping, vec![url], "OKEN_HTTP_TOKEN")
+        }
+        (None, None) => anyhow::bail!("pass a mapping name or --netbox"),
+    };
+    let token = token.or_else(|| std::env::var(token_var).ok().filter(|t| !t.is_empty()));
+    let (mut entries, skipped) = cloud::http_hosts(&urls, &mapping, token.as_deref(), &name)?;
+    if let Some(user) = &user {
+        for entry in entries.values_mut() {
+            entry.user = Some(user.clone());
+        }
+    }
+    let found = entries.len();
+    let summary = hosts_toml::sync_managed(&hosts_toml_path()?, &name, entries, prune)?;
+    println!(
+        "Synced {found} host(s) from {name}: {} added, {} updated, {} removed",
+        summary.added,
+        summary.updated,
+        summary.removed.len()
+    );
+    for alias in &summary.removed {
+        println!("  - {alias}");
+    }
+    if skipped > 0 {
+        println!("Skipped {skipped} without an alias or address");
+    }
+    Ok(())
+}
+
+enum ImportSource {
+    Kubectl(Option<String>),
+    Vagrant(std::path::PathBuf),
+    Docker,
+    /// State path and an optional `[mappings.<name>]`.
+    Terraform(std::path::PathBuf, Option<String>),
+    /// An Ansible inventory file.
+    Ansible(std::path::PathBuf),
+}
+
+/// Import hosts from another tool into its `[managed.<source>]` section,
+/// so importing again refreshes them.
+fn run_import_command(
+    source: ImportSource,
+    ip: cli::AddressChoice,
+    user: Option<&str>,
+    prune: bool,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let mut skipped = 0;
+    let (section, from, mut entries) = match source {
+        ImportSource::Kubectl(context) => {
+            let (context, nodes) = import::kubectl_nodes(context.as_deref())?;
+            let (entries, n) = cloud::to_entries(nodes, ip, user, None);
+            skipped = n;
+            ("kubectl", context, entries)
+        }
+        ImportSource::Vagrant(dir) => {
+            let dir = std::fs::canonicalize(&dir).unwrap_or(dir);
+            ("vagrant", dir.display().to_string(), import::vagrant_hosts(&dir)?)
+        }
+        ImportSource::Docker => ("docker", "docker contexts".to_string(), import::docker_hosts()?),
+        ImportSource::Terraform(path, mapping) => {
+            let mapping = match mapping {
+                Some(name) => Some(cfg.mappings.get(&name).with_context(|| {
+                    format!("no [mappings.{name}] table in config.toml")
+                })?),
+                None => None,
+            };
+            let (entries, n) = import::terraform_hosts(&path, mapping)?;
+            skipped = n;
+            ("terraform", path.display().to_string(), entries)
+        }
+        ImportSource::Ansible(path) => {
+            ("ansible", path.display().to_string(), ansible::inventory_hosts(&path)?)
+        }
+    };
+    if let Some(user) = user {
+        for entry in entries.values_mut() {
+            entry.user = Some(user.to_string());
+        }
+    }
+    let found = entries.len();
+    let summary = hosts_toml::sync_managed(&hosts_toml_path()?, section, entries, prune)?;
+    println!(
+        "Imported {found} host(s) from {from}: {} added, {} updated, {} removed",
+        summary.added,
+        summary.updated,
+        summary.removed.len()
+    );
+    for alias in &summary.removed {
+        println!("  - {alias}");
+    }
+    if skipped > 0 {
+        println!("Skipped {skipped} without an alias or address");
+    }
+    Ok(())
+}
+
+/// The saved hosts named in `names`, then every unexpired host with one of
+/// `tags`, without repeats. For commands that work on several hosts at once.
+fn select_hosts(names: &[String], tags: &[String]) -> Result<Vec<hosts::Host>> {
+    if names.is_empty() && tags.is_empty() {
+        anyhow::bail!("name the hosts, or pick them with --tag");
+    }
+    let now = daemon::unix_now();
+    let all = hosts::list_all_hosts()?;
+    let mut selected: Vec<hosts::Host> = Vec::new();
+    for name in names {
+        let host = hosts::find(&all, name)
+            .with_context(|| format!("no saved host named '{name}'"))?;
+        if !selected.iter().any(|h| h.alias == host.alias) {
+            selected.push(host.clone());
+        }
+    }
+    for h in &all {
+        let tagged = tags.iter().any(|w| h.has_tag(w));
+        if tagged && !h.is_expired(now) && !selected.iter().any(|s| s.alias == h.alias) {
+            selected.push(h.clone());
+        }
+    }
+    if selected.is_empty() {
+        anyhow::bail!("no hosts with tag {}", tags.join(" or "));
+    }
+    Ok(selected)
+}
+
+/// `oken cluster`: the named hosts plus every host with one of `tags`, each
+/// in its own tmux pane with synchronized input.
+fn run_cluster_command(names: &[String], tags: &[String], opts: &ConnectOpts) -> Result<()> {
+    let aliases: Vec<String> = select_hosts(names, tags)?.into_iter().map(|h| h.alias).collect();
+    // --yes stays behind, so danger hosts still ask (and one answer reaches every pane)
+    cluster::open(&aliases, &session_flags(opts), opts.dry_run)
+}
+
+/// Add the hosts listed on stdin: the first word of each line, a saved alias
+/// or anything ssh accepts (`user@host`, an ssh_config alias). Blank lines
+/// and `#` comments are skipped, and so are hosts already selected.
+fn read_hosts_from_stdin(selected: &mut Vec<hosts::Host>) -> Result<()> {
+    let all = hosts::list_all_hosts()?;
+    for line in io::stdin().lock().lines() {
+        let line = line.context("failed to read hosts from stdin")?;
+        let Some(target) = line.split('#').next().and_then(|l| l.split_whitespace().next())
+        else {
+            continue;
+        };
+        if selected.iter().any(|h| h.is_named(target)) {
+            continue;
+        }
+        let host = hosts::find(&all, target).cloned().unwrap_or_else(|| {
+            log::debug!("'{target}' from stdin isn't a saved host; passing it to ssh as is");
+            hosts::Host {
+                alias: target.to_string(),
+                ..Default::default()
+            }
+        });
+        selected.push(host);
+    }
+    if selected.is_empty() {
+        anyhow::bail!("no hosts on stdin");
+    }
+    Ok(())
+}
+
+/// `oken exec`: run `command` on the selected hosts over ssh, streaming their
+/// output, and exit 1 if it failed anywhere. With `sudo`, the command runs
+/// through sudo, as the given user or else each host's `become_user`.
+/// `stdin_taken` means stdin held the host list, so prompts use the terminal.
+fn run_exec_command(
+    selected: &[hosts::Host],
+    parallel: usize,
+    sudo: Option<Option<String>>,
+    stdin_taken: bool,
+    command: &[String],
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    if let Some(h) = selected.iter().find(|h| h.transport == Transport::Ssm) {
+        let alias = &h.alias;
+        anyhow::bail!("'{alias}' connects with aws ssm start-session, which can't run a command");
+    }
+    let ssh_bin = ssh::find_ssh()?.display().to_string();
+    let mut jobs: Vec<exec::Job> = selected
+        .iter()
+        .map(|h| {
+            let mut args = build_ssh_args(h, None, &cfg.proxies);
+            inject_keepalive(&mut args, cfg.keepalive_interval);
+            // Password and host key prompts can't be answered with many hosts at once
+            args.splice(0..0, ["-o".to_string(), "BatchMode=yes".to_string()]);
+            let mut argv = vec![ssh_bin.clone()];
+            argv.extend(args);
+            match &sudo {
+                Some(user) => {
+                    let user = user.as_deref().or(h.become_user.as_deref());
+                    argv.push(exec::sudo_command(command, user));
+                }
+                None => argv.extend(command.iter().cloned()),
+            }
+            exec::Job {
+                alias: h.alias.clone(),
+                argv,
+                input: None,
+            }
+        })
+        .collect();
+    if opts.dry_run {
+        for job in &jobs {
+            print_dry_run(&job.argv);
+        }
+        return Ok(());
+    }
+
+    let dangerous: Vec<(&hosts::Host, danger::DangerMatch)> = selected
+        .iter()
+        .map(|h| (h, danger::evaluate(h, cfg)))
+        .filter(|(_, d)| !d.is_empty())
+        .collect();
+    let reason = opts.reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    if !dangerous.is_empty() {
+        let names: Vec<&str> = dangerous.iter().map(|(h, _)| h.alias.as_str()).collect();
+        if cfg.compliance && reason.is_none() {
+            anyhow::bail!("compliance mode: pass --reason to run on {}", names.join(", "));
+        }
+        if !opts.yes {
+            let terminal = if stdin_taken {
+                std::fs::File::open("/dev/tty").ok().map(|tty| {
+                    Box::new(io::BufReader::new(tty)) as Box<dyn BufRead>
+                })
+            } else {
+                io::stdin()
+                    .is_terminal()
+                    .then(|| Box::new(io::stdin().lock()) as Box<dyn BufRead>)
+            };
+            let Some(mut terminal) = terminal else {
+                let names = names.join(", ");
+                anyhow::bail!("confirmation needed for {names}; pass --yes to go ahead");
+            };
+            for (h, d) in &dangerous {
+                print_danger_banner(h, d, false);
+            }
+            let question = format!("Run `{}` on {} host(s)?", command.join(" "), jobs.len());
+            if !prompt::confirm_from(&question, false, &mut terminal)? {
+                return Ok(());
+            }
+        }
+    }
+
+    if sudo.is_some() {
+        let ask = "[sudo] password for the remote hosts: ";
+        let password = if stdin_taken {
+            prompt::read_hidden_from_tty(ask)?
+        } else {
+            prompt::read_hidden(ask)?
+        };
+        for job in &mut jobs {
+            job.input = Some(format!("{password}\n"));
+        }
+    }
+    let outcomes = exec::run(&jobs, parallel);
+    for ((host, job), outcome) in selected.iter().zip(&jobs).zip(&outcomes) {
+        audit::log_session(&audit::SessionEntry {
+            alias: &job.alias,
+            target: &format!("exec: {}", command.join(" ")),
+            duration_secs: outcome.duration.as_secs(),
+            exit_code: outcome.exit_code,
+            recording: None,
+            reason,
+            danger: !danger::evaluate(host, cfg).is_empty(),
+        });
+    }
+    if outcomes.iter().any(|o| o.exit_code != 0) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// `oken snippet`: list the `[snippets]` in config.toml, or run one the way
+/// `oken exec` runs a command.
+fn run_snippet_command(
+    cmd: SnippetCommand,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    match cmd {
+        SnippetCommand::List => {
+            if cfg.snippets.is_empty() {
+                println!("No snippets. Add them under [snippets] in config.toml.");
+                return Ok(());
+            }
+            let name_w = cfg.snippets.keys().map(String::len).max().unwrap_or(4).max(4);
+            println!("{:<name_w$}  COMMAND", "NAME");
+            for (name, snippet) in &cfg.snippets {
+                let sudo = match (&snippet.become_user, snippet.sudo) {
+                    (Some(user), _) => format!("[sudo -u {user}] "),
+                    (None, true) => "[sudo] ".to_string(),
+                    (None, false) => String::new(),
+                };
+                println!("{name:<name_w$}  {sudo}{}", snippet.command);
+                if let Some(description) = &snippet.description {
+                    println!("{:<name_w$}  {}", "", color::paint("2", description));
+                }
+            }
+            Ok(())
+        }
+        SnippetCommand::Run {
+            name,
+            hosts,
+            tag,
+            parallel,
+            sudo,
+            become_user,
+        } => {
+            let Some(snippet) = cfg.snippets.get(&name) else {
+                anyhow::bail!("no snippet named '{name}' in config.toml (see oken snippet list)");
+            };
+            let become_user = become_user.or_else(|| snippet.become_user.clone());
+            let sudo = (sudo || snippet.sudo || become_user.is_some()).then_some(become_user);
+            let selected = select_hosts(&hosts, &tag)?;
+            let command = [snippet.command.clone()];
+            run_exec_command(&selected, parallel, sudo, false, &command, opts, cfg)
+        }
+    }
+}
+
+/// `oken wake`: send a Wake-on-LAN packet to a saved host's `mac_address`,
+/// from this machine or over ssh from a host on its network (`via`, else its
+/// last ProxyJump hop), then wait for its ssh port and connect.
+fn run_wake_command(
+    alias: &str,
+    via: Option<&str>,
+    local: bool,
+    timeout_secs: u64,
+    no_connect: bool,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let all = hosts::list_all_hosts()?;
+    let host = hosts::find(&all, alias)
+        .with_context(|| format!("no saved host named '{alias}'"))?;
+    let mac = host.mac_address.as_deref().with_context(|| {
+        format!("'{alias}' has no mac_address; add one with `oken host edit {alias}`")
+    })?;
+    let mac = wake::parse_mac(mac)?;
+    let route = ssh_route(host, cfg);
+    let sender = match (via, &route) {
+        (Some(via), _) => Some((via.to_string(), host_ssh_args(via, cfg)?)),
+        (None, Some(ssh::Route::Jump { jumps, .. })) if !local => {
+            // The last hop shares the host's network; earlier ones lead to it
+            let (earlier, last) = match jumps.rsplit_once(',') {
+                Some((earlier, last)) => (vec!["-J".to_string(), earlier.to_string()], last),
+                None => (Vec::new(), jumps.as_str()),
+            };
+            let mut args = earlier;
+            args.push(format!("ssh://{last}"));
+            Some((last.to_string(), args))
+        }
+        _ => None,
+    };
+
+    let mac_text = wake::format_mac(&mac);
+    match sender {
+        Some((name, args)) => {
+            let mut argv = vec![ssh::find_ssh()?.display().to_string()];
+            argv.extend(args);
+            argv.push(wake::remote_command(&mac));
+            if opts.dry_run {
+                print_dry_run(&argv);
+            } else {
+                let status = std::process::Command::new(&argv[0])
+                    .args(&argv[1..])
+                    .status()
+                    .context("failed to run ssh")?;
+                if !status.success() {
+                    anyhow::bail!("failed to send the wake-up packet from '{name}' ({status})");
                 }
-                Ok(None) => println!("Started tunnel '{name}'"),
-                Err(e) => anyhow::bail!("could not check tunnel status: {e}"),
+                eprintln!("Sent a wake-up packet to '{alias}' ({mac_text}) from '{name}'");
             }
-            Ok(())
         }
-
-        TunnelCommand::Remove { name } => {
-            tunnels::remove_tunnel(&path, &name)?;
-            println!("Removed tunnel '{name}'");
-            Ok(())
+        None if opts.dry_run => {
+            println!("# broadcast a Wake-on-LAN packet for {mac_text} to 255.255.255.255:9")
         }
+        None => {
+            wake::send_local(&mac)?;
+            eprintln!("Sent a wake-up packet to '{alias}' ({mac_text})");
+        }
+    }
+    if opts.dry_run {
+        return if no_connect { Ok(()) } else { connect_to_host(host, opts, cfg) };
+    }
 
-        TunnelCommand::Stop { name } => {
-            let all = tunnels::load_tunnels(&path)?;
-            let entry = all
-                .get(&name)
-                .ok_or_else(|| anyhow::anyhow!("tunnel '{name}' not found"))?;
-
-            let sock = tunnels::socket_path(&name)?;
-            let ssh = ssh::find_ssh()?;
+    let route = route.with_context(|| format!("can't tell where '{alias}' listens for ssh"))?;
+    let timeout = std::time::Duration::from_secs(timeout_secs);
+    if !wake::wait_for_ssh(alias, &route, timeout) {
+        anyhow::bail!("'{alias}' didn't answer on its ssh port within {timeout_secs}s");
+    }
+    eprintln!("'{alias}' is up");
+    if no_connect {
+        return Ok(());
+    }
+    connect_to_host(host, opts, cfg)
+}
 
-            let status = std::process::Command::new(&ssh)
-                .args(["-S", &sock.to_string_lossy(), "-O", "stop", &entry.host])
-                .status()
-                .map_err(|e| anyhow::anyhow!("failed to stop tunnel: {e}"))?;
+/// `oken reboot` / `oken shutdown`: confirm (through the danger gate for
+/// production hosts), then run `shutdown` on the host over ssh and log it.
+fn run_power_command(
+    action: power::Action,
+    target: &str,
+    when: &str,
+    opts: &ConnectOpts,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let when = power::parse_when(when)?;
+    let all = hosts::list_all_hosts()?;
+    let host = hosts::find(&all, target).cloned().unwrap_or_else(|| hosts::Host {
+        alias: target.to_string(),
+        ..Default::default()
+    });
+    if host.transport == Transport::Ssm {
+        anyhow::bail!("'{target}' connects with aws ssm start-session, which can't run a command");
+    }
+    let ssh_args = build_ssh_args(&host, None, &cfg.proxies);
+    let mut argv = vec![ssh::find_ssh()?.display().to_string()];
+    if io::stdin().is_terminal() {
+        // sudo may need to ask for a password
+        argv.push("-t".to_string());
+    }
+    argv.extend(ssh_args.iter().cloned());
+    argv.push(power::remote_command(action, &when));
+    if opts.dry_run {
+        print_dry_run(&argv);
+        return Ok(());
+    }
 
-            if status.success() {
-                println!("Stopped tunnel '{name}'");
-            } else {
-                anyhow::bail!("failed to stop tunnel '{name}'");
+    let what = format!("{} '{}' {}", action.verb(), host.alias, power::describe_when(&when));
+    if !opts.yes && (host.owner.is_some() || host.team.is_some()) {
+        let owner = format!("Owned by {}", describe_owner(&host));
+        eprintln!("{}", color::epaint("2", owner));
+    }
+    let reason = if danger::evaluate(&host, cfg).is_empty() {
+        if !opts.yes {
+            if !io::stdin().is_terminal() {
+                anyhow::bail!("confirmation needed to {} '{target}'; pass --yes", action.as_str());
             }
-            Ok(())
-        }
-
-        TunnelCommand::List => {
-            let all = tunnels::load_tunnels(&path)?;
-            if all.is_empty() {
-                println!("No tunnels configured. Use `oken tunnel add` to add one.");
+            if !prompt::confirm(&format!("{what}?"), false)? {
                 return Ok(());
             }
+        }
+        None
+    } else {
+        if !opts.yes || cfg.compliance {
+            eprintln!("{what}.");
+        }
+        match danger_gate(&host, &ssh_args, opts, cfg)? {
+            Gate::Proceed { reason } => reason,
+            Gate::Declined => return Ok(()),
+        }
+    };
 
-            let mut entries: Vec<_> = all.into_iter().collect();
-            entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let start = std::time::Instant::now();
+    let status = std::process::Command::new(&argv[0])
+        .args(&argv[1..])
+        .status()
+        .context("failed to run ssh")?;
+    let exit_code = status.code().unwrap_or(255);
+    audit::log_session(&audit::SessionEntry {
+        alias: &host.alias,
+        target: &format!("{} {when}", action.as_str()),
+        duration_secs: start.elapsed().as_secs(),
+        exit_code,
+        recording: None,
+        reason: reason.as_deref(),
+        danger: !danger::evaluate(&host, cfg).is_empty(),
+    });
+    let alias = &host.alias;
+    match exit_code {
+        0 if power::is_immediate(&when) => eprintln!("'{alias}' is going down"),
+        0 => eprintln!("'{alias}' will {} {}", action.as_str(), power::describe_when(&when)),
+        // ssh loses the connection when the host goes down under it
+        255 if power::is_immediate(&when) => {
+            eprintln!("Connection to '{alias}' closed; it is probably going down")
+        }
+        code => anyhow::bail!("{} failed on '{alias}' (exit {code})", action.as_str()),
+    }
+    Ok(())
+}
 
-            let name_w = entries.iter().map(|(n, _)| n.len()).max().unwrap_or(4).max(4);
-            let host_w = entries
-                .iter()
-                .map(|(_, e)| e.host.len())
-                .max()
-                .unwrap_or(4)
-                .max(4);
+#[derive(serde::Serialize)]
+struct PingResult {
+    alias: String,
+    address: String,
+    up: bool,
+    latency_ms: Option<u64>,
+    /// Jump hosts or ProxyCommand the probe went through.
+    via: Option<String>,
+}
+
+/// `oken ping`: probe every saved host (or those with one of `tags`) at
+/// once, the way ssh would reach it. Exits 1 when any host is down.
+fn run_ping_command(
+    tags: &[String],
+    json: bool,
+    timeout_ms: u64,
+    cfg: &oken_config::OkenConfig,
+) -> Result<()> {
+    let now = daemon::unix_now();
+    let mut all = hosts::list_all_hosts()?;
+    let tagged = |h: &hosts::Host| tags.iter().any(|w| h.has_tag(w));
+    all.retain(|h| !h.is_expired(now) && (tags.is_empty() || tagged(h)));
+    if all.is_empty() {
+        anyhow::bail!("no hosts to ping");
+    }
+    let timeout = std::time::Duration::from_millis(timeout_ms);
+    let results: Vec<PingResult> = all
+        .chunks(32)
+        .flat_map(|chunk| {
+            std::thread::scope(|s| {
+                let handles: Vec<_> = chunk
+                    .iter()
+                    .map(|h| s.spawn(move || ping_host(h, timeout, cfg)))
+                    .collect();
+                handles.into_iter().filter_map(|h| h.join().ok()).collect::<Vec<_>>()
+            })
+        })
+        .collect();
 
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else {
+        let name_w = results.iter().map(|r| r.alias.len()).max().unwrap_or(4).max(4);
+        let addr_w = results.iter().map(|r| r.address.len()).max().unwrap_or(7).max(7);
+        println!("{:<name_w$}  {:<addr_w$}  STATUS  LATENCY  VIA", "NAME", "ADDRESS");
+        for r in &results {
+            let status = if r.up {
+                color::paint("32", "up    ")
+            } else {
+                color::paint("31", "down  ")
+            };
+            let latency = r.latency_ms.map(|ms| format!("{ms}ms")).unwrap_or_else(|| "-".into());
             println!(
-                "{:<name_w$}  {:<host_w$}  {:>7}  {}",
-                "NAME", "HOST", "STATUS", "FLAGS"
+                "{:<name_w$}  {:<addr_w$}  {status}  {latency:>7}  {}",
+                r.alias,
+                r.address,
+                r.via.as_deref().unwrap_or("-")
             );
-            for (name, entry) in &entries {
-                let status = if tunnels::is_running(name, &entry.host) {
-                    "running"
-                } else {
-                    "stopped"
-                };
-                let flags = entry.ssh_flags.join(" ");
-                println!("{:<name_w$}  {:<host_w$}  {:>7}  {}", name, entry.host, status, flags);
-            }
-            Ok(())
         }
     }
+    if results.iter().any(|r| !r.up) {
+        std::process::exit(1);
+    }
+    Ok(())
 }
 
-/// Extract only SSH flags (and their values) from args; all positionals are dropped.
-fn extract_ssh_flags(args: &[String]) -> Vec<String> {
-    let mut result = Vec::new();
-    let mut skip_next = false;
-    for arg in args {
-        if skip_next {
-            result.push(arg.clone());
-            skip_next = false;
-            continue;
-        }
-        if ssh::FLAGS_WITH_VALUES.contains(&arg.as_str()) {
-            result.push(arg.clone());
-            skip_next = true;
-            continue;
-        }
-        if arg.starts_with('-') {
-            result.push(arg.clone());
-            continue;
+/// How ssh would reach a saved host, or just its address and port when
+/// `ssh -G` can't tell.
+fn ssh_route(host: &hosts::Host, cfg: &oken_config::OkenConfig) -> Option<ssh::Route> {
+    ssh::resolve_route(&build_ssh_args(host, None, &cfg.proxies)).or_else(|| {
+        let (hostname, port) = probe::endpoint(host)?;
+        Some(ssh::Route::Direct { hostname, port })
+    })
+}
+
+fn ping_host(
+    host: &hosts::Host,
+    timeout: std::time::Duration,
+    cfg: &oken_config::OkenConfig,
+) -> PingResult {
+    let route = ssh_route(host, cfg);
+    let (address, via) = match &route {
+        Some(ssh::Route::Direct { hostname, port }) => (format!("{hostname}:{port}"), None),
+        Some(ssh::Route::Jump {
+            jumps,
+            hostname,
+            port,
+        }) => (format!("{hostname}:{port}"), Some(jumps.clone())),
+        Some(ssh::Route::Command {
+            command,
+            hostname,
+            port,
+        }) => (format!("{hostname}:{port}"), Some(command.clone())),
+        None => ("?".to_string(), None),
+    };
+    let latency = route.and_then(|r| probe::route(&r, timeout));
+    PingResult {
+        alias: host.alias.clone(),
+        address,
+        up: latency.is_some(),
+        latency_ms: latency.map(|d| d.as_millis() as u64),
+        via,
+    }
+}
+
+fn run_discover_command(
+    cidr: &str,
+    port: u16,
+    concurrency: usize,
+    timeout_ms: u64,
+    user: Option<String>,
+    tags: Vec<String>,
+) -> Result<()> {
+    let addrs = discover::parse_cidr(cidr)?;
+    eprintln!("Scanning {} address(es) on port {port}…", addrs.len());
+    let timeout = std::time::Duration::from_millis(timeout_ms);
+    let mut found = discover::scan(&addrs, port, concurrency, timeout);
+    if found.is_empty() {
+        println!("No SSH servers found in {cidr}.");
+        return Ok(());
+    }
+    discover::add_fingerprints(&mut found, port);
+
+    let known = hosts::list_all_hosts().unwrap_or_default();
+    let saved_as = |ip: &str| {
+        known
+            .iter()
+            .find(|h| h.hostname.as_deref() == Some(ip))
+            .map(|h| h.alias.clone())
+    };
+    println!(
+        "{:>3}  {:<15}  {:>7}  {:<40}  HOST KEY",
+        "#", "ADDRESS", "LATENCY", "BANNER"
+    );
+    for (i, f) in found.iter().enumerate() {
+        let ip = f.ip.to_string();
+        let note = saved_as(&ip)
+            .map(|a| format!("  {}", color::paint("2", format!("saved as {a}"))))
+            .unwrap_or_default();
+        println!(
+            "{:>3}  {:<15}  {:>5}ms  {:<40}  {}{note}",
+            i + 1,
+            ip,
+            f.latency.as_millis(),
+            f.banner.as_deref().unwrap_or("-"),
+            f.fingerprint.as_deref().unwrap_or("-"),
+        );
+    }
+    if !io::stdin().is_terminal() {
+        return Ok(());
+    }
+
+    eprint!("Save which? (e.g. 1 3-5, all; Enter to skip): ");
+    io::stderr().flush()?;
+    let Some(line) = io::stdin().lock().lines().next().transpose()? else {
+        return Ok(());
+    };
+    let path = hosts_toml_path()?;
+    for i in discover::parse_selection(&line, found.len())? {
+        let ip = found[i].ip.to_string();
+        let default = ip.replace('.', "-");
+        eprint!("Alias for {ip} (Enter = {default}): ");
+        io::stderr().flush()?;
+        let alias = io::stdin().lock().lines().next().transpose()?.unwrap_or_default();
+        let alias = if alias.trim().is_empty() {
+            default
+        } else {
+            alias.trim().to_string()
+        };
+        let entry = hosts_toml::HostEntry {
+            hostname: ip,
+            user: user.clone(),
+            port: (port != 22).then_some(port),
+            tags: tags.clone(),
+            ..Default::default()
+        };
+        match hosts_toml::add_host(&path, &alias, entry) {
+            Ok(()) => println!("Added host '{alias}'"),
+            Err(e) => eprintln!("oken: {e}"),
         }
-        // Non-flag positional: skip (it's the host or an unrecognised arg)
     }
-    result
+    Ok(())
+}
+
+type SyncSource = (
+    &'static str,
+    Vec<cloud::Machine>,
+    Option<cloud::Fallback>,
+    cli::AddressChoice,
+    Option<String>,
+);
+
+fn sync_api(provider: cloud::ApiProvider, api: cli::ApiSync) -> Result<SyncSource> {
+    Ok((provider.name(), provider.machines(api.token)?, None, api.ip, api.user))
 }
 
-fn run_host_command(cmd: HostCommand) -> Result<()> {
+fn run_host_command(cmd: HostCommand, cfg: &oken_config::OkenConfig) -> Result<()> {
     match cmd {
         HostCommand::Add {
             name,
@@ -592,29 +3231,107 @@ fn run_host_command(cmd: HostCommand) -> Result<()> {
             port,
             key,
             tag,
+            record,
+            proxy,
+            ttl,
+            expires,
+            owner,
+            team,
+            from_history,
         } => {
-            let (user, hostname) = if let Some((u, h)) = target.split_once('@') {
-                (Some(u.to_string()), h.to_string())
-            } else {
-                (None, target)
+            let expires = match (ttl, expires) {
+                (Some(ttl), _) => {
+                    let secs = time_utils::parse_ttl(&ttl).with_context(|| {
+                        format!("invalid --ttl '{ttl}' (expected e.g. 90m, 12h, 7d, 2w)")
+                    })?;
+                    Some(time_utils::unix_to_iso8601(daemon::unix_now() + secs))
+                }
+                (None, Some(date)) => {
+                    if time_utils::iso8601_to_unix(&date).is_none() {
+                        anyhow::bail!(
+                            "invalid --expires '{date}' (expected YYYY-MM-DD or \
+                             YYYY-MM-DDTHH:MM:SSZ)"
+                        );
+                    }
+                    Some(date)
+                }
+                (None, None) => None,
             };
-
-            let entry = hosts_toml::HostEntry {
-                hostname,
-                user,
-                port,
-                identity_file: key.map(|p| p.to_string_lossy().to_string()),
-                tags: tag,
+            let picked = match (name, target) {
+                _ if from_history => wizard::from_history(tag)?,
+                (Some(name), Some(target)) => vec![wizard::Answers {
+                    name,
+                    target,
+                    port,
+                    key: key.map(|p| p.to_string_lossy().to_string()),
+                    tags: tag,
+                }],
+                (name, _) => wizard::run(name, port, key, tag)?.into_iter().collect(),
             };
 
             let path = hosts_toml_path()?;
-            hosts_toml::add_host(&path, &name, entry)?;
-            println!("Added host '{name}'");
+            for wizard::Answers {
+                name,
+                target,
+                port,
+                key,
+                tags,
+            } in picked
+            {
+                let (user, hostname) = if let Some((u, h)) = target.split_once('@') {
+                    (Some(u.to_string()), h.to_string())
+                } else {
+                    (None, target)
+                };
+
+                let entry = hosts_toml::HostEntry {
+                    hostname,
+                    user,
+                    port,
+                    identity_file: key,
+                    tags,
+                    record,
+                    proxy: proxy.clone(),
+                    expires: expires.clone(),
+                    owner: owner.clone(),
+                    team: team.clone(),
+                    ..Default::default()
+                };
+
+                hosts_toml::add_host(&path, &name, entry)?;
+                match &expires {
+                    Some(at) => println!("Added host '{name}' (expires {at})"),
+                    None => println!("Added host '{name}'"),
+                }
+            }
             Ok(())
         }
 
-        HostCommand::List => {
+        HostCommand::List {
+            expired,
+            owner,
+            team,
+            format,
+        } => {
             let mut all = hosts::list_all_hosts().unwrap_or_default();
+            let matches = |value: &Option<String>, wanted: &Option<String>| match wanted {
+                Some(w) => value.as_ref().is_some_and(|v| v.eq_ignore_ascii_case(w)),
+                None => true,
+            };
+            all.retain(|h| matches(&h.owner, &owner) && matches(&h.team, &team));
+            let now = daemon::unix_now();
+            let expired_count = all.iter().filter(|h| h.is_expired(now)).count();
+            all.retain(|h| h.is_expired(now) == expired);
+            if format != ListFormat::Table {
+                return print_hosts(&all, format);
+            }
+            if expired {
+                return list_expired_hosts(&all);
+            }
+            if all.is_empty() && (owner.is_some() || team.is_some()) {
+                println!("No hosts match --owner/--team.");
+                return Ok(());
+            }
             if all.is_empty() {
                 println!("No hosts found. Add one with: oken host add <name> <user@host>");
                 return Ok(());
@@ -639,11 +3356,31 @@ fn run_host_command(cmd: HostCommand) -> Result<()> {
                 .unwrap_or(6)
                 .max(6);
 
+            let rtts = cfg.latency_probe.then(|| {
+                let cache = latency::load();
+                latency::maybe_refresh(&cache);
+                cache.rtt_ms
+            });
+            let rtt_header = if rtts.is_some() { format!("{:>6}  ", "RTT") } else { String::new() };
+            // Only inventories that record owners get the column
+            let owners: Vec<String> = all.iter().map(describe_owner).collect();
+            let any_owner = all.iter().any(|h| h.owner.is_some() || h.team.is_some());
+            let owner_w = if any_owner {
+                owners.iter().map(|o| o.chars().count()).max().unwrap_or(0).max(5)
+            } else {
+                0
+            };
+            let owner_header = if owner_w > 0 {
+                format!("{:<owner_w$}  ", "OWNER")
+            } else {
+                String::new()
+            };
+
             println!(
-                "{:<name_w$}  {:<target_w$}  {:>5}  {:<16}  {}",
-                "NAME", "TARGET", "PORT", "TAGS", "SOURCE"
+                "{:<name_w$}  {:<target_w$}  {:>5}  {rtt_header}{:<16}  {owner_header}SOURCE",
+                "NAME", "TARGET", "PORT", "TAGS"
             );
-            for h in &all {
+            for (h, owner) in all.iter().zip(&owners) {
                 let target = match (&h.user, &h.hostname) {
                     (Some(u), Some(hn)) => format!("{u}@{hn}"),
                     (None, Some(hn)) => hn.clone(),
@@ -656,43 +3393,69 @@ fn run_host_command(cmd: HostCommand) -> Result<()> {
                     h.tags.join(", ")
                 };
                 let source = if h.from_ssh_config {
-                    "\x1b[2mssh config\x1b[0m"
+                    color::paint("2", "ssh config")
+                } else if let Some(provider) = &h.managed_by {
+                    color::paint("2", provider)
                 } else {
-                    ""
+                    String::new()
+                };
+                let rtt = rtts
+                    .as_ref()
+                    .map(|l| format!("{:>6}  ", latency::format(l.get(&h.alias).copied())))
+                    .unwrap_or_default();
+                let owner = if owner_w > 0 {
+                    format!("{owner:<owner_w$}  ")
+                } else {
+                    String::new()
                 };
                 println!(
-                    "{:<name_w$}  {:<target_w$}  {:>5}  {:<16}  {}",
+                    "{:<name_w$}  {:<target_w$}  {:>5}  {rtt}{:<16}  {owner}{}",
                     h.alias, target, port, tags, source
                 );
             }
+            if expired_count > 0 {
+                let hint = "see oken host list --expired";
+                let note = format!("{expired_count} expired host(s) hidden; {hint}");
+                println!("{}", color::paint("2", note));
+            }
             Ok(())
         }
 
+        HostCommand::Show { name } => show_host(&name, cfg),
+
+        HostCommand::Prune { months, no_dns } => prune_hosts(months, !no_dns),
+
+        HostCommand::Dedupe => dedupe_hosts(cfg),
+
         HostCommand::Remove { name } => {
             let all = hosts::list_all_hosts().unwrap_or_default();
-            if let Some(h) = all.iter().find(|h| h.alias == name) {
-                if h.from_ssh_config {
-                    eprintln!("'{name}' is managed by ~/.ssh/config — remove it there instead.");
-                    std::process::exit(1);
-                }
+            if let Some(h) = all.iter().find(|h| h.alias == name)
+                && h.from_ssh_config
+            {
+                eprintln!("'{name}' is managed by ~/.ssh/config — remove it there instead.");
+                std::process::exit(1);
             }
             let path = hosts_toml_path()?;
             hosts_toml::remove_host(&path, &name)?;
             println!("Removed host '{name}'");
+            if let Some(h) = all.iter().find(|h| h.alias == name) {
+                offer_forget_host_keys(h, cfg);
+            }
             Ok(())
         }
 
         HostCommand::Edit { name } => {
             if let Some(ref n) = name {
                 let all = hosts::list_all_hosts().unwrap_or_default();
-                if let Some(h) = all.iter().find(|h| &h.alias == n) {
-                    if h.from_ssh_config {
-                        eprintln!("'{n}' is managed by ~/.ssh/config — edit that file instead.");
-                        std::process::exit(1);
-                    }
+                if let Some(h) = all.iter().find(|h| &h.alias == n)
+                    && h.from_ssh_config
+                {
+                    eprintln!("'{n}' is managed by ~/.ssh/config — edit that file instead.");
+                    std::process::exit(1);
                 }
             }
             let path = hosts_toml_path()?;
+            config::create_parent(&path)?;
             let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
             let status = std::process::Command::new(&editor).arg(&path).status()?;
             if !status.success() {
@@ -702,3 +3465,374 @@ fn run_host_command(cmd: HostCommand) -> Result<()> {
         }
     }
 }
+
+/// `alice (platform)`, `alice`, `(platform)`, or `-` for a host nobody claimed.
+fn describe_owner(h: &hosts::Host) -> String {
+    match (&h.owner, &h.team) {
+        (Some(owner), Some(team)) => format!("{owner} ({team})"),
+        (Some(owner), None) => owner.clone(),
+        (None, Some(team)) => format!("({team})"),
+        (None, None) => "-".to_string(),
+    }
+}
+
+/// `host list --format json|tsv`: every field, for scripts.
+fn print_hosts(hosts: &[hosts::Host], format: ListFormat) -> Result<()> {
+    #[derive(serde::Serialize)]
+    struct Listed<'a> {
+        #[serde(flatten)]
+        host: &'a hosts::Host,
+        source: &'static str,
+    }
+    if format == ListFormat::Json {
+        let listed: Vec<Listed> = hosts
+            .iter()
+            .map(|host| Listed {
+                host,
+                source: host.source(),
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&listed)?);
+        return Ok(());
+    }
+    println!(
+        "alias\tuser\thostname\tport\ttags\tsource\tmanaged_by\tidentity_file\texpires\thost_key\t\
+         owner\tteam\taliases"
+    );
+    for h in hosts {
+        let fields = [
+            h.alias.clone(),
+            h.user.clone().unwrap_or_default(),
+            h.hostname.clone().unwrap_or_default(),
+            h.port.map(|p| p.to_string()).unwrap_or_default(),
+            h.tags.join(","),
+            h.source().to_string(),
+            h.managed_by.clone().unwrap_or_default(),
+            h.identity_file.clone().unwrap_or_default(),
+            h.expires.clone().unwrap_or_default(),
+            h.host_key.clone().unwrap_or_default(),
+            h.owner.clone().unwrap_or_default(),
+            h.team.clone().unwrap_or_default(),
+            h.aliases.join(","),
+        ];
+        println!("{}", tsv_row(&fields));
+    }
+    Ok(())
+}
+
+/// Tab-separated fields, with tabs and newlines inside them turned into spaces.
+fn tsv_row(fields: &[String]) -> String {
+    let clean: Vec<String> = fields.iter().map(|f| f.replace(['\t', '\n'], " ")).collect();
+    clean.join("\t")
+}
+
+/// `oken __complete hosts|tags`: what the completion scripts offer, one per
+/// line. Tags include those on tunnels, since `tunnel start --tag` takes them.
+fn print_completions(what: &str) -> Result<()> {
+    let now = daemon::unix_now();
+    let all = hosts::list_all_hosts().unwrap_or_default();
+    let live = all.iter().filter(|h| !h.is_expired(now));
+    let words: std::collections::BTreeSet<String> = match what {
+        "hosts" => live
+            .flat_map(|h| std::iter::once(&h.alias).chain(&h.aliases).cloned())
+            .collect(),
+        "tags" => {
+            let tunnels = tunnels::load_tunnels(&tunnels_toml_path()?).unwrap_or_default();
+            live.flat_map(|h| h.tags.iter())
+                .chain(tunnels.values().flat_map(|t| t.tags.iter()))
+                .cloned()
+                .collect()
+        }
+        other => anyhow::bail!("unknown completion list '{other}' (expected hosts or tags)"),
+    };
+    for word in words {
+        println!("{word}");
+    }
+    Ok(())
+}
+
+/// `oken tag list`: each tag with the (unexpired) hosts that carry it.
+fn list_tags(format: ListFormat) -> Result<()> {
+    #[derive(serde::Serialize)]
+    struct Tag<'a> {
+        tag: &'a str,
+        hosts: Vec<&'a str>,
+    }
+    let all = hosts::list_all_hosts()?;
+    let now = daemon::unix_now();
+    let mut by_tag: std::collections::BTreeMap<&str, Vec<&str>> = Default::default();
+    for h in all.iter().filter(|h| !h.is_expired(now)) {
+        for tag in &h.tags {
+            by_tag.entry(tag).or_default().push(&h.alias);
+        }
+    }
+    match format {
+        ListFormat::Json => {
+            let tags: Vec<Tag> =
+                by_tag.into_iter().map(|(tag, hosts)| Tag { tag, hosts }).collect();
+            println!("{}", serde_json::to_string_pretty(&tags)?);
+        }
+        ListFormat::Tsv => {
+            println!("tag\tcount\thosts");
+            for (tag, hosts) in by_tag {
+                let fields = [tag.to_string(), hosts.len().to_string(), hosts.join(",")];
+                println!("{}", tsv_row(&fields));
+            }
+        }
+        ListFormat::Table if by_tag.is_empty() => {
+            println!("No tags yet. Add some with: oken host add <name> <user@host> --tag <tag>");
+        }
+        ListFormat::Table => {
+            let tag_w = by_tag.keys().map(|t| t.len()).max().unwrap_or(3).max(3);
+            println!("{:<tag_w$}  COUNT  HOSTS", "TAG");
+            for (tag, hosts) in by_tag {
+                println!("{tag:<tag_w$}  {:>5}  {}", hosts.len(), hosts.join(", "));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `oken host list --expired`: show the expired hosts and offer to remove them.
+fn list_expired_hosts(expired: &[hosts::Host]) -> Result<()> {
+    if expired.is_empty() {
+        println!("No expired hosts.");
+        return Ok(());
+    }
+    let name_w = expired.iter().map(|h| h.alias.len()).max().unwrap_or(4).max(4);
+    println!("{:<name_w$}  {:<21}  TARGET", "NAME", "EXPIRED");
+    for h in expired {
+        println!(
+            "{:<name_w$}  {:<21}  {}",
+            h.alias,
+            h.expires.as_deref().unwrap_or("-"),
+            h.hostname.as_deref().unwrap_or("-")
+        );
+    }
+    if !io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let question = format!("Remove {} expired host(s) from hosts.toml?", expired.len());
+    if !prompt::confirm(&question, false)? {
+        return Ok(());
+    }
+    let names: Vec<String> = expired.iter().map(|h| h.alias.clone()).collect();
+    hosts_toml::remove_hosts(&hosts_toml_path()?, &names)?;
+    println!("Removed {} host(s)", names.len());
+    Ok(())
+}
+
+/// `oken host prune`: list hosts.toml hosts that look unused and offer to
+/// archive or delete them. Provider-managed and ssh config hosts are left
+/// to their sources.
+fn prune_hosts(months: u64, dns: bool) -> Result<()> {
+    let mut candidates = hosts::list_all_hosts()?;
+    candidates.retain(|h| !h.from_ssh_config && h.managed_by.is_none());
+    let recent: std::collections::HashMap<String, String> = history::last_connected_hosts()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|r| (r.alias, r.last_connected))
+        .collect();
+    if dns {
+        eprintln!("Checking {} host(s)…", candidates.len());
+    }
+    let stale = stale::find_stale(&candidates, &recent, daemon::unix_now(), months, dns);
+    if stale.is_empty() {
+        println!("No stale hosts.");
+        return Ok(());
+    }
+    let name_w = stale.iter().map(|s| s.alias.len()).max().unwrap_or(4).max(4);
+    println!("{:>3}  {:<name_w$}  WHY", "#", "NAME");
+    for (i, s) in stale.iter().enumerate() {
+        println!("{:>3}  {:<name_w$}  {}", i + 1, s.alias, s.reasons.join("; "));
+    }
+    if !io::stdin().is_terminal() {
+        return Ok(());
+    }
+
+    let path = hosts_toml_path()?;
+    let mut left: Vec<usize> = (0..stale.len()).collect();
+    for (verb, done) in [("Archive", "Archived"), ("Delete", "Deleted")] {
+        if left.is_empty() {
+            break;
+        }
+        eprint!("{verb} which? (e.g. 1 3-5, all; Enter to skip): ");
+        io::stderr().flush()?;
+        let Some(line) = io::stdin().lock().lines().next().transpose()? else {
+            return Ok(());
+        };
+        let picked: Vec<usize> = if line.trim().eq_ignore_ascii_case("all") {
+            left.clone()
+        } else {
+            discover::parse_selection(&line, stale.len())?
+                .into_iter()
+                .filter(|i| left.contains(i))
+                .collect()
+        };
+        if picked.is_empty() {
+            continue;
+        }
+        let names: Vec<String> = picked.iter().map(|&i| stale[i].alias.clone()).collect();
+        if verb == "Archive" {
+            hosts_toml::archive_hosts(&path, &names)?;
+        } else {
+            hosts_toml::remove_hosts(&path, &names)?;
+        }
+        println!("{done} {}", names.join(", "));
+        left.retain(|i| !picked.contains(i));
+    }
+    Ok(())
+}
+
+/// `oken host dedupe`: find hosts that reach the same user, machine and
+/// port, show each group side by side, and merge the group's hosts.toml
+/// entries into the one picked to keep.
+fn dedupe_hosts(cfg: &oken_config::OkenConfig) -> Result<()> {
+    let now = daemon::unix_now();
+    let mut all = hosts::list_all_hosts()?;
+    all.retain(|h| !h.is_expired(now));
+    eprintln!("Checking {} host(s)…", all.len());
+    let destinations = dedupe::resolve(&all, &cfg.proxies);
+    let groups = dedupe::find_duplicates(&destinations);
+    if groups.is_empty() {
+        println!("No duplicate hosts.");
+        return Ok(());
+    }
+    let recent: std::collections::HashMap<String, String> = history::last_connected_hosts()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|r| (r.alias, r.last_connected))
+        .collect();
+    let path = hosts_toml_path()?;
+    let saved = hosts_toml::load_hosts_toml(&path)?;
+    for group in &groups {
+        let members: Vec<&hosts::Host> = group.iter().map(|&i| &all[i]).collect();
+        if let Some(dest) = &destinations[group[0]] {
+            println!("\n{} {}", color::paint("1", "Same destination:"), dest.describe());
+        }
+        print_side_by_side(&members, &recent);
+        if !io::stdin().is_terminal() {
+            continue;
+        }
+        eprint!("Keep which? (1-{}, Enter to skip): ", members.len());
+        io::stderr().flush()?;
+        let Some(line) = io::stdin().lock().lines().next().transpose()? else {
+            return Ok(());
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.parse::<usize>().ok().and_then(|n| members.get(n.wrapping_sub(1))) {
+            Some(keep) => merge_duplicates(&path, &saved, keep, &members)?,
+            None => eprintln!("Skipped: '{line}' is not a number from 1 to {}", members.len()),
+        }
+    }
+    Ok(())
+}
+
+/// The hosts of a duplicate group in columns, one row per setting.
+fn print_side_by_side(
+    members: &[&hosts::Host],
+    recent: &std::collections::HashMap<String, String>,
+) {
+    const LABELS: [&str; 7] = ["", "source", "address", "key", "tags", "owner", "last used"];
+    let or_dash = |s: Option<String>| s.filter(|s| !s.is_empty()).unwrap_or_else(|| "-".into());
+    let columns: Vec<[String; 7]> = members
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            let user = h.user.as_deref().map(|u| format!("{u}@")).unwrap_or_default();
+            let port = h.port.map(|p| format!(":{p}")).unwrap_or_default();
+            let source = match &h.managed_by {
+                Some(provider) => format!("managed.{provider}"),
+                None => h.source().to_string(),
+            };
+            [
+                format!("{}) {}", i + 1, h.alias),
+                source,
+                or_dash(h.hostname.as_ref().map(|hostname| format!("{user}{hostname}{port}"))),
+                or_dash(h.identity_file.clone()),
+                or_dash(Some(h.tags.join(", "))),
+                describe_owner(h),
+                match recent.get(&h.alias) {
+                    Some(at) => at.get(..10).unwrap_or(at).to_string(),
+                    None => "never".to_string(),
+                },
+            ]
+        })
+        .collect();
+    for (row, label) in LABELS.iter().enumerate() {
+        let mut line = format!("  {label:<11}");
+        for column in &columns {
+            let width = column.iter().map(|c| c.chars().count()).max().unwrap_or(0) + 3;
+            line.push_str(&format!("{:<width$}", column[row]));
+        }
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Merge the hosts.toml `[hosts]` entries of a duplicate group into one under
+/// `keep`'s alias, moving the others' history and tunnels over to it. Hosts
+/// from ~/.ssh/config or a provider sync stay where they are.
+fn merge_duplicates(
+    path: &std::path::Path,
+    saved: &std::collections::HashMap<String, hosts_toml::HostEntry>,
+    keep: &hosts::Host,
+    members: &[&hosts::Host],
+) -> Result<()> {
+    let editable: Vec<String> = members
+        .iter()
+        .filter(|h| !h.from_ssh_config && h.managed_by.is_none())
+        .map(|h| h.alias.clone())
+        .collect();
+    let base = if editable.contains(&keep.alias) { Some(&keep.alias) } else { editable.first() };
+    let Some(entry) = base.and_then(|alias| saved.get(alias)) else {
+        eprintln!("None of these are in hosts.toml; merge them where they're defined.");
+        return Ok(());
+    };
+    let others: Vec<hosts_toml::HostEntry> = editable
+        .iter()
+        .filter(|alias| Some(*alias) != base)
+        .filter_map(|alias| saved.get(alias).cloned())
+        .collect();
+    let gone: Vec<String> = editable.iter().filter(|a| **a != keep.alias).cloned().collect();
+    if !gone.is_empty() {
+        let merged = dedupe::merge_entries(entry.clone(), &others);
+        hosts_toml::merge_hosts(path, &keep.alias, merged, &editable)?;
+        for alias in &gone {
+            if let Err(e) = history::rename_alias(alias, &keep.alias) {
+                log::warn!("could not move the history of '{alias}': {e:#}");
+            }
+        }
+        let tunnels_path = tunnels_toml_path()?;
+        for (name, tunnel) in tunnels::load_tunnels(&tunnels_path).unwrap_or_default() {
+            // A plain `host` can name a saved host as well as `host_alias`
+            let via_alias = tunnel.host_alias.as_ref().is_some_and(|a| gone.contains(a));
+            let via_host = tunnel.host_alias.is_none() && gone.contains(&tunnel.host);
+            if !via_alias && !via_host {
+                continue;
+            }
+            tunnels::update_tunnel(&tunnels_path, &name, |t| {
+                if via_alias {
+                    t.host_alias = Some(keep.alias.clone());
+                } else {
+                    t.host = keep.alias.clone();
+                }
+            })?;
+            println!("Tunnel '{name}' now goes through '{}'", keep.alias);
+        }
+        println!("Merged {} into '{}'", gone.join(", "), keep.alias);
+    }
+    for h in members.iter().filter(|h| !editable.contains(&h.alias)) {
+        let alias = &h.alias;
+        if h.alias == keep.alias {
+            println!("'{alias}' in hosts.toml now takes over from {}", h.source());
+        } else if let Some(provider) = &h.managed_by {
+            println!("'{alias}' comes from `oken cloud sync {provider}` and stays");
+        } else {
+            println!("'{alias}' is still in ~/.ssh/config; remove its Host block there if unused");
+        }
+    }
+    Ok(())
+}
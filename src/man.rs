@@ -0,0 +1,195 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Arg, Command, CommandFactory};
+
+use crate::cli::Cli;
+
+/// `oken man`: print oken(1) to stdout, or with `--dir` write it along with
+/// an `oken-<command>(1)` page for every subcommand, for packagers.
+pub fn run(dir: Option<PathBuf>) -> Result<()> {
+    let mut cmd = Cli::command();
+    cmd.build();
+    let Some(dir) = dir else {
+        print!("{}", render(&cmd, "oken"));
+        return Ok(());
+    };
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("could not create {}", dir.display()))?;
+    let mut pages = Vec::new();
+    collect(&cmd, "oken", &mut pages);
+    for (name, page) in &pages {
+        let path = dir.join(format!("{name}.1"));
+        std::fs::write(&path, page)
+            .with_context(|| format!("could not write {}", path.display()))?;
+    }
+    println!("Wrote {} man pages to {}", pages.len(), dir.display());
+    Ok(())
+}
+
+/// Commands shown in `oken --help`, without clap's `help`.
+fn subcommands(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands().filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+}
+
+fn collect(cmd: &Command, name: &str, pages: &mut Vec<(String, String)>) {
+    pages.push((name.to_string(), render(cmd, name)));
+    for sub in subcommands(cmd) {
+        collect(sub, &format!("{name}-{}", sub.get_name()), pages);
+    }
+}
+
+fn render(cmd: &Command, name: &str) -> String {
+    let mut page = format!(
+        ".TH {} 1 \"\" \"oken {}\" \"User Commands\"\n",
+        name.to_uppercase().replace('-', "\\-"),
+        env!("CARGO_PKG_VERSION")
+    );
+    let about = cmd.get_about().map(|a| a.to_string()).unwrap_or_default();
+    let _ = writeln!(page, ".SH NAME\n{} \\- {}", escape(name), escape(&about));
+
+    let usage = cmd.clone().render_usage().to_string();
+    let usage = usage.trim_start_matches("Usage:").trim();
+    let _ = writeln!(page, ".SH SYNOPSIS\n.B {}", escape(usage));
+
+    if let Some(long) = cmd.get_long_about() {
+        let _ = writeln!(page, ".SH DESCRIPTION\n{}", escape(&long.to_string()));
+    } else if !about.is_empty() {
+        let _ = writeln!(page, ".SH DESCRIPTION\n{}", escape(&about));
+    }
+
+    let args: Vec<&Arg> = cmd.get_arguments().filter(|a| !a.is_hide_set()).collect();
+    let (positionals, options): (Vec<&Arg>, Vec<&Arg>) =
+        args.into_iter().partition(|a| a.is_positional());
+    if !positionals.is_empty() {
+        page.push_str(".SH ARGUMENTS\n");
+        for arg in positionals {
+            let _ = writeln!(page, ".TP\n\\fI{}\\fR", escape(&value_name(arg)));
+            page.push_str(&arg_help(arg));
+        }
+    }
+    if !options.is_empty() {
+        page.push_str(".SH OPTIONS\n");
+        for arg in options {
+            let _ = writeln!(page, ".TP\n{}", option_heading(arg));
+            page.push_str(&arg_help(arg));
+        }
+    }
+
+    let subs: Vec<&Command> = subcommands(cmd).collect();
+    if !subs.is_empty() {
+        page.push_str(".SH COMMANDS\n");
+        for sub in &subs {
+            let about = sub.get_about().map(|a| a.to_string()).unwrap_or_default();
+            let _ = writeln!(
+                page,
+                ".TP\n\\fB{}\\fR\n{}\nSee \\fB{}\\-{}\\fR(1).",
+                escape(sub.get_name()),
+                escape(&about),
+                escape(name),
+                escape(sub.get_name())
+            );
+        }
+    }
+
+    page.push_str(".SH SEE ALSO\n");
+    if let Some((parent, _)) = name.rsplit_once('-') {
+        let _ = write!(page, "\\fB{}\\fR(1), ", escape(parent));
+    }
+    page.push_str("\\fBssh\\fR(1)\n");
+    page
+}
+
+/// `\fB\-t\fR, \fB\-\-tag\fR \fITAG\fR`
+fn option_heading(arg: &Arg) -> String {
+    let mut names = Vec::new();
+    if let Some(short) = arg.get_short() {
+        names.push(format!("\\fB\\-{short}\\fR"));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+    }
+    let mut heading = names.join(", ");
+    if arg.get_action().takes_values() {
+        let _ = write!(heading, " \\fI{}\\fR", escape(&value_name(arg)));
+    }
+    heading
+}
+
+fn value_name(arg: &Arg) -> String {
+    match arg.get_value_names() {
+        Some(names) => names.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(" "),
+        None => arg.get_id().as_str().to_uppercase(),
+    }
+}
+
+/// The help text, then the choices and default clap would list in `--help`.
+fn arg_help(arg: &Arg) -> String {
+    let mut text = arg
+        .get_long_help()
+        .or(arg.get_help())
+        .map(|h| h.to_string())
+        .unwrap_or_default();
+    let values: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| v.get_name().to_string())
+        .collect();
+    if !arg.get_action().takes_values() {
+        return format!("{}\n", escape(text.trim()));
+    }
+    if !values.is_empty() {
+        let _ = write!(text, " [possible values: {}]", values.join(", "));
+    }
+    let defaults: Vec<String> = arg
+        .get_default_values()
+        .iter()
+        .map(|v| v.to_string_lossy().into_owned())
+        .collect();
+    if !defaults.is_empty() {
+        let _ = write!(text, " [default: {}]", defaults.join(", "));
+    }
+    format!("{}\n", escape(text.trim()))
+}
+
+/// Make text safe for roff: backslashes and hyphens are escaped, and lines
+/// starting with `.` or `'` are not read as requests.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\e")
+        .replace('-', "\\-")
+        .lines()
+        .map(|line| {
+            if line.starts_with(['.', '\'']) {
+                format!("\\&{line}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_roff() {
+        assert_eq!(escape("--tag a\\b"), "\\-\\-tag a\\eb");
+        assert_eq!(escape("first\n.second\n'third"), "first\n\\&.second\n\\&'third");
+    }
+
+    #[test]
+    fn writes_a_page_per_command() {
+        let mut cmd = Cli::command();
+        cmd.build();
+        let mut pages = Vec::new();
+        collect(&cmd, "oken", &mut pages);
+        let names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names.contains(&"oken"));
+        assert!(names.contains(&"oken-host-add"));
+        assert!(!names.iter().any(|n| n.ends_with("-help")));
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::hosts_toml::HostEntry;
+
+/// How to pick hosts out of a JSON document, from a `[mappings.<name>]`
+/// table in config.toml. Every field is a path like `results[*]`,
+/// `attributes.tags.Name` or `resources[?type=='aws_instance'].instances[*]`;
+/// `a || b` uses the first path that yields a value.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Mapping {
+    /// Path to the list of hosts.
+    pub items: String,
+    /// Relative to each item, like the rest.
+    pub alias: String,
+    pub hostname: String,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub port: Option<String>,
+    /// Each path may yield a string or a list of strings.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Endpoint for `oken cloud sync http`.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Path to the next page's URL in each response (e.g. `next`).
+    #[serde(default)]
+    pub next: Option<String>,
+    /// Authorization scheme for `--token` (default `Bearer`).
+    #[serde(default)]
+    pub auth: Option<String>,
+}
+
+impl Mapping {
+    /// Hosts keyed by alias, tagged with `source` plus the mapped tags, and
+    /// how many items were skipped for lacking an alias or hostname.
+    pub fn apply(&self, json: &Value, source: &str) -> (BTreeMap<String, HostEntry>, usize) {
+        let mut hosts = BTreeMap::new();
+        let mut skipped = 0;
+        for item in select(json, &self.items) {
+            let (Some(alias), Some(hostname)) =
+                (first_string(item, &self.alias), first_string(item, &self.hostname))
+            else {
+                skipped += 1;
+                continue;
+            };
+            let mut tags = vec![source.to_string()];
+            for path in &self.tags {
+                for value in select(item, path) {
+                    let values = match value {
+                        Value::Array(list) => list.iter().filter_map(as_string).collect(),
+                        v => as_string(v).into_iter().collect::<Vec<_>>(),
+                    };
+                    for tag in values {
+                        if !tag.is_empty() && !tags.contains(&tag) {
+                            tags.push(tag);
+                        }
+                    }
+                }
+            }
+            let mut alias = slug(&alias);
+            if hosts.contains_key(&alias) {
+                alias = (2..)
+                    .map(|n| format!("{alias}-{n}"))
+                    .find(|a| !hosts.contains_key(a))
+                    .unwrap_or(alias);
+            }
+            // IPAM tools often store addresses as `10.0.0.5/24`
+            let hostname = match hostname.split_once('/') {
+                Some((ip, _)) if ip.parse::<std::net::IpAddr>().is_ok() => ip.to_string(),
+                _ => hostname,
+            };
+            let entry = HostEntry {
+                hostname,
+                user: self.user.as_deref().and_then(|p| first_string(item, p)),
+                port: self
+                    .port
+                    .as_deref()
+                    .and_then(|p| first_string(item, p))
+                    .and_then(|p| p.parse().ok()),
+                tags,
+                ..Default::default()
+            };
+            hosts.insert(alias, entry);
+        }
+        (hosts, skipped)
+    }
+}
+
+/// The first non-empty string (or number) a path yields.
+pub fn first_string(value: &Value, path: &str) -> Option<String> {
+    select(value, path)
+        .into_iter()
+        .filter_map(as_string)
+        .find(|s| !s.is_empty())
+}
+
+fn as_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Evaluate a path against `value`. Supports `a.b`, `[0]`, `[*]` (every
+/// element or object value), `[?a=='v' && b!='w' || c=='x']` filters and
+/// top-level `||` alternatives.
+pub fn select<'a>(value: &'a Value, path: &str) -> Vec<&'a Value> {
+    for alternative in split_top_level(path) {
+        let found: Vec<&Value> = eval(value, alternative.trim())
+            .into_iter()
+            .filter(|v| !v.is_null() && v.as_str() != Some(""))
+            .collect();
+        if !found.is_empty() {
+            return found;
+        }
+    }
+    Vec::new()
+}
+
+/// Split on `||` outside of `[...]`.
+fn split_top_level(path: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let bytes = path.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' => depth += 1,
+            b']' => depth -= 1,
+            b'|' if depth == 0 && bytes.get(i + 1) == Some(&b'|') => {
+                parts.push(&path[start..i]);
+                start = i + 2;
+                i += 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    parts.push(&path[start..]);
+    parts
+}
+
+fn eval<'a>(root: &'a Value, path: &str) -> Vec<&'a Value> {
+    let mut current = vec![root];
+    let mut rest = path;
+    while !rest.is_empty() {
+        rest = rest.trim_start_matches('.');
+        if let Some(inner) = rest.strip_prefix('[') {
+            let Some(end) = inner.find(']') else {
+                return Vec::new();
+            };
+            let selector = &inner[..end];
+            rest = &inner[end + 1..];
+            current = current
+                .into_iter()
+                .flat_map(|v| apply_selector(v, selector))
+                .collect();
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let key = &rest[..end];
+            rest = &rest[end..];
+            current = current.into_iter().filter_map(|v| v.get(key)).collect();
+        }
+    }
+    current
+}
+
+fn apply_selector<'a>(value: &'a Value, selector: &str) -> Vec<&'a Value> {
+    let elements: Vec<&Value> = match value {
+        Value::Array(list) => list.iter().collect(),
+        Value::Object(map) => map.values().collect(),
+        _ => return Vec::new(),
+    };
+    if selector == "*" {
+        return elements;
+    }
+    if let Some(filter) = selector.strip_prefix('?') {
+        return elements
+            .into_iter()
+            .filter(|e| {
+                filter
+                    .split("||")
+                    .any(|alt| alt.split("&&").all(|cond| matches(e, cond)))
+            })
+            .collect();
+    }
+    match (value, selector.parse::<usize>()) {
+        (Value::Array(list), Ok(i)) => list.get(i).into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `key=='value'` (or `!=`) against one element.
+fn matches(element: &Value, condition: &str) -> bool {
+    let (key, expected, equal) = match condition.split_once("!=") {
+        Some((k, v)) => (k, v, false),
+        None => match condition.split_once("==") {
+            Some((k, v)) => (k, v, true),
+            None => return first_string(element, condition.trim()).is_some(),
+        },
+    };
+    let expected = expected.trim().trim_matches(['\'', '"']);
+    let actual = first_string(element, key.trim());
+    (actual.as_deref() == Some(expected)) == equal
+}
+
+/// Lowercase a machine name into an alias: `Web Server #1` -> `web-server-1`.
+pub fn slug(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            out.push(c.to_ascii_lowercase());
+        } else if !out.is_empty() && !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    out.trim_end_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_paths_and_mappings() {
+        let json: Value = serde_json::from_str(
+            r#"{"resources": [
+                {"type": "aws_instance", "name": "web", "instances": [
+                    {"attributes": {"public_ip": "", "private_ip": "10.0.0.5",
+                                    "tags": {"Name": "Web 1", "Role": "web"}}}]},
+                {"type": "aws_s3_bucket", "instances": [{"attributes": {"id": "b"}}]},
+                {"type": "hcloud_server", "instances": [
+                    {"attributes": {"name": "db", "ipv4_address": "5.6.7.8",
+                                    "labels": {"tier": "data"}}}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            select(&json, "resources[?type=='hcloud_server'].instances[0].attributes.name"),
+            [&Value::from("db")]
+        );
+        assert_eq!(select(&json, "resources[?type!='aws_instance']").len(), 2);
+
+        let mapping = Mapping {
+            items: "resources[?type=='aws_instance' || type=='hcloud_server'].instances[*]"
+                .to_string(),
+            alias: "attributes.tags.Name || attributes.name".to_string(),
+            hostname: "attributes.public_ip || attributes.ipv4_address || attributes.private_ip"
+                .to_string(),
+            user: None,
+            port: None,
+            tags: vec!["attributes.tags.Role".to_string(), "attributes.labels[*]".to_string()],
+            url: None,
+            next: None,
+            auth: None,
+        };
+        let (hosts, skipped) = mapping.apply(&json, "terraform");
+        assert_eq!(skipped, 0);
+        assert_eq!(hosts["web-1"].hostname, "10.0.0.5");
+        assert_eq!(hosts["web-1"].tags, ["terraform", "web"]);
+        assert_eq!(hosts["db"].hostname, "5.6.7.8");
+        assert_eq!(hosts["db"].tags, ["terraform", "data"]);
+    }
+}
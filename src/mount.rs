@@ -0,0 +1,155 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::ssh;
+use crate::util;
+
+/// A live sshfs mount, from the system mount table.
+pub struct Mount {
+    /// `user@host:/path` as given to sshfs.
+    pub source: String,
+    pub mountpoint: PathBuf,
+}
+
+/// Returns `~/mnt/<alias>`
+pub fn default_mountpoint(alias: &str) -> Result<PathBuf> {
+    let home = dirs::home_dir().context("could not determine home directory")?;
+    Ok(home.join("mnt").join(alias))
+}
+
+/// Mount `remote_path` (default: the login directory) of the host described
+/// by `ssh_args` at `mountpoint`, creating the directory if needed.
+pub fn mount(ssh_args: &[String], remote_path: &str, mountpoint: &Path) -> Result<()> {
+    if !util::on_path("sshfs") {
+        bail!("sshfs not found on PATH (install sshfs, or macFUSE + sshfs on macOS)");
+    }
+    if list()?.iter().any(|m| m.mountpoint == mountpoint) {
+        bail!("{} is already mounted", mountpoint.display());
+    }
+    std::fs::create_dir_all(mountpoint)
+        .with_context(|| format!("failed to create {}", mountpoint.display()))?;
+    let status = Command::new("sshfs")
+        .args(sshfs_args(ssh_args, remote_path, mountpoint))
+        .status()
+        .context("failed to run sshfs")?;
+    if !status.success() {
+        // Don't leave an empty mountpoint behind
+        let _ = std::fs::remove_dir(mountpoint);
+        bail!("sshfs failed ({status})");
+    }
+    Ok(())
+}
+
+/// Unmount an sshfs mount and remove its (now empty) mountpoint.
+pub fn unmount(mountpoint: &Path) -> Result<()> {
+    let path = mountpoint.to_string_lossy();
+    let status = if cfg!(target_os = "macos") {
+        Command::new("umount").arg(mountpoint).status()
+    } else if util::on_path("fusermount3") {
+        Command::new("fusermount3").args(["-u", &path]).status()
+    } else {
+        Command::new("fusermount").args(["-u", &path]).status()
+    }
+    .context("failed to run the unmount command")?;
+    if !status.success() {
+        bail!("failed to unmount {path} (is a shell or editor still using it?)");
+    }
+    let _ = std::fs::remove_dir(mountpoint);
+    Ok(())
+}
+
+/// Every sshfs mount on the system.
+pub fn list() -> Result<Vec<Mount>> {
+    let output = Command::new("mount")
+        .output()
+        .context("failed to run mount")?;
+    Ok(parse_mounts(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Pick the sshfs entries out of `mount` output. Linux prints
+/// `src on /dir type fuse.sshfs (…)`; macOS prints `src on /dir (macfuse, …)`.
+fn parse_mounts(output: &str) -> Vec<Mount> {
+    output
+        .lines()
+        .filter(|l| l.contains("sshfs") || l.contains("macfuse") || l.contains("osxfuse"))
+        .filter_map(|line| {
+            let (source, rest) = line.split_once(" on ")?;
+            if !source.contains(':') {
+                return None;
+            }
+            let end = rest
+                .find(" type ")
+                .or_else(|| rest.find(" ("))
+                .unwrap_or(rest.len());
+            Some(Mount {
+                source: source.to_string(),
+                mountpoint: PathBuf::from(&rest[..end]),
+            })
+        })
+        .collect()
+}
+
+/// Translate ssh connection args into `sshfs target:path mountpoint -o …`.
+fn sshfs_args(ssh_args: &[String], remote_path: &str, mountpoint: &Path) -> Vec<String> {
+    let target = ssh::extract_target_host_full(ssh_args).unwrap_or_default();
+    let mut out = vec![
+        format!("{target}:{remote_path}"),
+        mountpoint.to_string_lossy().into_owned(),
+        // Survive dropped connections and laptop sleep instead of hanging
+        "-o".to_string(),
+        "reconnect,ServerAliveInterval=15,ServerAliveCountMax=3".to_string(),
+    ];
+    let mut iter = ssh_args.iter();
+    while let Some(arg) = iter.next() {
+        if !ssh::FLAGS_WITH_VALUES.contains(&arg.as_str()) {
+            continue;
+        }
+        let Some(value) = iter.next() else {
+            break;
+        };
+        let option = match arg.as_str() {
+            "-p" => format!("Port={value}"),
+            "-i" => format!("IdentityFile={value}"),
+            "-l" => format!("User={value}"),
+            "-J" => format!("ProxyJump={value}"),
+            "-o" => value.clone(),
+            _ => continue,
+        };
+        out.extend(["-o".to_string(), option]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sshfs_mounts() {
+        let linux = "proc on /proc type proc (rw,relatime)\n\
+                     ubuntu@10.0.0.1: on /home/me/mnt/pw type fuse.sshfs (rw,nosuid)\n";
+        let mounts = parse_mounts(linux);
+        assert_eq!(mounts.len(), 1);
+        assert_eq!(mounts[0].source, "ubuntu@10.0.0.1:");
+        assert_eq!(mounts[0].mountpoint, Path::new("/home/me/mnt/pw"));
+
+        let macos = "deploy@web1:/srv on /Users/me/mnt/web1 (macfuse, nodev, nosuid)\n";
+        assert_eq!(parse_mounts(macos)[0].mountpoint, Path::new("/Users/me/mnt/web1"));
+
+        let args: Vec<String> = ["deploy@web1", "-p", "2222", "-i", "/k"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(
+            sshfs_args(&args, "/srv", Path::new("/m"))[..2],
+            ["deploy@web1:/srv", "/m"]
+        );
+        assert!(sshfs_args(&args, "", Path::new("/m")).ends_with(&[
+            "-o".to_string(),
+            "Port=2222".to_string(),
+            "-o".to_string(),
+            "IdentityFile=/k".to_string(),
+        ]));
+    }
+}
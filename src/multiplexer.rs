@@ -0,0 +1,164 @@
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use oken_core::oken_config::OpenIn;
+use oken_core::util::shell_quote;
+
+/// The terminal multiplexer oken is running inside.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux,
+    Zellij,
+}
+
+/// tmux or zellij, from the variables they set for their panes.
+pub fn current() -> Option<Multiplexer> {
+    let set = |var: &str| std::env::var_os(var).is_some_and(|v| !v.is_empty());
+    if set("TMUX") {
+        Some(Multiplexer::Tmux)
+    } else if set("ZELLIJ") {
+        Some(Multiplexer::Zellij)
+    } else {
+        None
+    }
+}
+
+/// Run `argv` in a new window (zellij: tab) or a split pane named `name`.
+pub fn open(
+    mux: Multiplexer,
+    open_in: OpenIn,
+    name: &str,
+    argv: &[String],
+    dry_run: bool,
+) -> Result<()> {
+    let argv = hold_on_failure(argv);
+    match (mux, open_in) {
+        (_, OpenIn::Here) => unreachable!("connections opened here don't need a multiplexer"),
+        (Multiplexer::Tmux, OpenIn::Window) => {
+            let mut args = strings(&["new-window", "-n", name]);
+            args.extend(argv);
+            tmux(&args, dry_run)
+        }
+        (Multiplexer::Tmux, OpenIn::Split) => {
+            // Panes have no names, but a title shows in the border and pane list
+            let mut args = strings(&["split-window", "-P", "-F", "#{pane_id}"]);
+            args.extend(argv);
+            let pane = if dry_run {
+                print_command("tmux", &args);
+                "{pane}".to_string()
+            } else {
+                tmux_output(&args)?
+            };
+            tmux(&strings(&["select-pane", "-t", &pane, "-T", name]), dry_run)
+        }
+        (Multiplexer::Zellij, OpenIn::Window) => {
+            // new-tab can't take a command, only a layout that runs one
+            let path = std::env::temp_dir().join(format!("oken-tab-{}.kdl", std::process::id()));
+            let layout = path.to_string_lossy();
+            let args = strings(&["action", "new-tab", "--name", name, "--layout", &layout]);
+            if dry_run {
+                print_command("zellij", &args);
+                return Ok(());
+            }
+            std::fs::write(&path, zellij_layout(&argv))
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            let result = run("zellij", &args);
+            let _ = std::fs::remove_file(&path);
+            result
+        }
+        (Multiplexer::Zellij, OpenIn::Split) => {
+            let mut args = strings(&["run", "--name", name, "--close-on-exit", "--"]);
+            args.extend(argv);
+            if dry_run {
+                print_command("zellij", &args);
+                return Ok(());
+            }
+            run("zellij", &args)
+        }
+    }
+}
+
+/// Wrap a command so its pane stays open until Enter when it fails (a
+/// refused prompt, an unreachable host) and the error can still be read.
+/// Multiplexers run a command given as several words directly rather than
+/// through the user's shell, so this is always sh.
+pub fn hold_on_failure(argv: &[String]) -> Vec<String> {
+    let words: Vec<String> = argv.iter().map(|a| shell_quote(a)).collect();
+    let script = format!(
+        "{} || {{ printf \"\\n[oken exited with %s; press Enter to close]\" $?; read _; }}",
+        words.join(" ")
+    );
+    strings(&["sh", "-c", &script])
+}
+
+/// A one-tab zellij layout that runs `argv`, with the usual tab and status bars.
+fn zellij_layout(argv: &[String]) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let args: Vec<String> = argv[1..].iter().map(|a| quote(a)).collect();
+    let lines = [
+        "layout {".to_string(),
+        "    pane size=1 borderless=true {".to_string(),
+        "        plugin location=\"zellij:tab-bar\"".to_string(),
+        "    }".to_string(),
+        format!("    pane command={} close_on_exit=true {{", quote(&argv[0])),
+        format!("        args {}", args.join(" ")),
+        "    }".to_string(),
+        "    pane size=2 borderless=true {".to_string(),
+        "        plugin location=\"zellij:status-bar\"".to_string(),
+        "    }".to_string(),
+        "}".to_string(),
+    ];
+    lines.join("\n") + "\n"
+}
+
+pub fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+fn print_command(program: &str, args: &[String]) {
+    let quoted: Vec<String> = args.iter().map(|a| shell_quote(a)).collect();
+    println!("{program} {}", quoted.join(" "));
+}
+
+fn run(program: &str, args: &[String]) -> Result<()> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("failed to run {program}"))?;
+    if !status.success() {
+        bail!("{program} {} failed ({status})", args[0]);
+    }
+    Ok(())
+}
+
+/// Run a tmux command, or print it for `--dry-run`.
+pub fn tmux(args: &[String], dry_run: bool) -> Result<()> {
+    if dry_run {
+        print_command("tmux", args);
+        return Ok(());
+    }
+    run("tmux", args)
+}
+
+/// Run a tmux command that prints something (`-P -F …`) and return it.
+pub fn tmux_output(args: &[String]) -> Result<String> {
+    let output = Command::new("tmux").args(args).output().context("failed to run tmux")?;
+    if !output.status.success() {
+        bail!("tmux {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_failed_commands_open() {
+        let argv = strings(&["/opt/my oken/oken", "--record", "web-1"]);
+        let cmd = hold_on_failure(&argv);
+        assert_eq!(cmd[..2], ["sh", "-c"]);
+        assert!(cmd[2].starts_with("'/opt/my oken/oken' --record web-1 || {"));
+        assert!(cmd[2].ends_with("$?; read _; }"));
+    }
+}
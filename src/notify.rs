@@ -0,0 +1,122 @@
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static WHEN_FOCUSED: AtomicBool = AtomicBool::new(false);
+
+/// Apply `notify_when_focused` from config.toml.
+pub fn init(when_focused: bool) {
+    WHEN_FOCUSED.store(when_focused, Ordering::Relaxed);
+}
+
+/// Show a desktop notification, unless oken's terminal is known to be the
+/// one in front.
+pub fn send(title: &str, body: &str) {
+    if !wanted(WHEN_FOCUSED.load(Ordering::Relaxed), focused) {
+        log::debug!("not notifying about \"{title}\", the terminal is in front");
+        return;
+    }
+    show(title, body);
+}
+
+/// Show a desktop notification whatever has the focus. Best-effort: does
+/// nothing but log at debug level when no notifier is available (headless
+/// boxes, SSH-in-SSH, etc.). The platform's own notifier is run rather than
+/// talking to the notification service from oken, which would pull D-Bus
+/// into every build for a feature that is off by default.
+pub fn show(title: &str, body: &str) {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification \"{}\" with title \"{}\"",
+            escape_applescript(body),
+            escape_applescript(title)
+        );
+        let mut c = Command::new("osascript");
+        c.args(["-e", &script]);
+        c
+    } else if cfg!(windows) {
+        // No dependency-free notifier on Windows; ring the terminal bell instead.
+        eprint!("\x07");
+        return;
+    } else {
+        let mut c = Command::new("notify-send");
+        c.args(["--app-name=oken", title, body]);
+        c
+    };
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let status = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => log::debug!("{program} failed to show a notification ({status})"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::debug!("no notification shown: {program} is not installed");
+        }
+        Err(e) => log::debug!("failed to run {program}: {e}"),
+    }
+}
+
+/// Whether to notify. The focus check only runs when it matters, and a
+/// terminal whose focus can't be told counts as in the background.
+fn wanted(when_focused: bool, focused: impl FnOnce() -> Option<bool>) -> bool {
+    when_focused || focused() != Some(true)
+}
+
+/// Whether the terminal oken runs in has the focus, where that can be told:
+/// inside tmux, a pane in a window that isn't showing doesn't; on X11, the
+/// terminal's `WINDOWID` (xterm, Konsole, …) is compared with the active
+/// window from xdotool. `None` anywhere else.
+fn focused() -> Option<bool> {
+    if let Ok(pane) = std::env::var("TMUX_PANE") {
+        let output = Command::new("tmux")
+            .args(["display-message", "-p", "-t", &pane])
+            .arg("#{session_attached} #{window_active}")
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        // A visible pane says nothing about whether the terminal tmux is in has the focus
+        return tmux_showing(&String::from_utf8_lossy(&output.stdout)).filter(|showing| !showing);
+    }
+    let window: u64 = std::env::var("WINDOWID").ok()?.parse().ok()?;
+    std::env::var_os("DISPLAY")?;
+    let output = Command::new("xdotool")
+        .arg("getactivewindow")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let active: u64 = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+    Some(active == window)
+}
+
+/// Whether tmux's `#{session_attached} #{window_active}` says the pane is on
+/// screen somewhere.
+fn tmux_showing(output: &str) -> Option<bool> {
+    match output.split_whitespace().collect::<Vec<_>>()[..] {
+        [attached, active] => Some(attached != "0" && active == "1"),
+        _ => None,
+    }
+}
+
+fn escape_applescript(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notifies_unless_the_terminal_has_the_focus() {
+        assert!(wanted(false, || Some(false)));
+        assert!(wanted(false, || None));
+        assert!(!wanted(false, || Some(true)));
+        assert!(wanted(true, || unreachable!("no need to check the focus")));
+
+        assert_eq!(tmux_showing("1 1\n"), Some(true));
+        assert_eq!(tmux_showing("1 0\n"), Some(false));
+        assert_eq!(tmux_showing("0 1\n"), Some(false));
+        assert_eq!(tmux_showing(""), None);
+    }
+}
@@ -1,4 +1,9 @@
-use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use toml_edit::DocumentMut;
 
 fn default_reconnect() -> bool {
     true
@@ -9,14 +14,101 @@ fn default_retries() -> u32 {
 fn default_delay() -> u64 {
     5
 }
+fn default_max_delay() -> u64 {
+    60
+}
 fn default_keepalive() -> u32 {
     60
 }
+fn default_notify_after() -> u64 {
+    300
+}
 fn default_danger_tags() -> Vec<String> {
     vec!["prod".to_string(), "production".to_string()]
 }
+fn default_host_key_review() -> bool {
+    true
+}
+fn default_update_check() -> bool {
+    true
+}
+fn default_update_interval() -> u64 {
+    24
+}
+
+/// How a danger-tagged connection must be confirmed.
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DangerConfirmation {
+    /// Answer `y` to a `Continue? [y/N]` prompt.
+    #[default]
+    YesNo,
+    /// Type the host's alias exactly.
+    TypeAlias,
+}
+
+impl DangerConfirmation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::YesNo => "yes-no",
+            Self::TypeAlias => "type-alias",
+        }
+    }
+}
+
+/// Which releases the update check and `oken self-update` consider.
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateChannel {
+    /// Full releases only.
+    #[default]
+    Stable,
+    /// Pre-releases (`v0.4.0-beta.1`) too.
+    Beta,
+}
+
+impl UpdateChannel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stable => "stable",
+            Self::Beta => "beta",
+        }
+    }
+}
+
+/// Where a host picked in the picker opens when oken runs inside tmux or zellij.
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum OpenIn {
+    /// Take over the current pane.
+    #[default]
+    Here,
+    /// A new window (a tab in zellij) named after the alias.
+    Window,
+    /// A new pane split off the current one.
+    Split,
+}
+
+impl OpenIn {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Here => "here",
+            Self::Window => "window",
+            Self::Split => "split",
+        }
+    }
+}
 
-#[derive(Deserialize)]
+/// A `[[danger_rules]]` entry: a glob matched against host tags and aliases.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct DangerRule {
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Extra line shown in the warning banner, e.g. "Change ticket required".
+    pub message: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct OkenConfig {
     #[serde(default = "default_reconnect")]
     pub reconnect: bool,
@@ -24,10 +116,186 @@ pub struct OkenConfig {
     pub reconnect_retries: u32,
     #[serde(default = "default_delay")]
     pub reconnect_delay_secs: u64,
+    #[serde(default = "default_max_delay")]
+    pub reconnect_max_delay_secs: u64,
+    /// Probe the ssh port and only relaunch ssh once the network is back.
+    #[serde(default)]
+    pub reconnect_wait_for_network: bool,
     #[serde(default = "default_keepalive")]
     pub keepalive_interval: u32,
     #[serde(default = "default_danger_tags")]
     pub danger_tags: Vec<String>,
+    #[serde(default)]
+    pub danger_rules: Vec<DangerRule>,
+    #[serde(default)]
+    pub danger_confirmation: DangerConfirmation,
+    /// Hosts carrying any of these tags are always recorded.
+    #[serde(default)]
+    pub record_tags: Vec<String>,
+    /// Tags whose sessions start in safe mode, as with `--safe`.
+    #[serde(default)]
+    pub safe_tags: Vec<String>,
+    /// Extra regexes for secrets to hide in audit targets and recordings.
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+    /// Require a reason and force recording for danger-tagged hosts.
+    #[serde(default)]
+    pub compliance: bool,
+    /// Old spelling of `hooks.on_connect`, moved there on loading.
+    #[serde(default, skip_serializing)]
+    pub pre_connect: Vec<String>,
+    /// Old spelling of `hooks.on_disconnect`, moved there on loading.
+    #[serde(default, skip_serializing)]
+    pub post_disconnect: Vec<String>,
+    /// `[defaults]`: login settings for hosts.toml hosts that don't set their own.
+    #[serde(default)]
+    pub defaults: HostDefaults,
+    /// `[hooks]`: shell commands run on connection events.
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// Desktop notifications for dropped connections and long sessions ending.
+    #[serde(default)]
+    pub notify: bool,
+    /// Only notify about sessions that ran at least this long.
+    #[serde(default = "default_notify_after")]
+    pub notify_after_secs: u64,
+    /// Also notify while oken's terminal has the focus, where that can be told.
+    #[serde(default)]
+    pub notify_when_focused: bool,
+    /// Notify when a session on a danger host has had no input this long (0 = never).
+    #[serde(default)]
+    pub danger_idle_secs: u64,
+    /// Terminal background colour (e.g. "#3a0000") while connected to a danger host.
+    #[serde(default)]
+    pub danger_background: Option<String>,
+    /// iTerm2 profile to switch to while connected to a danger host.
+    #[serde(default)]
+    pub danger_iterm_profile: Option<String>,
+    /// URL that receives a JSON POST when a danger-host session starts and ends.
+    #[serde(default)]
+    pub danger_webhook: Option<String>,
+    /// Show the host key and ask before the first connection to a saved host.
+    #[serde(default = "default_host_key_review")]
+    pub host_key_review: bool,
+    /// Measure round-trip times to hosts in the background for the RTT column.
+    #[serde(default)]
+    pub latency_probe: bool,
+    /// Check for a newer release in the background and print a notice.
+    #[serde(default = "default_update_check")]
+    pub update_check: bool,
+    /// Hours between background update checks.
+    #[serde(default = "default_update_interval")]
+    pub update_interval_hours: u64,
+    #[serde(default)]
+    pub update_channel: UpdateChannel,
+    /// Proxy URL for the update check and `oken self-update`, instead of
+    /// `HTTPS_PROXY` and friends.
+    #[serde(default)]
+    pub update_proxy: Option<String>,
+    /// Inside tmux or zellij, open picked hosts in a new window or split.
+    #[serde(default)]
+    pub picker_open: OpenIn,
+    /// Tag namespace (`env` for `env:prod`) the picker groups hosts by,
+    /// instead of their first tag.
+    #[serde(default)]
+    pub picker_group_by: Option<String>,
+    /// `[proxies]`: named ProxyCommand presets that hosts use with `proxy = "<name>"`.
+    #[serde(default)]
+    pub proxies: BTreeMap<String, String>,
+    /// `[mappings.<name>]`: how to read hosts out of JSON for `oken import`.
+    #[serde(default)]
+    pub mappings: BTreeMap<String, crate::mapping::Mapping>,
+    /// `[snippets.<name>]`: saved commands for `oken snippet run`.
+    #[serde(default)]
+    pub snippets: BTreeMap<String, Snippet>,
+}
+
+/// A `[snippets.<name>]` entry: a command to run on several hosts by name.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Snippet {
+    pub command: String,
+    /// Shown by `oken snippet list`.
+    pub description: Option<String>,
+    /// Run the command with sudo, as with `oken exec --sudo`.
+    #[serde(default)]
+    pub sudo: bool,
+    /// Run the command with sudo as this user; implies `sudo`.
+    pub become_user: Option<String>,
+}
+
+/// Values for hosts in hosts.toml that leave them out. Hosts from
+/// ~/.ssh/config keep what that file says.
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct HostDefaults {
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_file: Option<String>,
+    /// Tags for hosts that have none.
+    pub tags: Vec<String>,
+}
+
+impl HostDefaults {
+    /// `user=deploy, port=2222`, for `oken config`; empty when nothing is set.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(user) = &self.user {
+            parts.push(format!("user={user}"));
+        }
+        if let Some(port) = self.port {
+            parts.push(format!("port={port}"));
+        }
+        if let Some(file) = &self.identity_file {
+            parts.push(format!("identity_file={file}"));
+        }
+        if !self.tags.is_empty() {
+            parts.push(format!("tags={}", self.tags.join(",")));
+        }
+        parts.join(", ")
+    }
+}
+
+/// The `[defaults]` table, read without the warnings `load_config` gives
+/// about the rest of the file (it has given them already).
+pub fn host_defaults() -> HostDefaults {
+    config_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| toml::from_str::<OkenConfig>(&content).ok())
+        .map(|cfg| cfg.defaults)
+        .unwrap_or_default()
+}
+
+/// Commands for each connection event, run through the shell with the host's
+/// details in `OKEN_*` environment variables.
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct Hooks {
+    /// Before connecting, ahead of the host's own; a failing command aborts
+    /// the connection.
+    pub on_connect: Vec<String>,
+    /// After the session ends, after the host's own.
+    pub on_disconnect: Vec<String>,
+    /// Before each attempt to reconnect a dropped session.
+    pub on_reconnect: Vec<String>,
+    /// Before connecting to a danger host, after `on_connect`; a failing command aborts.
+    pub on_danger_connect: Vec<String>,
+}
+
+impl Hooks {
+    /// Names of the events that have commands, for `oken config`.
+    pub fn events(&self) -> Vec<&'static str> {
+        [
+            ("on_connect", &self.on_connect),
+            ("on_disconnect", &self.on_disconnect),
+            ("on_reconnect", &self.on_reconnect),
+            ("on_danger_connect", &self.on_danger_connect),
+        ]
+        .into_iter()
+        .filter(|(_, commands)| !commands.is_empty())
+        .map(|(event, _)| event)
+        .collect()
+    }
 }
 
 impl Default for OkenConfig {
@@ -36,8 +304,38 @@ impl Default for OkenConfig {
             reconnect: default_reconnect(),
             reconnect_retries: default_retries(),
             reconnect_delay_secs: default_delay(),
+            reconnect_max_delay_secs: default_max_delay(),
+            reconnect_wait_for_network: false,
             keepalive_interval: default_keepalive(),
             danger_tags: default_danger_tags(),
+            danger_rules: Vec::new(),
+            danger_confirmation: DangerConfirmation::default(),
+            record_tags: Vec::new(),
+            safe_tags: Vec::new(),
+            redact_patterns: Vec::new(),
+            compliance: false,
+            pre_connect: Vec::new(),
+            post_disconnect: Vec::new(),
+            defaults: HostDefaults::default(),
+            hooks: Hooks::default(),
+            notify: false,
+            notify_after_secs: default_notify_after(),
+            notify_when_focused: false,
+            danger_idle_secs: 0,
+            danger_background: None,
+            danger_iterm_profile: None,
+            danger_webhook: None,
+            host_key_review: default_host_key_review(),
+            latency_probe: false,
+            update_check: default_update_check(),
+            update_interval_hours: default_update_interval(),
+            update_channel: UpdateChannel::default(),
+            update_proxy: None,
+            picker_open: OpenIn::default(),
+            picker_group_by: None,
+            proxies: BTreeMap::new(),
+            mappings: BTreeMap::new(),
+            snippets: BTreeMap::new(),
         }
     }
 }
@@ -52,12 +350,367 @@ pub fn load_config() -> OkenConfig {
     if cfg.reconnect_delay_secs == 0 {
         cfg.reconnect_delay_secs = 1;
     }
+    if cfg.update_interval_hours == 0 {
+        cfg.update_interval_hours = 1;
+    }
     cfg
 }
 
+/// Parse config.toml, reporting why it can't be used (`load_config` falls
+/// back to the defaults instead). A missing file is fine.
+pub fn check_config(path: &std::path::Path) -> anyhow::Result<()> {
+    if path.exists() {
+        toml::from_str::<OkenConfig>(&std::fs::read_to_string(path)?)?;
+    }
+    Ok(())
+}
+
+/// `~/.config/oken/config.toml`.
+pub fn config_path() -> Result<PathBuf> {
+    Ok(crate::config::config_dir()?.join("config.toml"))
+}
+
 fn load_config_impl() -> Option<OkenConfig> {
-    let config_dir = crate::config::config_dir().ok()?;
-    let path = config_dir.join("config.toml");
-    let content = std::fs::read_to_string(path).ok()?;
-    toml::from_str(&content).ok()
+    let path = config_path().ok()?;
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        log::debug!("no {}, using the default settings", path.display());
+        return None;
+    };
+    match toml::from_str(&content) {
+        Ok(mut cfg) => {
+            log::debug!("loaded settings from {}", path.display());
+            for problem in unknown_keys(&content) {
+                log::warn!("{}: {problem}", path.display());
+            }
+            fold_old_hooks(&mut cfg, &path);
+            Some(cfg)
+        }
+        Err(e) => {
+            log::warn!("{} is invalid, using the default settings: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Move the top-level `pre_connect` and `post_disconnect` lists into
+/// `[hooks]`, ahead of the commands already there.
+fn fold_old_hooks(cfg: &mut OkenConfig, path: &std::path::Path) {
+    if cfg.pre_connect.is_empty() && cfg.post_disconnect.is_empty() {
+        return;
+    }
+    log::warn!(
+        "{}: pre_connect and post_disconnect are deprecated; \
+         use on_connect and on_disconnect under [hooks]",
+        path.display()
+    );
+    cfg.hooks.on_connect.splice(0..0, std::mem::take(&mut cfg.pre_connect));
+    cfg.hooks.on_disconnect.splice(0..0, std::mem::take(&mut cfg.post_disconnect));
+}
+
+/// What a config.toml setting holds, so `oken config set` can check values.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Bool,
+    Integer,
+    Text,
+    /// A list of strings.
+    List,
+    /// One of a fixed set of strings.
+    Choice(&'static [&'static str]),
+    /// Tables that are edited by hand (`[[danger_rules]]`, `[mappings.<name>]`,
+    /// `[snippets.<name>]`).
+    Table,
+}
+
+/// Every setting config.toml understands, by dotted key; `*` stands for any name.
+const SETTINGS: &[(&str, Kind)] = &[
+    ("reconnect", Kind::Bool),
+    ("reconnect_retries", Kind::Integer),
+    ("reconnect_delay_secs", Kind::Integer),
+    ("reconnect_max_delay_secs", Kind::Integer),
+    ("reconnect_wait_for_network", Kind::Bool),
+    ("keepalive_interval", Kind::Integer),
+    ("danger_tags", Kind::List),
+    ("danger_rules", Kind::Table),
+    ("danger_confirmation", Kind::Choice(&["yes-no", "type-alias"])),
+    ("record_tags", Kind::List),
+    ("safe_tags", Kind::List),
+    ("redact_patterns", Kind::List),
+    ("compliance", Kind::Bool),
+    // Deprecated spellings of hooks.on_connect and hooks.on_disconnect
+    ("pre_connect", Kind::List),
+    ("post_disconnect", Kind::List),
+    ("defaults.user", Kind::Text),
+    ("defaults.port", Kind::Integer),
+    ("defaults.identity_file", Kind::Text),
+    ("defaults.tags", Kind::List),
+    ("hooks.on_connect", Kind::List),
+    ("hooks.on_disconnect", Kind::List),
+    ("hooks.on_reconnect", Kind::List),
+    ("hooks.on_danger_connect", Kind::List),
+    ("notify", Kind::Bool),
+    ("notify_after_secs", Kind::Integer),
+    ("notify_when_focused", Kind::Bool),
+    ("danger_idle_secs", Kind::Integer),
+    ("danger_background", Kind::Text),
+    ("danger_iterm_profile", Kind::Text),
+    ("danger_webhook", Kind::Text),
+    ("host_key_review", Kind::Bool),
+    ("latency_probe", Kind::Bool),
+    ("update_check", Kind::Bool),
+    ("update_interval_hours", Kind::Integer),
+    ("update_channel", Kind::Choice(&["stable", "beta"])),
+    ("update_proxy", Kind::Text),
+    ("picker_open", Kind::Choice(&["here", "window", "split"])),
+    ("picker_group_by", Kind::Text),
+    ("proxies.*", Kind::Text),
+    ("mappings.*", Kind::Table),
+    ("snippets.*", Kind::Table),
+];
+
+/// The kind of setting at a dotted key like `hooks.on_connect`, or `None`
+/// for keys oken doesn't know. Keys inside a `Table` setting match it.
+pub fn setting_kind(key: &str) -> Option<Kind> {
+    let parts: Vec<&str> = key.split('.').collect();
+    SETTINGS.iter().find_map(|&(pattern, kind)| {
+        let pattern: Vec<&str> = pattern.split('.').collect();
+        let deeper = kind == Kind::Table && parts.len() > pattern.len();
+        let matches = (parts.len() == pattern.len() || deeper)
+            && pattern.iter().zip(&parts).all(|(p, k)| *p == "*" || p == k);
+        matches.then_some(kind)
+    })
+}
+
+/// Whether `key` names a section holding settings, like `hooks` or `proxies`.
+fn is_section(key: &str) -> bool {
+    SETTINGS
+        .iter()
+        .any(|(pattern, _)| pattern.strip_prefix(key).is_some_and(|rest| rest.starts_with('.')))
+}
+
+/// The active value of a setting or section (defaults merged with
+/// config.toml), or `None` when an optional setting is unset.
+pub fn get(key: &str) -> Result<Option<toml::Value>> {
+    let effective = toml::Value::try_from(load_config())?;
+    let value = key.split('.').try_fold(&effective, |v, part| v.get(part));
+    if value.is_none() && setting_kind(key).is_none() && !is_section(key) {
+        bail!("unknown setting '{key}'");
+    }
+    Ok(value.cloned())
+}
+
+/// Keys in a config.toml that oken doesn't know (and so ignores), each with
+/// a suggestion when it looks like a typo of a known one.
+pub fn unknown_keys(content: &str) -> Vec<String> {
+    fn walk(table: &dyn toml_edit::TableLike, prefix: &str, found: &mut Vec<String>) {
+        for (name, item) in table.iter() {
+            let key = if prefix.is_empty() { name.to_string() } else { format!("{prefix}.{name}") };
+            if setting_kind(&key).is_some() {
+                continue;
+            }
+            match item.as_table_like() {
+                Some(inner) if is_section(&key) => walk(inner, &key, found),
+                _ => found.push(match closest_setting(&key) {
+                    Some(known) => format!("unknown setting '{key}' (did you mean '{known}'?)"),
+                    None => format!("unknown setting '{key}'"),
+                }),
+            }
+        }
+    }
+    let mut found = Vec::new();
+    if let Ok(doc) = content.parse::<DocumentMut>() {
+        walk(doc.as_table(), "", &mut found);
+    }
+    found
+}
+
+/// The known setting within two edits of `key`, if any.
+fn closest_setting(key: &str) -> Option<&'static str> {
+    SETTINGS
+        .iter()
+        .map(|(pattern, _)| *pattern)
+        .filter(|pattern| !pattern.contains('*'))
+        .map(|pattern| (edit_distance(key, pattern), pattern))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, pattern)| pattern)
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev + usize::from(ca != *cb);
+            prev = row[j + 1];
+            row[j + 1] = substitute.min(prev + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Write a setting to config.toml, keeping the rest of the file (comments
+/// included) as it was. Lists take one or more values, or a single TOML
+/// array like `["a", "b"]`; everything else takes exactly one.
+pub fn set(key: &str, values: &[String]) -> Result<()> {
+    let kind = setting_kind(key).with_context(|| format!("unknown setting '{key}'"))?;
+    let value = parse_value(key, kind, values)?;
+    let (parents, name) = split_key(key);
+    edit_config(key, |doc| {
+        let mut table = doc.as_table_mut();
+        for part in parents {
+            table = table
+                .entry(part)
+                .or_insert_with(toml_edit::table)
+                .as_table_mut()
+                .with_context(|| format!("'{part}' in config.toml is not a table"))?;
+        }
+        table.insert(name, toml_edit::value(value));
+        Ok(true)
+    })?;
+    Ok(())
+}
+
+/// Remove a setting from config.toml so its default applies again. Returns
+/// whether it was set.
+pub fn unset(key: &str) -> Result<bool> {
+    if setting_kind(key).is_none() && !is_section(key) {
+        bail!("unknown setting '{key}'");
+    }
+    let path: Vec<&str> = key.split('.').collect();
+    edit_config(key, |doc| Ok(remove_key(doc.as_table_mut(), &path)))
+}
+
+/// Remove the key at `path`, and the tables it leaves empty.
+fn remove_key(table: &mut toml_edit::Table, path: &[&str]) -> bool {
+    match path {
+        [] => false,
+        [name] => table.remove(name).is_some(),
+        [part, rest @ ..] => {
+            let Some(inner) = table.get_mut(part).and_then(|item| item.as_table_mut()) else {
+                return false;
+            };
+            let removed = remove_key(inner, rest);
+            if removed && inner.is_empty() {
+                table.remove(part);
+            }
+            removed
+        }
+    }
+}
+
+fn split_key(key: &str) -> (Vec<&str>, &str) {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let name = parts.pop().unwrap_or_default();
+    (parts, name)
+}
+
+fn parse_value(key: &str, kind: Kind, values: &[String]) -> Result<toml_edit::Value> {
+    let [raw] = values else {
+        if kind == Kind::List {
+            return Ok(values.iter().map(String::as_str).collect::<toml_edit::Array>().into());
+        }
+        bail!("'{key}' takes a single value");
+    };
+    Ok(match kind {
+        Kind::Bool => raw
+            .parse::<bool>()
+            .map_err(|_| anyhow!("'{key}' must be true or false"))?
+            .into(),
+        Kind::Integer => raw
+            .parse::<u32>()
+            .map_err(|_| anyhow!("'{key}' must be a whole number"))
+            .map(i64::from)?
+            .into(),
+        Kind::Text => raw.as_str().into(),
+        Kind::Choice(choices) => {
+            if !choices.contains(&raw.as_str()) {
+                bail!("'{key}' must be one of: {}", choices.join(", "));
+            }
+            raw.as_str().into()
+        }
+        Kind::List if raw.starts_with('[') => {
+            let value: toml_edit::Value = raw
+                .parse()
+                .with_context(|| format!("'{raw}' is not a TOML array"))?;
+            let strings = value.as_array().is_some_and(|a| a.iter().all(|v| v.is_str()));
+            if !strings {
+                bail!("'{key}' must be a list of strings");
+            }
+            value
+        }
+        Kind::List => toml_edit::Array::from_iter([raw.as_str()]).into(),
+        Kind::Table => bail!("'{key}' is a table; change it with `oken config edit`"),
+    })
+}
+
+/// Apply `change` to config.toml and write it back, unless it changed nothing
+/// or the result wouldn't load.
+fn edit_config(
+    key: &str,
+    change: impl FnOnce(&mut DocumentMut) -> Result<bool>,
+) -> Result<bool> {
+    let path = config_path()?;
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let mut doc: DocumentMut = content
+        .parse()
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    if !change(&mut doc)? {
+        return Ok(false);
+    }
+    let text = doc.to_string();
+    toml::from_str::<OkenConfig>(&text)
+        .with_context(|| format!("not saving '{key}', config.toml would be invalid"))?;
+    crate::config::create_parent(&path)?;
+    std::fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_setting_kinds() {
+        assert!(setting_kind("reconnect") == Some(Kind::Bool));
+        assert!(setting_kind("hooks.on_connect") == Some(Kind::List));
+        assert!(setting_kind("proxies.office") == Some(Kind::Text));
+        assert!(setting_kind("mappings.netbox.items") == Some(Kind::Table));
+        assert!(setting_kind("snippets.deploy.sudo") == Some(Kind::Table));
+        assert!(setting_kind("hooks").is_none() && is_section("hooks"));
+        assert!(setting_kind("reconect").is_none() && !is_section("reconect"));
+        assert!(setting_kind("proxies.office.x").is_none());
+    }
+
+    #[test]
+    fn moves_old_hook_keys_into_hooks() {
+        let content = "pre_connect = [\"a\"]\npost_disconnect = [\"z\"]\n\
+                       [hooks]\non_connect = [\"b\"]\non_disconnect = [\"y\"]\n";
+        let mut cfg: OkenConfig = toml::from_str(content).unwrap();
+        fold_old_hooks(&mut cfg, std::path::Path::new("config.toml"));
+        assert_eq!(cfg.hooks.on_connect, ["a", "b"]);
+        assert_eq!(cfg.hooks.on_disconnect, ["z", "y"]);
+        assert!(unknown_keys(content).is_empty());
+    }
+
+    #[test]
+    fn reports_unknown_keys() {
+        let content = "reconect = true\nnotify = true\n[hooks]\non_conect = []\n\
+                       [proxies]\noffice = \"ssh -W %h:%p gw\"\n[picker]\ntheme = 1\n";
+        assert_eq!(
+            unknown_keys(content),
+            [
+                "unknown setting 'reconect' (did you mean 'reconnect'?)",
+                "unknown setting 'hooks.on_conect' (did you mean 'hooks.on_connect'?)",
+                "unknown setting 'picker'",
+            ]
+        );
+    }
 }
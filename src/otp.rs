@@ -0,0 +1,331 @@
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use ring::hmac;
+
+use crate::color::epaint;
+use crate::prompt;
+
+/// Keyring service name the seeds are stored under, one account per host.
+const SERVICE: &str = "oken-totp";
+
+/// A TOTP generator (RFC 6238), from a base32 seed or an `otpauth://` URI.
+struct Totp {
+    secret: Vec<u8>,
+    digits: u32,
+    period: u64,
+    algorithm: hmac::Algorithm,
+}
+
+impl Totp {
+    /// The code for the period containing `now` (Unix seconds).
+    fn code(&self, now: u64) -> String {
+        let counter = (now / self.period).to_be_bytes();
+        let key = hmac::Key::new(self.algorithm, &self.secret);
+        let mac = hmac::sign(&key, &counter);
+        let mac = mac.as_ref();
+        // Dynamic truncation: 31 bits starting at the offset in the last nibble
+        let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+        let bytes = [mac[offset], mac[offset + 1], mac[offset + 2], mac[offset + 3]];
+        let value = u32::from_be_bytes(bytes) & 0x7fff_ffff;
+        let code = value % 10u32.pow(self.digits);
+        format!("{code:0width$}", width = self.digits as usize)
+    }
+
+    fn remaining(&self, now: u64) -> u64 {
+        self.period - now % self.period
+    }
+}
+
+/// Accept a bare base32 seed (spaces and case don't matter) or the
+/// `otpauth://totp/…?secret=…` URI a QR code holds.
+fn parse_seed(input: &str) -> Result<Totp> {
+    let input = input.trim();
+    let mut totp = Totp {
+        secret: Vec::new(),
+        digits: 6,
+        period: 30,
+        algorithm: hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+    };
+    let Some(uri) = input.strip_prefix("otpauth://") else {
+        totp.secret = base32_decode(input).context("the seed is not valid base32")?;
+        return Ok(totp);
+    };
+    if !uri.starts_with("totp/") {
+        bail!("only otpauth://totp/ URIs are supported");
+    }
+    let query = uri.split_once('?').map(|(_, q)| q).unwrap_or_default();
+    for pair in query.split('&') {
+        match pair.split_once('=') {
+            Some(("secret", v)) => {
+                totp.secret = base32_decode(v).context("the URI's secret is not valid base32")?;
+            }
+            Some(("digits", v)) => totp.digits = v.parse().context("invalid digits in the URI")?,
+            Some(("period", v)) => totp.period = v.parse().context("invalid period in the URI")?,
+            Some(("algorithm", v)) => {
+                totp.algorithm = match v.to_ascii_uppercase().as_str() {
+                    "SHA1" => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+                    "SHA256" => hmac::HMAC_SHA256,
+                    "SHA512" => hmac::HMAC_SHA512,
+                    other => bail!("unsupported algorithm {other} in the URI"),
+                }
+            }
+            _ => {}
+        }
+    }
+    if totp.secret.is_empty() {
+        bail!("the URI has no secret");
+    }
+    if !(6..=8).contains(&totp.digits) || totp.period == 0 {
+        bail!("the URI asks for {} digits every {}s, which isn't valid", totp.digits, totp.period);
+    }
+    Ok(totp)
+}
+
+/// RFC 4648 base32, ignoring case, spaces and `=` padding.
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in input.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u32 - 'A' as u32,
+            c @ '2'..='7' => c as u32 - '2' as u32 + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+/// Save a seed in the OS keyring: the login keychain on macOS, the Secret
+/// Service (GNOME Keyring, KWallet) through `secret-tool` elsewhere.
+fn store(host: &str, seed: &str) -> Result<()> {
+    // The seed goes in on stdin, never in argv where `ps` would show it
+    let status = if cfg!(target_os = "macos") {
+        // `security -i` reads its commands from stdin
+        let command = format!(
+            "add-generic-password -U -s {SERVICE} -a {} -w {}\n",
+            security_quote(host),
+            security_quote(seed)
+        );
+        let mut security = Command::new("security");
+        security.arg("-i").stdout(Stdio::null());
+        run_with_input(&mut security, &command)
+    } else if cfg!(windows) {
+        bail!("storing TOTP seeds needs a keyring, which oken doesn't support on Windows yet");
+    } else {
+        let label = format!("oken TOTP seed for {host}");
+        let mut secret_tool = Command::new("secret-tool");
+        secret_tool.args(["store", "--label", &label, "service", SERVICE, "account", host]);
+        run_with_input(&mut secret_tool, seed)
+    };
+    let status = status.context(keyring_hint())?;
+    // `security -i` carries on past a command that fails, so check the seed landed
+    let saved = !cfg!(target_os = "macos") || lookup(host)?.as_deref() == Some(seed.trim());
+    if !status.success() || !saved {
+        bail!("the keyring did not save the seed for '{host}'");
+    }
+    Ok(())
+}
+
+/// Run `command` with `input` on its stdin.
+fn run_with_input(command: &mut Command, input: &str) -> io::Result<std::process::ExitStatus> {
+    let mut child = command.stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    child.wait()
+}
+
+/// `arg` as a single word on a `security -i` command line.
+fn security_quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The stored seed for a host, if there is one.
+fn lookup(host: &str) -> Result<Option<String>> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["find-generic-password", "-s", SERVICE, "-a", host, "-w"])
+            .stderr(Stdio::null())
+            .output()
+    } else if cfg!(windows) {
+        return Ok(None);
+    } else {
+        Command::new("secret-tool")
+            .args(["lookup", "service", SERVICE, "account", host])
+            .stderr(Stdio::null())
+            .output()
+    };
+    let output = output.context(keyring_hint())?;
+    let seed = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((output.status.success() && !seed.is_empty()).then_some(seed))
+}
+
+fn remove(host: &str) -> Result<bool> {
+    let status = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["delete-generic-password", "-s", SERVICE, "-a", host])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+    } else if cfg!(windows) {
+        return Ok(false);
+    } else {
+        // secret-tool clear succeeds whether or not there was a seed
+        if lookup(host)?.is_none() {
+            return Ok(false);
+        }
+        Command::new("secret-tool")
+            .args(["clear", "service", SERVICE, "account", host])
+            .status()
+    };
+    Ok(status.context(keyring_hint())?.success())
+}
+
+fn keyring_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "failed to run `security`"
+    } else {
+        "failed to run secret-tool (install libsecret-tools, or your distro's libsecret package)"
+    }
+}
+
+/// Put text on the clipboard with whichever tool the platform has.
+fn copy(text: &str) -> Result<()> {
+    let tools: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(windows) {
+        &[("clip", &[])]
+    } else {
+        &[
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    };
+    for (tool, args) in tools {
+        let Ok(mut child) = Command::new(tool).args(*args).stdin(Stdio::piped()).spawn() else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+    bail!("no clipboard tool found (install wl-clipboard, xclip or xsel)")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `oken otp <host>`: print the current code, or with `--set`/`--remove`
+/// manage the host's seed in the keyring.
+pub fn run(host: &str, set: bool, remove_seed: bool, copy_code: bool) -> Result<()> {
+    if set {
+        let seed = prompt::read_hidden("TOTP seed or otpauth:// URI: ")?;
+        // Check it before saving, so a typo shows up now rather than at login
+        parse_seed(&seed)?;
+        store(host, &seed)?;
+        println!("Saved the TOTP seed for '{host}' in the keyring");
+        return Ok(());
+    }
+    if remove_seed {
+        if remove(host)? {
+            println!("Removed the TOTP seed for '{host}'");
+        } else {
+            println!("No TOTP seed stored for '{host}'");
+        }
+        return Ok(());
+    }
+    let seed = lookup(host)?.with_context(|| {
+        format!("no TOTP seed for '{host}'; add one with `oken otp {host} --set`")
+    })?;
+    let totp = parse_seed(&seed)?;
+    let now = now();
+    let code = totp.code(now);
+    if copy_code {
+        copy(&code)?;
+        eprintln!("Copied the code for '{host}' (valid for {}s)", totp.remaining(now));
+    } else {
+        println!("{code}");
+        if io::stdout().is_terminal() {
+            eprintln!("{}", epaint("2", format!("valid for {}s", totp.remaining(now))));
+        }
+    }
+    Ok(())
+}
+
+/// For hosts with `mfa = true`: show the current code before ssh asks for it.
+/// Quietly does nothing when no seed is stored.
+pub fn show_before_connect(host: &str) {
+    let seed = match lookup(host) {
+        Ok(Some(seed)) => seed,
+        Ok(None) => {
+            log::debug!("'{host}' has mfa = true but no TOTP seed; add one with oken otp --set");
+            return;
+        }
+        Err(e) => {
+            log::warn!("could not read the TOTP seed for '{host}': {e:#}");
+            return;
+        }
+    };
+    let totp = match parse_seed(&seed) {
+        Ok(totp) => totp,
+        Err(e) => {
+            log::warn!("the TOTP seed for '{host}' is unusable: {e:#}");
+            return;
+        }
+    };
+    let now = now();
+    let remaining = totp.remaining(now);
+    let mut note = format!("2FA code for {host}: {} (valid for {remaining}s", totp.code(now));
+    // Close to the end of a period, the code may be stale by the time it's typed
+    if remaining < 10 {
+        note.push_str(&format!(", then {}", totp.code(now + remaining)));
+    }
+    note.push(')');
+    eprintln!("{}", epaint("1", note));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_keychain_arguments() {
+        assert_eq!(security_quote("web1"), r#""web1""#);
+        assert_eq!(
+            security_quote(r#"otpauth://totp/a b?secret=X&issuer="c\d""#),
+            r#""otpauth://totp/a b?secret=X&issuer=\"c\\d\"""#
+        );
+    }
+
+    #[test]
+    fn generates_rfc_6238_codes() {
+        // The RFC's SHA-1 seed, "12345678901234567890", in base32
+        let totp = parse_seed("gezd gnbv gy3t qojq gezd gnbv gy3t qojq").unwrap();
+        assert_eq!(totp.code(59), "287082");
+        assert_eq!(totp.code(1111111109), "081804");
+        assert_eq!(totp.remaining(59), 1);
+
+        let uri = "otpauth://totp/oken:prod?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&digits=8";
+        assert_eq!(parse_seed(uri).unwrap().code(59), "94287082");
+        assert!(parse_seed("not base32!").is_err());
+        assert!(parse_seed("otpauth://hotp/x?secret=GEZDGNBV").is_err());
+    }
+}
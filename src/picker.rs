@@ -12,23 +12,46 @@ use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
 use ratatui::Terminal;
 
+use crate::cloud;
+use crate::color;
+use crate::daemon;
 use crate::history;
+use crate::latency;
 use crate::hosts;
 use crate::time_utils;
 
 struct PickerHost {
     host: hosts::Host,
     last_connected: Option<String>,
+    /// Reachability from okend, when the daemon is running.
+    reachable: Option<bool>,
+    /// Cached round-trip time, when `latency_probe` is on.
+    rtt: Option<Option<u64>>,
+    /// The tag whose header the host is listed under: its first, or its tag
+    /// in the `picker_group_by` namespace. `None` goes under "other".
+    group: Option<String>,
 }
 
 /// Open the fuzzy host picker TUI. Returns the selected host or an error if cancelled.
 pub fn run_picker(initial_filter: Option<&str>) -> Result<hosts::Host> {
-    let all_hosts = hosts::list_all_hosts().unwrap_or_default();
+    let now = daemon::unix_now();
+    let mut all_hosts = hosts::list_all_hosts().unwrap_or_default();
+    all_hosts.retain(|h| !h.is_expired(now));
     if all_hosts.is_empty() {
         bail!("no hosts found — add one with: oken host add <name> <user@host>");
     }
 
     let recent = history::last_connected_hosts().unwrap_or_default();
+    // Only ask okend — probing here would make the picker slow to open
+    let status = daemon::query().map(|s| s.hosts).unwrap_or_default();
+    // tailscaled already knows which peers are online, so that's cheap to ask
+    let tailnet = cloud::tailscale_online();
+    let cfg = crate::oken_config::load_config();
+    let rtts = cfg.latency_probe.then(|| {
+        let cache = latency::load();
+        latency::maybe_refresh(&cache);
+        cache.rtt_ms
+    });
 
     // Build PickerHost list merged with history
     let mut picker_hosts: Vec<PickerHost> = all_hosts
@@ -36,20 +59,34 @@ pub fn run_picker(initial_filter: Option<&str>) -> Result<hosts::Host> {
         .map(|host| {
             let last_connected = recent
                 .iter()
-                .find(|r| r.alias == host.alias)
+                .find(|r| host.is_named(&r.alias))
                 .map(|r| r.last_connected.clone());
+            let reachable = status.get(&host.alias).map(|s| s.reachable).or_else(|| {
+                host.hostname.as_ref().and_then(|h| tailnet.get(h).copied())
+            });
+            let rtt = rtts.as_ref().map(|l| l.get(&host.alias).copied());
+            let group = match cfg.picker_group_by.as_deref() {
+                Some(namespace) => {
+                    let wanted = format!("{namespace}:*");
+                    host.tags.iter().find(|t| hosts::tag_matches(t, &wanted)).cloned()
+                }
+                None => host.tags.first().cloned(),
+            };
             PickerHost {
                 host,
                 last_connected,
+                reachable,
+                rtt,
+                group,
             }
         })
         .collect();
 
-    // Sort: group by first tag (alphabetically), untagged last.
+    // Sort: by group (alphabetically), ungrouped last.
     // Within each group, most recently connected first, then alphabetical.
     picker_hosts.sort_by(|a, b| {
-        let a_group = a.host.tags.first().map(|s| s.as_str()).unwrap_or("\u{FFFF}");
-        let b_group = b.host.tags.first().map(|s| s.as_str()).unwrap_or("\u{FFFF}");
+        let a_group = a.group.as_deref().unwrap_or("\u{FFFF}");
+        let b_group = b.group.as_deref().unwrap_or("\u{FFFF}");
         let group_cmp = a_group.cmp(b_group);
         if group_cmp != std::cmp::Ordering::Equal {
             return group_cmp;
@@ -98,8 +135,7 @@ fn run_picker_loop(
             *selected = matched - 1;
         }
 
-        let show_headers =
-            filtered.iter().any(|&idx| !picker_hosts[idx].host.tags.is_empty());
+        let show_headers = filtered.iter().any(|&idx| picker_hosts[idx].group.is_some());
 
         // Compute which render-row (including group headers) the selected item lands on,
         // then adjust scroll_offset to keep it in view.
@@ -129,41 +165,35 @@ fn run_picker_loop(
             );
         })?;
 
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind != KeyEventKind::Press {
-                    continue;
+        if event::poll(Duration::from_millis(50))?
+            && let Event::Key(key) = event::read()?
+        {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Esc => bail!("cancelled"),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    bail!("cancelled")
+                }
+                KeyCode::Enter if !filtered.is_empty() => {
+                    return Ok(picker_hosts[filtered[*selected]].host.clone());
+                }
+                KeyCode::Up if *selected > 0 => {
+                    *selected -= 1;
                 }
-                match key.code {
-                    KeyCode::Esc => bail!("cancelled"),
-                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        bail!("cancelled")
-                    }
-                    KeyCode::Enter => {
-                        if !filtered.is_empty() {
-                            return Ok(picker_hosts[filtered[*selected]].host.clone());
-                        }
-                    }
-                    KeyCode::Up => {
-                        if *selected > 0 {
-                            *selected -= 1;
-                        }
-                    }
-                    KeyCode::Down => {
-                        if *selected + 1 < matched {
-                            *selected += 1;
-                        }
-                    }
-                    KeyCode::Backspace => {
-                        search.pop();
-                        *selected = 0;
-                    }
-                    KeyCode::Char(c) => {
-                        search.push(c);
-                        *selected = 0;
-                    }
-                    _ => {}
+                KeyCode::Down if *selected + 1 < matched => {
+                    *selected += 1;
                 }
+                KeyCode::Backspace => {
+                    search.pop();
+                    *selected = 0;
+                }
+                KeyCode::Char(c) => {
+                    search.push(c);
+                    *selected = 0;
+                }
+                _ => {}
             }
         }
     }
@@ -181,7 +211,7 @@ fn render_row_of(
     let mut last_group: Option<Option<String>> = None;
     for (i, &idx) in filtered.iter().enumerate() {
         if show_headers {
-            let group = picker_hosts[idx].host.tags.first().cloned();
+            let group = picker_hosts[idx].group.clone();
             if last_group.as_ref() != Some(&group) {
                 last_group = Some(group);
                 row += 1; // header row
@@ -201,12 +231,15 @@ fn filter_hosts(picker_hosts: &[PickerHost], query: &str) -> Vec<usize> {
     }
     let q = query.to_lowercase();
     if q.starts_with('#') {
-        let tag_q = &q[1..];
+        // `#env:prod #role:db`: each term has to match one of the host's tags
+        let terms: Vec<&str> = q.split_whitespace().map(|t| t.trim_start_matches('#')).collect();
         return picker_hosts
             .iter()
             .enumerate()
             .filter(|(_, ph)| {
-                ph.host.tags.iter().any(|t| t.to_lowercase().contains(tag_q))
+                terms.iter().all(|term| {
+                    ph.host.tags.iter().any(|t| tag_filter_matches(&t.to_lowercase(), term))
+                })
             })
             .map(|(i, _)| i)
             .collect();
@@ -217,6 +250,7 @@ fn filter_hosts(picker_hosts: &[PickerHost], query: &str) -> Vec<usize> {
         .filter(|(_, ph)| {
             let h = &ph.host;
             h.alias.to_lowercase().contains(&q)
+                || h.aliases.iter().any(|a| a.to_lowercase().contains(&q))
                 || h.hostname
                     .as_deref()
                     .is_some_and(|hn| hn.to_lowercase().contains(&q))
@@ -229,6 +263,18 @@ fn filter_hosts(picker_hosts: &[PickerHost], query: &str) -> Vec<usize> {
         .collect()
 }
 
+/// Whether a (lowercased) tag matches a `#` term as typed so far: `env:pr`
+/// wants the `env` namespace and a value containing `pr`, and `env:*` any
+/// value; a term without a namespace can appear anywhere in the tag.
+fn tag_filter_matches(tag: &str, term: &str) -> bool {
+    match term.split_once(':') {
+        Some((namespace, value)) => tag.split_once(':').is_some_and(|(ns, v)| {
+            ns == namespace && v.contains(value.trim_end_matches('*'))
+        }),
+        None => tag.contains(term),
+    }
+}
+
 fn draw_search_line(frame: &mut ratatui::Frame, area: Rect, search: &str, matched: usize, total: usize) {
     let count = format!("{} / {} hosts", matched, total);
     let search_text = format!("  Search: {}\u{2588}", search);
@@ -237,9 +283,9 @@ fn draw_search_line(frame: &mut ratatui::Frame, area: Rect, search: &str, matche
         .saturating_sub(search_text.len() as u16 + count.len() as u16 + 2);
 
     let line = Line::from(vec![
-        Span::styled(search_text, Style::default().fg(Color::White)),
+        Span::styled(search_text, fg(Color::White)),
         Span::raw(" ".repeat(padding as usize)),
-        Span::styled(count, Style::default().fg(Color::DarkGray)),
+        Span::styled(count, fg(Color::DarkGray)),
         Span::raw("  "),
     ]);
     frame.render_widget(Paragraph::new(line), area);
@@ -255,19 +301,19 @@ fn draw_host_list(
 ) {
     let block = Block::default()
         .borders(Borders::TOP)
-        .border_style(Style::default().fg(Color::DarkGray));
+        .border_style(fg(Color::DarkGray));
 
     if filtered.is_empty() {
         let msg = ListItem::new(Line::from(vec![Span::styled(
             "  No matches",
-            Style::default().fg(Color::DarkGray),
+            fg(Color::DarkGray),
         )]));
         frame.render_widget(List::new(vec![msg]).block(block), area);
         return;
     }
 
-    // Determine if we should show group headers (any tagged hosts in the filtered set)
-    let show_headers = filtered.iter().any(|&idx| !picker_hosts[idx].host.tags.is_empty());
+    // Determine if we should show group headers (any grouped hosts in the filtered set)
+    let show_headers = filtered.iter().any(|&idx| picker_hosts[idx].group.is_some());
 
     let mut items: Vec<ListItem> = Vec::new();
     // Sentinel: use a value that can never match a real group
@@ -279,20 +325,25 @@ fn draw_host_list(
 
         // Insert group header when group changes
         if show_headers {
-            let group = h.tags.first().cloned();
+            let group = ph.group.clone();
             if last_group.as_ref() != Some(&group) {
                 last_group = Some(group.clone());
                 let label = group.as_deref().unwrap_or("other");
                 items.push(ListItem::new(Line::from(vec![Span::styled(
                     format!("  {label}"),
-                    Style::default()
-                        .fg(Color::DarkGray)
-                        .add_modifier(Modifier::BOLD),
+                    fg(Color::DarkGray).add_modifier(Modifier::BOLD),
                 )])));
             }
         }
 
         let prefix = if i == selected { "> " } else { "  " };
+        let dot = match ph.reachable {
+            Some(true) => Span::styled("● ", fg(Color::Green)),
+            // Without color, a hollow dot tells unreachable hosts apart
+            Some(false) if !color::stderr() => Span::raw("○ "),
+            Some(false) => Span::styled("● ", fg(Color::Red)),
+            None => Span::raw(""),
+        };
         let target = match (&h.user, &h.hostname) {
             (Some(u), Some(hn)) => format!("{}@{}", u, hn),
             (None, Some(hn)) => hn.clone(),
@@ -309,22 +360,31 @@ fn draw_host_list(
             .map(format_relative_time)
             .unwrap_or_default();
 
+        let rtt = ph
+            .rtt
+            .map(|r| format!("{:>6} ", latency::format(r)))
+            .unwrap_or_default();
+
         // Pad alias to 16 chars, target to 24 chars, tags to 20 chars
         let text = format!(
-            "{}{:<16} {:<24} {:<20} {}",
-            prefix, h.alias, target, tags, time,
+            "{:<16} {:<24} {:<20} {rtt}{}",
+            h.alias, target, tags, time,
         );
 
         let style = if i == selected {
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::White)
-                .add_modifier(Modifier::BOLD)
+            let highlight = if color::stderr() {
+                Style::default().fg(Color::Black).bg(Color::White)
+            } else {
+                Style::default().add_modifier(Modifier::REVERSED)
+            };
+            highlight.add_modifier(Modifier::BOLD)
         } else {
             Style::default()
         };
 
-        items.push(ListItem::new(Line::styled(text, style)));
+        items.push(ListItem::new(
+            Line::from(vec![Span::raw(prefix), dot, Span::raw(text)]).style(style),
+        ));
     }
 
     // Only render the rows that fit in the visible window (area height minus border).
@@ -333,7 +393,7 @@ fn draw_host_list(
     frame.render_widget(List::new(visible).block(block), area);
 }
 
-fn format_relative_time(iso: &str) -> String {
+pub fn format_relative_time(iso: &str) -> String {
     // Parse ISO 8601 timestamp like "2026-02-27T10:30:00Z"
     let parts: Vec<&str> = iso.split('T').collect();
     if parts.len() != 2 {
@@ -383,3 +443,12 @@ fn format_relative_time(iso: &str) -> String {
         format!("{months}mo ago")
     }
 }
+
+/// A foreground color, dropped when colors are off (`NO_COLOR`, `--color never`).
+fn fg(color: Color) -> Style {
+    if color::stderr() {
+        Style::default().fg(color)
+    } else {
+        Style::default()
+    }
+}
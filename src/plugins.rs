@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{Context, Result};
+
+use crate::config;
+
+/// The `oken-<name>` executable on PATH that handles `oken <name>`, git-style.
+/// Names that look like ssh targets (`user@host`, `10.0.0.1`) never match.
+pub fn find(name: &str) -> Option<PathBuf> {
+    let plain = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if name.is_empty() || !name.chars().all(plain) {
+        return None;
+    }
+    let file = format!("oken-{name}{}", std::env::consts::EXE_SUFFIX);
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(&file))
+        .find(|p| is_executable(p))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &std::path::Path) -> bool {
+    path.is_file()
+}
+
+/// Run a plugin with the rest of the command line and return its exit code.
+/// It learns where oken keeps its files from `OKEN_*` variables, so it can
+/// read hosts.toml or call back into `oken` without guessing paths.
+pub fn run(path: &std::path::Path, args: &[String]) -> Result<i32> {
+    let config_dir = config::config_dir()?;
+    let mut cmd = Command::new(path);
+    cmd.args(args)
+        .env("OKEN_VERSION", env!("CARGO_PKG_VERSION"))
+        .env("OKEN_CONFIG_DIR", &config_dir)
+        .env("OKEN_DATA_DIR", config::data_dir()?)
+        .env("OKEN_STATE_DIR", config::state_dir()?)
+        .env("OKEN_CONFIG_FILE", config_dir.join("config.toml"))
+        .env("OKEN_HOSTS_FILE", crate::hosts_toml_path()?)
+        .env("OKEN_TUNNELS_FILE", crate::tunnels_toml_path()?);
+    if let Ok(exe) = std::env::current_exe() {
+        cmd.env("OKEN_BIN", exe);
+    }
+    log::debug!("running plugin {}", path.display());
+    let status = cmd
+        .status()
+        .with_context(|| format!("failed to run plugin {}", path.display()))?;
+    Ok(status.code().unwrap_or(1))
+}
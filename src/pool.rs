@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use oken_core::history::{self, RecentHost};
+use oken_core::hosts::Host;
+
+use crate::cli::AnyStrategy;
+
+/// Where round-robin remembers the last host it picked for each tag.
+fn state_path() -> Result<PathBuf> {
+    oken_core::config::state_file("any.json")
+}
+
+fn load() -> BTreeMap<String, String> {
+    state_path()
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn store(last: &BTreeMap<String, String>) -> Result<()> {
+    let path = state_path()?;
+    oken_core::config::create_parent(&path)?;
+    std::fs::write(path, serde_json::to_string(last)?)?;
+    Ok(())
+}
+
+/// `oken --tag <tag> --any`: one of `hosts` (all tagged `tag`, at least one)
+/// to connect to without opening the picker.
+pub fn pick<'a>(hosts: &[&'a Host], tag: &str, strategy: AnyStrategy) -> &'a Host {
+    let mut hosts = hosts.to_vec();
+    hosts.sort_by(|a, b| a.alias.cmp(&b.alias));
+    let i = match strategy {
+        AnyStrategy::Lru => {
+            let recent = history::last_connected_hosts().unwrap_or_default();
+            least_recent(&hosts, &recent)
+        }
+        AnyStrategy::RoundRobin => {
+            let mut last = load();
+            let i = next_after(&hosts, last.get(tag).map(String::as_str));
+            last.insert(tag.to_string(), hosts[i].alias.clone());
+            if let Err(e) = store(&last) {
+                log::warn!("could not remember the round-robin position: {e:#}");
+            }
+            i
+        }
+    };
+    hosts[i]
+}
+
+/// The host connected to longest ago, or the first never connected to.
+/// `recent` is newest first.
+fn least_recent(hosts: &[&Host], recent: &[RecentHost]) -> usize {
+    let age = |h: &Host| recent.iter().position(|r| r.alias == h.alias).unwrap_or(usize::MAX);
+    (0..hosts.len()).rev().max_by_key(|&i| age(hosts[i])).unwrap_or(0)
+}
+
+/// The host after `last` in alias order, wrapping around. A `last` that's no
+/// longer in the group starts over from where it would have been.
+fn next_after(hosts: &[&Host], last: Option<&str>) -> usize {
+    let Some(last) = last else {
+        return 0;
+    };
+    hosts.iter().position(|h| h.alias.as_str() > last).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_through_the_group() {
+        let named = |alias: &str| Host {
+            alias: alias.to_string(),
+            ..Default::default()
+        };
+        let owned = [named("w1"), named("w2"), named("w3")];
+        let hosts: Vec<&Host> = owned.iter().collect();
+        assert_eq!(next_after(&hosts, None), 0);
+        assert_eq!(next_after(&hosts, Some("w1")), 1);
+        assert_eq!(next_after(&hosts, Some("w3")), 0);
+        assert_eq!(next_after(&hosts, Some("w15")), 1);
+
+        let recent = |alias: &str| RecentHost {
+            alias: alias.to_string(),
+            last_connected: String::new(),
+        };
+        assert_eq!(least_recent(&hosts, &[recent("w3"), recent("w1")]), 1);
+        assert_eq!(least_recent(&hosts, &[recent("w2"), recent("w3"), recent("w1")]), 0);
+        assert_eq!(least_recent(&hosts, &[]), 0);
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::{Result, bail};
+use oken_core::util::shell_quote;
+
+/// What `oken reboot` and `oken shutdown` ask the host to do.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Reboot,
+    Shutdown,
+}
+
+impl Action {
+    /// The name used in prompts and the audit log.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::Reboot => "reboot",
+            Action::Shutdown => "shutdown",
+        }
+    }
+
+    /// "Reboot" / "Shut down", to start a sentence with.
+    pub fn verb(self) -> &'static str {
+        match self {
+            Action::Reboot => "Reboot",
+            Action::Shutdown => "Shut down",
+        }
+    }
+
+    fn flag(self) -> &'static str {
+        match self {
+            Action::Reboot => "-r",
+            Action::Shutdown => "-h",
+        }
+    }
+}
+
+/// Check a `--when` value: `now`, `+M` (minutes from now) or `HH:MM`, the
+/// forms `shutdown` takes on Linux, macOS and the BSDs.
+pub fn parse_when(when: &str) -> Result<String> {
+    let when = when.trim();
+    let number = |s: &str, max: u32| {
+        !s.is_empty()
+            && s.len() <= 4
+            && s.bytes().all(|b| b.is_ascii_digit())
+            && s.parse::<u32>().is_ok_and(|n| n <= max)
+    };
+    let minutes = when.strip_prefix('+').is_some_and(|m| number(m, 9999));
+    let clock = when
+        .split_once(':')
+        .is_some_and(|(h, m)| number(h, 23) && m.len() == 2 && number(m, 59));
+    if when == "now" || minutes || clock {
+        Ok(when.to_string())
+    } else {
+        bail!("--when takes now, +MINUTES or HH:MM, not '{when}'")
+    }
+}
+
+/// "now", "in 5 minutes" or "at 22:30", for messages.
+pub fn describe_when(when: &str) -> String {
+    match when.strip_prefix('+') {
+        Some("0") => "now".to_string(),
+        Some("1") => "in 1 minute".to_string(),
+        Some(minutes) => format!("in {minutes} minutes"),
+        None if when == "now" => "now".to_string(),
+        None => format!("at {when}"),
+    }
+}
+
+/// True when the host goes down as soon as the command runs, so the
+/// connection dropping under it is expected.
+pub fn is_immediate(when: &str) -> bool {
+    when == "now" || when == "+0"
+}
+
+/// The remote command line: `shutdown` directly when logged in as root,
+/// through sudo otherwise. Wrapped in `sh -c` whatever the login shell is.
+pub fn remote_command(action: Action, when: &str) -> String {
+    let shutdown = format!("shutdown {} {}", action.flag(), shell_quote(when));
+    let script = format!("if [ \"$(id -u)\" -eq 0 ]; then {shutdown}; else sudo {shutdown}; fi");
+    format!("sh -c {}", shell_quote(&script))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_when() {
+        assert_eq!(parse_when("+5").unwrap(), "+5");
+        assert_eq!(parse_when("22:30").unwrap(), "22:30");
+        assert_eq!(parse_when("now").unwrap(), "now");
+        assert!(parse_when("5").is_err());
+        assert!(parse_when("24:00").is_err());
+        assert!(parse_when("+5; rm -rf /").is_err());
+        assert!(parse_when("++5").is_err());
+        assert_eq!(describe_when("+5"), "in 5 minutes");
+        assert_eq!(describe_when("22:30"), "at 22:30");
+        let cmd = remote_command(Action::Reboot, "+5");
+        assert!(cmd.contains("then shutdown -r +5; else sudo shutdown -r +5; fi"));
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::{BufRead, BufReader};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::hosts::Host;
+use crate::ssh::Route;
+
+/// Time a TCP connect to `host:port`, trying each resolved address in turn.
+/// Returns `None` if name resolution fails or nothing accepts the connection.
+pub fn tcp_connect(host: &str, port: u16, timeout: Duration) -> Option<Duration> {
+    // DNS failing is the usual symptom of no network after wake
+    let addrs = (host, port).to_socket_addrs().ok()?;
+    for addr in addrs {
+        let start = Instant::now();
+        if TcpStream::connect_timeout(&addr, timeout).is_ok() {
+            return Some(start.elapsed());
+        }
+    }
+    None
+}
+
+/// The address to probe for a saved host: the one a connection would use
+/// when it has several. Hosts from ~/.ssh/config have no stored hostname, so
+/// those are resolved with `ssh -G` (which also picks the first ProxyJump
+/// hop when one is configured).
+pub fn endpoint(host: &Host) -> Option<(String, u16)> {
+    match oken_core::addresses::choose(host) {
+        Some(hostname) => Some((hostname, host.port.unwrap_or(22))),
+        None => crate::ssh::resolve_endpoint(std::slice::from_ref(&host.alias)),
+    }
+}
+
+/// Time to reach the host's ssh port the way ssh would: a TCP connect when
+/// it's direct, or until the server's `SSH-` banner comes back through the
+/// jump hosts (with `ssh -W`, in batch mode) or ProxyCommand.
+pub fn route(route: &Route, timeout: Duration) -> Option<Duration> {
+    let mut cmd = match route {
+        Route::Direct { hostname, port } => return tcp_connect(hostname, *port, timeout),
+        Route::Jump {
+            jumps,
+            hostname,
+            port,
+        } => {
+            // The last hop is the one that dials the host; the rest are -J hops
+            let (earlier, last) = match jumps.rsplit_once(',') {
+                Some((earlier, last)) => (Some(earlier), last),
+                None => (None, jumps.as_str()),
+            };
+            let mut cmd = Command::new(crate::ssh::find_ssh().ok()?);
+            cmd.args(["-o", "BatchMode=yes", "-o"])
+                .arg(format!("ConnectTimeout={}", timeout.as_secs().max(1)));
+            if let Some(earlier) = earlier {
+                cmd.args(["-J", earlier]);
+            }
+            cmd.arg("-W").arg(format!("[{hostname}]:{port}")).arg(format!("ssh://{last}"));
+            cmd
+        }
+        Route::Command { command, .. } => {
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", command]);
+            cmd
+        }
+    };
+    let start = Instant::now();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let stdout = child.stdout.take()?;
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        let _ = BufReader::new(stdout).read_line(&mut line);
+        let _ = tx.send(line);
+    });
+    let banner = rx.recv_timeout(timeout).ok();
+    let elapsed = start.elapsed();
+    let _ = child.kill();
+    let _ = child.wait();
+    banner.filter(|l| l.starts_with("SSH-")).map(|_| elapsed)
+}
@@ -0,0 +1,114 @@
+use std::fs::File;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use anyhow::{Context, Result, bail};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+
+/// Read a line without echoing it when stdin is a terminal (seeds,
+/// passwords); piped input is read as is.
+pub fn read_hidden(prompt: &str) -> Result<String> {
+    if !io::stdin().is_terminal() {
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line)?;
+        return Ok(line.trim_end_matches(['\n', '\r']).to_string());
+    }
+    hidden_line(prompt)
+}
+
+/// Like `read_hidden`, but always from the terminal, for when stdin carries
+/// something else (`oken exec --stdin`).
+pub fn read_hidden_from_tty(prompt: &str) -> Result<String> {
+    File::open("/dev/tty").context("no terminal to ask on")?;
+    hidden_line(prompt)
+}
+
+/// Ask a yes/no question on stderr, with `[y/N]` or `[Y/n]` after it, and
+/// read the answer from stdin. An empty answer, or none, means `default`.
+pub fn confirm(question: &str, default: bool) -> Result<bool> {
+    confirm_from(question, default, &mut io::stdin().lock())
+}
+
+/// Like `confirm`, but reading the answer from `input` (the terminal, when
+/// stdin carries something else).
+pub fn confirm_from(question: &str, default: bool, input: &mut dyn BufRead) -> Result<bool> {
+    eprint!("{question} {} ", if default { "[Y/n]" } else { "[y/N]" });
+    io::stderr().flush()?;
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    let answer = line.trim();
+    if answer.is_empty() {
+        return Ok(default);
+    }
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+/// Prompt on stderr and read a line from the terminal without echoing it.
+/// The terminal is in raw mode meanwhile, so Ctrl-C arrives as a key rather
+/// than a signal and the terminal is always put back before oken exits.
+fn hidden_line(prompt: &str) -> Result<String> {
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+    let raw = RawMode::enable()?;
+    let line = read_keys();
+    drop(raw);
+    eprintln!();
+    line
+}
+
+fn read_keys() -> Result<String> {
+    let mut line = String::new();
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Enter => return Ok(line),
+            KeyCode::Char('c') if ctrl => bail!("interrupted"),
+            KeyCode::Char('d') if ctrl && line.is_empty() => return Ok(line),
+            KeyCode::Char('u') if ctrl => line.clear(),
+            KeyCode::Backspace => {
+                line.pop();
+            }
+            KeyCode::Char(c) if !ctrl => line.push(c),
+            _ => {}
+        }
+    }
+}
+
+/// Keeps the terminal in raw mode until dropped, so an error or a panic
+/// while reading doesn't leave it without echo.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode().context("failed to set up the terminal")?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_yes_no_answers() {
+        let answer = |input: &str, default| confirm_from("Go?", default, &mut input.as_bytes());
+        assert!(answer("y\n", false).unwrap());
+        assert!(answer(" YES \n", false).unwrap());
+        assert!(!answer("no\n", true).unwrap());
+        assert!(!answer("yep\n", false).unwrap());
+        assert!(answer("\n", true).unwrap());
+        assert!(!answer("", false).unwrap());
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+
+/// Presets available without any config. `%h`/`%p` are expanded by ssh;
+/// `{1}`, `{2}`, … are the words after the preset name in a host's `proxy`.
+const BUILTIN: &[(&str, &str)] = &[
+    (
+        "aws-ssm",
+        "aws ssm start-session --target %h --document-name AWS-StartSSHSession \
+         --parameters portNumber=%p",
+    ),
+    ("cloudflared", "cloudflared access ssh --hostname %h"),
+    ("gcp-iap", "gcloud compute start-iap-tunnel %h %p --listen-on-stdin"),
+    ("corkscrew", "corkscrew {1} {2} %h %p"),
+    ("nc-socks", "nc -X 5 -x {1}:{2} %h %p"),
+];
+
+/// Expand a host's `proxy` setting into a ProxyCommand. The first word names a
+/// preset from `[proxies]` in config.toml or a built-in one; the rest fills its
+/// `{1}`, `{2}`, … (split on spaces and `:`), or is appended when the preset
+/// has no placeholders. Anything that isn't a preset is used as the command.
+pub fn expand(spec: &str, presets: &BTreeMap<String, String>) -> Result<String> {
+    let spec = spec.trim();
+    let (name, args) = spec.split_once(char::is_whitespace).unwrap_or((spec, ""));
+    let args = args.trim();
+    let Some(template) = presets
+        .get(name)
+        .map(String::as_str)
+        .or_else(|| BUILTIN.iter().find(|(n, _)| *n == name).map(|(_, t)| *t))
+    else {
+        return Ok(spec.to_string());
+    };
+
+    let values: Vec<&str> = args
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .filter(|v| !v.is_empty())
+        .collect();
+    if !template.contains('{') {
+        return Ok(if args.is_empty() {
+            template.to_string()
+        } else {
+            format!("{template} {args}")
+        });
+    }
+    let mut command = template.replace("{args}", args);
+    for i in 1.. {
+        let placeholder = format!("{{{i}}}");
+        if !command.contains(&placeholder) {
+            break;
+        }
+        let Some(value) = values.get(i - 1) else {
+            bail!("proxy preset '{name}' needs more arguments: {template}");
+        };
+        command = command.replace(&placeholder, value);
+    }
+    Ok(command)
+}
+
+/// Names of the built-in presets, for `oken config`.
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    BUILTIN.iter().map(|(name, _)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_presets() {
+        let mut presets = BTreeMap::new();
+        presets.insert("gw".to_string(), "ssh -W %h:%p gateway-{1}".to_string());
+        assert_eq!(
+            expand("corkscrew http-proxy:3128", &presets).unwrap(),
+            "corkscrew http-proxy 3128 %h %p"
+        );
+        assert_eq!(
+            expand("cloudflared --id abc", &presets).unwrap(),
+            "cloudflared access ssh --hostname %h --id abc"
+        );
+        assert_eq!(expand("gw eu", &presets).unwrap(), "ssh -W %h:%p gateway-eu");
+        assert!(expand("corkscrew http-proxy", &presets).is_err());
+        assert_eq!(
+            expand("ssh -W %h:%p bastion", &presets).unwrap(),
+            "ssh -W %h:%p bastion"
+        );
+    }
+}
@@ -1,25 +1,185 @@
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+
+use crate::color::epaint;
+use crate::host_keys::{self, Exit};
+use crate::hosts::Host;
+use crate::recording::Recording;
+use crate::time_utils::backoff_delay;
+
+/// How aggressively to reconnect after a dropped connection.
+pub struct Policy<'a> {
+    /// Maximum reconnect attempts; 0 retries forever.
+    pub max_retries: u32,
+    /// Delay before the first attempt; doubles on each subsequent attempt.
+    pub base_delay_secs: u64,
+    /// Upper bound for the backoff delay.
+    pub max_delay_secs: u64,
+    /// Host label for desktop notifications, or `None` to stay quiet.
+    pub notify: Option<&'a str>,
+    /// Before relaunching ssh, wait until the ssh port accepts TCP connections.
+    pub wait_for_network: bool,
+    /// Called with the attempt number right before each relaunch.
+    pub on_reconnect: &'a dyn Fn(u32),
+    /// The saved host, for explaining a changed host key.
+    pub host: Option<&'a Host>,
+    /// Give up at once when the first attempt can't reach the server, since
+    /// the caller has another hostname to try.
+    pub failover: bool,
+}
 
 /// Run SSH with automatic reconnect on connection loss (exit code 255).
-/// Retries up to `max_retries` times with `delay_secs` between attempts.
-/// Returns the final exit code so the caller can log it and exit cleanly.
-pub fn run_with_reconnect(args: &[String], max_retries: u32, delay_secs: u64) -> Result<i32> {
+/// Waits with exponential backoff and jitter between attempts; the countdown
+/// can be skipped with Enter. Returns how the last attempt ended so the
+/// caller can log it and exit cleanly.
+pub fn run_with_reconnect(
+    args: &[String],
+    recording: Option<&Recording>,
+    policy: &Policy,
+) -> Result<Exit> {
     let mut attempt = 0u32;
+    // Resolved on the first drop — most sessions never need it
+    let mut endpoint: Option<Option<(String, u16)>> = None;
     loop {
-        let code = crate::ssh::run(args)?;
+        let code = match host_keys::run_ssh(args, recording, policy.host)? {
+            Exit::Code(code) => code,
+            // Retrying can't get past a refused host key
+            Exit::KeyRefused => return Ok(Exit::KeyRefused),
+            Exit::Unreachable if attempt == 0 && policy.failover => return Ok(Exit::Unreachable),
+            Exit::Unreachable => 255,
+        };
 
-        if code == 255 && attempt < max_retries {
+        if code == 255 && (policy.max_retries == 0 || attempt < policy.max_retries) {
             attempt += 1;
-            eprintln!(
-                "\x1b[2mConnection lost. Reconnecting ({attempt}/{max_retries})…\x1b[0m"
-            );
-            thread::sleep(Duration::from_secs(delay_secs));
+            let progress = if policy.max_retries == 0 {
+                format!("attempt {attempt}")
+            } else {
+                format!("{attempt}/{}", policy.max_retries)
+            };
+            if let Some(host) = policy.notify {
+                crate::notify::send(
+                    "oken: connection lost",
+                    &format!("{host} dropped — reconnecting ({progress})"),
+                );
+            }
+            let delay = backoff_delay(attempt, policy.base_delay_secs, policy.max_delay_secs);
+            countdown(jitter(delay), &progress);
+            if policy.wait_for_network
+                && let Some((host, port)) =
+                    endpoint.get_or_insert_with(|| crate::ssh::resolve_endpoint(args))
+            {
+                wait_for_network(host, *port);
+            }
+            (policy.on_reconnect)(attempt);
             continue;
         }
 
-        return Ok(code);
+        if code == 255
+            && attempt > 0
+            && let Some(host) = policy.notify
+        {
+            crate::notify::send(
+                "oken: reconnect failed",
+                &format!("{host} is unreachable after {attempt} attempts"),
+            );
+        }
+        return Ok(Exit::Code(code));
+    }
+}
+
+/// "Equal jitter": a random delay in `[delay/2, delay]`, so many clients dropped
+/// at once don't all reconnect in lockstep.
+fn jitter(delay: u64) -> u64 {
+    let half = delay / 2;
+    if half == 0 {
+        return delay;
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as u64;
+    half + nanos % (delay - half + 1)
+}
+
+/// Print a one-line countdown, returning early if the user presses Enter.
+fn countdown(secs: u64, progress: &str) {
+    for remaining in (1..=secs).rev() {
+        let note = format!(
+            "Connection lost. Reconnecting in {remaining}s ({progress}) — Enter to retry now…"
+        );
+        // \x1b[K clears what's left of a longer previous line
+        eprint!("\r{}\x1b[K", epaint("2", note));
+        if wait_for_enter(Duration::from_secs(1)) {
+            break;
+        }
+    }
+    eprintln!();
+}
+
+/// Block until `host:port` accepts a TCP connection, polling every few seconds.
+/// Enter skips the wait and lets ssh try anyway.
+fn wait_for_network(host: &str, port: u16) {
+    let mut announced = false;
+    loop {
+        if crate::probe::tcp_connect(host, port, Duration::from_secs(3)).is_some() {
+            if announced {
+                eprintln!("{}", epaint("2", "Network is back."));
+            }
+            return;
+        }
+        if !announced {
+            let note =
+                format!("Waiting for {host}:{port} to become reachable — Enter to retry now…");
+            eprintln!("{}", epaint("2", note));
+            announced = true;
+        }
+        if wait_for_enter(Duration::from_secs(3)) {
+            return;
+        }
+    }
+}
+
+/// Wait up to `timeout` for an Enter key press. Falls back to a plain sleep
+/// when no terminal is available.
+fn wait_for_enter(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        match event::poll(left) {
+            Ok(true) => {
+                if let Ok(Event::Key(key)) = event::read()
+                    && key.kind == KeyEventKind::Press
+                    && key.code == KeyCode::Enter
+                {
+                    return true;
+                }
+            }
+            Ok(false) => return false,
+            Err(_) => {
+                thread::sleep(left);
+                return false;
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_and_caps() {
+        assert_eq!(backoff_delay(1, 5, 60), 5);
+        assert_eq!(backoff_delay(2, 5, 60), 10);
+        assert_eq!(backoff_delay(4, 5, 60), 40);
+        assert_eq!(backoff_delay(5, 5, 60), 60);
+        assert_eq!(backoff_delay(80, 5, 60), 60);
+        for _ in 0..20 {
+            let d = jitter(10);
+            assert!((5..=10).contains(&d));
+        }
     }
 }
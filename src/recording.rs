@@ -0,0 +1,217 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+
+use crate::time_utils;
+use crate::util::{on_path, shell_quote};
+
+/// The external tool used to capture a session transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recorder {
+    /// `asciinema rec` — preferred when installed, produces `.cast` files.
+    Asciinema,
+    /// util-linux `script` with a separate timing file for `scriptreplay`.
+    ScriptLinux,
+    /// BSD/macOS `script -r`, replayed with `script -p`.
+    ScriptBsd,
+}
+
+/// A transcript file that one or more ssh attempts are appended to.
+pub struct Recording {
+    pub path: PathBuf,
+    recorder: Recorder,
+    /// Where the shell inside `asciinema rec` writes the program's exit code.
+    exit_file: PathBuf,
+}
+
+/// Returns `~/.local/share/oken/recordings`.
+pub fn recordings_dir() -> Result<PathBuf> {
+    Ok(crate::config::data_dir()?.join("recordings"))
+}
+
+/// Pick a recorder and allocate a transcript path for a new session with `alias`.
+pub fn start(alias: &str) -> Result<Recording> {
+    let recorder = detect_recorder()?;
+    let ext = match recorder {
+        Recorder::Asciinema => "cast",
+        Recorder::ScriptLinux | Recorder::ScriptBsd => "log",
+    };
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    // "2026-02-28T10:42:01Z" → "20260228T104201Z"
+    let stamp: String = time_utils::unix_to_iso8601(secs)
+        .chars()
+        .filter(|c| *c != '-' && *c != ':')
+        .collect();
+    let safe_alias: String = alias
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let dir = recordings_dir()?;
+    let mut path = dir.join(format!("{stamp}-{safe_alias}.{ext}"));
+    crate::config::create_parent(&path)?;
+    // Two sessions started in the same second must not share a transcript
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{stamp}-{safe_alias}-{n}.{ext}"));
+        n += 1;
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let exit_file = crate::config::state_dir()?.join("recordings").join(format!("{name}.exit"));
+    Ok(Recording {
+        path,
+        recorder,
+        exit_file,
+    })
+}
+
+impl Recording {
+    /// File name relative to the recordings dir, as stored in the audit log.
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Build a command that runs `program args…` under the recorder.
+    /// Repeated calls (e.g. across reconnects) append to the same transcript.
+    pub fn command(&self, program: &Path, args: &[String]) -> Command {
+        let path = self.path.to_string_lossy().to_string();
+        let append = self.path.exists();
+        match self.recorder {
+            Recorder::Asciinema => {
+                // asciinema exits 0 whatever the program did, so the shell
+                // leaves the program's exit code behind for `exit_code`, in a
+                // file created afresh so nothing stale or planted is read
+                let _ = std::fs::remove_file(&self.exit_file);
+                let created = crate::config::create_parent(&self.exit_file).and_then(|()| {
+                    let mut file = std::fs::OpenOptions::new();
+                    file.write(true).create_new(true).open(&self.exit_file)?;
+                    Ok(())
+                });
+                if let Err(e) = created {
+                    log::debug!("could not create {}: {e:#}", self.exit_file.display());
+                }
+                let command = format!(
+                    "{}; echo $? > {}",
+                    shell_command(program, args),
+                    shell_quote(&self.exit_file.to_string_lossy())
+                );
+                let mut cmd = Command::new("asciinema");
+                cmd.args(["rec", "--quiet"]);
+                if append {
+                    cmd.arg("--append");
+                }
+                cmd.args(["-c", &command, &path]);
+                cmd
+            }
+            Recorder::ScriptLinux => {
+                let mut cmd = Command::new("script");
+                cmd.args(["-q", "-a", "-e"])
+                    .arg(format!("--timing={}", timing_path(&self.path).display()))
+                    .args(["-c", &shell_command(program, args), &path]);
+                cmd
+            }
+            Recorder::ScriptBsd => {
+                let mut cmd = Command::new("script");
+                cmd.args(["-q", "-a", "-r", &path]).arg(program).args(args);
+                cmd
+            }
+        }
+    }
+}
+
+/// The exit code of a session, given how the program (or the recorder
+/// wrapping it, with `recording`) exited.
+pub fn exit_code(recording: Option<&Recording>, status: ExitStatus) -> i32 {
+    if let Some(r) = recording.filter(|r| r.recorder == Recorder::Asciinema) {
+        let code = std::fs::read_to_string(&r.exit_file).ok().and_then(|c| c.trim().parse().ok());
+        let _ = std::fs::remove_file(&r.exit_file);
+        if let Some(code) = code {
+            return code;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Replay a transcript created by `start()`.
+pub fn play(path: &Path) -> Result<()> {
+    if !path.exists() {
+        bail!("recording not found: {}", path.display());
+    }
+    let path_str = path.to_string_lossy().to_string();
+    let mut cmd = if path.extension().is_some_and(|e| e == "cast") {
+        let mut c = Command::new("asciinema");
+        c.args(["play", &path_str]);
+        c
+    } else if cfg!(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd"
+    )) {
+        let mut c = Command::new("script");
+        c.args(["-p", &path_str]);
+        c
+    } else {
+        let timing = timing_path(path);
+        if on_path("scriptreplay") && timing.exists() {
+            let mut c = Command::new("scriptreplay");
+            c.arg(format!("--timing={}", timing.display()))
+                .arg(&path_str);
+            c
+        } else {
+            // No replay tool — dump the raw transcript instead.
+            let mut c = Command::new("cat");
+            c.arg(&path_str);
+            c
+        }
+    };
+    let status = cmd
+        .status()
+        .with_context(|| format!("failed to replay {}", path.display()))?;
+    if !status.success() {
+        bail!("replay exited with status {status}");
+    }
+    Ok(())
+}
+
+fn detect_recorder() -> Result<Recorder> {
+    if on_path("asciinema") {
+        return Ok(Recorder::Asciinema);
+    }
+    if on_path("script") {
+        if cfg!(any(
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd"
+        )) {
+            return Ok(Recorder::ScriptBsd);
+        }
+        return Ok(Recorder::ScriptLinux);
+    }
+    bail!("session recording needs `asciinema` or `script` on PATH")
+}
+
+fn timing_path(path: &Path) -> PathBuf {
+    path.with_extension("timing")
+}
+
+/// Join a program and its args into a single `sh -c` string, single-quoting each word.
+fn shell_command(program: &Path, args: &[String]) -> String {
+    std::iter::once(program.to_string_lossy().to_string())
+        .chain(args.iter().cloned())
+        .map(|a| shell_quote(&a))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,82 @@
+use anyhow::{Result, bail};
+use base64::Engine as _;
+use oken_core::ssh;
+
+/// bash rc for a safe-mode shell: the user's own `.bashrc`, then stand-ins
+/// that refuse destructive commands, `noclobber`, and a red `SAFE` prompt.
+/// A guard rail against slips, not a sandbox: `command rm` still works.
+const RC: &str = r#"rm -f -- "$BASH_SOURCE"
+[ -f ~/.bashrc ] && . ~/.bashrc
+export OKEN_SAFE=1
+__oken_refuse() {
+    printf '\e[1;31moken safe mode:\e[0m %s is disabled here (exit to leave safe mode)\n' "$*" >&2
+    return 1
+}
+__oken_blocked=" rm rmdir mv dd shred truncate mkfs fdisk parted wipefs chmod chown chgrp kill pkill killall reboot shutdown halt poweroff crontab iptables su "
+for __oken_cmd in $__oken_blocked; do
+    eval "$__oken_cmd() { __oken_refuse $__oken_cmd; }"
+done
+unset __oken_cmd
+systemctl() {
+    case "$1" in
+        status|show|cat|list-*|is-*) command systemctl "$@" ;;
+        *) __oken_refuse "systemctl $1" ;;
+    esac
+}
+sudo() {
+    case "$1" in
+        -*|bash|sh|zsh|env) __oken_refuse "sudo $1" ;;
+        systemctl) case "$2" in
+            status|show|cat|list-*|is-*) command sudo "$@" ;;
+            *) __oken_refuse "sudo systemctl $2" ;;
+        esac ;;
+        *) if [[ "$__oken_blocked" == *" $1 "* ]]; then __oken_refuse "sudo $1"; else command sudo "$@"; fi ;;
+    esac
+}
+set -o noclobber
+PS1="\[\e[1;37;41m\] SAFE \[\e[0m\] $PS1"
+"#;
+
+/// The login shell command that writes `RC` to a temp file and starts bash
+/// with it. `RC` travels as base64, since ssh takes `RemoteCommand` as a
+/// single line. Hosts without bash refuse rather than opening a normal shell.
+fn remote_command() -> String {
+    let rc = base64::engine::general_purpose::STANDARD.encode(RC);
+    format!(
+        "if command -v bash >/dev/null 2>&1; then f=$(mktemp) && echo {rc} | base64 -d > \"$f\" && \
+         exec bash --rcfile \"$f\" -i; else echo 'oken: safe mode needs bash on the host' >&2; \
+         exit 1; fi"
+    )
+}
+
+/// Start the session in safe mode: ask for a terminal and run the safe
+/// shell as the `RemoteCommand`, which keeps reconnect working since the
+/// args still look like an interactive login.
+pub fn wrap(args: &mut Vec<String>) -> Result<()> {
+    if ssh::is_non_interactive(args) {
+        bail!("safe mode opens an interactive shell; it can't run a remote command or -N/-W");
+    }
+    // ssh expands %-tokens in RemoteCommand
+    let command = remote_command().replace('%', "%%");
+    args.splice(0..0, ["-t".to_string(), "-o".to_string(), format!("RemoteCommand={command}")]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_the_safe_shell_as_the_remote_command() {
+        let mut args = vec!["deploy@10.0.0.5".to_string()];
+        wrap(&mut args).unwrap();
+        assert_eq!(args[..2], ["-t", "-o"]);
+        assert!(args[2].starts_with("RemoteCommand=if command -v bash"));
+        assert!(!args[2].contains('\n'));
+        assert_eq!(args[3], "deploy@10.0.0.5");
+        assert!(!ssh::is_non_interactive(&args));
+
+        let mut args = vec!["web1".to_string(), "uptime".to_string()];
+        assert!(wrap(&mut args).is_err());
+    }
+}
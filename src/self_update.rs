@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+
+use crate::oken_config::OkenConfig;
+use crate::update_check::{self, CURRENT_VERSION};
+
+const DOWNLOADS: &str = "https://github.com/linkwithjoydeep/oken/releases/download";
+/// The target triple this binary was built for, which names its release asset.
+const TARGET: &str = env!("BUILD_TARGET");
+
+/// `oken self-update`: download the release archive for this platform, check
+/// it against the published SHA-256, and swap it in for the running binary.
+pub fn run(version: Option<&str>, cfg: &OkenConfig, dry_run: bool) -> Result<()> {
+    let proxy = cfg.update_proxy.as_deref();
+    let tag = match version {
+        Some(v) => format!("v{}", v.trim_start_matches('v')),
+        None => {
+            let tag = update_check::fetch_latest_tag(cfg.update_channel, proxy)?;
+            if !update_check::is_newer(tag.trim_start_matches('v'), CURRENT_VERSION) {
+                println!("already up to date (v{CURRENT_VERSION})");
+                return Ok(());
+            }
+            tag
+        }
+    };
+    if tag.trim_start_matches('v') == CURRENT_VERSION {
+        println!("already at {tag}");
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()
+        .and_then(|p| p.canonicalize())
+        .context("could not find the running oken binary")?;
+    let asset = asset_name();
+    let url = format!("{DOWNLOADS}/{tag}/{asset}");
+    if dry_run {
+        println!("would download {url}");
+        println!("would verify it against {url}.sha256");
+        println!("would replace {} (v{CURRENT_VERSION} → {tag})", exe.display());
+        return Ok(());
+    }
+
+    let dir = exe.parent().context("the oken binary has no parent directory")?;
+    // Unpack next to the binary so the final rename stays on one filesystem
+    let work = dir.join(format!(".oken-update-{}", std::process::id()));
+    std::fs::create_dir_all(&work).with_context(|| {
+        format!("could not write to {} (try again with sudo?)", dir.display())
+    })?;
+    let result = install(&url, &asset, &work, &exe, &tag, proxy);
+    let _ = std::fs::remove_dir_all(&work);
+    result?;
+    println!("Updated oken v{CURRENT_VERSION} → {tag}");
+    Ok(())
+}
+
+fn install(
+    url: &str,
+    asset: &str,
+    work: &Path,
+    exe: &Path,
+    tag: &str,
+    proxy: Option<&str>,
+) -> Result<()> {
+    println!("Downloading {url}");
+    let archive = download(url, proxy)?;
+    let published = String::from_utf8(download(&format!("{url}.sha256"), proxy)?)
+        .context("checksum file is not text")?;
+    let expected = published
+        .split_whitespace()
+        .next()
+        .context("checksum file is empty")?
+        .to_ascii_lowercase();
+    let actual = sha256_hex(&archive);
+    if actual != expected {
+        bail!("checksum mismatch for {asset}: expected {expected}, got {actual}");
+    }
+    log::debug!("sha256 {actual} matches");
+
+    let archive_path = work.join(asset);
+    std::fs::write(&archive_path, &archive)?;
+    // bsdtar (macOS, Windows) and GNU tar both unpack .tar.xz and .zip
+    let status = Command::new("tar")
+        .arg("-xf")
+        .arg(&archive_path)
+        .arg("-C")
+        .arg(work)
+        .status()
+        .context("failed to run tar to unpack the release")?;
+    if !status.success() {
+        bail!("tar could not unpack {asset}");
+    }
+    let new = find_binary(work).with_context(|| format!("no oken binary in {asset}"))?;
+
+    // Make sure it runs here before replacing a working binary with it
+    let output = Command::new(&new)
+        .arg("--version")
+        .output()
+        .context("the downloaded oken binary does not run on this system")?;
+    let reported = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() || !reported.contains(tag.trim_start_matches('v')) {
+        bail!("the downloaded binary reports '{}', not {tag}", reported.trim());
+    }
+    replace(&new, exe)
+}
+
+/// `oken-x86_64-unknown-linux-gnu.tar.xz`, as cargo-dist names the archives.
+fn asset_name() -> String {
+    let ext = if cfg!(windows) { "zip" } else { "tar.xz" };
+    format!("oken-{TARGET}.{ext}")
+}
+
+fn download(url: &str, proxy: Option<&str>) -> Result<Vec<u8>> {
+    let response = update_check::agent(proxy, Duration::from_secs(120))?
+        .get(url)
+        .set("User-Agent", &format!("oken/{CURRENT_VERSION}"))
+        .call()
+        .with_context(|| format!("could not download {url}"))?;
+    let mut body = Vec::new();
+    response.into_reader().read_to_end(&mut body)?;
+    Ok(body)
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// The `oken` executable somewhere under `dir` (archives wrap it in a folder).
+fn find_binary(dir: &Path) -> Option<PathBuf> {
+    let name = format!("oken{}", std::env::consts::EXE_SUFFIX);
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if let Some(found) = find_binary(&path) {
+                return Some(found);
+            }
+        } else if path.file_name().is_some_and(|n| n == name.as_str()) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Rename the new binary over the old one, so a crash midway leaves either
+/// the old or the new version in place, never half a file.
+#[cfg(unix)]
+fn replace(new: &Path, exe: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(new, std::fs::Permissions::from_mode(0o755))?;
+    std::fs::rename(new, exe).with_context(|| format!("could not replace {}", exe.display()))
+}
+
+/// Windows won't overwrite a running executable, but it will rename one.
+#[cfg(not(unix))]
+fn replace(new: &Path, exe: &Path) -> Result<()> {
+    let old = exe.with_extension("old.exe");
+    let _ = std::fs::remove_file(&old);
+    std::fs::rename(exe, &old).with_context(|| format!("could not move {}", exe.display()))?;
+    if let Err(e) = std::fs::rename(new, exe) {
+        let _ = std::fs::rename(&old, exe);
+        return Err(e).with_context(|| format!("could not replace {}", exe.display()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_like_sha256sum() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
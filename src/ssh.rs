@@ -1,10 +1,14 @@
+use std::collections::BTreeMap;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result, bail};
 
+use crate::hosts::Host;
+use crate::recording::Recording;
+
 /// Find the system `ssh` binary, skipping our own binary if oken is aliased as `ssh`.
-pub(crate) fn find_ssh() -> Result<PathBuf> {
+pub fn find_ssh() -> Result<PathBuf> {
     let our_exe = env::current_exe().ok();
 
     // Search PATH for `ssh`, skipping any entry that resolves to our own binary
@@ -13,10 +17,10 @@ pub(crate) fn find_ssh() -> Result<PathBuf> {
             let candidate = dir.join("ssh");
             if candidate.is_file() {
                 // Skip if this is actually us (oken aliased as ssh)
-                if let Some(ref ours) = our_exe {
-                    if same_file(&candidate, ours) {
-                        continue;
-                    }
+                if let Some(ref ours) = our_exe
+                    && same_file(&candidate, ours)
+                {
+                    continue;
                 }
                 return Ok(candidate);
             }
@@ -27,10 +31,10 @@ pub(crate) fn find_ssh() -> Result<PathBuf> {
     for path in ["/usr/bin/ssh", "/usr/local/bin/ssh"] {
         let p = PathBuf::from(path);
         if p.is_file() {
-            if let Some(ref ours) = our_exe {
-                if same_file(&p, ours) {
-                    continue;
-                }
+            if let Some(ref ours) = our_exe
+                && same_file(&p, ours)
+            {
+                continue;
             }
             return Ok(p);
         }
@@ -40,7 +44,7 @@ pub(crate) fn find_ssh() -> Result<PathBuf> {
 }
 
 /// Check if two paths refer to the same file (following symlinks).
-fn same_file(a: &PathBuf, b: &PathBuf) -> bool {
+fn same_file(a: &Path, b: &Path) -> bool {
     match (a.canonicalize(), b.canonicalize()) {
         (Ok(a), Ok(b)) => a == b,
         _ => false,
@@ -48,7 +52,7 @@ fn same_file(a: &PathBuf, b: &PathBuf) -> bool {
 }
 
 /// SSH flags that take a following argument value.
-pub(crate) const FLAGS_WITH_VALUES: &[&str] = &[
+pub const FLAGS_WITH_VALUES: &[&str] = &[
     "-p", "-i", "-o", "-l", "-L", "-R", "-D", "-F", "-J", "-W", "-b", "-c", "-m", "-e", "-S", "-E",
     "-B", "-w", "-O",
 ];
@@ -84,8 +88,26 @@ pub fn extract_target_host(args: &[String]) -> Option<String> {
 /// Like `extract_target_host()` but returns the full `user@host` string
 /// instead of stripping the user part.
 pub fn extract_target_host_full(args: &[String]) -> Option<String> {
+    target_index(args).map(|i| args[i].clone())
+}
+
+/// The args with the target's host part swapped for `hostname`, keeping any
+/// `user@`.
+pub fn with_target_host(args: &[String], hostname: &str) -> Vec<String> {
+    let mut args = args.to_vec();
+    if let Some(i) = target_index(&args) {
+        args[i] = match args[i].split_once('@') {
+            Some((user, _)) => format!("{user}@{hostname}"),
+            None => hostname.to_string(),
+        };
+    }
+    args
+}
+
+/// Position of the first positional argument, the target.
+fn target_index(args: &[String]) -> Option<usize> {
     let mut skip_next = false;
-    for arg in args {
+    for (i, arg) in args.iter().enumerate() {
         if skip_next {
             skip_next = false;
             continue;
@@ -97,11 +119,52 @@ pub fn extract_target_host_full(args: &[String]) -> Option<String> {
         if arg.starts_with('-') {
             continue;
         }
-        return Some(arg.clone());
+        return Some(i);
     }
     None
 }
 
+/// True if the args ask ssh for something other than an interactive shell:
+/// a remote command after the target, `-N` (no command), or `-W` (stdio
+/// forwarding). Re-running these on reconnect could repeat side effects.
+pub fn is_non_interactive(args: &[String]) -> bool {
+    let mut skip_next = false;
+    let mut seen_target = false;
+    for arg in args {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+        if arg == "-W" {
+            return true;
+        }
+        if FLAGS_WITH_VALUES.contains(&arg.as_str()) {
+            skip_next = true;
+            continue;
+        }
+        if let Some(flags) = arg.strip_prefix('-') {
+            // Combined short flags like `-fNT`; stop at the first flag that
+            // takes a value, since the rest is that value (e.g. `-oNumber…`)
+            for c in flags.chars() {
+                if c == 'N' {
+                    return true;
+                }
+                if FLAGS_WITH_VALUES.iter().any(|f| f.ends_with(c)) {
+                    break;
+                }
+            }
+            continue;
+        }
+        if seen_target {
+            // ssh keeps parsing options after the destination (`host -p 22`);
+            // the next positional starts the remote command
+            return true;
+        }
+        seen_target = true;
+    }
+    false
+}
+
 /// Extract the port from SSH arguments (scans for `-p <port>`).
 pub fn extract_port(args: &[String]) -> Option<u16> {
     let mut iter = args.iter();
@@ -124,17 +187,300 @@ pub fn extract_identity_file(args: &[String]) -> Option<String> {
     None
 }
 
+/// How ssh would reach a host, according to `ssh -G`.
+#[derive(Debug, PartialEq)]
+pub enum Route {
+    Direct { hostname: String, port: u16 },
+    /// Through ProxyJump hops, `[user@]host[:port]` separated by commas.
+    Jump { jumps: String, hostname: String, port: u16 },
+    /// Through a ProxyCommand, with `%h`, `%p`, `%r` and `%%` substituted.
+    Command { command: String, hostname: String, port: u16 },
+}
+
+/// Resolve how ssh would reach the host in these args, using `ssh -G` so
+/// ~/.ssh/config (HostName, Port, ProxyJump, ProxyCommand) is honoured.
+pub fn resolve_route(args: &[String]) -> Option<Route> {
+    let ssh = find_ssh().ok()?;
+    let output = std::process::Command::new(&ssh)
+        .arg("-G")
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_route(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Whether ssh would forward the agent for these args: `-A`, `-o ForwardAgent`
+/// or ~/.ssh/config, as `ssh -G` reports it. `-a` turns it off.
+pub fn forwards_agent(args: &[String]) -> bool {
+    let Ok(ssh) = find_ssh() else {
+        return false;
+    };
+    let Ok(output) = std::process::Command::new(&ssh)
+        .arg("-G")
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+    else {
+        return false;
+    };
+    // `yes`, or the agent socket to forward; only `no` turns it off
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|l| l.strip_prefix("forwardagent "))
+        .is_some_and(|v| v != "no")
+}
+
+/// Resolve the host and port ssh would actually dial for these args.
+/// With a ProxyJump, the first jump host is returned instead.
+pub fn resolve_endpoint(args: &[String]) -> Option<(String, u16)> {
+    endpoint(resolve_route(args)?)
+}
+
+fn endpoint(route: Route) -> Option<(String, u16)> {
+    match route {
+        Route::Jump { jumps, .. } => {
+            // "[user@]host[:port][,next…]" — only the first hop is directly reachable
+            let first = jumps.split(',').next()?;
+            let host_port = first.rsplit_once('@').map(|(_, h)| h).unwrap_or(first);
+            Some(match host_port.rsplit_once(':') {
+                Some((h, p)) => (h.to_string(), p.parse().unwrap_or(22)),
+                None => (host_port.to_string(), 22),
+            })
+        }
+        Route::Direct { hostname, port } | Route::Command { hostname, port, .. } => {
+            Some((hostname, port))
+        }
+    }
+}
+
+fn parse_route(ssh_g: &str) -> Option<Route> {
+    let mut hostname = None;
+    let mut port = 22;
+    let mut user = String::new();
+    let mut jump = None;
+    let mut command = None;
+    for line in ssh_g.lines() {
+        match line.split_once(' ') {
+            Some(("hostname", v)) => hostname = Some(v.to_string()),
+            Some(("port", v)) => port = v.parse().unwrap_or(22),
+            Some(("user", v)) => user = v.to_string(),
+            Some(("proxyjump", v)) if v != "none" => jump = Some(v.to_string()),
+            Some(("proxycommand", v)) if v != "none" => command = Some(v.to_string()),
+            _ => {}
+        }
+    }
+    let hostname = hostname?;
+    if let Some(jumps) = jump {
+        return Some(Route::Jump { jumps, hostname, port });
+    }
+    if let Some(template) = command {
+        let mut command = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('%', Some(token @ ('h' | 'p' | 'r' | '%'))) => {
+                    chars.next();
+                    match token {
+                        'h' => command.push_str(&hostname),
+                        'p' => command.push_str(&port.to_string()),
+                        'r' => command.push_str(&user),
+                        _ => command.push('%'),
+                    }
+                }
+                _ => command.push(c),
+            }
+        }
+        return Some(Route::Command { command, hostname, port });
+    }
+    Some(Route::Direct { hostname, port })
+}
+
 /// Run `ssh` with the given arguments and return its exit code.
 /// Uses spawn()+wait() on all platforms so post-connection work (audit log,
 /// update check flush) can happen after the session ends.
-pub fn run(args: &[String]) -> Result<i32> {
+/// When `recording` is set, the session is wrapped in the recorder.
+pub fn run(args: &[String], recording: Option<&Recording>) -> Result<i32> {
     let ssh = find_ssh().context("failed to locate ssh")?;
-    let status = std::process::Command::new(&ssh)
-        .args(args)
+    let mut cmd = match recording {
+        Some(rec) => rec.command(&ssh, args),
+        None => {
+            let mut c = std::process::Command::new(&ssh);
+            c.args(args);
+            c
+        }
+    };
+    let status = cmd
         .stdin(std::process::Stdio::inherit())
         .stdout(std::process::Stdio::inherit())
         .stderr(std::process::Stdio::inherit())
         .status()
         .with_context(|| format!("failed to run ssh at {}", ssh.display()))?;
-    Ok(status.code().unwrap_or(1))
+    Ok(crate::recording::exit_code(recording, status))
+}
+
+/// Expand a leading `~/` to the home directory, for paths from hosts.toml
+/// that are handed to ssh or checked without a shell in between.
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Why ssh can't use an identity file.
+#[derive(Debug, PartialEq)]
+pub enum IdentityProblem {
+    Missing,
+    /// Accessible by group or others, so ssh ignores the key. Holds the mode.
+    TooOpen(u32),
+}
+
+/// Check an identity file the way ssh will: it has to exist and, on Unix, be
+/// private to its owner (0600 or stricter).
+pub fn identity_problem(path: &Path) -> Option<IdentityProblem> {
+    let Ok(meta) = std::fs::metadata(path) else {
+        return Some(IdentityProblem::Missing);
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = meta.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Some(IdentityProblem::TooOpen(mode));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = meta;
+    None
+}
+
+/// ssh arguments that reach `host`: the target, port, identity file,
+/// certificate and a `ProxyCommand` for its proxy or SSM/IAP transport.
+/// `address`, such as the one `addresses::choose` picked, replaces `hostname`,
+/// and `proxies` are the `[proxies]` presets from config.toml.
+pub fn build_ssh_args(
+    host: &Host,
+    address: Option<&str>,
+    proxies: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut args = Vec::new();
+
+    match (&host.user, address.or(host.hostname.as_deref())) {
+        (Some(user), Some(hostname)) => args.push(format!("{}@{}", user, hostname)),
+        (None, Some(hostname)) => args.push(hostname.to_string()),
+        // ssh_config-only host (no hostname stored) — use alias and let SSH resolve it
+        _ => args.push(host.alias.clone()),
+    }
+
+    if let Some(port) = host.port {
+        args.push("-p".to_string());
+        args.push(port.to_string());
+    }
+    if !host.address_family.is_any() {
+        args.push("-o".to_string());
+        args.push(format!("AddressFamily={}", host.address_family.as_str()));
+    }
+    if let Some(ref identity) = host.identity_file {
+        args.push("-i".to_string());
+        args.push(expand_tilde(identity).to_string_lossy().into_owned());
+    }
+    if let Some(ref cert) = host.certificate_file {
+        args.push("-o".to_string());
+        args.push(format!("CertificateFile={}", expand_tilde(cert).display()));
+    }
+    // ssh over SSM or IAP goes through that service's proxy preset
+    let connect_proxy = host.connect.proxy_preset().map(|preset| {
+        std::iter::once(preset.to_string())
+            .chain(host.connect_args.iter().map(|a| crate::util::shell_quote(a)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    });
+    if let Some(ref spec) = host.proxy.clone().or(connect_proxy) {
+        match crate::proxy::expand(spec, proxies) {
+            Ok(command) => {
+                args.push("-o".to_string());
+                args.push(format!("ProxyCommand={command}"));
+            }
+            Err(e) => log::warn!("{e:#}; connecting without a proxy"),
+        }
+    }
+
+    args
+}
+
+/// Extract only SSH flags (and their values) from args; all positionals are dropped.
+pub fn extract_ssh_flags(args: &[String]) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut skip_next = false;
+    for arg in args {
+        if skip_next {
+            result.push(arg.clone());
+            skip_next = false;
+            continue;
+        }
+        if FLAGS_WITH_VALUES.contains(&arg.as_str()) {
+            result.push(arg.clone());
+            skip_next = true;
+            continue;
+        }
+        if arg.starts_with('-') {
+            result.push(arg.clone());
+            continue;
+        }
+        // Non-flag positional: skip (it's the host or an unrecognised arg)
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_non_interactive_args() {
+        let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+        assert!(!is_non_interactive(&args("-p 2222 deploy@web1")));
+        assert!(!is_non_interactive(&args("deploy@web1 -p 2222 -i id")));
+        assert!(!is_non_interactive(&args("-o NumberOfPasswordPrompts=1 web1")));
+        assert!(!is_non_interactive(&args("-oNumberOfPasswordPrompts=1 web1")));
+        assert!(is_non_interactive(&args("web1 uptime")));
+        assert!(is_non_interactive(&args("-fN -L 8080:localhost:80 web1")));
+        assert!(is_non_interactive(&args("-W db:5432 bastion")));
+    }
+
+    #[test]
+    fn swaps_the_target_host() {
+        let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+        assert_eq!(
+            with_target_host(&args("-o ServerAliveInterval=60 deploy@10.0.0.5 -p 22"), "vpn.example.com"),
+            args("-o ServerAliveInterval=60 deploy@vpn.example.com -p 22")
+        );
+        assert_eq!(with_target_host(&args("-v web1"), "10.0.0.6"), args("-v 10.0.0.6"));
+    }
+
+    #[test]
+    fn parses_ssh_g_endpoint() {
+        let parse_endpoint = |out| endpoint(parse_route(out)?);
+        let out = "user deploy\nhostname 10.0.1.50\nport 2222\nproxyjump none\n";
+        assert_eq!(parse_endpoint(out), Some(("10.0.1.50".to_string(), 2222)));
+
+        let out = "hostname 10.0.1.50\nport 22\nproxyjump ops@bastion:2200,inner\n";
+        assert_eq!(parse_endpoint(out), Some(("bastion".to_string(), 2200)));
+
+        let out = "user me\nhostname db\nport 22\nproxycommand nc -X 5 %r@%h %p 100%%\n";
+        assert_eq!(
+            parse_route(out),
+            Some(Route::Command {
+                command: "nc -X 5 me@db 22 100%".to_string(),
+                hostname: "db".to_string(),
+                port: 22,
+            })
+        );
+    }
 }
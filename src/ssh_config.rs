@@ -10,17 +10,36 @@ pub fn parse_ssh_config() -> Result<Vec<String>> {
         return Ok(Vec::new());
     }
     let mut hosts = Vec::new();
-    parse_file(&config_path, &home, &mut hosts)?;
+    parse_file(&config_path, &home, &mut hosts, &mut Vec::new())?;
     hosts.sort();
     hosts.dedup();
     Ok(hosts)
 }
 
-fn parse_file(path: &Path, home: &Path, hosts: &mut Vec<String>) -> Result<()> {
+/// `Include` patterns in `~/.ssh/config` (or the files it includes) that
+/// match no file, with the file they appear in.
+pub fn missing_includes() -> Vec<(PathBuf, String)> {
+    let home = dirs::home_dir().unwrap_or_default();
+    let mut missing = Vec::new();
+    let _ = parse_file(&home.join(".ssh/config"), &home, &mut Vec::new(), &mut missing);
+    missing
+}
+
+fn parse_file(
+    path: &Path,
+    home: &Path,
+    hosts: &mut Vec<String>,
+    missing: &mut Vec<(PathBuf, String)>,
+) -> Result<()> {
     let contents = match std::fs::read_to_string(path) {
         Ok(c) => c,
-        Err(_) => return Ok(()), // silently skip unreadable files
+        Err(e) => {
+            // Skip unreadable files
+            log::debug!("skipping {}: {e}", path.display());
+            return Ok(());
+        }
     };
+    log::trace!("reading {}", path.display());
 
     let mut in_match_block = false;
 
@@ -46,6 +65,8 @@ fn parse_file(path: &Path, home: &Path, hosts: &mut Vec<String>) -> Result<()> {
                     // Skip wildcard patterns
                     if !alias.contains('*') && !alias.contains('?') {
                         hosts.push(alias.to_string());
+                    } else {
+                        log::trace!("skipping pattern Host {alias}");
                     }
                 }
             }
@@ -53,7 +74,11 @@ fn parse_file(path: &Path, home: &Path, hosts: &mut Vec<String>) -> Result<()> {
                 in_match_block = true;
             }
             "include" if !in_match_block => {
-                process_include(value, home, path, hosts)?;
+                for pattern in value.split_whitespace() {
+                    if !process_include(pattern, home, path, hosts, missing)? {
+                        missing.push((path.to_path_buf(), pattern.to_string()));
+                    }
+                }
             }
             _ => {}
         }
@@ -63,7 +88,7 @@ fn parse_file(path: &Path, home: &Path, hosts: &mut Vec<String>) -> Result<()> {
 }
 
 /// Split a line into keyword and value, handling both `Key Value` and `Key=Value`.
-fn split_keyword(line: &str) -> Option<(&str, &str)> {
+pub fn split_keyword(line: &str) -> Option<(&str, &str)> {
     // Handle `Key=Value`
     if let Some(eq_pos) = line.find('=') {
         let key = line[..eq_pos].trim();
@@ -82,7 +107,14 @@ fn split_keyword(line: &str) -> Option<(&str, &str)> {
     Some((key, val))
 }
 
-fn process_include(pattern: &str, home: &Path, config_path: &Path, hosts: &mut Vec<String>) -> Result<()> {
+/// Parse the files an Include pattern matches. Returns false if it matches none.
+fn process_include(
+    pattern: &str,
+    home: &Path,
+    config_path: &Path,
+    hosts: &mut Vec<String>,
+    missing: &mut Vec<(PathBuf, String)>,
+) -> Result<bool> {
     let expanded = expand_tilde(pattern, home);
 
     // If not absolute, resolve relative to the directory containing the config file
@@ -96,18 +128,23 @@ fn process_include(pattern: &str, home: &Path, config_path: &Path, hosts: &mut V
     // Use glob to expand wildcards
     let paths = match glob::glob(&base) {
         Ok(p) => p,
-        Err(_) => return Ok(()),
+        Err(e) => {
+            log::debug!("{}: bad Include pattern {pattern}: {e}", config_path.display());
+            return Ok(false);
+        }
     };
 
-    for entry in paths {
-        if let Ok(path) = entry {
-            if path.is_file() {
-                parse_file(&path, home, hosts)?;
-            }
+    let mut matched = false;
+    for path in paths.flatten() {
+        if path.is_file() {
+            matched = true;
+            parse_file(&path, home, hosts, missing)?;
         }
     }
-
-    Ok(())
+    if !matched {
+        log::debug!("{}: Include {pattern} matches no files", config_path.display());
+    }
+    Ok(matched)
 }
 
 fn expand_tilde(path: &str, home: &Path) -> PathBuf {
@@ -120,6 +157,69 @@ fn expand_tilde(path: &str, home: &Path) -> PathBuf {
     }
 }
 
+/// Write the hosts saved in hosts.toml to `~/.config/oken/ssh_config` and make
+/// sure `~/.ssh/config` includes that file, so tools that only read the ssh
+/// config (VS Code Remote-SSH, rsync, git, …) know oken's aliases too.
+/// Returns true if the Include line had to be added.
+pub fn sync_include(hosts: &[crate::hosts::Host]) -> Result<bool> {
+    let config_dir = crate::config::config_dir()?;
+    std::fs::create_dir_all(&config_dir)?;
+    let generated = config_dir.join("ssh_config");
+    std::fs::write(&generated, render_hosts(hosts))?;
+
+    let home = dirs::home_dir().unwrap_or_default();
+    let config_path = home.join(".ssh/config");
+    let existing = std::fs::read_to_string(&config_path).unwrap_or_default();
+    let generated = generated.display().to_string();
+    let include = if generated.contains(' ') {
+        format!("Include \"{generated}\"")
+    } else {
+        format!("Include {generated}")
+    };
+    if existing.lines().any(|l| l.trim() == include) {
+        return Ok(false);
+    }
+    std::fs::create_dir_all(home.join(".ssh"))?;
+    // Include has to come before the first Host block, or it only applies inside it
+    std::fs::write(
+        &config_path,
+        format!("# Hosts saved with oken\n{include}\n\n{existing}"),
+    )?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&config_path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(true)
+}
+
+/// `Host` blocks for the hosts that come from hosts.toml.
+fn render_hosts(hosts: &[crate::hosts::Host]) -> String {
+    let mut out =
+        String::from("# Generated by oken from hosts.toml. Changes here are overwritten.\n");
+    for host in hosts.iter().filter(|h| !h.from_ssh_config) {
+        let names: Vec<&str> =
+            std::iter::once(&host.alias).chain(&host.aliases).map(String::as_str).collect();
+        out.push_str(&format!("\nHost {}\n", names.join(" ")));
+        if let Some(hostname) = &host.hostname {
+            out.push_str(&format!("    HostName {hostname}\n"));
+        }
+        if let Some(user) = &host.user {
+            out.push_str(&format!("    User {user}\n"));
+        }
+        if let Some(port) = host.port {
+            out.push_str(&format!("    Port {port}\n"));
+        }
+        if !host.address_family.is_any() {
+            out.push_str(&format!("    AddressFamily {}\n", host.address_family.as_str()));
+        }
+        if let Some(identity) = &host.identity_file {
+            out.push_str(&format!("    IdentityFile {identity}\n"));
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -137,7 +237,7 @@ mod tests {
 
         let home = dir.path();
         let mut hosts = Vec::new();
-        parse_file(&config, home, &mut hosts).unwrap();
+        parse_file(&config, home, &mut hosts, &mut Vec::new()).unwrap();
         assert!(hosts.contains(&"foo".to_string()));
         assert!(hosts.contains(&"bar".to_string()));
         assert!(hosts.contains(&"baz".to_string()));
@@ -145,6 +245,29 @@ mod tests {
         assert!(!hosts.iter().any(|h| h.contains('*')));
     }
 
+    #[test]
+    fn renders_saved_hosts() {
+        let hosts = [
+            crate::hosts::Host {
+                alias: "prod-web".to_string(),
+                hostname: Some("10.0.1.50".to_string()),
+                user: Some("deploy".to_string()),
+                port: Some(2222),
+                ..Default::default()
+            },
+            crate::hosts::Host {
+                alias: "from-config".to_string(),
+                from_ssh_config: true,
+                ..Default::default()
+            },
+        ];
+        let rendered = render_hosts(&hosts);
+        assert!(rendered.contains(
+            "Host prod-web\n    HostName 10.0.1.50\n    User deploy\n    Port 2222\n"
+        ));
+        assert!(!rendered.contains("from-config"));
+    }
+
     #[test]
     fn include_directive() {
         let dir = tempfile::tempdir().unwrap();
@@ -156,13 +279,15 @@ mod tests {
 
         let config = ssh_dir.join("config");
         let mut f = std::fs::File::create(&config).unwrap();
-        writeln!(f, "Include {}", included.display()).unwrap();
+        writeln!(f, "Include {} conf.d/*", included.display()).unwrap();
         writeln!(f, "Host main-host").unwrap();
 
         let home = dir.path();
         let mut hosts = Vec::new();
-        parse_file(&config, home, &mut hosts).unwrap();
+        let mut missing = Vec::new();
+        parse_file(&config, home, &mut hosts, &mut missing).unwrap();
         assert!(hosts.contains(&"main-host".to_string()));
         assert!(hosts.contains(&"included-host".to_string()));
+        assert_eq!(missing, [(config, "conf.d/*".to_string())]);
     }
 }
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, ToSocketAddrs};
+
+use crate::hosts::Host;
+use crate::time_utils;
+
+/// Average month, for "not used in N months".
+const MONTH_SECS: u64 = 30 * 86400;
+
+/// A saved host that looks unused, and why.
+pub struct Stale {
+    pub alias: String,
+    pub reasons: Vec<String>,
+}
+
+/// Why a host with this last connection time (ISO 8601, from history) is
+/// stale, if it is.
+fn history_reason(last_connected: Option<&str>, now: u64, months: u64) -> Option<String> {
+    let Some(last) = last_connected else {
+        return Some("never connected".to_string());
+    };
+    let at = time_utils::iso8601_to_unix(last)?;
+    let age = now.saturating_sub(at) / MONTH_SECS;
+    (age >= months).then(|| format!("last connected {} ({age} months ago)", &last[..10]))
+}
+
+/// Whether oken resolves the hostname itself: not for IP addresses, or
+/// hosts reached through a proxy or cloud service that resolves it remotely.
+fn needs_dns(host: &Host) -> bool {
+    let Some(hostname) = host.hostname.as_deref() else {
+        return false;
+    };
+    hostname.parse::<IpAddr>().is_err() && host.proxy.is_none() && host.connect.is_direct()
+}
+
+/// Hosts not connected to within `months` (or ever), and with `dns`, hosts
+/// whose name no longer resolves. `last_connected` maps aliases to history
+/// timestamps.
+pub fn find_stale(
+    hosts: &[Host],
+    last_connected: &HashMap<String, String>,
+    now: u64,
+    months: u64,
+    dns: bool,
+) -> Vec<Stale> {
+    let unresolved: Vec<bool> = std::thread::scope(|scope| {
+        let lookups: Vec<_> = hosts
+            .iter()
+            .map(|host| {
+                scope.spawn(move || {
+                    dns && needs_dns(host)
+                        && (host.hostname.as_deref().unwrap_or_default(), host.port.unwrap_or(22))
+                            .to_socket_addrs()
+                            .is_err()
+                })
+            })
+            .collect();
+        lookups.into_iter().map(|l| l.join().unwrap_or(false)).collect()
+    });
+    hosts
+        .iter()
+        .zip(unresolved)
+        .filter_map(|(host, unresolved)| {
+            let last = last_connected.get(&host.alias).map(String::as_str);
+            let mut reasons: Vec<String> = history_reason(last, now, months).into_iter().collect();
+            if unresolved {
+                reasons.push(format!("{} does not resolve", host.hostname.as_deref()?));
+            }
+            (!reasons.is_empty()).then(|| Stale {
+                alias: host.alias.clone(),
+                reasons,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_unused_hosts() {
+        let now = time_utils::iso8601_to_unix("2026-10-01").unwrap();
+        assert_eq!(history_reason(None, now, 6).as_deref(), Some("never connected"));
+        assert_eq!(history_reason(Some("2026-09-01T10:00:00Z"), now, 6), None);
+        assert_eq!(
+            history_reason(Some("2025-12-24T10:00:00Z"), now, 6).as_deref(),
+            Some("last connected 2025-12-24 (9 months ago)")
+        );
+
+        let host = |alias: &str, hostname: &str| Host {
+            alias: alias.to_string(),
+            hostname: Some(hostname.to_string()),
+            ..Default::default()
+        };
+        let hosts = [
+            host("web", "10.0.0.1"),
+            host("gone", "gone.invalid"),
+            host("new", "10.0.0.2"),
+        ];
+        let recent = HashMap::from([
+            ("web".to_string(), "2026-09-30T08:00:00Z".to_string()),
+            ("gone".to_string(), "2026-09-30T08:00:00Z".to_string()),
+        ]);
+        let stale = find_stale(&hosts, &recent, now, 6, true);
+        assert_eq!(stale.len(), 2);
+        assert_eq!(stale[0].alias, "gone");
+        assert_eq!(stale[0].reasons, ["gone.invalid does not resolve"]);
+        assert_eq!(stale[1].reasons, ["never connected"]);
+        assert!(find_stale(&hosts, &recent, now, 6, false)[0].alias == "new");
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use oken_core::hosts;
+use oken_core::oken_config::OkenConfig;
+use oken_core::time_utils;
+
+use crate::audit::{self, Record};
+use crate::danger;
+
+/// Sessions during one week, Monday to Sunday (UTC).
+#[derive(Default)]
+struct Week {
+    sessions: usize,
+    secs: u64,
+    /// Seconds per host alias.
+    hosts: BTreeMap<String, u64>,
+}
+
+/// Days since the epoch of the Monday starting the week `secs` falls in.
+fn week_start(secs: u64) -> i64 {
+    let days = (secs / 86400) as i64;
+    // 1970-01-01 was a Thursday
+    days - (days + 3) % 7
+}
+
+/// Add up `records` by the week they started in, from the week starting on
+/// day `first` on.
+fn tally<'a>(records: impl Iterator<Item = &'a Record>, first: i64) -> BTreeMap<i64, Week> {
+    let mut weeks: BTreeMap<i64, Week> = BTreeMap::new();
+    for r in records {
+        let Some(at) = time_utils::iso8601_to_unix(&r.time.replace(' ', "T")) else {
+            continue;
+        };
+        let start = week_start(at);
+        if start < first {
+            continue;
+        }
+        let secs = r.duration_secs.unwrap_or(0);
+        let week = weeks.entry(start).or_default();
+        week.sessions += 1;
+        week.secs += secs;
+        *week.hosts.entry(r.alias.clone()).or_default() += secs;
+    }
+    weeks
+}
+
+/// `oken stats`: sessions and time spent in them for each of the last
+/// `weeks` weeks, from the audit log. With `danger_only`, just the sessions
+/// on danger hosts.
+pub fn show(danger_only: bool, weeks: u32, cfg: &OkenConfig) -> Result<()> {
+    let all = hosts::list_all_hosts().unwrap_or_default();
+    // Entries from before the log recorded it go by the host's tags today
+    let is_danger = |r: &&Record| {
+        r.danger.unwrap_or_else(|| {
+            hosts::find(&all, &r.alias).is_some_and(|h| !danger::evaluate(h, cfg).is_empty())
+        })
+    };
+    let records = audit::recent(usize::MAX)?;
+    let this_week = week_start(crate::daemon::unix_now());
+    let first = this_week - 7 * (i64::from(weeks.max(1)) - 1);
+    let tallies = if danger_only {
+        tally(records.iter().filter(is_danger), first)
+    } else {
+        tally(records.iter(), first)
+    };
+    if tallies.is_empty() {
+        let what = if danger_only { "on danger hosts " } else { "" };
+        println!("No sessions {what}in the last {} weeks.", weeks.max(1));
+        return Ok(());
+    }
+
+    println!("{:<10}  {:>8}  {:>8}  TOP HOSTS", "WEEK OF", "SESSIONS", "TIME");
+    let empty = Week::default();
+    let (mut sessions, mut secs) = (0, 0);
+    for start in (first..=this_week).step_by(7) {
+        let week = tallies.get(&start).unwrap_or(&empty);
+        let (y, m, d) = time_utils::civil_from_days(start);
+        let time = match week.secs {
+            0 if week.sessions == 0 => "-".to_string(),
+            s => audit::format_duration(s),
+        };
+        let mut top: Vec<(&String, &u64)> = week.hosts.iter().collect();
+        top.sort_by(|a, b| b.1.cmp(a.1));
+        let top: Vec<String> = top
+            .iter()
+            .take(3)
+            .map(|(alias, s)| format!("{alias} ({})", audit::format_duration(**s)))
+            .collect();
+        let line = format!("{y:04}-{m:02}-{d:02}  {:>8}  {time:>8}  {}", week.sessions, top.join(", "));
+        println!("{}", line.trim_end());
+        sessions += week.sessions;
+        secs += week.secs;
+    }
+    println!("{:<10}  {sessions:>8}  {:>8}", "total", audit::format_duration(secs));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_up_sessions_by_week() {
+        // 2026-10-12 is a Monday
+        let monday = time_utils::epoch_days(2026, 10, 12);
+        assert_eq!(week_start(time_utils::iso8601_to_unix("2026-10-18T23:59:59Z").unwrap()), monday);
+        assert_eq!(week_start(time_utils::iso8601_to_unix("2026-10-12T00:00:00Z").unwrap()), monday);
+
+        let record = |time: &str, alias: &str, secs| Record {
+            id: 0,
+            time: time.to_string(),
+            alias: alias.to_string(),
+            target: String::new(),
+            duration_secs: Some(secs),
+            exit_code: Some(0),
+            recording: None,
+            reason: String::new(),
+            danger: Some(true),
+        };
+        let records = [
+            record("2026-10-05 09:00:00", "prod-web", 60),
+            record("2026-10-13 09:00:00", "prod-web", 600),
+            record("2026-10-14 17:30:00", "prod-db", 120),
+        ];
+        let weeks = tally(records.iter(), monday);
+        assert_eq!(weeks.len(), 1);
+        let week = &weeks[&monday];
+        assert_eq!((week.sessions, week.secs), (2, 720));
+        assert_eq!(week.hosts["prod-db"], 120);
+    }
+}
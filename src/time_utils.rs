@@ -9,6 +9,40 @@ pub fn unix_to_iso8601(secs: u64) -> String {
     format!("{y:04}-{mo:02}-{d:02}T{h:02}:{m:02}:{s:02}Z")
 }
 
+/// Parse `2026-03-01` (midnight UTC) or `2026-03-01T14:30[:00]Z` to seconds
+/// since the Unix epoch.
+pub fn iso8601_to_unix(s: &str) -> Option<u64> {
+    let (date, time) = s.split_once('T').unwrap_or((s, "00:00:00"));
+    let date: Vec<u32> = date.split('-').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    let time: Vec<u64> = time
+        .trim_end_matches('Z')
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let (&[y, mo, d], &[h, m, ref rest @ ..]) = (&date[..], &time[..]) else {
+        return None;
+    };
+    if !(1..=12).contains(&mo) || !(1..=31).contains(&d) || h > 23 || m > 59 {
+        return None;
+    }
+    let days = u64::try_from(epoch_days(y, mo, d)).ok()?;
+    Some(days * 86400 + h * 3600 + m * 60 + rest.first().copied().unwrap_or(0))
+}
+
+/// Parse a duration like `90m`, `12h`, `7d` or `2w` into seconds.
+pub fn parse_ttl(s: &str) -> Option<u64> {
+    let split = s.find(|c: char| !c.is_ascii_digit())?;
+    let n: u64 = s[..split].parse().ok()?;
+    let unit = match &s[split..] {
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return None,
+    };
+    n.checked_mul(unit)
+}
+
 /// Days since Unix epoch for a given calendar date.
 pub fn epoch_days(year: u32, month: u32, day: u32) -> i64 {
     let y = year as i64;
@@ -34,3 +68,30 @@ pub fn civil_from_days(z: i64) -> (i32, u32, u32) {
     let y = if m <= 2 { y + 1 } else { y };
     (y as i32, m as u32, d as u32)
 }
+
+/// `base * 2^(attempt-1)`, capped at `max`.
+pub fn backoff_delay(attempt: u32, base: u64, max: u64) -> u64 {
+    let factor = 1u64
+        .checked_shl(attempt.saturating_sub(1))
+        .unwrap_or(u64::MAX);
+    base.saturating_mul(factor).min(max.max(base))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dates_and_ttls() {
+        assert_eq!(iso8601_to_unix("1970-01-02"), Some(86400));
+        let t = iso8601_to_unix("2026-03-01T14:30:05Z").unwrap();
+        assert_eq!(unix_to_iso8601(t), "2026-03-01T14:30:05Z");
+        assert_eq!(iso8601_to_unix("2026-03-01T14:30Z"), Some(t - 5));
+        assert_eq!(iso8601_to_unix("2026-13-01"), None);
+        assert_eq!(iso8601_to_unix("next week"), None);
+        assert_eq!(parse_ttl("7d"), Some(7 * 86400));
+        assert_eq!(parse_ttl("90m"), Some(5400));
+        assert_eq!(parse_ttl("7"), None);
+        assert_eq!(parse_ttl("d"), None);
+    }
+}
@@ -0,0 +1,57 @@
+use std::io::{IsTerminal, Write};
+
+use crate::oken_config::OkenConfig;
+
+/// Terminal appearance change for the duration of a danger-host session.
+/// Restores the original look when dropped.
+pub struct Tint {
+    restore: String,
+}
+
+/// Tint the terminal if `danger_background` or `danger_iterm_profile` is set.
+/// Returns `None` when nothing is configured or stderr is not a terminal.
+pub fn apply(cfg: &OkenConfig) -> Option<Tint> {
+    if !std::io::stderr().is_terminal() {
+        return None;
+    }
+    let mut set = String::new();
+    let mut restore = String::new();
+
+    if let Some(ref color) = cfg.danger_background {
+        // OSC 11 sets the background colour, OSC 111 resets it to the profile default
+        set.push_str(&osc(&format!("11;{color}")));
+        restore.push_str(&osc("111"));
+    }
+    if let Some(ref profile) = cfg.danger_iterm_profile {
+        set.push_str(&osc(&format!("1337;SetProfile={profile}")));
+        // iTerm2 exports the active profile name; fall back to its stock profile
+        let original = std::env::var("ITERM_PROFILE").unwrap_or_else(|_| "Default".to_string());
+        restore.push_str(&osc(&format!("1337;SetProfile={original}")));
+    }
+    if set.is_empty() {
+        return None;
+    }
+    emit(&set);
+    Some(Tint { restore })
+}
+
+impl Drop for Tint {
+    fn drop(&mut self) {
+        emit(&self.restore);
+    }
+}
+
+/// Wrap an OSC sequence, using tmux passthrough when running inside tmux.
+fn osc(body: &str) -> String {
+    if std::env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;\x1b\x1b]{body}\x07\x1b\\")
+    } else {
+        format!("\x1b]{body}\x07")
+    }
+}
+
+fn emit(seq: &str) {
+    let mut err = std::io::stderr();
+    let _ = err.write_all(seq.as_bytes());
+    let _ = err.flush();
+}
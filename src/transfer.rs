@@ -0,0 +1,131 @@
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::ssh;
+use crate::util;
+
+/// Run an interactive sftp session. `ssh_args` are connection args as built
+/// for ssh: the target plus `-p`/`-i`/… flags.
+pub fn run_sftp(ssh_args: &[String]) -> Result<i32> {
+    if !util::on_path("sftp") {
+        bail!("sftp not found on PATH (install the OpenSSH client tools)");
+    }
+    let status = Command::new("sftp")
+        .args(sftp_args(ssh_args))
+        .status()
+        .context("failed to run sftp")?;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Copy `sources` to `dest` with scp, recursing into directories. Either side
+/// may be remote (`target:path`); scp draws its own progress meter.
+pub fn run_scp(ssh_args: &[String], sources: &[String], dest: &str) -> Result<()> {
+    if !util::on_path("scp") {
+        bail!("scp not found on PATH (install the OpenSSH client tools)");
+    }
+    let status = Command::new("scp")
+        .args(scp_args(ssh_args, sources, dest))
+        .status()
+        .context("failed to run scp")?;
+    if !status.success() {
+        bail!("scp failed ({status})");
+    }
+    Ok(())
+}
+
+/// The args `run_scp` passes to scp.
+pub fn scp_args(ssh_args: &[String], sources: &[String], dest: &str) -> Vec<String> {
+    let mut args = sftp_args(ssh_args);
+    // Drop the target; the remote side is spelled out in `sources`/`dest`
+    args.pop();
+    args.push("-r".to_string());
+    args.extend(sources.iter().cloned());
+    args.push(dest.to_string());
+    args
+}
+
+/// `target:path` for scp, where an empty path means the login directory.
+pub fn remote_spec(ssh_args: &[String], path: &str) -> String {
+    let target = ssh::extract_target_host_full(ssh_args).unwrap_or_default();
+    format!("{target}:{path}")
+}
+
+/// Resolve a remote path given to `oken pull` against the remembered
+/// directory. Absolute and `~` paths are used as they are.
+pub fn resolve_remote(path: &str, last_dir: Option<&str>) -> String {
+    match last_dir {
+        Some(dir) if !path.starts_with('/') && !path.starts_with('~') => {
+            format!("{}/{path}", dir.trim_end_matches('/'))
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// The directory part of a remote path, if it names one.
+pub fn remote_parent(path: &str) -> Option<&str> {
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) => Some("/"),
+        Some((dir, _)) => Some(dir),
+        None => None,
+    }
+}
+
+/// Rearrange ssh connection args for sftp/scp: options first and the target
+/// last, with the port spelled `-P` and the login name passed as an option
+/// (sftp's `-l` is a bandwidth limit).
+fn sftp_args(ssh_args: &[String]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut iter = ssh_args.iter();
+    while let Some(arg) = iter.next() {
+        if ssh::FLAGS_WITH_VALUES.contains(&arg.as_str()) {
+            let Some(value) = iter.next() else {
+                break;
+            };
+            match arg.as_str() {
+                "-p" => out.extend(["-P".to_string(), value.clone()]),
+                "-l" => out.extend(["-o".to_string(), format!("User={value}")]),
+                _ => out.extend([arg.clone(), value.clone()]),
+            }
+        } else if arg.starts_with('-') {
+            out.push(arg.clone());
+        }
+    }
+    out.extend(ssh::extract_target_host_full(ssh_args));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_remote_paths() {
+        assert_eq!(resolve_remote("app.log", Some("/var/log/")), "/var/log/app.log");
+        assert_eq!(resolve_remote("/etc/hosts", Some("/var/log")), "/etc/hosts");
+        assert_eq!(resolve_remote("app.log", None), "app.log");
+        assert_eq!(remote_parent("/var/log/app.log"), Some("/var/log"));
+        assert_eq!(remote_parent("/etc"), Some("/"));
+        assert_eq!(remote_parent("app.log"), None);
+    }
+
+    #[test]
+    fn translates_ssh_args_for_sftp() {
+        let args: Vec<String> = "deploy@web1 -p 2222 -i ~/.ssh/deploy -l root"
+            .split(' ')
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            sftp_args(&args),
+            [
+                "-P",
+                "2222",
+                "-i",
+                "~/.ssh/deploy",
+                "-o",
+                "User=root",
+                "deploy@web1"
+            ]
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+
+use crate::hosts_toml::Transport;
+use crate::recording::{self, Recording};
+use crate::ssh;
+use crate::util;
+
+/// Run `mosh` in place of ssh, translating the ssh args: connection flags go
+/// into mosh's `--ssh` command and any remote command follows `--`.
+/// mosh roams across networks itself, so there is no reconnect wrapper.
+pub fn run_mosh(args: &[String], recording: Option<&Recording>) -> Result<i32> {
+    if !util::on_path("mosh") {
+        bail!("mosh not found on PATH (install mosh, or connect without --mosh)");
+    }
+    let ssh = ssh::find_ssh().context("failed to locate ssh")?;
+    let mosh_args = mosh_args(&ssh, args);
+    let program = Path::new("mosh");
+    let mut cmd = match recording {
+        Some(rec) => rec.command(program, &mosh_args),
+        None => {
+            let mut c = Command::new(program);
+            c.args(&mosh_args);
+            c
+        }
+    };
+    let status = cmd
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .context("failed to run mosh")?;
+    Ok(recording::exit_code(recording, status))
+}
+
+/// An et session that fails this quickly almost certainly never connected.
+const ET_CONNECT_GRACE: Duration = Duration::from_secs(10);
+
+/// Run Eternal Terminal (`et`) in place of ssh. Returns `None` when et is not
+/// installed or could not reach an etserver, so the caller can fall back to ssh.
+pub fn run_et(args: &[String], recording: Option<&Recording>) -> Result<Option<i32>> {
+    if !util::on_path("et") {
+        let note = "oken: et not found on PATH, falling back to ssh";
+        eprintln!("{}", crate::color::epaint("2", note));
+        return Ok(None);
+    }
+    let Some(et_args) = et_args(args) else {
+        return Ok(None);
+    };
+    let program = Path::new("et");
+    let mut cmd = match recording {
+        Some(rec) => rec.command(program, &et_args),
+        None => {
+            let mut c = Command::new(program);
+            c.args(&et_args);
+            c
+        }
+    };
+    let start = Instant::now();
+    let status = cmd
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .context("failed to run et")?;
+    let code = recording::exit_code(recording, status);
+    if code != 0 && start.elapsed() < ET_CONNECT_GRACE {
+        let note = "oken: et could not connect (is etserver running?), falling back to ssh";
+        eprintln!("{}", crate::color::epaint("2", note));
+        return Ok(None);
+    }
+    Ok(Some(code))
+}
+
+/// Open a shell with `aws ssm start-session` in place of ssh, for hosts with
+/// `connect = "ssm"` and no ssh user. Session Manager does its own auth, so
+/// ports, keys and ssh options don't apply.
+pub fn run_ssm(args: &[String], extra: &[String], recording: Option<&Recording>) -> Result<i32> {
+    if !util::on_path("aws") {
+        bail!("aws CLI not found on PATH (Session Manager also needs session-manager-plugin)");
+    }
+    let ssm_args = ssm_args(args, extra)?;
+    let program = Path::new("aws");
+    let mut cmd = match recording {
+        Some(rec) => rec.command(program, &ssm_args),
+        None => {
+            let mut c = Command::new(program);
+            c.args(&ssm_args);
+            c
+        }
+    };
+    let status = cmd
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .context("failed to run aws ssm start-session")?;
+    Ok(recording::exit_code(recording, status))
+}
+
+fn ssm_args(args: &[String], extra: &[String]) -> Result<Vec<String>> {
+    let Some(target) = ssh::extract_target_host(args) else {
+        bail!("no instance ID to start a Session Manager session with");
+    };
+    let mut ssm_args: Vec<String> = ["ssm", "start-session", "--target", &target]
+        .map(String::from)
+        .to_vec();
+    ssm_args.extend(extra.iter().cloned());
+    Ok(ssm_args)
+}
+
+/// The program and args a session over `transport` runs, for `--dry-run`.
+/// et sessions that et can't express show the ssh they fall back to.
+pub fn command_line(
+    transport: Transport,
+    args: &[String],
+    extra: &[String],
+) -> Result<Vec<String>> {
+    let ssh = ssh::find_ssh().context("failed to locate ssh")?;
+    let (program, program_args) = match transport {
+        Transport::Mosh => ("mosh".to_string(), mosh_args(&ssh, args)),
+        Transport::Ssm => ("aws".to_string(), ssm_args(args, extra)?),
+        Transport::Et => match et_args(args) {
+            Some(et_args) => ("et".to_string(), et_args),
+            None => (ssh.display().to_string(), args.to_vec()),
+        },
+        Transport::Ssh => (ssh.display().to_string(), args.to_vec()),
+    };
+    Ok(std::iter::once(program).chain(program_args).collect())
+}
+
+/// Translate ssh args for et: `-o`, `-p`, `-i` and `-l` become `--ssh-option`s
+/// and a remote command becomes `-c`. Returns `None` for anything et can't
+/// express (tunnels, jump hosts, …).
+fn et_args(args: &[String]) -> Option<Vec<String>> {
+    let mut out = Vec::new();
+    let mut target = None;
+    let mut command = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !command.is_empty() {
+            command.push(arg.clone());
+            continue;
+        }
+        let option = match arg.as_str() {
+            "-o" => iter.next()?.clone(),
+            "-p" => format!("Port={}", iter.next()?),
+            "-i" => format!("IdentityFile={}", iter.next()?),
+            "-l" => format!("User={}", iter.next()?),
+            a if a.starts_with('-') => return None,
+            _ if target.is_none() => {
+                target = Some(arg.clone());
+                continue;
+            }
+            _ => {
+                command.push(arg.clone());
+                continue;
+            }
+        };
+        out.push("--ssh-option".to_string());
+        out.push(option);
+    }
+    out.push(target?);
+    if !command.is_empty() {
+        out.push("-c".to_string());
+        out.push(command.join(" "));
+    }
+    Some(out)
+}
+
+/// Split ssh args into `--ssh=<ssh flags>`, the target, and `-- <command>`.
+fn mosh_args(ssh: &Path, args: &[String]) -> Vec<String> {
+    let mut ssh_cmd = vec![util::shell_quote(&ssh.to_string_lossy())];
+    let mut target = None;
+    let mut command = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !command.is_empty() {
+            command.push(arg.clone());
+        } else if ssh::FLAGS_WITH_VALUES.contains(&arg.as_str()) {
+            ssh_cmd.push(util::shell_quote(arg));
+            if let Some(value) = iter.next() {
+                ssh_cmd.push(util::shell_quote(value));
+            }
+        } else if arg.starts_with('-') {
+            ssh_cmd.push(util::shell_quote(arg));
+        } else if target.is_none() {
+            target = Some(arg.clone());
+        } else {
+            // ssh parses options after the destination too; the next positional
+            // starts the remote command
+            command.push(arg.clone());
+        }
+    }
+
+    let mut out = vec![format!("--ssh={}", ssh_cmd.join(" "))];
+    out.extend(target);
+    if !command.is_empty() {
+        out.push("--".to_string());
+        out.extend(command);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_ssh_args_for_et() {
+        let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+        assert_eq!(
+            et_args(&args(
+                "-o ServerAliveInterval=60 deploy@web1 -p 2222 uptime"
+            ))
+            .unwrap(),
+            vec![
+                "--ssh-option",
+                "ServerAliveInterval=60",
+                "--ssh-option",
+                "Port=2222",
+                "deploy@web1",
+                "-c",
+                "uptime",
+            ]
+        );
+        assert!(et_args(&args("-L 8080:localhost:80 web1")).is_none());
+    }
+
+    #[test]
+    fn translates_ssh_args_for_mosh() {
+        let args: Vec<String> = "-o ServerAliveInterval=60 deploy@web1 -p 2222 tail -f log"
+            .split(' ')
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            mosh_args(Path::new("/usr/bin/ssh"), &args),
+            vec![
+                "--ssh=/usr/bin/ssh -o ServerAliveInterval=60 -p 2222",
+                "deploy@web1",
+                "--",
+                "tail",
+                "-f",
+                "log",
+            ]
+        );
+    }
+}
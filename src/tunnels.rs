@@ -1,14 +1,104 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result, bail};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(from = "RawEntry", into = "RawEntry")]
 pub struct TunnelEntry {
     pub host: String,
+    /// A saved host whose user, address, port and key are looked up at start
+    /// time, so editing the host fixes every tunnel through it. `host` and
+    /// the matching `ssh_flags` are filled in by `load_effective`.
+    pub host_alias: Option<String>,
     pub ssh_flags: Vec<String>,
+    pub tags: Vec<String>,
+    /// Probe each local forward after starting (same as `--verify`).
+    pub verify: bool,
+    /// Args with `{{name}}` / `{{name|default}}` placeholders, filled in at
+    /// `oken tunnel start <name> --<param> <value>`. `host` and `ssh_flags`
+    /// are empty until the template is instantiated.
+    pub template: Option<String>,
+}
+
+/// On disk a tunnel is either a table or, for templates, just the args string:
+/// `db = "-L {{local|5432}}:localhost:5432 {{host}}"`
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum RawEntry {
+    Template(String),
+    Full(FullEntry),
+}
+
+#[derive(Deserialize, Serialize)]
+struct FullEntry {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    host: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    host_alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ssh_flags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    verify: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    template: Option<String>,
+}
+
+impl From<RawEntry> for TunnelEntry {
+    fn from(raw: RawEntry) -> Self {
+        match raw {
+            RawEntry::Template(template) => TunnelEntry {
+                template: Some(template),
+                ..Default::default()
+            },
+            RawEntry::Full(f) => TunnelEntry {
+                host: f.host,
+                host_alias: f.host_alias,
+                ssh_flags: f.ssh_flags,
+                tags: f.tags,
+                verify: f.verify,
+                template: f.template,
+            },
+        }
+    }
+}
+
+impl From<TunnelEntry> for RawEntry {
+    fn from(entry: TunnelEntry) -> Self {
+        match entry.template {
+            Some(template) if entry.tags.is_empty() && !entry.verify => {
+                RawEntry::Template(template)
+            }
+            // Templates are saved uninstantiated; the values live in the params file
+            Some(template) => RawEntry::Full(FullEntry {
+                host: String::new(),
+                host_alias: None,
+                ssh_flags: Vec::new(),
+                tags: entry.tags,
+                verify: entry.verify,
+                template: Some(template),
+            }),
+            None => RawEntry::Full(FullEntry {
+                // An alias is resolved again on every load
+                host: if entry.host_alias.is_some() {
+                    String::new()
+                } else {
+                    entry.host
+                },
+                host_alias: entry.host_alias,
+                ssh_flags: entry.ssh_flags,
+                tags: entry.tags,
+                verify: entry.verify,
+                template: None,
+            }),
+        }
+    }
 }
 
 pub fn load_tunnels(path: &Path) -> Result<HashMap<String, TunnelEntry>> {
@@ -33,9 +123,50 @@ pub fn remove_tunnel(path: &Path, name: &str) -> Result<()> {
     if tunnels.remove(name).is_none() {
         bail!("tunnel '{name}' not found");
     }
+    if let Ok(params) = params_path(name) {
+        let _ = std::fs::remove_file(params);
+    }
+    save_tunnels(path, &tunnels)
+}
+
+/// Apply `edit` to a saved tunnel and write it back.
+pub fn update_tunnel(
+    path: &Path,
+    name: &str,
+    edit: impl FnOnce(&mut TunnelEntry),
+) -> Result<()> {
+    let mut tunnels = load_tunnels(path)?;
+    let entry = tunnels
+        .get_mut(name)
+        .ok_or_else(|| anyhow::anyhow!("tunnel '{name}' not found"))?;
+    edit(entry);
     save_tunnels(path, &tunnels)
 }
 
+impl TunnelEntry {
+    /// Drop every `-L`/`-R`/`-D` forward whose spec is `spec`. Returns false if none matched.
+    pub fn remove_forward(&mut self, spec: &str) -> bool {
+        let before = self.ssh_flags.len();
+        let mut kept = Vec::with_capacity(before);
+        let mut iter = self.ssh_flags.drain(..).peekable();
+        while let Some(flag) = iter.next() {
+            if matches!(flag.as_str(), "-L" | "-R" | "-D")
+                && iter.peek().is_some_and(|v| v == spec)
+            {
+                iter.next();
+                continue;
+            }
+            if flag.len() > 2 && ["-L", "-R", "-D"].contains(&&flag[..2]) && flag[2..] == *spec {
+                continue;
+            }
+            kept.push(flag);
+        }
+        drop(iter);
+        self.ssh_flags = kept;
+        self.ssh_flags.len() != before
+    }
+}
+
 fn save_tunnels(path: &Path, tunnels: &HashMap<String, TunnelEntry>) -> Result<()> {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
@@ -45,29 +176,1063 @@ fn save_tunnels(path: &Path, tunnels: &HashMap<String, TunnelEntry>) -> Result<(
     Ok(())
 }
 
-/// Returns `~/.local/share/oken/tunnels/<name>.sock`
+/// Like `load_tunnels`, but ready to run: templates are filled in from their
+/// saved (or default) parameters and host aliases are resolved from hosts.toml.
+/// A tunnel that can't be resolved keeps an empty `host`. `proxies` are the
+/// `[proxies]` presets from config.toml.
+pub fn load_effective(
+    path: &Path,
+    proxies: &BTreeMap<String, String>,
+) -> Result<HashMap<String, TunnelEntry>> {
+    let mut tunnels = load_tunnels(path)?;
+    let mut hosts = None;
+    for (name, entry) in tunnels.iter_mut() {
+        if let Some(template) = &entry.template
+            && let Ok((host, ssh_flags)) = instantiate(template, &load_params(name))
+        {
+            entry.host = host;
+            entry.ssh_flags = ssh_flags;
+        }
+        // A plain `host` (or a template's {{host}}) may name a saved host too
+        let alias = match &entry.host_alias {
+            Some(alias) => alias.clone(),
+            None if !entry.host.is_empty() => entry.host.clone(),
+            None => continue,
+        };
+        let hosts = hosts.get_or_insert_with(|| crate::hosts::list_all_hosts().unwrap_or_default());
+        match crate::hosts::find(hosts, &alias) {
+            Some(h) => {
+                entry.host_alias = Some(alias);
+                let mut args = crate::ssh::build_ssh_args(h, None, proxies);
+                entry.host = args.remove(0);
+                args.append(&mut entry.ssh_flags);
+                entry.ssh_flags = args;
+            }
+            None if entry.host_alias.is_some() => entry.host.clear(),
+            None => {}
+        }
+    }
+    Ok(tunnels)
+}
+
+/// Merge `params` into a template tunnel's saved parameters, checking that
+/// each one exists in the template and that none are left without a value.
+pub fn set_params(path: &Path, name: &str, params: BTreeMap<String, String>) -> Result<()> {
+    let tunnels = load_tunnels(path)?;
+    let entry = tunnels
+        .get(name)
+        .ok_or_else(|| anyhow::anyhow!("tunnel '{name}' not found"))?;
+    let Some(template) = &entry.template else {
+        bail!("tunnel '{name}' is not a template and takes no parameters");
+    };
+    let known = placeholders(template);
+    if let Some(key) = params.keys().find(|k| !known.iter().any(|(p, _)| p == *k)) {
+        bail!("template '{name}' has no {{{{{key}}}}} parameter");
+    }
+    let mut merged = load_params(name);
+    merged.extend(params);
+    instantiate(template, &merged).with_context(|| format!("can't start template '{name}'"))?;
+    let path = params_path(name)?;
+    crate::config::create_parent(&path)?;
+    std::fs::write(path, toml::to_string(&merged)?)?;
+    Ok(())
+}
+
+/// Parse the `--key value` / `--key=value` pairs given after a template's name.
+pub fn parse_params(args: &[String]) -> Result<BTreeMap<String, String>> {
+    let mut params = BTreeMap::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let Some(key) = arg.strip_prefix("--") else {
+            bail!("unexpected argument '{arg}' (template parameters look like --host web1)");
+        };
+        let (key, value) = match key.split_once('=') {
+            Some((k, v)) => (k, v.to_string()),
+            None => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--{key} needs a value"))?;
+                (key, value.clone())
+            }
+        };
+        params.insert(key.to_string(), value);
+    }
+    Ok(params)
+}
+
+/// The flags a template needs before it can start, e.g. `--host <host>`.
+pub fn template_usage(template: &str) -> String {
+    placeholders(template)
+        .into_iter()
+        .filter(|(_, default)| default.is_none())
+        .map(|(key, _)| format!("--{key} <{key}>"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns `~/.local/state/oken/tunnels/<name>.params.toml`
+fn params_path(name: &str) -> Result<PathBuf> {
+    tunnel_file(name, "params.toml")
+}
+
+fn load_params(name: &str) -> BTreeMap<String, String> {
+    params_path(name)
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| toml::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+/// The distinct `{{name}}` / `{{name|default}}` placeholders in a template.
+fn placeholders(template: &str) -> Vec<(String, Option<String>)> {
+    let mut found: Vec<(String, Option<String>)> = Vec::new();
+    substitute(template, |key, default| {
+        if !found.iter().any(|(k, _)| k == key) {
+            found.push((key.to_string(), default.map(String::from)));
+        }
+        Some(String::new())
+    });
+    found
+}
+
+/// Fill in a template and split it into its target host and ssh flags.
+fn instantiate(template: &str, params: &BTreeMap<String, String>) -> Result<(String, Vec<String>)> {
+    let mut missing = Vec::new();
+    let filled = substitute(template, |key, default| {
+        let value = params.get(key).map(String::as_str).or(default);
+        if value.is_none() && !missing.contains(&key.to_string()) {
+            missing.push(key.to_string());
+        }
+        value.map(String::from)
+    });
+    if !missing.is_empty() {
+        let flags: Vec<_> = missing.iter().map(|k| format!("--{k}")).collect();
+        bail!("needs {}", flags.join(", "));
+    }
+    let args: Vec<String> = filled.split_whitespace().map(String::from).collect();
+    let host = crate::ssh::extract_target_host_full(&args)
+        .ok_or_else(|| anyhow::anyhow!("template has no target host"))?;
+    Ok((host, crate::ssh::extract_ssh_flags(&args)))
+}
+
+/// Replace each `{{key}}` or `{{key|default}}` with `value(key, default)`,
+/// leaving it empty when that returns `None`.
+fn substitute(
+    template: &str,
+    mut value: impl FnMut(&str, Option<&str>) -> Option<String>,
+) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let inner = &rest[start + 2..start + 2 + len];
+        let (key, default) = match inner.split_once('|') {
+            Some((k, d)) => (k.trim(), Some(d.trim())),
+            None => (inner.trim(), None),
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&value(key, default).unwrap_or_default());
+        rest = &rest[start + len + 4..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Returns `~/.local/state/oken/tunnels/<name>.sock`
 pub fn socket_path(name: &str) -> Result<PathBuf> {
-    let data_dir = crate::config::data_dir()?;
-    let tunnels_dir = data_dir.join("tunnels");
-    std::fs::create_dir_all(&tunnels_dir)?;
-    Ok(tunnels_dir.join(format!("{name}.sock")))
+    tunnel_file(name, "sock")
+}
+
+/// `<name>.<ext>` in the state directory's `tunnels/`, moved there from the
+/// data directory where older versions kept it.
+fn tunnel_file(name: &str, ext: &str) -> Result<PathBuf> {
+    crate::config::state_file(&format!("tunnels/{name}.{ext}"))
+}
+
+/// A live tunnel's ControlMaster.
+pub struct MasterStatus {
+    /// PID of the master ssh process, as reported by `ssh -O check`.
+    pub pid: Option<u32>,
+    /// How long the master has been up, from its socket's creation time.
+    pub uptime: Option<Duration>,
 }
 
 /// Check if a tunnel is running via SSH ControlMaster check.
 pub fn is_running(name: &str, host: &str) -> bool {
-    let Ok(sock) = socket_path(name) else {
-        return false;
-    };
-    if !sock.exists() {
-        return false;
-    }
-    let ssh = crate::ssh::find_ssh()
-        .unwrap_or_else(|_| PathBuf::from("ssh"));
-    Command::new(&ssh)
+    status(name, host).is_some()
+}
+
+/// Query a tunnel's ControlMaster. Returns `None` when it isn't running.
+pub fn status(name: &str, host: &str) -> Option<MasterStatus> {
+    let sock = socket_path(name).ok()?;
+    let meta = std::fs::metadata(&sock).ok()?;
+    let ssh = crate::ssh::find_ssh().unwrap_or_else(|_| PathBuf::from("ssh"));
+    let output = Command::new(&ssh)
         .args(["-S", &sock.to_string_lossy(), "-O", "check", host])
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // "Master running (pid=12345)" on stderr
+    let msg = String::from_utf8_lossy(&output.stderr);
+    let pid = msg
+        .split_once("pid=")
+        .and_then(|(_, rest)| rest.split(')').next())
+        .and_then(|p| p.trim().parse().ok());
+    let uptime = meta
+        .modified()
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok());
+    Some(MasterStatus { pid, uptime })
+}
+
+/// Human-readable forwards from a tunnel's ssh flags, e.g.
+/// `local 5432 → prod-db:5432` for `-L 5432:localhost:5432` to `prod-db`.
+pub fn describe_forwards(entry: &TunnelEntry) -> Vec<String> {
+    // "localhost" on the far side of a -L means the tunnel host itself
+    let remote = entry
+        .host
+        .rsplit_once('@')
+        .map(|(_, h)| h)
+        .unwrap_or(&entry.host);
+    forwards(entry).iter().map(|f| f.describe(remote)).collect()
+}
+
+/// One `-L`, `-R` or `-D` forward of a tunnel.
+#[derive(Debug, Serialize)]
+pub struct Forward {
+    /// `local` (-L), `remote` (-R) or `socks` (-D).
+    pub kind: &'static str,
+    /// Listen address, when the spec names one.
+    pub bind: Option<String>,
+    /// Listen port; none for unix sockets and specs oken can't read.
+    pub port: Option<u16>,
+    /// Where connections go, as seen from the far side; none for socks.
+    pub dest: Option<String>,
+    pub dest_port: Option<u16>,
+    /// The spec as given to ssh.
+    pub spec: String,
+}
+
+impl Forward {
+    fn parse(kind: &str, spec: &str) -> Self {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let (bind, listen, dest) = match (kind, parts.as_slice()) {
+            ("D", [port]) => (None, Some(*port), None),
+            ("D", [bind, port]) => (Some(*bind), Some(*port), None),
+            (_, [port, host, hostport]) => (None, Some(*port), Some((*host, *hostport))),
+            (_, [bind, port, host, hostport]) => {
+                (Some(*bind), Some(*port), Some((*host, *hostport)))
+            }
+            _ => (None, None, None),
+        };
+        Forward {
+            kind: match kind {
+                "L" => "local",
+                "R" => "remote",
+                _ => "socks",
+            },
+            bind: bind.map(str::to_string),
+            port: listen.and_then(|p| p.parse().ok()),
+            dest: dest.map(|(host, _)| host.to_string()),
+            dest_port: dest.and_then(|(_, port)| port.parse().ok()),
+            spec: spec.to_string(),
+        }
+    }
+
+    /// `local 5432 → db:5432`, with a local-looking destination of a -L
+    /// shown as `remote`, the tunnel host.
+    fn describe(&self, remote: &str) -> String {
+        let is_local = |h: &str| matches!(h, "localhost" | "127.0.0.1" | "::1");
+        match (self.kind, self.port, self.dest.as_deref(), self.dest_port) {
+            ("socks", Some(port), _, _) => format!("socks {port}"),
+            ("local", Some(port), Some(host), Some(hostport)) => {
+                let host = if is_local(host) { remote } else { host };
+                format!("local {port} → {host}:{hostport}")
+            }
+            ("remote", Some(port), Some(host), Some(hostport)) => {
+                format!("remote {port} → {host}:{hostport}")
+            }
+            (kind, ..) => format!("-{} {}", kind[..1].to_uppercase(), self.spec),
+        }
+    }
+}
+
+/// The forwards in a tunnel's ssh flags, in order.
+pub fn forwards(entry: &TunnelEntry) -> Vec<Forward> {
+    let mut out = Vec::new();
+    let mut iter = entry.ssh_flags.iter();
+    while let Some(flag) = iter.next() {
+        let (kind, spec) = match flag.as_str() {
+            "-L" | "-R" | "-D" => match iter.next() {
+                Some(v) => (&flag[1..], v.as_str()),
+                None => break,
+            },
+            f if f.len() > 2 && ["-L", "-R", "-D"].contains(&&f[..2]) => (&f[1..2], &f[2..]),
+            _ => continue,
+        };
+        out.push(Forward::parse(kind, spec));
+    }
+    out
+}
+
+/// The ssh args that run a tunnel's ControlMaster.
+pub fn master_args(name: &str, entry: &TunnelEntry) -> Result<Vec<String>> {
+    let mut cmd_args = vec![
+        "-N".to_string(),
+        "-M".to_string(),
+        "-S".to_string(),
+        socket_path(name)?.to_string_lossy().to_string(),
+    ];
+    // Make a dead link (e.g. after sleep) end the master so it can be restarted,
+    // and fail outright instead of running with a forward that couldn't bind
+    if !entry.ssh_flags.iter().any(|f| f.contains("ServerAliveInterval")) {
+        cmd_args.extend(
+            ["-o", "ServerAliveInterval=15", "-o", "ServerAliveCountMax=3"].map(String::from),
+        );
+    }
+    cmd_args.extend(["-o".to_string(), "ExitOnForwardFailure=yes".to_string()]);
+    cmd_args.extend(entry.ssh_flags.clone());
+    cmd_args.push(entry.host.clone());
+    Ok(cmd_args)
+}
+
+/// Start a tunnel as a backgrounded ControlMaster. Errors if ssh exits
+/// immediately (bad host, auth failure, port already in use, …).
+pub fn start(name: &str, entry: &TunnelEntry) -> Result<()> {
+    check_local_ports(entry)?;
+    let sock = socket_path(name)?;
+    crate::config::create_parent(&sock)?;
+    // A master that died uncleanly leaves its socket behind, and ssh will
+    // not create a new master on top of it
+    if sock.exists() {
+        let _ = std::fs::remove_file(&sock);
+    }
+    let ssh = crate::ssh::find_ssh()?;
+    let cmd_args = master_args(name, entry)?;
+
+    // The master outlives us, so its stderr goes to a log instead of the terminal
+    let log = log_path(name)?;
+    rotate_log(&log);
+    let mut log_file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log)
+        .with_context(|| format!("failed to open {}", log.display()))?;
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    writeln!(
+        log_file,
+        "--- {} starting: ssh {}",
+        crate::time_utils::unix_to_iso8601(secs),
+        cmd_args.join(" ")
+    )?;
+    let log_start = log_file.metadata().map(|m| m.len()).unwrap_or(0);
+
+    let mut child = Command::new(&ssh)
+        .args(&cmd_args)
+        .stdin(Stdio::null())
         .stdout(Stdio::null())
+        .stderr(log_file)
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("failed to start tunnel: {e}"))?;
+
+    // Brief wait to catch immediate failures (bad host, auth error, etc.)
+    std::thread::sleep(Duration::from_millis(500));
+    match child.try_wait() {
+        Ok(Some(status)) => {
+            // Surface what ssh said, which would otherwise only be in the log
+            let output = std::fs::read(&log).unwrap_or_default();
+            let output = String::from_utf8_lossy(output.get(log_start as usize..).unwrap_or(&[]));
+            for line in output.lines().filter(|l| !l.trim().is_empty()) {
+                eprintln!("{line}");
+            }
+            bail!(
+                "tunnel '{name}' failed to start (exit {})",
+                status.code().unwrap_or(1)
+            )
+        }
+        Ok(None) => Ok(()),
+        Err(e) => bail!("could not check tunnel status: {e}"),
+    }
+}
+
+/// Returns `~/.local/state/oken/tunnels/<name>.log`
+pub fn log_path(name: &str) -> Result<PathBuf> {
+    tunnel_file(name, "log")
+}
+
+/// Keep one previous log (`<name>.log.1`) once the current one passes 1 MiB.
+fn rotate_log(path: &Path) {
+    const MAX_LOG_BYTES: u64 = 1024 * 1024;
+    if std::fs::metadata(path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+        let _ = std::fs::rename(path, path.with_extension("log.1"));
+    }
+}
+
+/// Print the last `lines` lines of a tunnel's log, then keep printing new
+/// output as it arrives when `follow` is set.
+pub fn show_logs(name: &str, lines: usize, follow: bool) -> Result<()> {
+    let path = log_path(name)?;
+    if !path.exists() && !follow {
+        println!("No log for tunnel '{name}' yet.");
+        return Ok(());
+    }
+    let content = std::fs::read(&path).unwrap_or_default();
+    let text = String::from_utf8_lossy(&content);
+    let all: Vec<&str> = text.lines().collect();
+    for line in &all[all.len().saturating_sub(lines)..] {
+        println!("{line}");
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut pos = content.len() as u64;
+    let mut out = std::io::stdout();
+    loop {
+        std::thread::sleep(Duration::from_millis(500));
+        let len = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if len < pos {
+            // Rotated or truncated — start over from the top of the new file
+            pos = 0;
+        }
+        if len == pos {
+            continue;
+        }
+        let mut file = std::fs::File::open(&path)?;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        pos += buf.len() as u64;
+        out.write_all(&buf)?;
+        out.flush()?;
+    }
+}
+
+/// A local listening port requested by `-L` or `-D`.
+struct LocalPort {
+    /// Index of the forward spec within `ssh_flags`.
+    index: usize,
+    /// Whether the spec is glued to its flag (`-L5432:…`).
+    joined: bool,
+    bind: Option<String>,
+    port: u16,
+}
+
+fn local_ports(flags: &[String]) -> Vec<LocalPort> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < flags.len() {
+        let flag = flags[i].as_str();
+        let (kind, spec, index, joined) = match flag {
+            "-L" | "-D" if i + 1 < flags.len() => (&flag[1..], flags[i + 1].as_str(), i + 1, false),
+            f if f.len() > 2 && (f.starts_with("-L") || f.starts_with("-D")) => {
+                (&f[1..2], &f[2..], i, true)
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let parts: Vec<&str> = spec.split(':').collect();
+        // [bind:]port for -D, [bind:]port:host:hostport for -L
+        let (bind, port) = match (kind, parts.as_slice()) {
+            ("D", [port]) | ("L", [port, _, _]) => (None, *port),
+            ("D", [bind, port]) | ("L", [bind, port, _, _]) => (Some(bind.to_string()), *port),
+            _ => (None, ""),
+        };
+        if let Ok(port) = port.parse() {
+            out.push(LocalPort {
+                index,
+                joined,
+                bind,
+                port,
+            });
+        }
+        i = index + 1;
+    }
+    out
+}
+
+fn port_is_free(bind: Option<&str>, port: u16) -> bool {
+    let addr = match bind {
+        None | Some("localhost") | Some("") => "127.0.0.1",
+        Some("*") => "0.0.0.0",
+        Some(b) => b,
+    };
+    std::net::TcpListener::bind((addr, port)).is_ok()
+}
+
+/// Best-effort "postgres (pid 812)" for whoever is listening on `port`.
+fn port_owner(port: u16) -> Option<String> {
+    let output = Command::new("lsof")
+        .args(["-nP", &format!("-iTCP:{port}"), "-sTCP:LISTEN", "-Fpc"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let pid = text.lines().find_map(|l| l.strip_prefix('p'))?;
+    let cmd = text.lines().find_map(|l| l.strip_prefix('c')).unwrap_or("?");
+    Some(format!("{cmd} (pid {pid})"))
+}
+
+/// Current use of a tunnel's local (`-L`/`-D`) forwards.
+#[derive(Debug, Default, PartialEq)]
+pub struct Traffic {
+    /// Client connections open through the forwards right now.
+    pub connections: usize,
+    /// Bytes sent to and received from those clients. Only known where `ss`
+    /// reports per-socket counters (Linux).
+    pub bytes: Option<(u64, u64)>,
+}
+
+/// Count the connections ssh has accepted on a tunnel's local forward ports,
+/// using `ss` (with byte counters) or `lsof`. `None` when neither works or
+/// the tunnel has no local forwards.
+pub fn traffic(entry: &TunnelEntry) -> Option<Traffic> {
+    let ports: Vec<u16> = local_ports(&entry.ssh_flags).iter().map(|lp| lp.port).collect();
+    if ports.is_empty() {
+        return None;
+    }
+    if let Ok(out) = Command::new("ss")
+        .args(["-tniH", "state", "established"])
+        .stderr(Stdio::null())
+        .output()
+        && out.status.success()
+    {
+        return Some(ss_traffic(&String::from_utf8_lossy(&out.stdout), &ports));
+    }
+    let out = Command::new("lsof")
+        .args(["-nP", "-iTCP", "-sTCP:ESTABLISHED", "-Fn"])
         .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    // "n127.0.0.1:5432->127.0.0.1:55012": the local side comes first
+    let connections = String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|l| l.strip_prefix('n')?.split_once("->"))
+        .filter(|(local, _)| port_of(local).is_some_and(|p| ports.contains(&p)))
+        .count();
+    Some(Traffic {
+        connections,
+        bytes: None,
+    })
+}
+
+/// Sum up `ss -tniH state established` output for sockets whose local port
+/// is one of `ports`. Each socket line is followed by an indented line of
+/// counters such as `bytes_sent:5000 bytes_received:120`.
+fn ss_traffic(listing: &str, ports: &[u16]) -> Traffic {
+    let (mut connections, mut sent, mut received) = (0, 0, 0);
+    let mut counting = false;
+    for line in listing.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            // Recv-Q Send-Q Local:Port Peer:Port
+            counting = line
+                .split_whitespace()
+                .nth(2)
+                .and_then(port_of)
+                .is_some_and(|p| ports.contains(&p));
+            if counting {
+                connections += 1;
+            }
+            continue;
+        }
+        if !counting {
+            continue;
+        }
+        for field in line.split_whitespace() {
+            let Some((key, value)) = field.split_once(':') else {
+                continue;
+            };
+            let value: u64 = value.parse().unwrap_or(0);
+            match key {
+                "bytes_sent" => sent += value,
+                "bytes_received" => received += value,
+                _ => {}
+            }
+        }
+    }
+    Traffic {
+        connections,
+        bytes: Some((sent, received)),
+    }
+}
+
+/// Port of an `addr:port` socket address (IPv6 addresses contain colons too).
+fn port_of(addr: &str) -> Option<u16> {
+    addr.rsplit_once(':')?.1.parse().ok()
+}
+
+/// Fail with a readable message if a local forward port is already taken,
+/// rather than letting ssh exit with a cryptic bind error.
+fn check_local_ports(entry: &TunnelEntry) -> Result<()> {
+    for lp in local_ports(&entry.ssh_flags) {
+        if !port_is_free(lp.bind.as_deref(), lp.port) {
+            let owner = port_owner(lp.port)
+                .map(|o| format!(" by {o}"))
+                .unwrap_or_default();
+            bail!(
+                "local port {} is already in use{owner} (use --auto-port to pick a free one)",
+                lp.port
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Move any local forward whose port is taken to the next free port,
+/// printing each substitution. The saved profile is left unchanged.
+pub fn pick_free_ports(entry: &TunnelEntry) -> TunnelEntry {
+    let mut entry = entry.clone();
+    let ports = local_ports(&entry.ssh_flags);
+    let mut taken: Vec<u16> = ports.iter().map(|lp| lp.port).collect();
+    for lp in ports {
+        if port_is_free(lp.bind.as_deref(), lp.port) {
+            continue;
+        }
+        let Some(free) = (lp.port.saturating_add(1)..=u16::MAX)
+            .take(100)
+            .find(|p| !taken.contains(p) && port_is_free(lp.bind.as_deref(), *p))
+        else {
+            continue;
+        };
+        taken.push(free);
+        let owner = port_owner(lp.port)
+            .map(|o| format!(" by {o}"))
+            .unwrap_or_default();
+        println!("Local port {} is in use{owner}; using {free} instead", lp.port);
+        let spec = &mut entry.ssh_flags[lp.index];
+        let (prefix, rest) = spec.split_at(if lp.joined { 2 } else { 0 });
+        *spec = format!("{prefix}{}", replace_port(rest, lp.bind.is_some(), free));
+    }
+    entry
+}
+
+/// Swap the local port in a `[bind:]port[:host:hostport]` spec.
+fn replace_port(spec: &str, has_bind: bool, port: u16) -> String {
+    let mut parts: Vec<String> = spec.split(':').map(String::from).collect();
+    parts[usize::from(has_bind)] = port.to_string();
+    parts.join(":")
+}
+
+/// Connect through each `-L` forward and check the far end answers. ssh
+/// accepts locally no matter what, then closes the connection straight away
+/// if the remote side refuses — so an immediate EOF means nothing is
+/// listening there. Returns one warning per broken forward.
+pub fn verify_forwards(entry: &TunnelEntry) -> Vec<String> {
+    let remote = entry
+        .host
+        .rsplit_once('@')
+        .map(|(_, h)| h)
+        .unwrap_or(&entry.host);
+    let mut warnings = Vec::new();
+    for lp in local_ports(&entry.ssh_flags) {
+        let (flag, spec) = if lp.joined {
+            entry.ssh_flags[lp.index].split_at(2)
+        } else {
+            (entry.ssh_flags[lp.index - 1].as_str(), entry.ssh_flags[lp.index].as_str())
+        };
+        if flag != "-L" {
+            continue;
+        }
+        let what = Forward::parse("L", spec).describe(remote);
+        let addr = match lp.bind.as_deref() {
+            None | Some("localhost") | Some("") | Some("*") => "127.0.0.1",
+            Some(b) => b,
+        };
+        if let Err(problem) = probe_forward(addr, lp.port) {
+            warnings.push(format!("{what}: {problem}"));
+        }
+    }
+    warnings
+}
+
+/// Check on the remote host that every `-R` port is actually listening,
+/// using the tunnel's own master connection to run `ss` (or `netstat`).
+/// Catches port-in-use on the remote and the GatewayPorts trap, where a
+/// public bind silently falls back to loopback. Does nothing when the tunnel
+/// has no `-R` forwards or the remote has neither tool.
+pub fn verify_remote_forwards(name: &str, entry: &TunnelEntry) -> Vec<String> {
+    let forwards = remote_forwards(&entry.ssh_flags);
+    if forwards.is_empty() {
+        return Vec::new();
+    }
+    let (Ok(sock), Ok(ssh)) = (socket_path(name), crate::ssh::find_ssh()) else {
+        return Vec::new();
+    };
+    let Ok(output) = Command::new(&ssh)
+        .args(["-S", &sock.to_string_lossy(), &entry.host])
+        .arg("ss -ltn 2>/dev/null || netstat -ltn 2>/dev/null || netstat -an -p tcp")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+    else {
+        return Vec::new();
+    };
+    let listing = String::from_utf8_lossy(&output.stdout);
+    if listing.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let mut warnings = Vec::new();
+    for (bind, port) in forwards {
+        let bound = listening_addresses(&listing, port);
+        if bound.is_empty() {
+            warnings.push(format!(
+                "remote port {port} is not listening on {} (already in use there?)",
+                entry.host
+            ));
+        } else if bind.as_deref().is_some_and(|b| !is_loopback(b))
+            && bound.iter().all(|a| is_loopback(a))
+        {
+            warnings.push(format!(
+                "remote port {port} is only bound to loopback on {} — set `GatewayPorts clientspecified` in its sshd_config to expose it",
+                entry.host
+            ));
+        }
+    }
+    warnings
+}
+
+/// `(bind, port)` for each `-R [bind:]port[:host:hostport]`. Port 0 (let the
+/// server choose) and unix socket forwards are skipped.
+fn remote_forwards(flags: &[String]) -> Vec<(Option<String>, u16)> {
+    let mut out = Vec::new();
+    let mut iter = flags.iter();
+    while let Some(flag) = iter.next() {
+        let spec = match flag.as_str() {
+            "-R" => match iter.next() {
+                Some(v) => v.as_str(),
+                None => break,
+            },
+            f if f.len() > 2 && f.starts_with("-R") => &f[2..],
+            _ => continue,
+        };
+        let parts: Vec<&str> = spec.split(':').collect();
+        let (bind, port) = match parts.as_slice() {
+            [port] | [port, _, _] => (None, *port),
+            [bind, port] | [bind, port, _, _] => (Some(bind.to_string()), *port),
+            _ => continue,
+        };
+        if let Ok(port) = port.parse::<u16>()
+            && port != 0
+        {
+            out.push((bind, port));
+        }
+    }
+    out
+}
+
+/// Addresses listening on `port` in `ss -ltn` / `netstat` output. The local
+/// address is the fourth column in all three formats; the port follows the
+/// last `:` (Linux) or `.` (BSD).
+fn listening_addresses(listing: &str, port: u16) -> Vec<String> {
+    listing
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(3))
+        .filter_map(|local| {
+            let (addr, p) = local.rsplit_once([':', '.'])?;
+            (p.parse() == Ok(port)).then(|| addr.to_string())
+        })
+        .collect()
+}
+
+fn is_loopback(addr: &str) -> bool {
+    let addr = addr.trim_start_matches('[').trim_end_matches(']');
+    matches!(addr, "localhost" | "::1") || addr.starts_with("127.")
+}
+
+fn probe_forward(addr: &str, port: u16) -> std::result::Result<(), &'static str> {
+    use std::net::TcpStream;
+
+    // The master may still be authenticating, so give the listener a moment
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut stream = loop {
+        match TcpStream::connect((addr, port)) {
+            Ok(s) => break s,
+            Err(_) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(250)),
+            Err(_) => return Err("local port is not listening"),
+        }
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_millis(1500)));
+    let mut buf = [0u8; 64];
+    match stream.read(&mut buf) {
+        // Closed without a word: ssh couldn't connect on the remote side
+        Ok(0) => Err("remote side refused the connection (nothing listening?)"),
+        // A banner (ssh, smtp, mysql, …) or a quiet server waiting for us to speak
+        Ok(_) => Ok(()),
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(())
+        }
+        Err(_) => Err("connection reset by the remote side"),
+    }
+}
+
+/// Ask a tunnel's ControlMaster to exit.
+pub fn stop(name: &str, entry: &TunnelEntry) -> Result<()> {
+    let sock = socket_path(name)?;
+    let ssh = crate::ssh::find_ssh()?;
+
+    let status = Command::new(&ssh)
+        .args(["-S", &sock.to_string_lossy(), "-O", "stop", &entry.host])
         .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
+        .map_err(|e| anyhow::anyhow!("failed to stop tunnel: {e}"))?;
+    if !status.success() {
+        bail!("failed to stop tunnel '{name}'");
+    }
+    Ok(())
+}
+
+/// Per-tunnel restart state for a `Supervisor`.
+#[derive(Default)]
+struct WatchState {
+    failures: u32,
+    restarts: u32,
+    next_try: Option<Instant>,
+}
+
+/// Restarts dead tunnels, backing off exponentially (up to 5 minutes) while
+/// a tunnel keeps failing. Shared by `oken tunnel watch` and the daemon.
+#[derive(Default)]
+pub struct Supervisor {
+    states: HashMap<String, WatchState>,
+}
+
+impl Supervisor {
+    /// Check every tunnel accepted by `include` once, restarting any that are down.
+    /// Tunnels are reloaded each pass so ones added or edited meanwhile are picked up.
+    pub fn tick(
+        &mut self,
+        path: &Path,
+        proxies: &BTreeMap<String, String>,
+        include: impl Fn(&str) -> bool,
+    ) -> Result<()> {
+        let all = load_effective(path, proxies)?;
+        let mut selected: Vec<_> = all.iter().filter(|(n, _)| include(n)).collect();
+        selected.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (name, entry) in selected {
+            // A template nobody has given values yet
+            if entry.host.is_empty() {
+                continue;
+            }
+            let state = self.states.entry(name.clone()).or_default();
+            if state.next_try.is_some_and(|t| Instant::now() < t) {
+                continue;
+            }
+            if is_running(name, &entry.host) {
+                state.failures = 0;
+                state.next_try = None;
+                continue;
+            }
+            match start(name, entry) {
+                Ok(()) => {
+                    log(&format!("started tunnel '{name}'"));
+                    state.failures = 0;
+                    state.restarts += 1;
+                    state.next_try = None;
+                }
+                Err(e) => {
+                    state.failures += 1;
+                    let delay = crate::time_utils::backoff_delay(state.failures, 5, 300);
+                    log(&format!("{e:#}; retrying in {delay}s"));
+                    state.next_try = Some(Instant::now() + Duration::from_secs(delay));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// How many times this supervisor has (re)started `name`.
+    pub fn restarts(&self, name: &str) -> u32 {
+        self.states.get(name).map_or(0, |s| s.restarts)
+    }
+}
+
+/// Keep tunnels alive, checking every `interval`. An empty `names` watches
+/// every saved tunnel. Runs until interrupted.
+pub fn watch(
+    path: &Path,
+    names: &[String],
+    interval: Duration,
+    proxies: &BTreeMap<String, String>,
+) -> Result<()> {
+    let all = load_tunnels(path)?;
+    for name in names {
+        if !all.contains_key(name) {
+            bail!("tunnel '{name}' not found");
+        }
+    }
+    let mut supervisor = Supervisor::default();
+    loop {
+        supervisor.tick(path, proxies, |n| names.is_empty() || names.iter().any(|x| x == n))?;
+        std::thread::sleep(interval);
+    }
+}
+
+/// Marker that the user wants this tunnel up: set by `oken tunnel start`,
+/// cleared by `oken tunnel stop`. The daemon only revives wanted tunnels.
+fn wanted_path(name: &str) -> Result<PathBuf> {
+    tunnel_file(name, "wanted")
+}
+
+pub fn set_wanted(name: &str, wanted: bool) {
+    let Ok(path) = wanted_path(name) else {
+        return;
+    };
+    if wanted {
+        let _ = crate::config::create_parent(&path).and_then(|()| Ok(std::fs::write(&path, "")?));
+    } else {
+        let _ = std::fs::remove_file(&path);
+    }
+}
+
+pub fn is_wanted(name: &str) -> bool {
+    wanted_path(name).is_ok_and(|p| p.exists())
+}
+
+fn log(msg: &str) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    eprintln!("[{}] {msg}", crate::time_utils::unix_to_iso8601(secs));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_forwards() {
+        let entry = TunnelEntry {
+            host: "ubuntu@prod-db".to_string(),
+            ssh_flags: [
+                "-L",
+                "5432:localhost:5432",
+                "-L127.0.0.1:6379:cache:6379",
+                "-R",
+                "9000:localhost:3000",
+                "-D",
+                "1080",
+                "-v",
+            ]
+            .map(String::from)
+            .to_vec(),
+            tags: Vec::new(),
+            verify: false,
+            template: None,
+            host_alias: None,
+        };
+        assert_eq!(
+            describe_forwards(&entry),
+            vec![
+                "local 5432 → prod-db:5432",
+                "local 6379 → cache:6379",
+                "remote 9000 → localhost:3000",
+                "socks 1080",
+            ]
+        );
+        let parsed = forwards(&entry);
+        assert_eq!(parsed[1].bind.as_deref(), Some("127.0.0.1"));
+        assert_eq!(
+            (parsed[1].port, parsed[1].dest.as_deref(), parsed[1].dest_port),
+            (Some(6379), Some("cache"), Some(6379))
+        );
+        assert_eq!((parsed[3].kind, parsed[3].port), ("socks", Some(1080)));
+
+        let ports: Vec<_> = local_ports(&entry.ssh_flags)
+            .iter()
+            .map(|lp| (lp.index, lp.bind.clone(), lp.port))
+            .collect();
+        assert_eq!(
+            ports,
+            vec![
+                (1, None, 5432),
+                (2, Some("127.0.0.1".to_string()), 6379),
+                (6, None, 1080),
+            ]
+        );
+        assert_eq!(
+            replace_port("127.0.0.1:6379:cache:6379", true, 6380),
+            "127.0.0.1:6380:cache:6379"
+        );
+
+        assert_eq!(
+            remote_forwards(&["-R".to_string(), "0.0.0.0:8080:localhost:3000".to_string()]),
+            vec![(Some("0.0.0.0".to_string()), 8080)]
+        );
+        let ss = "State  Recv-Q Send-Q Local Address:Port Peer Address:Port\n\
+                  LISTEN 0      128        127.0.0.1:8080      0.0.0.0:*\n\
+                  LISTEN 0      128            [::1]:8080         [::]:*\n\
+                  LISTEN 0      128          0.0.0.0:22        0.0.0.0:*\n";
+        let bound = listening_addresses(ss, 8080);
+        assert_eq!(bound, vec!["127.0.0.1", "[::1]"]);
+        assert!(bound.iter().all(|a| is_loopback(a)));
+        let bsd = "tcp4       0      0  *.8080                 *.*                    LISTEN\n";
+        assert_eq!(listening_addresses(bsd, 8080), vec!["*"]);
+
+        let mut entry = entry;
+        assert!(entry.remove_forward("127.0.0.1:6379:cache:6379"));
+        assert!(entry.remove_forward("1080"));
+        assert!(!entry.remove_forward("1080"));
+        assert_eq!(
+            entry.ssh_flags,
+            ["-L", "5432:localhost:5432", "-R", "9000:localhost:3000", "-v"]
+        );
+    }
+
+    #[test]
+    fn instantiates_templates() {
+        let template = "-L {{local|5432}}:localhost:5432 {{host}}";
+        assert_eq!(template_usage(template), "--host <host>");
+        assert!(instantiate(template, &BTreeMap::new()).is_err());
+
+        let args: Vec<String> = ["--host", "staging-db", "--local=15432"]
+            .map(String::from)
+            .to_vec();
+        let params = parse_params(&args).unwrap();
+        let (host, flags) = instantiate(template, &params).unwrap();
+        assert_eq!(host, "staging-db");
+        assert_eq!(flags, ["-L", "15432:localhost:5432"]);
+
+        let tunnels: HashMap<String, TunnelEntry> = toml::from_str(&format!(
+            "db = \"{template}\"\n[web]\nhost = \"hk\"\nssh_flags = [\"-D\", \"1080\"]\n"
+        ))
+        .unwrap();
+        assert_eq!(tunnels["db"].template.as_deref(), Some(template));
+        assert_eq!(tunnels["web"].host, "hk");
+        let saved = toml::to_string(&tunnels).unwrap();
+        assert!(saved.contains(&format!("db = \"{template}\"")));
+    }
+
+    #[test]
+    fn sums_forward_traffic() {
+        let ss = "0      0      127.0.0.1:34918 127.0.0.1:5432\n\
+                  \t bbr bytes_acked:1 bytes_received:5000 segs_out:3\n\
+                  0      0      127.0.0.1:5432  127.0.0.1:34918\n\
+                  \t bbr bytes_sent:5000 bytes_acked:5000 bytes_received:120\n\
+                  0      0      [::1]:1080  [::1]:40000\n\
+                  \t bbr bytes_sent:10 bytes_received:20\n";
+        assert_eq!(
+            ss_traffic(ss, &[5432, 1080]),
+            Traffic {
+                connections: 2,
+                bytes: Some((5010, 140)),
+            }
+        );
+    }
 }
@@ -1,63 +1,86 @@
 use std::io::IsTerminal;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-const CHECK_INTERVAL_SECS: u64 = 86_400; // 24 hours
-const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
-const RELEASES_API: &str =
-    "https://api.github.com/repos/linkwithjoydeep/oken/releases/latest";
+use std::cmp::Ordering;
+
+use crate::color::{epaint, paint};
+use crate::oken_config::{OkenConfig, UpdateChannel};
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Release notes longer than this are cut, with a link to the full text.
+const NOTES_MAX_LINES: usize = 40;
+const RELEASES_API: &str = "https://api.github.com/repos/linkwithjoydeep/oken/releases";
 
 /// Show an update notice if a newer version was found by a previous check,
-/// then kick off a background refresh if 24 h have elapsed.
+/// then kick off a background refresh if `update_interval_hours` have elapsed.
 /// Returns immediately — never blocks the SSH connection.
-pub fn maybe_notify() {
+pub fn maybe_notify(cfg: &OkenConfig) {
     // Only print to interactive terminals; skip when piped or scripted.
-    if !std::io::stderr().is_terminal() {
+    if !std::io::stderr().is_terminal() || crate::logging::quiet() {
+        log::trace!("not checking for updates: stderr is not a terminal, or --quiet");
         return;
     }
+    if !cfg.update_check {
+        log::trace!("not checking for updates: update_check = false");
+        return;
+    }
+    let channel = cfg.update_channel;
 
-    let Ok(state_path) = crate::config::data_dir().map(|d| d.join("update_state")) else {
+    let Ok(state_path) = crate::config::state_file("update_state") else {
         return;
     };
 
     // Show a notice if the cached state already knows about a newer version.
-    if let Some(latest_tag) = read_cached_tag(&state_path) {
+    if let Some(latest_tag) = read_cached_tag(&state_path, channel) {
+        log::debug!("latest release from the last update check: {latest_tag}");
         let latest_ver = latest_tag.trim_start_matches('v');
         if is_newer(latest_ver, CURRENT_VERSION) {
-            let install_cmd = if cfg!(windows) {
-                "powershell -c \"irm https://github.com/linkwithjoydeep/oken/releases/latest/download/oken-installer.ps1 | iex\""
-            } else {
-                "curl -LsSf https://github.com/linkwithjoydeep/oken/releases/latest/download/oken-installer.sh | sh"
-            };
             eprintln!(
-                "\x1b[33moken {latest_tag} is available\x1b[0m \x1b[2m(you have v{CURRENT_VERSION})\x1b[0m"
+                "{} {}",
+                epaint("33", format!("oken {latest_tag} is available")),
+                epaint("2", format!("(you have v{CURRENT_VERSION})"))
             );
-            eprintln!("\x1b[2mUpdate: {install_cmd}\x1b[0m");
+            eprintln!("{}", epaint("2", "Update: oken self-update"));
         }
     }
 
-    // Spawn a background thread to refresh the cache if 24 h have elapsed.
-    // The result is written to disk and shown on the *next* invocation.
-    if should_check(&state_path) {
+    // Spawn a background thread to refresh the cache once the interval has
+    // elapsed. The result is written to disk and shown on the *next* invocation.
+    if should_check(&state_path, channel, cfg.update_interval_hours.saturating_mul(3600)) {
+        log::debug!(
+            "last update check was over {}h ago, checking in the background",
+            cfg.update_interval_hours
+        );
+        let proxy = cfg.update_proxy.clone();
         std::thread::spawn(move || {
-            if let Ok(tag) = fetch_latest_tag() {
+            let fetched = fetch_latest_tag(channel, proxy.as_deref());
+            if let Err(e) = &fetched {
+                log::debug!("update check failed: {e:#}");
+            }
+            if let Ok(tag) = fetched {
                 let now = SystemTime::now()
                     .duration_since(UNIX_EPOCH)
                     .unwrap_or_default()
                     .as_secs();
-                // Format: "<unix_timestamp>\t<tag>"
-                let _ = std::fs::write(&state_path, format!("{now}\t{tag}"));
+                let _ = crate::config::create_parent(&state_path);
+                let _ = std::fs::write(&state_path, state_line(now, &tag, channel));
             }
         });
     }
 }
 
 /// Immediately check for updates, print the result, and refresh the cache.
-/// Used by `oken update`.
-pub fn force_check() -> anyhow::Result<()> {
-    print!("Checking for updates… ");
+/// Used by `oken update`, which checks even when `update_check` is off.
+pub fn force_check(cfg: &OkenConfig) -> anyhow::Result<()> {
+    let channel = cfg.update_channel;
+    match channel {
+        UpdateChannel::Stable => print!("Checking for updates… "),
+        UpdateChannel::Beta => print!("Checking for updates (beta channel)… "),
+    }
     std::io::Write::flush(&mut std::io::stdout())?;
 
-    let tag = fetch_latest_tag()?;
+    let release = fetch_latest(channel, cfg.update_proxy.as_deref())?;
+    let tag = &release.tag;
     let latest_ver = tag.trim_start_matches('v');
 
     // Refresh the cache so the background check timer resets
@@ -65,18 +88,20 @@ pub fn force_check() -> anyhow::Result<()> {
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs();
-    if let Ok(state_path) = crate::config::data_dir().map(|d| d.join("update_state")) {
-        let _ = std::fs::write(state_path, format!("{now}\t{tag}"));
+    if let Ok(state_path) = crate::config::state_file("update_state") {
+        let _ = crate::config::create_parent(&state_path);
+        let _ = std::fs::write(state_path, state_line(now, tag, channel));
     }
 
     if is_newer(latest_ver, CURRENT_VERSION) {
-        let install_cmd = if cfg!(windows) {
-            "powershell -c \"irm https://github.com/linkwithjoydeep/oken/releases/latest/download/oken-installer.ps1 | iex\""
-        } else {
-            "curl -LsSf https://github.com/linkwithjoydeep/oken/releases/latest/download/oken-installer.sh | sh"
-        };
         println!("{tag} is available (you have v{CURRENT_VERSION})");
-        println!("Run: {install_cmd}");
+        if !release.notes.trim().is_empty() {
+            println!("\n{}\n", render_notes(&release.notes));
+        }
+        if !release.url.is_empty() {
+            println!("Release notes: {}", release.url);
+        }
+        println!("Run: oken self-update");
     } else {
         println!("already up to date (v{CURRENT_VERSION})");
     }
@@ -86,65 +111,243 @@ pub fn force_check() -> anyhow::Result<()> {
 
 // ── helpers ──────────────────────────────────────────────────────────────────
 
-fn read_cached_tag(path: &std::path::Path) -> Option<String> {
+/// Format: "<unix_timestamp>\t<tag>\t<channel>"
+fn state_line(now: u64, tag: &str, channel: UpdateChannel) -> String {
+    format!("{now}\t{tag}\t{}", channel.as_str())
+}
+
+/// The timestamp and tag of the last check, if it was made on `channel`.
+/// States written before channels existed count as stable.
+fn read_state(path: &std::path::Path, channel: UpdateChannel) -> Option<(u64, String)> {
     let content = std::fs::read_to_string(path).ok()?;
-    // Second whitespace-separated token is the tag
-    content.split_whitespace().nth(1).map(str::to_string)
+    let mut fields = content.split_whitespace();
+    let ts = fields.next()?.parse().ok()?;
+    let tag = fields.next()?.to_string();
+    let cached = fields.next().unwrap_or("stable");
+    (cached == channel.as_str()).then_some((ts, tag))
+}
+
+fn read_cached_tag(path: &std::path::Path, channel: UpdateChannel) -> Option<String> {
+    read_state(path, channel).map(|(_, tag)| tag)
 }
 
-fn should_check(path: &std::path::Path) -> bool {
-    let last_ts: u64 = std::fs::read_to_string(path)
-        .ok()
-        .and_then(|s| s.split_whitespace().next().and_then(|t| t.parse().ok()))
-        .unwrap_or(0);
+fn should_check(path: &std::path::Path, channel: UpdateChannel, interval_secs: u64) -> bool {
+    let last_ts = read_state(path, channel).map_or(0, |(ts, _)| ts);
 
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs();
 
-    now.saturating_sub(last_ts) >= CHECK_INTERVAL_SECS
+    now.saturating_sub(last_ts) >= interval_secs
 }
 
-fn fetch_latest_tag() -> anyhow::Result<String> {
-    let response = ureq::AgentBuilder::new()
-        .timeout_connect(Duration::from_secs(3))
-        .timeout(Duration::from_secs(5))
-        .build()
-        .get(RELEASES_API)
+/// A GitHub release: its tag, its notes (markdown) and its web page.
+struct Release {
+    tag: String,
+    notes: String,
+    url: String,
+}
+
+pub fn fetch_latest_tag(channel: UpdateChannel, proxy: Option<&str>) -> anyhow::Result<String> {
+    fetch_latest(channel, proxy).map(|release| release.tag)
+}
+
+/// The newest release on `channel`. GitHub's `latest` skips pre-releases,
+/// so the beta channel picks the highest from the recent list.
+fn fetch_latest(channel: UpdateChannel, proxy: Option<&str>) -> anyhow::Result<Release> {
+    let url = match channel {
+        UpdateChannel::Stable => format!("{RELEASES_API}/latest"),
+        UpdateChannel::Beta => format!("{RELEASES_API}?per_page=30"),
+    };
+    let response = agent(proxy, Duration::from_secs(5))?
+        .get(&url)
         .set("User-Agent", &format!("oken/{CURRENT_VERSION}"))
         .set("Accept", "application/vnd.github.v3+json")
         .call()?
         .into_string()?;
 
-    extract_tag_name(&response)
+    match channel {
+        UpdateChannel::Stable => extract_release(&response),
+        UpdateChannel::Beta => newest_release(&response),
+    }
+}
+
+/// An HTTP client for GitHub that goes through `update_proxy` if set, or
+/// else the proxy in `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY` unless `NO_PROXY`
+/// covers github.com.
+pub fn agent(proxy: Option<&str>, timeout: Duration) -> anyhow::Result<ureq::Agent> {
+    let builder = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(3))
+        .timeout(timeout);
+    let builder = match proxy {
+        Some(url) => {
+            log::debug!("connecting to GitHub through update_proxy");
+            let proxy = ureq::Proxy::new(url)
+                .map_err(|e| anyhow::anyhow!("invalid update_proxy '{url}': {e}"))?;
+            builder.proxy(proxy)
+        }
+        None => {
+            let no_proxy = std::env::var("NO_PROXY")
+                .or_else(|_| std::env::var("no_proxy"))
+                .unwrap_or_default();
+            builder.try_proxy_from_env(!no_proxy_matches(&no_proxy, "github.com"))
+        }
+    };
+    Ok(builder.build())
+}
+
+/// Whether a `NO_PROXY` list (`localhost,.corp,*`) exempts `host`.
+fn no_proxy_matches(no_proxy: &str, host: &str) -> bool {
+    no_proxy.split(',').map(str::trim).any(|entry| {
+        let entry = entry.trim_start_matches("*.").trim_start_matches('.');
+        let suffix = host.strip_suffix(entry).is_some_and(|rest| rest.ends_with('.'));
+        entry == "*" || (!entry.is_empty() && (host == entry || suffix))
+    })
+}
+
+/// The highest version among the non-draft releases in a list response.
+fn newest_release(json: &str) -> anyhow::Result<Release> {
+    let v: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| anyhow::anyhow!("invalid JSON from GitHub API: {e}"))?;
+    v.as_array()
+        .into_iter()
+        .flatten()
+        .filter(|r| !r["draft"].as_bool().unwrap_or(false))
+        .filter_map(release_from)
+        .filter(|r| parse_version(r.tag.trim_start_matches('v')).is_some())
+        .max_by(|a, b| {
+            compare_versions(a.tag.trim_start_matches('v'), b.tag.trim_start_matches('v'))
+        })
+        .ok_or_else(|| anyhow::anyhow!("no releases found in GitHub API response"))
 }
 
-fn extract_tag_name(json: &str) -> anyhow::Result<String> {
+fn extract_release(json: &str) -> anyhow::Result<Release> {
     let v: serde_json::Value = serde_json::from_str(json)
         .map_err(|e| anyhow::anyhow!("invalid JSON from GitHub API: {e}"))?;
-    v["tag_name"]
-        .as_str()
-        .map(str::to_string)
-        .ok_or_else(|| anyhow::anyhow!("tag_name not found in GitHub API response"))
+    release_from(&v).ok_or_else(|| anyhow::anyhow!("tag_name not found in GitHub API response"))
+}
+
+fn release_from(v: &serde_json::Value) -> Option<Release> {
+    Some(Release {
+        tag: v["tag_name"].as_str()?.to_string(),
+        notes: v["body"].as_str().unwrap_or_default().replace('\r', ""),
+        url: v["html_url"].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+/// Release notes for the terminal: headings in bold, bullets as `•`, code
+/// highlighted and links reduced to their text, indented under the notice.
+fn render_notes(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in markdown.lines() {
+        let line = line.trim_end();
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(paint("2", format!("      {line}")));
+        } else if trimmed.is_empty() {
+            // Collapse runs of blank lines, and drop leading ones
+            if lines.last().is_some_and(|l: &String| !l.is_empty()) {
+                lines.push(String::new());
+            }
+        } else if trimmed.starts_with('#') {
+            let heading = trimmed.trim_start_matches('#').trim();
+            lines.push(format!("  {}", paint("1", inline(heading, false))));
+        } else if let Some(item) = ["- ", "* ", "+ "].iter().find_map(|b| trimmed.strip_prefix(b)) {
+            let indent = " ".repeat(line.len() - trimmed.len());
+            lines.push(format!("  {indent}• {}", inline(item, true)));
+        } else {
+            lines.push(format!("  {}", inline(line, true)));
+        }
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    if lines.len() > NOTES_MAX_LINES {
+        let more = lines.len() - NOTES_MAX_LINES;
+        lines.truncate(NOTES_MAX_LINES);
+        lines.push(paint("2", format!("  … {more} more lines")));
+    }
+    lines.join("\n")
+}
+
+/// `**bold**`, `` `code` `` and `[text](url)` within a line; with `styled`
+/// off the markers are just removed.
+fn inline(text: &str, styled: bool) -> String {
+    let style = |code: &str, s: &str| if styled { paint(code, s) } else { s.to_string() };
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find(['`', '*', '[']) {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(after) = rest.strip_prefix('`')
+            && let Some(end) = after.find('`')
+        {
+            out.push_str(&style("36", &after[..end]));
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix("**")
+            && let Some(end) = after.find("**")
+        {
+            out.push_str(&style("1", &after[..end]));
+            rest = &after[end + 2..];
+        } else if let Some(after) = rest.strip_prefix('[')
+            && let Some(close) = after.find("](")
+            && let Some(end) = after[close..].find(')')
+        {
+            out.push_str(&after[..close]);
+            rest = &after[close + end + 1..];
+        } else {
+            out.push_str(&rest[..1]);
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
 }
 
 /// Returns true if `latest` is a higher semver than `current`.
-fn is_newer(latest: &str, current: &str) -> bool {
-    let parse = |s: &str| -> Option<(u32, u32, u32)> {
-        let mut it = s.splitn(4, '.');
-        Some((
-            it.next()?.parse().ok()?,
-            it.next()?.parse().ok()?,
-            // strip pre-release suffix (e.g. "1-beta.1" → 1)
-            it.next()?
-                .split(['-', '+'])
-                .next()?
-                .parse()
-                .ok()?,
-        ))
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    parse_version(latest).is_some()
+        && parse_version(current).is_some()
+        && compare_versions(latest, current) == Ordering::Greater
+}
+
+/// `0.4.0-beta.1+build` → `((0, 4, 0), "beta.1")`; build metadata is ignored.
+fn parse_version(s: &str) -> Option<((u32, u32, u32), &str)> {
+    let s = s.split('+').next()?;
+    let (core, pre) = s.split_once('-').unwrap_or((s, ""));
+    let mut it = core.splitn(3, '.');
+    let core = (
+        it.next()?.parse().ok()?,
+        it.next()?.parse().ok()?,
+        it.next()?.parse().ok()?,
+    );
+    Some((core, pre))
+}
+
+/// Semver precedence: a pre-release sorts before its release, and its
+/// dot-separated parts compare numerically when both are numbers.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (Some((a_core, a_pre)), Some((b_core, b_pre))) = (parse_version(a), parse_version(b))
+    else {
+        return Ordering::Equal;
     };
-    matches!((parse(latest), parse(current)), (Some(l), Some(c)) if l > c)
+    a_core.cmp(&b_core).then_with(|| match (a_pre.is_empty(), b_pre.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => {
+            let part = |p: &str| p.parse::<u64>().map_err(|_| p.to_string());
+            let a_parts = a_pre.split('.').map(part);
+            let b_parts = b_pre.split('.').map(part);
+            a_parts.cmp(b_parts)
+        }
+    })
 }
 
 // ── tests ─────────────────────────────────────────────────────────────────────
@@ -156,7 +359,7 @@ mod tests {
     #[test]
     fn extracts_tag_name() {
         let json = r#"{"tag_name":"v0.2.0","name":"oken v0.2.0"}"#;
-        assert_eq!(extract_tag_name(json).unwrap(), "v0.2.0");
+        assert_eq!(extract_release(json).unwrap().tag, "v0.2.0");
     }
 
     #[test]
@@ -167,4 +370,51 @@ mod tests {
         assert!(!is_newer("0.1.0", "0.1.0"));
         assert!(!is_newer("0.0.9", "0.1.0"));
     }
+
+    #[test]
+    fn renders_release_notes() {
+        let notes = concat!(
+            "## What's Changed\n\n\n",
+            "* Add `oken man` by [@someone](https://github.com/someone) in #12\n",
+            "  - **nested** item\n\n",
+            "```\nfn main() {}\n```\n\n",
+        );
+        assert_eq!(
+            render_notes(notes),
+            concat!(
+                "  What's Changed\n\n",
+                "  • Add oken man by @someone in #12\n",
+                "    • nested item\n\n",
+                "      fn main() {}",
+            )
+        );
+    }
+
+    #[test]
+    fn honors_no_proxy() {
+        assert!(no_proxy_matches("localhost, github.com", "github.com"));
+        assert!(no_proxy_matches(".github.com", "api.github.com"));
+        assert!(no_proxy_matches("*", "github.com"));
+        assert!(!no_proxy_matches("notgithub.com,,", "github.com"));
+        assert!(!no_proxy_matches("", "github.com"));
+    }
+
+    #[test]
+    fn orders_pre_releases() {
+        assert!(is_newer("0.4.0-beta.1", "0.3.8"));
+        assert!(is_newer("0.4.0", "0.4.0-beta.2"));
+        assert!(is_newer("0.4.0-beta.10", "0.4.0-beta.2"));
+        assert!(is_newer("0.4.0-rc.1", "0.4.0-beta.3"));
+        assert!(!is_newer("0.4.0-beta.1", "0.4.0"));
+    }
+
+    #[test]
+    fn picks_newest_release_for_beta() {
+        let json = r#"[
+            {"tag_name":"v0.3.8","draft":false,"prerelease":false},
+            {"tag_name":"v0.4.0-beta.2","draft":false,"prerelease":true},
+            {"tag_name":"v0.5.0","draft":true,"prerelease":false}
+        ]"#;
+        assert_eq!(newest_release(json).unwrap().tag, "v0.4.0-beta.2");
+    }
 }
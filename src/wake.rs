@@ -0,0 +1,115 @@
+use std::io::{IsTerminal, Write};
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use oken_core::util::shell_quote;
+use oken_core::ssh::Route;
+
+use crate::probe;
+
+/// Parse a MAC address written as `aa:bb:cc:dd:ee:ff`, `aa-bb-cc-dd-ee-ff`,
+/// `aabb.ccdd.eeff` or `aabbccddeeff`.
+pub fn parse_mac(s: &str) -> Result<[u8; 6]> {
+    let hex: String = s.chars().filter(|c| !matches!(c, ':' | '-' | '.')).collect();
+    if hex.len() != 12 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("'{s}' is not a MAC address (expected something like aa:bb:cc:dd:ee:ff)");
+    }
+    let mut mac = [0u8; 6];
+    for (i, byte) in mac.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)?;
+    }
+    Ok(mac)
+}
+
+/// `aa:bb:cc:dd:ee:ff`, the way wakeonlan and most tools take it.
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    let parts: Vec<String> = mac.iter().map(|b| format!("{b:02x}")).collect();
+    parts.join(":")
+}
+
+/// Six 0xff bytes, then the MAC sixteen times.
+pub fn magic_packet(mac: &[u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0xff; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(mac);
+    }
+    packet
+}
+
+/// Broadcast the magic packet on the local network (UDP port 9, discard).
+pub fn send_local(mac: &[u8; 6]) -> Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0").context("failed to open a UDP socket")?;
+    socket.set_broadcast(true)?;
+    socket
+        .send_to(&magic_packet(mac), "255.255.255.255:9")
+        .context("failed to broadcast the wake-up packet")?;
+    Ok(())
+}
+
+/// The command a host on the sleeping machine's LAN runs to send the packet:
+/// `wakeonlan` when it's installed, otherwise python3, which most servers have.
+pub fn remote_command(mac: &[u8; 6]) -> String {
+    let mac = format_mac(mac);
+    let hex = mac.replace(':', "");
+    let python = format!(
+        "import socket; s = socket.socket(socket.AF_INET, socket.SOCK_DGRAM); \
+         s.setsockopt(socket.SOL_SOCKET, socket.SO_BROADCAST, 1); \
+         s.sendto(bytes.fromhex(\"ff\" * 6 + \"{hex}\" * 16), (\"255.255.255.255\", 9))"
+    );
+    let script = format!(
+        "if command -v wakeonlan >/dev/null 2>&1; then wakeonlan {mac} >/dev/null; \
+         elif command -v python3 >/dev/null 2>&1; then python3 -c {}; \
+         else echo \"oken: neither wakeonlan nor python3 is installed here\" >&2; exit 1; fi",
+        shell_quote(&python)
+    );
+    format!("sh -c {}", shell_quote(&script))
+}
+
+/// Probe the host's ssh port every couple of seconds until it answers or
+/// `timeout` runs out, showing how long it's been. True once it's up.
+pub fn wait_for_ssh(alias: &str, route: &Route, timeout: Duration) -> bool {
+    let start = Instant::now();
+    let live = std::io::stderr().is_terminal();
+    if !live {
+        eprintln!("Waiting for '{alias}' to come up…");
+    }
+    loop {
+        let attempt = Instant::now();
+        if probe::route(route, Duration::from_secs(3)).is_some() {
+            if live {
+                eprintln!();
+            }
+            return true;
+        }
+        if start.elapsed() >= timeout {
+            if live {
+                eprintln!();
+            }
+            return false;
+        }
+        if live {
+            eprint!("\rWaiting for '{alias}' to come up… {}s", start.elapsed().as_secs());
+            let _ = std::io::stderr().flush();
+        }
+        std::thread::sleep(Duration::from_secs(2).saturating_sub(attempt.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_magic_packet() {
+        let mac = parse_mac("AA-bb-cc-00-11-22").unwrap();
+        assert_eq!(parse_mac("aabb.cc00.1122").unwrap(), mac);
+        assert_eq!(format_mac(&mac), "aa:bb:cc:00:11:22");
+        let packet = magic_packet(&mac);
+        assert_eq!(packet.len(), 102);
+        assert_eq!(packet[..6], [0xff; 6]);
+        assert_eq!(packet[96..], mac);
+        assert!(parse_mac("aa:bb:cc:dd:ee").is_err());
+        assert!(parse_mac("gg:bb:cc:dd:ee:ff").is_err());
+    }
+}
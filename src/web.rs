@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use oken_core::util::shell_quote;
+use oken_core::{hosts, tunnels};
+
+use crate::audit;
+use crate::daemon::{self, HostStatus};
+
+/// How many audit entries the page shows.
+const AUDIT_ENTRIES: usize = 25;
+
+/// Host status found by the server itself, for when okend isn't running.
+type StatusCache = Arc<Mutex<BTreeMap<String, HostStatus>>>;
+
+/// Serve the dashboard on 127.0.0.1:`port` until killed. Every request
+/// renders the page afresh; nothing on it changes anything. `proxies` are
+/// the `[proxies]` presets tunnels' hosts may use.
+pub fn serve(port: u16, tunnels_path: PathBuf, proxies: BTreeMap<String, String>) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("failed to listen on 127.0.0.1:{port}"))?;
+    let cache: StatusCache = Arc::default();
+    let probes = Arc::clone(&cache);
+    std::thread::spawn(move || {
+        loop {
+            // okend already probes every minute; only stand in for it
+            if daemon::query().is_none() {
+                let status = daemon::probe_hosts();
+                *probes.lock().unwrap_or_else(|e| e.into_inner()) = status;
+            }
+            std::thread::sleep(Duration::from_secs(60));
+        }
+    });
+    eprintln!("oken dashboard at http://127.0.0.1:{port}/ (Ctrl-C to stop)");
+    let tunnels_path = Arc::new(tunnels_path);
+    let proxies = Arc::new(proxies);
+    for stream in listener.incoming().flatten() {
+        let cache = Arc::clone(&cache);
+        let tunnels_path = Arc::clone(&tunnels_path);
+        let proxies = Arc::clone(&proxies);
+        std::thread::spawn(move || {
+            if let Err(e) = handle(stream, port, &tunnels_path, &proxies, &cache) {
+                log::debug!("dashboard request failed: {e:#}");
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(
+    stream: TcpStream,
+    port: u16,
+    tunnels_path: &Path,
+    proxies: &BTreeMap<String, String>,
+    cache: &StatusCache,
+) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut host = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("host")
+        {
+            host = Some(value.trim().to_string());
+        }
+    }
+    let mut parts = request.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    log::debug!("dashboard: {method} {path}");
+
+    // Another site pointing its own name at 127.0.0.1 must not get to read the page
+    let local = [format!("127.0.0.1:{port}"), format!("localhost:{port}")];
+    let (status, body) = if !host.is_some_and(|h| local.contains(&h)) {
+        ("403 Forbidden", "forbidden\n".to_string())
+    } else if method != "GET" && method != "HEAD" {
+        ("405 Method Not Allowed", "the dashboard is read-only\n".to_string())
+    } else if path == "/" {
+        ("200 OK", render(tunnels_path, proxies, cache))
+    } else {
+        ("404 Not Found", "not found\n".to_string())
+    };
+    let content_type = if status.starts_with("200") { "text/html" } else { "text/plain" };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}; charset=utf-8\r\n\
+         Content-Length: {}\r\nCache-Control: no-store\r\n\
+         Content-Security-Policy: default-src 'none'; style-src 'unsafe-inline'; \
+         script-src 'unsafe-inline'\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(body.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// The whole page: hosts, tunnels and recent audit entries.
+fn render(tunnels_path: &Path, proxies: &BTreeMap<String, String>, cache: &StatusCache) -> String {
+    let (statuses, checked_by) = match daemon::query() {
+        Some(snapshot) => (snapshot.hosts, "okend"),
+        None => (cache.lock().unwrap_or_else(|e| e.into_inner()).clone(), "oken web"),
+    };
+    let sections = [
+        hosts_section(&statuses, checked_by),
+        tunnels_section(tunnels_path, proxies),
+        audit_section(),
+    ];
+    format!(
+        "<!doctype html>\n<html><head><meta charset=\"utf-8\">\
+         <meta http-equiv=\"refresh\" content=\"30\">\
+         <title>oken</title><style>{STYLE}</style></head>\n<body>\n\
+         <h1>oken</h1>\n{}\n<script>{SCRIPT}</script>\n</body></html>\n",
+        sections.join("\n")
+    )
+}
+
+fn hosts_section(statuses: &BTreeMap<String, HostStatus>, checked_by: &str) -> String {
+    let now = daemon::unix_now();
+    let all = hosts::list_all_hosts().unwrap_or_default();
+    let mut rows = Vec::new();
+    for h in all.iter().filter(|h| !h.is_expired(now)) {
+        let user = h.user.as_deref().map(|u| format!("{u}@")).unwrap_or_default();
+        let port = h.port.map(|p| format!(":{p}")).unwrap_or_default();
+        let address = format!("{user}{}{port}", h.hostname.as_deref().unwrap_or(&h.alias));
+        let status = match statuses.get(&h.alias) {
+            Some(s) if s.reachable => match s.latency_ms {
+                Some(ms) => format!("<span class=\"up\">up</span> {ms} ms"),
+                None => "<span class=\"up\">up</span>".to_string(),
+            },
+            Some(_) => "<span class=\"down\">down</span>".to_string(),
+            None => "<span class=\"dim\">…</span>".to_string(),
+        };
+        rows.push(format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{status}</td><td>{}</td></tr>",
+            escape(&h.alias),
+            escape(&address),
+            escape(&h.tags.join(", ")),
+            copy_button(&format!("oken {}", shell_quote(&h.alias)))
+        ));
+    }
+    table(
+        &format!("Hosts <small>status checked every minute by {checked_by}</small>"),
+        &["Alias", "Address", "Tags", "Status", ""],
+        &rows,
+        "No saved hosts.",
+    )
+}
+
+fn tunnels_section(tunnels_path: &Path, proxies: &BTreeMap<String, String>) -> String {
+    let mut entries: Vec<_> = tunnels::load_effective(tunnels_path, proxies)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let rows: Vec<String> = entries
+        .iter()
+        .map(|(name, entry)| {
+            let status = match tunnels::status(name, &entry.host) {
+                Some(s) => match s.uptime {
+                    Some(up) => format!(
+                        "<span class=\"up\">running</span> {}",
+                        audit::format_duration(up.as_secs())
+                    ),
+                    None => "<span class=\"up\">running</span>".to_string(),
+                },
+                None => "<span class=\"dim\">stopped</span>".to_string(),
+            };
+            let host = entry.host_alias.as_deref().unwrap_or(&entry.host);
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{status}</td><td>{}</td></tr>",
+                escape(name),
+                escape(host),
+                escape(&tunnels::describe_forwards(entry).join(", ")),
+                copy_button(&format!("oken tunnel start {}", shell_quote(name)))
+            )
+        })
+        .collect();
+    table("Tunnels", &["Name", "Host", "Forwards", "Status", ""], &rows, "No tunnels.")
+}
+
+fn audit_section() -> String {
+    let rows: Vec<String> = audit::recent(AUDIT_ENTRIES)
+        .unwrap_or_default()
+        .iter()
+        .map(|r| {
+            let duration = r.duration_secs.map(audit::format_duration).unwrap_or_default();
+            let exit = match r.exit_code {
+                Some(0) => "0".to_string(),
+                Some(code) => format!("<span class=\"down\">{code}</span>"),
+                None => String::new(),
+            };
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{duration}</td><td>{exit}</td>\
+                 <td>{}</td></tr>",
+                escape(&r.time),
+                escape(&r.alias),
+                escape(&r.target),
+                escape(&r.reason)
+            )
+        })
+        .collect();
+    table(
+        "Recent sessions <small>UTC</small>",
+        &["Time", "Alias", "Target", "Duration", "Exit", "Reason"],
+        &rows,
+        "No sessions logged yet.",
+    )
+}
+
+fn table(title: &str, columns: &[&str], rows: &[String], empty: &str) -> String {
+    if rows.is_empty() {
+        return format!("<h2>{title}</h2>\n<p class=\"dim\">{empty}</p>");
+    }
+    let head: Vec<String> = columns.iter().map(|c| format!("<th>{c}</th>")).collect();
+    format!(
+        "<h2>{title}</h2>\n<table><tr>{}</tr>\n{}\n</table>",
+        head.concat(),
+        rows.join("\n")
+    )
+}
+
+fn copy_button(command: &str) -> String {
+    let command = escape(command);
+    format!("<button data-cmd=\"{command}\" title=\"{command}\">Copy</button>")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+const STYLE: &str = "\
+:root { color-scheme: light dark; font-family: system-ui, sans-serif; }
+body { margin: 2em auto; max-width: 72em; padding: 0 1em; }
+h2 small, .dim { color: GrayText; font-weight: normal; font-size: 0.8em; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 2em; }
+th, td { text-align: left; padding: 0.3em 0.8em; border-bottom: 1px solid #8884; }
+td { font-family: ui-monospace, monospace; font-size: 0.9em; }
+.up { color: #2a2; } .down { color: #d33; }
+button { cursor: pointer; }";
+
+const SCRIPT: &str = "\
+document.addEventListener('click', (e) => {
+  const b = e.target.closest('button[data-cmd]');
+  if (!b) return;
+  navigator.clipboard.writeText(b.dataset.cmd).then(() => {
+    b.textContent = 'Copied';
+    setTimeout(() => { b.textContent = 'Copy'; }, 1500);
+  });
+});";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(escape("<a href=\"x\">&'"), "&lt;a href=&quot;x&quot;&gt;&amp;&#39;");
+        assert!(copy_button("oken 'a b'").contains("data-cmd=\"oken &#39;a b&#39;\""));
+    }
+}
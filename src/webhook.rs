@@ -0,0 +1,95 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+use crate::time_utils;
+
+/// A danger-host session starting or ending.
+pub enum DangerEvent<'a> {
+    Connect {
+        host: &'a str,
+        reason: Option<&'a str>,
+    },
+    Disconnect {
+        host: &'a str,
+        duration_secs: u64,
+        exit_code: i32,
+    },
+}
+
+/// POST the event as JSON to `url`. Best-effort with a short timeout — a slow
+/// or broken webhook must never hold up the SSH session.
+pub fn post(url: &str, event: &DangerEvent) {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    let machine = local_hostname();
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let timestamp = time_utils::unix_to_iso8601(secs);
+
+    let body = match event {
+        DangerEvent::Connect { host, reason } => {
+            let mut text = format!("{user}@{machine} connected to {host}");
+            if let Some(r) = reason {
+                text.push_str(&format!(" — {r}"));
+            }
+            json!({
+                // `text` makes the payload render as-is in Slack incoming webhooks
+                "text": format!(":warning: {text}"),
+                "event": "connect",
+                "user": user,
+                "machine": machine,
+                "host": host,
+                "reason": reason,
+                "timestamp": timestamp,
+            })
+        }
+        DangerEvent::Disconnect {
+            host,
+            duration_secs,
+            exit_code,
+        } => json!({
+            "text": format!(
+                "{user}@{machine} disconnected from {host} after {} (exit {exit_code})",
+                crate::audit::format_duration(*duration_secs)
+            ),
+            "event": "disconnect",
+            "user": user,
+            "machine": machine,
+            "host": host,
+            "duration_secs": duration_secs,
+            "exit_code": exit_code,
+            "timestamp": timestamp,
+        }),
+    };
+
+    let result = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(2))
+        .timeout(Duration::from_secs(3))
+        .build()
+        .post(url)
+        .set("User-Agent", &format!("oken/{}", env!("CARGO_PKG_VERSION")))
+        .set("Content-Type", "application/json")
+        .send_string(&body.to_string());
+    if let Err(e) = result {
+        let note = format!("oken: danger_webhook failed: {e}");
+        eprintln!("{}", crate::color::epaint("2", note));
+    }
+}
+
+fn local_hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| {
+            std::process::Command::new("hostname")
+                .output()
+                .ok()
+                .and_then(|o| String::from_utf8(o.stdout).ok())
+                .map(|s| s.trim().to_string())
+        })
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
@@ -0,0 +1,333 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use oken_core::{history, hosts, oken_config, ssh};
+
+use crate::{color, discover, keys, prompt};
+
+/// One host for `oken host add` to save, given as arguments or asked for.
+/// The rest of the host's settings come from the command-line flags.
+pub struct Answers {
+    pub name: String,
+    pub target: String,
+    pub port: Option<u16>,
+    pub key: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Ask step by step for what `oken host add NAME TARGET` would take, skipping
+/// anything already given as a flag, and offer to try the connection before
+/// saving. None when the user decides not to save after a failed test.
+pub fn run(
+    name: Option<String>,
+    port: Option<u16>,
+    key: Option<PathBuf>,
+    tags: Vec<String>,
+) -> Result<Option<Answers>> {
+    if !io::stdin().is_terminal() {
+        bail!("oken host add needs NAME and TARGET when it can't ask for them");
+    }
+    let existing = hosts::list_all_hosts().unwrap_or_default();
+    eprintln!("{}", color::epaint("2", "Adding a host (Ctrl-C to stop)."));
+
+    let (target, port) = loop {
+        let answer = ask("Target (user@host): ")?;
+        if answer.is_empty() || answer.contains(char::is_whitespace) {
+            eprintln!("Enter the address to connect to, e.g. deploy@10.0.0.5 or web1.example.com");
+            continue;
+        }
+        // user@host:port, unless the flag already set the port (or it's IPv6)
+        match answer.rsplit_once(':') {
+            Some((target, p)) if port.is_none() && !target.contains(':') => match p.parse() {
+                Ok(p) => break (target.to_string(), Some(p)),
+                Err(_) => eprintln!("'{p}' is not a port number"),
+            },
+            _ => break (answer, port),
+        }
+    };
+    let hostname = target.rsplit('@').next().unwrap_or(&target);
+
+    let suggestion = name.unwrap_or_else(|| suggest_alias(hostname));
+    let name = loop {
+        let answer = ask(&format!("Alias [{suggestion}]: "))?;
+        let answer = if answer.is_empty() { suggestion.clone() } else { answer };
+        if answer.contains(char::is_whitespace) {
+            eprintln!("An alias can't contain spaces");
+        } else if existing.iter().any(|h| h.alias == answer) {
+            eprintln!("'{answer}' is already a host; pick another alias");
+        } else {
+            break answer;
+        }
+    };
+
+    let port = match port {
+        Some(port) => Some(port),
+        None => loop {
+            let answer = ask("Port [22]: ")?;
+            if answer.is_empty() {
+                break None;
+            }
+            match answer.parse() {
+                Ok(port) => break Some(port),
+                Err(_) => eprintln!("'{answer}' is not a port number"),
+            }
+        },
+    };
+
+    let key = match key {
+        Some(key) => Some(key.to_string_lossy().into_owned()),
+        None => ask_key()?,
+    };
+
+    let tags = if tags.is_empty() { ask_tags(&existing)? } else { tags };
+
+    let answers = Answers {
+        name,
+        target,
+        port,
+        key,
+        tags,
+    };
+    let tested = prompt::confirm("Test the connection now?", true)?;
+    if tested && !test_connection(&answers)? && !prompt::confirm("Save it anyway?", false)? {
+        eprintln!("Not saved.");
+        return Ok(None);
+    }
+    Ok(Some(answers))
+}
+
+/// `oken host add --from-history`: list the targets connected to without
+/// saving them, and ask which to save, under what alias and with which tags.
+/// `tags`, when given, go on every picked host instead of being asked for.
+pub fn from_history(tags: Vec<String>) -> Result<Vec<Answers>> {
+    if !io::stdin().is_terminal() {
+        bail!("oken host add --from-history asks which hosts to save, so it needs a terminal");
+    }
+    let existing = hosts::list_all_hosts().unwrap_or_default();
+    let saved = |t: &history::RecentTarget| {
+        existing.iter().any(|h| {
+            let same_user = t.user.is_none() || h.user == t.user;
+            h.alias == t.host || (h.hostname.as_deref() == Some(&t.host) && same_user)
+        })
+    };
+    let targets: Vec<history::RecentTarget> =
+        history::recent_targets()?.into_iter().filter(|t| !saved(t)).collect();
+    if targets.is_empty() {
+        eprintln!("Every host in the connection history is already saved.");
+        return Ok(Vec::new());
+    }
+
+    let address = |t: &history::RecentTarget| match &t.user {
+        Some(user) => format!("{user}@{}", t.host),
+        None => t.host.clone(),
+    };
+    let shown: Vec<String> = targets
+        .iter()
+        .map(|t| match t.port {
+            Some(port) => format!("{}:{port}", address(t)),
+            None => address(t),
+        })
+        .collect();
+    let width = shown.iter().map(String::len).max().unwrap_or(0);
+    eprintln!("Connected to but not saved:");
+    for (i, (t, shown)) in targets.iter().zip(&shown).enumerate() {
+        let times = match t.connections {
+            1 => "once".to_string(),
+            n => format!("{n} times"),
+        };
+        let last = t.last_connected.split('T').next().unwrap_or(&t.last_connected);
+        let when = color::epaint("2", format!("{times}, last on {last}"));
+        eprintln!("  {:>2}) {shown:<width$}  {when}", i + 1);
+    }
+    let picks = loop {
+        let answer = ask("Save which? (e.g. 1 3 5-7, \"all\"; Enter for none): ")?;
+        match discover::parse_selection(&answer, targets.len()) {
+            Ok(picks) => break picks,
+            Err(problem) => eprintln!("{problem}"),
+        }
+    };
+
+    let mut picked: Vec<Answers> = Vec::new();
+    for i in picks {
+        let target = &targets[i];
+        let suggestion = suggest_alias(&target.host);
+        let name = loop {
+            let answer = ask(&format!("Alias for {} [{suggestion}]: ", shown[i]))?;
+            let answer = if answer.is_empty() { suggestion.clone() } else { answer };
+            let taken = existing.iter().any(|h| h.alias == answer)
+                || picked.iter().any(|a| a.name == answer);
+            if answer.contains(char::is_whitespace) {
+                eprintln!("An alias can't contain spaces");
+            } else if taken {
+                eprintln!("'{answer}' is already a host; pick another alias");
+            } else {
+                break answer;
+            }
+        };
+        let tags = if tags.is_empty() { ask_tags(&existing)? } else { tags.clone() };
+        picked.push(Answers {
+            name,
+            target: address(target),
+            port: target.port,
+            key: None,
+            tags,
+        });
+    }
+    Ok(picked)
+}
+
+/// The first label of a host name (`web1` for web1.example.com), or the
+/// whole address with dashes for an IP.
+fn suggest_alias(hostname: &str) -> String {
+    let hostname = hostname.trim_matches(['[', ']']).to_lowercase();
+    let is_ip = hostname.parse::<std::net::IpAddr>().is_ok();
+    if is_ip {
+        hostname.replace([':', '.'], "-")
+    } else {
+        hostname.split('.').next().unwrap_or(&hostname).to_string()
+    }
+}
+
+/// List the keys in ~/.ssh and ask which one the host uses.
+fn ask_key() -> Result<Option<String>> {
+    let keys = keys::private_keys();
+    if keys.is_empty() {
+        let answer = ask("Identity file (Enter for ssh's default): ")?;
+        return Ok((!answer.is_empty()).then_some(answer));
+    }
+    eprintln!("Keys in ~/.ssh:");
+    for (i, key) in keys.iter().enumerate() {
+        eprintln!("  {}) {key}", i + 1);
+    }
+    loop {
+        let answer = ask("Key (number, name or path; Enter for ssh's default): ")?;
+        match pick_key(&answer, &keys) {
+            Ok(Some(key)) if !ssh::expand_tilde(&key).is_file() => {
+                eprintln!("{key} doesn't exist");
+            }
+            Ok(key) => {
+                if let Some(key) = key.as_ref().filter(|k| *k != &answer) {
+                    eprintln!("{}", color::epaint("2", format!("Using {key}")));
+                }
+                return Ok(key);
+            }
+            Err(problem) => eprintln!("{problem}"),
+        }
+    }
+}
+
+/// Resolve an answer to the key prompt: a number from the list, a key name
+/// or the start of one, or a path.
+fn pick_key(answer: &str, keys: &[String]) -> Result<Option<String>, String> {
+    if answer.is_empty() {
+        return Ok(None);
+    }
+    if answer.contains('/') {
+        return Ok(Some(answer.to_string()));
+    }
+    let in_ssh_dir = |name: &str| Some(format!("~/.ssh/{name}"));
+    if let Ok(n) = answer.parse::<usize>() {
+        return match keys.get(n.wrapping_sub(1)) {
+            Some(name) => Ok(in_ssh_dir(name)),
+            None => Err(format!("Pick a number from 1 to {}", keys.len())),
+        };
+    }
+    if keys.iter().any(|k| k == answer) {
+        return Ok(in_ssh_dir(answer));
+    }
+    let matching: Vec<&str> = keys
+        .iter()
+        .filter(|k| k.starts_with(answer))
+        .map(String::as_str)
+        .collect();
+    match matching[..] {
+        [name] => Ok(in_ssh_dir(name)),
+        [] => Err(format!("No key named '{answer}' in ~/.ssh")),
+        _ => Err(format!("'{answer}' could be {}", matching.join(", "))),
+    }
+}
+
+/// Show the tags other hosts use and ask for this one's.
+fn ask_tags(existing: &[hosts::Host]) -> Result<Vec<String>> {
+    let mut in_use: Vec<&str> = existing
+        .iter()
+        .flat_map(|h| h.tags.iter().map(String::as_str))
+        .collect();
+    in_use.sort_unstable();
+    in_use.dedup();
+    if !in_use.is_empty() {
+        eprintln!("Tags in use: {}", in_use.join(", "));
+    }
+    let answer = ask("Tags (space-separated, Enter for none): ")?;
+    // Reuse an existing tag's spelling, so `Prod` doesn't start a second `prod`
+    Ok(answer
+        .split([' ', ','])
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            let known = in_use.iter().find(|u| u.eq_ignore_ascii_case(t));
+            known.map_or_else(|| t.to_string(), |u| u.to_string())
+        })
+        .collect())
+}
+
+/// Run `true` on the host, letting ssh ask about the host key or a password
+/// as it would on a first connection. True when it worked.
+fn test_connection(answers: &Answers) -> Result<bool> {
+    let defaults = oken_config::host_defaults();
+    let target = match &defaults.user {
+        Some(user) if !answers.target.contains('@') => format!("{user}@{}", answers.target),
+        _ => answers.target.clone(),
+    };
+    let mut cmd = Command::new(ssh::find_ssh()?);
+    cmd.args(["-o", "ConnectTimeout=10"]);
+    if let Some(port) = answers.port.or(defaults.port) {
+        cmd.arg("-p").arg(port.to_string());
+    }
+    if let Some(key) = answers.key.as_ref().or(defaults.identity_file.as_ref()) {
+        cmd.arg("-i").arg(ssh::expand_tilde(key));
+    }
+    cmd.args([&target, "true"]);
+    log::debug!("testing with {cmd:?}");
+    let status = cmd.status().context("failed to run ssh")?;
+    if status.success() {
+        eprintln!("{}", color::epaint("32", format!("Connected to {target}.")));
+        return Ok(true);
+    }
+    match status.code() {
+        Some(code) => eprintln!("{}", color::epaint("31", format!("ssh exited with {code}."))),
+        None => eprintln!("{}", color::epaint("31", "ssh was interrupted.")),
+    }
+    Ok(false)
+}
+
+/// Prompt on stderr and read one trimmed line. End of input cancels.
+fn ask(prompt: &str) -> Result<String> {
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        eprintln!();
+        bail!("cancelled");
+    }
+    Ok(line.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_names_and_keys() {
+        assert_eq!(suggest_alias("Web1.example.com"), "web1");
+        assert_eq!(suggest_alias("10.0.0.5"), "10-0-0-5");
+        let keys = ["id_ed25519".to_string(), "id_rsa".to_string(), "work".to_string()];
+        assert_eq!(pick_key("2", &keys).unwrap().unwrap(), "~/.ssh/id_rsa");
+        assert_eq!(pick_key("wo", &keys).unwrap().unwrap(), "~/.ssh/work");
+        assert_eq!(pick_key("~/k/a", &keys).unwrap().unwrap(), "~/k/a");
+        assert_eq!(pick_key("", &keys).unwrap(), None);
+        assert!(pick_key("id", &keys).unwrap_err().contains("id_ed25519, id_rsa"));
+        assert!(pick_key("4", &keys).is_err());
+    }
+}